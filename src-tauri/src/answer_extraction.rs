@@ -0,0 +1,539 @@
+//! 从模型输出中提取答案：按模型配置的策略依次尝试，返回第一个成功的策略及其结果。
+//!
+//! config.json 中的配置（模型名不区分大小写，以 `*` 结尾时按前缀匹配；未配置的模型使用 default）：
+//! ```json
+//! {
+//!   "answerExtraction": {
+//!     "default": ["jsonSchema", "toolCall", "answerTag", "heuristic"],
+//!     "models": {
+//!       "deepseek-r1*": ["answerTag", "jsonSchema", "lastLine"],
+//!       "gpt-4o": ["jsonSchema"]
+//!     }
+//!   }
+//! }
+//! ```
+//! 所有策略执行前都会去掉 `<think>…</think>` 等思考过程以及包裹整段输出的代码块标记，
+//! 思考过程中的花括号、“答案：”等内容不会被误当作答案。
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+
+use crate::database::read_config_value;
+
+/// 思考过程使用的标签名
+const REASONING_TAGS: &str = "think|thinking|reasoning";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ExtractionStrategy {
+    /// 整段输出是带有 answer 字段的 JSON 对象（结构化输出 / JSON Schema 模式）
+    JsonSchema,
+    /// 工具（函数）调用参数中的 answer 字段，支持 OpenAI tool_calls、`<tool_call>` 标签等格式
+    ToolCall,
+    /// `<answer>…</answer>` 标签中的内容，有多个时取最后一个
+    AnswerTag,
+    /// 最后一个非空行，去掉“答案：”等前缀
+    LastLine,
+    /// 兼容旧版的启发式提取：文本中最后一个带 answer 字段的 JSON、“答案：”标记或简短的纯文本
+    Heuristic,
+}
+
+impl ExtractionStrategy {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ExtractionStrategy::JsonSchema => "jsonSchema",
+            ExtractionStrategy::ToolCall => "toolCall",
+            ExtractionStrategy::AnswerTag => "answerTag",
+            ExtractionStrategy::LastLine => "lastLine",
+            ExtractionStrategy::Heuristic => "heuristic",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct AnswerExtractionConfig {
+    /// 未单独配置的模型使用的策略
+    pub default: Vec<ExtractionStrategy>,
+    /// 键为模型名，值为该模型依次尝试的策略
+    pub models: HashMap<String, Vec<ExtractionStrategy>>,
+}
+
+impl Default for AnswerExtractionConfig {
+    fn default() -> Self {
+        AnswerExtractionConfig {
+            default: vec![
+                ExtractionStrategy::JsonSchema,
+                ExtractionStrategy::ToolCall,
+                ExtractionStrategy::AnswerTag,
+                ExtractionStrategy::Heuristic,
+            ],
+            models: HashMap::new(),
+        }
+    }
+}
+
+impl AnswerExtractionConfig {
+    /// 读取 config.json 中的 answerExtraction 配置，缺失或格式错误时使用默认策略
+    pub fn load() -> Self {
+        let Some(value) = read_config_value("answerExtraction") else {
+            return AnswerExtractionConfig::default();
+        };
+        serde_json::from_value(value).unwrap_or_else(|e| {
+            println!("⚠️ 答案提取配置格式错误，使用默认策略: {}", e);
+            AnswerExtractionConfig::default()
+        })
+    }
+
+    /// 模型使用的策略：完全匹配优先，其次是最长的前缀规则，都没有时使用 default
+    pub fn strategies_for(&self, model: Option<&str>) -> &[ExtractionStrategy] {
+        model
+            .and_then(|model| best_model_match(&self.models, model))
+            .map(|(_, strategies)| strategies.as_slice())
+            .unwrap_or(&self.default)
+    }
+}
+
+/// 模型名与规则的匹配程度（不区分大小写）：完全一致为 usize::MAX，`*` 结尾的前缀规则为前缀长度，不匹配为 None
+fn model_pattern_rank(pattern: &str, model: &str) -> Option<usize> {
+    let pattern = pattern.trim().to_lowercase();
+    let model = model.trim().to_lowercase();
+    match pattern.strip_suffix('*') {
+        Some(prefix) => model.starts_with(prefix).then_some(prefix.len()),
+        None => (pattern == model).then_some(usize::MAX),
+    }
+}
+
+/// 按模型名规则配置的项中与模型最匹配的一项：完全匹配优先，其次是最长的前缀规则；
+/// 匹配程度相同的规则（如只有大小写不同）取字典序最小的一条，结果不受 HashMap 遍历顺序影响
+pub(crate) fn best_model_match<'a, T>(
+    rules: &'a HashMap<String, T>,
+    model: &str,
+) -> Option<(&'a str, &'a T)> {
+    let mut best: Option<(usize, &str, &T)> = None;
+    for (pattern, value) in rules {
+        let Some(rank) = model_pattern_rank(pattern, model) else {
+            continue;
+        };
+        if best.is_none_or(|(best_rank, best_pattern, _)| {
+            rank > best_rank || (rank == best_rank && pattern.as_str() < best_pattern)
+        }) {
+            best = Some((rank, pattern, value));
+        }
+    }
+    best.map(|(_, pattern, value)| (pattern, value))
+}
+
+/// 提取结果
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExtractedAnswer {
+    pub answer: String,
+    /// 提取成功的策略；所有策略都失败时为 None，answer 为去掉思考过程后的原始输出
+    pub strategy: Option<ExtractionStrategy>,
+    /// 输出中含有思考过程并已去掉
+    pub stripped_reasoning: bool,
+}
+
+/// 去掉思考过程：成对的 `<think>…</think>`；只有结束标签时（开始标签由对话模板插入）去掉它之前的全部内容；
+/// 只有开始标签时（输出在思考阶段被截断）去掉它之后的全部内容。返回处理后的文本和是否去掉了内容。
+pub fn strip_reasoning(text: &str) -> (String, bool) {
+    let paired = Regex::new(&format!(r"(?is)<(?:{0})>.*?</(?:{0})>", REASONING_TAGS)).unwrap();
+    let leading = Regex::new(&format!(r"(?is)^.*</(?:{})>", REASONING_TAGS)).unwrap();
+    let trailing = Regex::new(&format!(r"(?is)<(?:{})>.*$", REASONING_TAGS)).unwrap();
+
+    let mut stripped = paired.replace_all(text, "").to_string();
+    stripped = leading.replace(&stripped, "").to_string();
+    stripped = trailing.replace(&stripped, "").to_string();
+    let changed = stripped != text;
+    (stripped.trim().to_string(), changed)
+}
+
+/// 去掉包裹整段输出的 markdown 代码块标记（```json … ```）
+pub fn strip_code_fence(text: &str) -> &str {
+    let mut cleaned = text.trim();
+    if let Some(rest) = cleaned.strip_prefix("```") {
+        cleaned = rest.trim_start_matches(|c: char| c.is_ascii_alphanumeric());
+    }
+    if let Some(rest) = cleaned.strip_suffix("```") {
+        cleaned = rest;
+    }
+    cleaned.trim()
+}
+
+/// JSON 中的 answer 字段（兼容拼写错误的 anwser）。数组按 `###` 连接，布尔值转为“正确”/“错误”。
+fn answer_field(value: &Value) -> Option<String> {
+    let answer = value.get("answer").or_else(|| value.get("anwser"))?;
+    let answer = match answer {
+        Value::String(text) => text.trim().to_string(),
+        Value::Number(number) => number.to_string(),
+        Value::Bool(true) => "正确".to_string(),
+        Value::Bool(false) => "错误".to_string(),
+        Value::Array(items) => items
+            .iter()
+            .filter_map(|item| match item {
+                Value::String(text) => Some(text.trim().to_string()),
+                Value::Number(number) => Some(number.to_string()),
+                _ => None,
+            })
+            .filter(|item| !item.is_empty())
+            .collect::<Vec<_>>()
+            .join("###"),
+        _ => return None,
+    };
+    Some(answer).filter(|answer| !answer.is_empty())
+}
+
+/// 文本中所有能完整解析的 JSON 对象，按出现顺序排列。
+/// 无法解析的 `{`（如思考文字中的“{A, B}”）会被跳过，字符串中的花括号不影响解析。
+fn json_objects(text: &str) -> Vec<Value> {
+    let mut objects = Vec::new();
+    let mut start = 0;
+    while let Some(offset) = text[start..].find('{') {
+        let begin = start + offset;
+        let mut stream = serde_json::Deserializer::from_str(&text[begin..]).into_iter::<Value>();
+        match stream.next() {
+            Some(Ok(value)) if value.is_object() => {
+                objects.push(value);
+                start = begin + stream.byte_offset();
+            }
+            _ => start = begin + 1,
+        }
+    }
+    objects
+}
+
+fn extract_json_schema(text: &str) -> Option<String> {
+    let value: Value = serde_json::from_str(text).ok()?;
+    answer_field(&value)
+}
+
+/// 值中的工具调用参数：`arguments`（字符串形式的 JSON 或对象）、`parameters`，
+/// 以及 `"type": "tool_use"` 的 `input`，按出现顺序收集
+fn collect_tool_arguments(value: &Value, arguments: &mut Vec<Value>) {
+    match value {
+        Value::Object(map) => {
+            for key in ["arguments", "parameters"] {
+                match map.get(key) {
+                    Some(Value::String(raw)) => {
+                        if let Ok(parsed) = serde_json::from_str::<Value>(raw) {
+                            arguments.push(parsed);
+                        }
+                    }
+                    Some(object @ Value::Object(_)) => arguments.push(object.clone()),
+                    _ => {}
+                }
+            }
+            if map.get("type").and_then(Value::as_str) == Some("tool_use") {
+                if let Some(input) = map.get("input") {
+                    arguments.push(input.clone());
+                }
+            }
+            for child in map.values() {
+                collect_tool_arguments(child, arguments);
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                collect_tool_arguments(item, arguments);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn extract_tool_call(text: &str) -> Option<String> {
+    let tagged = Regex::new(r"(?is)<tool_call>(.*?)</tool_call>").unwrap();
+    let mut values: Vec<Value> = tagged
+        .captures_iter(text)
+        .filter_map(|caps| serde_json::from_str(caps[1].trim()).ok())
+        .collect();
+    if values.is_empty() {
+        values = json_objects(text);
+    }
+
+    let mut arguments = Vec::new();
+    for value in &values {
+        collect_tool_arguments(value, &mut arguments);
+    }
+    arguments.iter().rev().find_map(answer_field)
+}
+
+fn extract_answer_tag(text: &str) -> Option<String> {
+    let re = Regex::new(r"(?is)<answer>(.*?)</answer>").unwrap();
+    re.captures_iter(text)
+        .filter_map(|caps| caps.get(1).map(|m| m.as_str().trim().to_string()))
+        .filter(|answer| !answer.is_empty())
+        .last()
+}
+
+fn extract_last_line(text: &str) -> Option<String> {
+    let prefix =
+        Regex::new(r"(?i)^(?:最终答案|正确答案|答案|final answer|answer)\s*[：:]\s*").unwrap();
+    let line = text
+        .lines()
+        .map(str::trim)
+        .rfind(|line| !line.is_empty() && !line.starts_with("```"))?;
+    let line = line.trim_matches('*').trim();
+    let answer = prefix.replace(line, "");
+    let answer = answer.trim_matches('*').trim();
+    Some(answer.to_string()).filter(|answer| !answer.is_empty())
+}
+
+fn extract_heuristic(text: &str) -> Option<String> {
+    // 1) 最后一个带 answer 字段的 JSON 对象
+    if let Some(answer) = json_objects(text).iter().rev().find_map(answer_field) {
+        return Some(answer);
+    }
+
+    // 2) 无法解析的 JSON 片段（如末尾多了逗号）中直接捕获 answer 字段
+    let re = Regex::new(r#"(?s)\{\s*"(?:answer|anwser)"\s*:\s*"(.*?)"[\s\S]*?\}"#).unwrap();
+    if let Some(answer) = re
+        .captures_iter(text)
+        .filter_map(|caps| caps.get(1).map(|m| m.as_str().trim().to_string()))
+        .filter(|answer| !answer.is_empty())
+        .last()
+    {
+        return Some(answer);
+    }
+
+    // 3) 中英文“答案：”或“answer:”后面的文本
+    let text_re = Regex::new(r"(?i)(?:答案|answer)[：:]\s*(.+?)(?:\n|$)").unwrap();
+    if let Some(answer) = text_re
+        .captures_iter(text)
+        .filter_map(|caps| caps.get(1).map(|m| m.as_str().trim().to_string()))
+        .filter(|answer| !answer.is_empty())
+        .last()
+    {
+        return Some(answer);
+    }
+
+    // 4) 不含 JSON 结构的简短纯文本直接作为答案
+    let lines = text.lines().filter(|line| !line.trim().is_empty()).count();
+    if !text.is_empty()
+        && text.len() < 2000
+        && !text.starts_with('{')
+        && !text.starts_with('[')
+        && lines <= 3
+    {
+        return Some(text.to_string());
+    }
+    None
+}
+
+/// 按给定的策略顺序提取答案
+pub fn extract_answer(content: &str, strategies: &[ExtractionStrategy]) -> ExtractedAnswer {
+    let (without_reasoning, stripped_reasoning) = strip_reasoning(content);
+    let cleaned = strip_code_fence(&without_reasoning);
+
+    for &strategy in strategies {
+        let answer = match strategy {
+            ExtractionStrategy::JsonSchema => extract_json_schema(cleaned),
+            ExtractionStrategy::ToolCall => extract_tool_call(cleaned),
+            ExtractionStrategy::AnswerTag => extract_answer_tag(cleaned),
+            ExtractionStrategy::LastLine => extract_last_line(cleaned),
+            ExtractionStrategy::Heuristic => extract_heuristic(cleaned),
+        };
+        if let Some(answer) = answer {
+            return ExtractedAnswer {
+                answer,
+                strategy: Some(strategy),
+                stripped_reasoning,
+            };
+        }
+    }
+
+    ExtractedAnswer {
+        answer: without_reasoning,
+        strategy: None,
+        stripped_reasoning,
+    }
+}
+
+/// 按 config.json 中为该模型配置的策略提取答案；不知道模型时使用默认策略
+pub fn extract_answer_for_model(content: &str, model: Option<&str>) -> ExtractedAnswer {
+    let config = AnswerExtractionConfig::load();
+    extract_answer(content, config.strategies_for(model))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn equally_ranked_model_rules_pick_the_same_pattern_every_time() {
+        for _ in 0..20 {
+            // 每个 HashMap 的遍历顺序不同
+            let rules: HashMap<String, i32> = [
+                ("GPT-4o".to_string(), 1),
+                ("gpt-4o".to_string(), 2),
+                ("Qwen*".to_string(), 3),
+                ("qwen*".to_string(), 4),
+                ("qwen3*".to_string(), 5),
+            ]
+            .into_iter()
+            .collect();
+            assert_eq!(best_model_match(&rules, "gpt-4o"), Some(("GPT-4o", &1)));
+            assert_eq!(best_model_match(&rules, "qwen2.5-72b"), Some(("Qwen*", &3)));
+            assert_eq!(best_model_match(&rules, "Qwen3-32B"), Some(("qwen3*", &5)));
+        }
+    }
+
+    #[test]
+    fn extracts_answers_from_model_output_corpus() {
+        use ExtractionStrategy::{AnswerTag, Heuristic, JsonSchema, LastLine, ToolCall};
+        let default = AnswerExtractionConfig::default().default;
+
+        // (说明, 策略, 模型输出, 期望答案, 期望命中的策略)
+        let corpus = vec![
+            (
+                "纯 JSON",
+                default.clone(),
+                r#"{"answer": "光合作用"}"#,
+                "光合作用",
+                Some(JsonSchema),
+            ),
+            (
+                "代码块包裹的 JSON",
+                default.clone(),
+                "```json\n{\n  \"answer\": \"正确\"\n}\n```",
+                "正确",
+                Some(JsonSchema),
+            ),
+            (
+                "多选题答案数组",
+                vec![JsonSchema],
+                r#"{"answer": ["北京", "上海"]}"#,
+                "北京###上海",
+                Some(JsonSchema),
+            ),
+            (
+                "DeepSeek-R1 思考过程中含花括号",
+                default.clone(),
+                "<think>\n集合 {A, B} 中，题目问的是 {\"answer\": \"错误示例\"} 这种格式吗？应该选 B。\n</think>\n\n{\"answer\": \"氧气\"}",
+                "氧气",
+                Some(JsonSchema),
+            ),
+            (
+                "只有思考结束标签",
+                default.clone(),
+                "首先回顾定义 {x | x > 0}，答案: 不是这个\n</think>\n{\"answer\": \"12\"}",
+                "12",
+                Some(JsonSchema),
+            ),
+            (
+                "分析后输出 JSON（思考文字含花括号）",
+                default.clone(),
+                "分析：选项 {A} 与 {B} 都提到了细胞壁，但只有 C 正确。\n\n{\"answer\": \"线粒体\"}",
+                "线粒体",
+                Some(Heuristic),
+            ),
+            (
+                "字符串中的花括号",
+                default.clone(),
+                "推理完毕。{\"answer\": \"f(x) = {x}\"}",
+                "f(x) = {x}",
+                Some(Heuristic),
+            ),
+            (
+                "OpenAI tool_calls",
+                default.clone(),
+                r#"{"role": "assistant", "content": null, "tool_calls": [{"id": "call_1", "type": "function", "function": {"name": "submit_answer", "arguments": "{\"answer\": \"长江\"}"}}]}"#,
+                "长江",
+                Some(ToolCall),
+            ),
+            (
+                "Qwen <tool_call> 标签",
+                vec![ToolCall],
+                "<think>需要调用提交工具</think>\n<tool_call>\n{\"name\": \"submit_answer\", \"arguments\": {\"answer\": \"错误\"}}\n</tool_call>",
+                "错误",
+                Some(ToolCall),
+            ),
+            (
+                "Anthropic tool_use",
+                vec![ToolCall],
+                r#"[{"type": "text", "text": "好的"}, {"type": "tool_use", "name": "submit_answer", "input": {"answer": true}}]"#,
+                "正确",
+                Some(ToolCall),
+            ),
+            (
+                "<answer> 标签",
+                default.clone(),
+                "<think>对比两个选项</think>根据课本内容，<answer>\n牛顿第一定律\n</answer>",
+                "牛顿第一定律",
+                Some(AnswerTag),
+            ),
+            (
+                "最后一行",
+                vec![LastLine],
+                "选项 A 描述的是有丝分裂。\n选项 B 描述的是减数分裂。\n**答案：B**",
+                "B",
+                Some(LastLine),
+            ),
+            (
+                "“答案：”标记",
+                default.clone(),
+                "这道题考查的是化学键。\n答案：离子键\n以上仅供参考，具体以教材为准。",
+                "离子键",
+                Some(Heuristic),
+            ),
+            (
+                "简短纯文本",
+                default.clone(),
+                "水的沸点是100摄氏度",
+                "水的沸点是100摄氏度",
+                Some(Heuristic),
+            ),
+            (
+                "严格 JSON 不接受混合文本",
+                vec![JsonSchema],
+                "<think>想一想</think>我认为是 {\"answer\": \"A\"}",
+                "我认为是 {\"answer\": \"A\"}",
+                None,
+            ),
+        ];
+
+        for (name, strategies, output, expected, strategy) in corpus {
+            let extracted = extract_answer(output, &strategies);
+            assert_eq!(extracted.answer, expected, "{}", name);
+            assert_eq!(extracted.strategy, strategy, "{}", name);
+        }
+
+        let (stripped, changed) = strip_reasoning("<THINK>a</THINK>答案<think>被截断的思考");
+        assert_eq!(stripped, "答案");
+        assert!(changed);
+        assert!(!strip_reasoning("{\"answer\": \"A\"}").1);
+    }
+
+    #[test]
+    fn extraction_strategies_are_chosen_per_model() {
+        let config: AnswerExtractionConfig = serde_json::from_value(serde_json::json!({
+            "models": {
+                "deepseek-*": ["jsonSchema"],
+                "deepseek-r1*": ["answerTag", "lastLine"],
+                "GPT-4o": ["toolCall"],
+            }
+        }))
+        .expect("config");
+
+        assert_eq!(
+            config.strategies_for(Some("deepseek-r1-distill")),
+            &[ExtractionStrategy::AnswerTag, ExtractionStrategy::LastLine]
+        );
+        assert_eq!(
+            config.strategies_for(Some("deepseek-v3")),
+            &[ExtractionStrategy::JsonSchema]
+        );
+        assert_eq!(
+            config.strategies_for(Some("gpt-4o")),
+            &[ExtractionStrategy::ToolCall]
+        );
+        assert_eq!(
+            config.strategies_for(Some("gpt-4o-mini")),
+            config.default.as_slice()
+        );
+        assert_eq!(config.strategies_for(None), config.default.as_slice());
+    }
+}
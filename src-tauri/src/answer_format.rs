@@ -0,0 +1,348 @@
+//! 答案格式配置档：返回给 OCS 时按配置档改写 QueryData.answer。题库中保存的答案始终是规范形式
+//! （多个答案用 ### 连接，判断题为“正确”/“错误”，选择题为选项内容），只在返回时转换。
+//!
+//! 内置 canonical / letters / symbols 三个预设，config.json 中可以覆盖或新增：
+//! ```json
+//! {
+//!   "answerFormats": { "ocs-a": { "separator": "#", "judgementTrue": "对", "judgementFalse": "错", "choiceStyle": "letter" } },
+//!   "defaultAnswerFormat": "canonical",
+//!   "multiUser": { "users": [{ "name": "张三", "token": "...", "answerFormat": "ocs-a" }] }
+//! }
+//! ```
+//! 自定义配置档中未填写的字段取 canonical 预设的值。选择顺序：请求中的 answerFormat 参数 >
+//! token（Authorization 请求头或 token 参数）对应用户的 answerFormat > defaultAnswerFormat。
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::answer_validation::{
+    normalize_judgement, ANSWER_SEPARATOR, JUDGEMENT_FALSE, JUDGEMENT_TRUE,
+};
+use crate::database::read_config_value;
+use crate::question_kind::{detect_question_kind, AnswerRule};
+use crate::question_options::choice_texts;
+
+pub const CANONICAL_FORMAT_NAME: &str = "canonical";
+
+/// 选择题答案的形式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ChoiceStyle {
+    /// 选项内容
+    #[default]
+    Text,
+    /// 选项字母
+    Letter,
+    /// 选项字母加内容，如“A. 北京”
+    LetterAndText,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct AnswerFormat {
+    /// 多个答案之间的分隔符
+    pub separator: String,
+    /// choiceStyle 为 letter 时选项字母之间的分隔符，未填写时使用 separator
+    pub letter_separator: Option<String>,
+    pub judgement_true: String,
+    pub judgement_false: String,
+    pub choice_style: ChoiceStyle,
+}
+
+impl Default for AnswerFormat {
+    fn default() -> Self {
+        AnswerFormat {
+            separator: ANSWER_SEPARATOR.to_string(),
+            letter_separator: None,
+            judgement_true: JUDGEMENT_TRUE.to_string(),
+            judgement_false: JUDGEMENT_FALSE.to_string(),
+            choice_style: ChoiceStyle::Text,
+        }
+    }
+}
+
+impl AnswerFormat {
+    /// 选择题返回连写的选项字母，如“ABD”
+    pub fn letters() -> Self {
+        AnswerFormat {
+            letter_separator: Some(String::new()),
+            choice_style: ChoiceStyle::Letter,
+            ..Default::default()
+        }
+    }
+
+    /// 判断题返回 √ / ×
+    pub fn symbols() -> Self {
+        AnswerFormat {
+            judgement_true: "√".to_string(),
+            judgement_false: "×".to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn judgement(&self, value: bool) -> String {
+        if value {
+            self.judgement_true.clone()
+        } else {
+            self.judgement_false.clone()
+        }
+    }
+
+    /// 把规范形式的答案转换为配置档的格式；options 与 query_type 为本次请求的选项与题型，
+    /// 选项字母按请求中的选项顺序计算。无法对应到选项的答案保留原内容。
+    pub fn format_answer(
+        &self,
+        answer: &str,
+        options: Option<&str>,
+        query_type: Option<&str>,
+    ) -> String {
+        if *self == AnswerFormat::default() {
+            return answer.to_string();
+        }
+        let parts: Vec<&str> = answer
+            .split(ANSWER_SEPARATOR)
+            .map(str::trim)
+            .filter(|part| !part.is_empty())
+            .collect();
+        if parts.is_empty() {
+            return answer.to_string();
+        }
+
+        let rule = query_type
+            .and_then(detect_question_kind)
+            .map(|kind| kind.answer_rule);
+        let choices = options.map(choice_texts).unwrap_or_default();
+
+        if let [part] = parts.as_slice() {
+            let judgement = match rule {
+                Some(AnswerRule::Judgement) => normalize_judgement(part),
+                // 题型未知时只转换规范形式的判断答案，避免把普通答案中的“对”“是”当作判断
+                None if !choices.iter().any(|choice| choice == part) => match *part {
+                    JUDGEMENT_TRUE => Some(true),
+                    JUDGEMENT_FALSE => Some(false),
+                    _ => None,
+                },
+                _ => None,
+            };
+            if let Some(value) = judgement {
+                return self.judgement(value);
+            }
+        }
+
+        let is_choice = matches!(
+            rule,
+            None | Some(AnswerRule::SingleChoice)
+                | Some(AnswerRule::MultipleChoice)
+                | Some(AnswerRule::Ordering)
+        );
+        if is_choice && self.choice_style != ChoiceStyle::Text {
+            let indexes: Option<Vec<usize>> = parts
+                .iter()
+                .map(|part| choices.iter().position(|choice| choice == part))
+                .collect();
+            let letters: Option<Vec<char>> = indexes
+                .iter()
+                .flatten()
+                .map(|&index| {
+                    char::from_u32('A' as u32 + index as u32).filter(char::is_ascii_uppercase)
+                })
+                .collect();
+            if let (Some(indexes), Some(letters)) = (indexes, letters) {
+                return match self.choice_style {
+                    ChoiceStyle::Letter => letters
+                        .iter()
+                        .map(char::to_string)
+                        .collect::<Vec<_>>()
+                        .join(self.letter_separator.as_deref().unwrap_or(&self.separator)),
+                    _ => indexes
+                        .iter()
+                        .zip(&letters)
+                        .map(|(&index, letter)| format!("{}. {}", letter, choices[index]))
+                        .collect::<Vec<_>>()
+                        .join(&self.separator),
+                };
+            }
+        }
+
+        parts.join(&self.separator)
+    }
+}
+
+/// 全部可用配置档以及默认 / 按用户的选择
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AnswerFormats {
+    pub formats: HashMap<String, AnswerFormat>,
+    pub default_format: String,
+    /// 键为用户 token
+    #[serde(skip)]
+    pub user_formats: HashMap<String, String>,
+}
+
+impl Default for AnswerFormats {
+    fn default() -> Self {
+        let formats = HashMap::from([
+            (CANONICAL_FORMAT_NAME.to_string(), AnswerFormat::default()),
+            ("letters".to_string(), AnswerFormat::letters()),
+            ("symbols".to_string(), AnswerFormat::symbols()),
+        ]);
+        AnswerFormats {
+            formats,
+            default_format: CANONICAL_FORMAT_NAME.to_string(),
+            user_formats: HashMap::new(),
+        }
+    }
+}
+
+impl AnswerFormats {
+    /// 读取内置预设并合并 config.json 中的配置；配置有误的条目会被忽略并打印警告
+    pub fn load() -> Self {
+        let mut formats = AnswerFormats::default();
+
+        if let Some(custom) =
+            read_config_value("answerFormats").and_then(|v| v.as_object().cloned())
+        {
+            for (name, value) in custom {
+                match serde_json::from_value::<AnswerFormat>(value) {
+                    Ok(format) => {
+                        formats.formats.insert(name, format);
+                    }
+                    Err(e) => println!("⚠️ 答案格式 {} 格式错误，已忽略: {}", name, e),
+                }
+            }
+        }
+
+        if let Some(name) =
+            read_config_value("defaultAnswerFormat").and_then(|v| v.as_str().map(str::to_string))
+        {
+            if formats.formats.contains_key(&name) {
+                formats.default_format = name;
+            } else {
+                println!(
+                    "⚠️ 默认答案格式 {} 不存在，使用 {}",
+                    name, CANONICAL_FORMAT_NAME
+                );
+            }
+        }
+
+        let users = read_config_value("multiUser")
+            .and_then(|v| v.get("users").and_then(|u| u.as_array()).cloned())
+            .unwrap_or_default();
+        for user in users {
+            let token = user.get("token").and_then(|v| v.as_str()).unwrap_or("");
+            let Some(name) = user.get("answerFormat").and_then(|v| v.as_str()) else {
+                continue;
+            };
+            if token.is_empty() || name.is_empty() {
+                continue;
+            }
+            if formats.formats.contains_key(name) {
+                formats
+                    .user_formats
+                    .insert(token.to_string(), name.to_string());
+            } else {
+                println!("⚠️ 用户的答案格式 {} 不存在，已忽略", name);
+            }
+        }
+
+        formats
+    }
+
+    /// 确定本次请求使用的配置档：请求指定的优先，其次是 token 对应用户的配置档，否则使用默认配置档。
+    /// 请求指定的配置档不存在时打印警告并按未指定处理。
+    pub fn resolve_request(
+        &self,
+        requested: Option<&str>,
+        token: Option<&str>,
+    ) -> (String, AnswerFormat) {
+        let requested = requested.map(str::trim).filter(|name| !name.is_empty());
+        if let Some(name) = requested.filter(|name| !self.formats.contains_key(*name)) {
+            println!("⚠️ 未知的答案格式: {}，已忽略", name);
+        }
+        let name = requested
+            .filter(|name| self.formats.contains_key(*name))
+            .or_else(|| {
+                token
+                    .and_then(|token| self.user_formats.get(token.trim()))
+                    .map(String::as_str)
+            })
+            .unwrap_or(&self.default_format);
+        let format = self.formats.get(name).cloned().unwrap_or_default();
+        (name.to_string(), format)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn answer_formats_rewrite_served_answers() {
+        let options = Some("A. 北京\nB. 上海\nC. 广州\nD. 深圳");
+        let canonical = AnswerFormat::default();
+        assert_eq!(
+            canonical.format_answer(" 北京###上海 ", options, Some("multiple")),
+            " 北京###上海 "
+        );
+
+        let letters = AnswerFormat::letters();
+        assert_eq!(
+            letters.format_answer("北京###深圳", options, Some("multiple")),
+            "AD"
+        );
+        assert_eq!(letters.format_answer("上海", options, None), "B");
+        // 无法对应到选项的答案保留原内容
+        assert_eq!(
+            letters.format_answer("北京###天津", options, Some("multiple")),
+            "北京###天津"
+        );
+        assert_eq!(letters.format_answer("正确", None, Some("判断题")), "正确");
+
+        let custom = AnswerFormat {
+            separator: "#".to_string(),
+            judgement_true: "对".to_string(),
+            judgement_false: "错".to_string(),
+            choice_style: ChoiceStyle::LetterAndText,
+            ..Default::default()
+        };
+        assert_eq!(
+            custom.format_answer("北京###上海", options, Some("1")),
+            "A. 北京#B. 上海"
+        );
+        assert_eq!(custom.format_answer("错误", None, Some("判断题")), "错");
+        assert_eq!(custom.format_answer("√", None, Some("judgement")), "对");
+        assert_eq!(custom.format_answer("正确", None, None), "对");
+        assert_eq!(custom.format_answer("对", None, None), "对");
+        assert_eq!(
+            custom.format_answer("水###二氧化碳", None, Some("completion")),
+            "水#二氧化碳"
+        );
+        assert_eq!(
+            AnswerFormat::symbols().format_answer("错误", None, Some("judgement")),
+            "×"
+        );
+    }
+
+    #[test]
+    fn answer_format_is_chosen_by_request_then_user_token() {
+        let mut formats = AnswerFormats::default();
+        formats
+            .user_formats
+            .insert("user-token".to_string(), "symbols".to_string());
+
+        let name = |requested: Option<&str>, token: Option<&str>| {
+            formats.resolve_request(requested, token).0
+        };
+        assert_eq!(name(None, None), "canonical");
+        assert_eq!(name(None, Some(" user-token ")), "symbols");
+        assert_eq!(name(Some("letters"), Some("user-token")), "letters");
+        assert_eq!(name(Some("missing"), Some("user-token")), "symbols");
+        assert_eq!(name(Some(" "), Some("other-token")), "canonical");
+
+        formats.default_format = "letters".to_string();
+        assert_eq!(
+            formats.resolve_request(None, None).1,
+            AnswerFormat::letters()
+        );
+    }
+}
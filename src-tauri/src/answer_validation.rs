@@ -0,0 +1,404 @@
+//! AI 答案校验：按题型检查提取出的答案是否合理。选择题的答案必须能对应到给出的选项
+//! （选项字母会还原为选项内容），判断题的答案统一为“正确”/“错误”。
+//!
+//! config.json 中的配置：
+//! ```json
+//! { "answerValidation": { "enabled": true, "onInvalid": "retry", "maxRetries": 1 } }
+//! ```
+//! 默认不启用。未通过校验的答案按 onInvalid 处理：flag 直接保存并标记为待纠错；retry 带上失败原因
+//! 重新询问模型（提示词见 promptTemplates.retry），重试次数用完、重试出错或模型未给出答案时
+//! 保存上一次的答案并标记为待纠错。
+
+use serde::{Deserialize, Serialize};
+use strsim::normalized_levenshtein;
+
+use crate::database::read_config_value;
+use crate::question_kind::AnswerRule;
+use crate::question_options::{choice_texts, parse_choices};
+use crate::text_normalize::normalize_text;
+
+/// 多个答案之间的分隔符
+pub const ANSWER_SEPARATOR: &str = "###";
+pub const JUDGEMENT_TRUE: &str = "正确";
+pub const JUDGEMENT_FALSE: &str = "错误";
+
+/// 多个选项字母之间可能出现的分隔符与括号
+const LETTER_SEPARATORS: &[char] = &[
+    ',', '，', '、', ';', '；', '/', '(', ')', '（', '）', '.', '。', '．',
+];
+
+/// 答案与选项内容的字符相似度达到该值时视为同一选项（模型常常漏掉或改写标点）
+const FUZZY_CHOICE_SIMILARITY: f64 = 0.9;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum InvalidAnswerAction {
+    /// 保存答案并标记为待纠错
+    #[default]
+    Flag,
+    /// 重新询问模型
+    Retry,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct AnswerValidationConfig {
+    pub enabled: bool,
+    pub on_invalid: InvalidAnswerAction,
+    /// onInvalid 为 retry 时最多重新询问的次数
+    pub max_retries: u32,
+}
+
+impl Default for AnswerValidationConfig {
+    fn default() -> Self {
+        AnswerValidationConfig {
+            enabled: false,
+            on_invalid: InvalidAnswerAction::Flag,
+            max_retries: 1,
+        }
+    }
+}
+
+impl AnswerValidationConfig {
+    /// 读取 config.json 中的 answerValidation 配置，缺失或格式错误时使用默认配置
+    pub fn load() -> Self {
+        let Some(value) = read_config_value("answerValidation") else {
+            return AnswerValidationConfig::default();
+        };
+        serde_json::from_value(value).unwrap_or_else(|e| {
+            println!("⚠️ 答案校验配置格式错误，使用默认配置: {}", e);
+            AnswerValidationConfig::default()
+        })
+    }
+
+    /// 校验失败时还能重新询问的次数
+    pub fn retry_limit(&self) -> u32 {
+        match self.on_invalid {
+            InvalidAnswerAction::Retry if self.enabled => self.max_retries,
+            _ => 0,
+        }
+    }
+}
+
+/// 校验结果：answer 为规范化后的答案（选项字母已还原为选项内容），
+/// problem 为未通过校验的原因，通过时为 None
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidatedAnswer {
+    pub answer: String,
+    pub problem: Option<String>,
+}
+
+impl ValidatedAnswer {
+    fn valid(answer: String) -> Self {
+        ValidatedAnswer {
+            answer,
+            problem: None,
+        }
+    }
+
+    fn invalid(answer: &str, problem: String) -> Self {
+        ValidatedAnswer {
+            answer: answer.to_string(),
+            problem: Some(problem),
+        }
+    }
+}
+
+/// 判断题答案的规范形式：对/√/true 等为 true，错/×/false 等为 false，无法识别时返回 None
+pub fn normalize_judgement(answer: &str) -> Option<bool> {
+    let normalized = normalize_text(answer).to_lowercase();
+    let normalized = normalized.trim_matches(|c: char| {
+        c.is_whitespace() || matches!(c, '.' | '!' | '"' | '\'' | '*' | '[' | ']' | '(' | ')')
+    });
+    match normalized {
+        "正确" | "对" | "对的" | "是" | "√" | "✓" | "✔" | "true" | "t" | "yes" | "y" | "right"
+        | "correct" => Some(true),
+        "错误" | "错" | "错的" | "否" | "不对" | "不正确" | "×" | "✗" | "✘" | "x" | "false"
+        | "f" | "no" | "n" | "wrong" | "incorrect" => Some(false),
+        _ => None,
+    }
+}
+
+fn judgement_text(value: bool) -> &'static str {
+    if value {
+        JUDGEMENT_TRUE
+    } else {
+        JUDGEMENT_FALSE
+    }
+}
+
+/// 答案中的选项字母（“A”、“ABD”、“A、B、D”、“选C”）。每一段必须是单个字母或不重复的大写字母
+/// （“bad”、“Ace” 等英文单词不是选项字母），且每个字母都对应一个选项，否则返回 None
+fn choice_letters(part: &str, choice_count: usize) -> Option<Vec<usize>> {
+    let part = part.trim();
+    let part = part
+        .strip_prefix("选项")
+        .or_else(|| part.strip_prefix('选'))
+        .unwrap_or(part);
+    let tokens: Vec<&str> = part
+        .split(|c: char| c.is_whitespace() || LETTER_SEPARATORS.contains(&c))
+        .filter(|token| !token.is_empty())
+        .collect();
+    if tokens.is_empty() {
+        return None;
+    }
+
+    let mut indexes = Vec::new();
+    for token in tokens {
+        let letters: Vec<char> = token.chars().collect();
+        let is_letter = matches!(letters.as_slice(), [c] if c.is_ascii_alphabetic());
+        let is_packed = letters.iter().all(char::is_ascii_uppercase) && {
+            let mut unique = letters.clone();
+            unique.sort_unstable();
+            unique.dedup();
+            unique.len() == letters.len()
+        };
+        if !is_letter && !is_packed {
+            return None;
+        }
+        for c in letters {
+            let index = (c.to_ascii_uppercase() as usize) - ('A' as usize);
+            if index >= choice_count {
+                return None;
+            }
+            indexes.push(index);
+        }
+    }
+    Some(indexes)
+}
+
+/// 答案中的一段对应的选项序号：先按选项内容比较（可带 “A.” 序号），再按选项字母，最后按字符相似度
+fn resolve_part(part: &str, choices: &[String]) -> Option<Vec<usize>> {
+    let parsed = parse_choices(part);
+    if let [text] = parsed.as_slice() {
+        if let Some(index) = choices.iter().position(|choice| choice == text) {
+            return Some(vec![index]);
+        }
+    }
+
+    if let Some(indexes) = choice_letters(part, choices.len()) {
+        return Some(indexes);
+    }
+
+    let text = parsed.join(" ");
+    choices
+        .iter()
+        .enumerate()
+        .map(|(index, choice)| (index, normalized_levenshtein(&text, choice)))
+        .filter(|(_, similarity)| *similarity >= FUZZY_CHOICE_SIMILARITY)
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(index, _)| vec![index])
+}
+
+/// 把答案的每一段按作答顺序对应到选项序号，有一段无法对应时返回该段
+fn resolve_in_order(parts: &[&str], choices: &[String]) -> Result<Vec<usize>, String> {
+    let mut indexes = Vec::new();
+    for part in parts {
+        let resolved = resolve_part(part, choices).ok_or_else(|| part.to_string())?;
+        indexes.extend(resolved);
+    }
+    Ok(indexes)
+}
+
+/// 与 resolve_in_order 相同，但去重并按选项顺序排列
+fn resolve_choices(parts: &[&str], choices: &[String]) -> Result<Vec<usize>, String> {
+    let mut indexes = resolve_in_order(parts, choices)?;
+    indexes.sort_unstable();
+    indexes.dedup();
+    Ok(indexes)
+}
+
+/// 按题型的答案要求校验答案。没有选项的选择题、未知题型的题目只做选项字母还原，不判为无效。
+pub fn validate_answer(
+    answer: &str,
+    options: Option<&str>,
+    rule: Option<AnswerRule>,
+) -> ValidatedAnswer {
+    let answer = answer.trim();
+    let parts: Vec<&str> = answer
+        .split(ANSWER_SEPARATOR)
+        .map(str::trim)
+        .filter(|part| !part.is_empty())
+        .collect();
+    if parts.is_empty() {
+        return ValidatedAnswer::invalid(answer, "答案为空".to_string());
+    }
+
+    let choices = options.map(parse_choices).unwrap_or_default();
+    let display = options.map(choice_texts).unwrap_or_default();
+    let join_choices = |indexes: &[usize]| -> String {
+        indexes
+            .iter()
+            .map(|&index| display[index].clone())
+            .collect::<Vec<_>>()
+            .join(ANSWER_SEPARATOR)
+    };
+    // 两种拆分方式得到的选项数量一致时才能按序号还原选项内容
+    let has_choices = !choices.is_empty() && choices.len() == display.len();
+
+    match rule {
+        Some(AnswerRule::SingleChoice) if has_choices => {
+            if parts.len() > 1 {
+                return ValidatedAnswer::invalid(answer, "单选题给出了多个答案".to_string());
+            }
+            match resolve_choices(&parts, &choices) {
+                Ok(indexes) if indexes.len() == 1 => ValidatedAnswer::valid(join_choices(&indexes)),
+                Ok(_) => ValidatedAnswer::invalid(answer, "单选题给出了多个答案".to_string()),
+                Err(part) => ValidatedAnswer::invalid(answer, format!("答案“{}”不在选项中", part)),
+            }
+        }
+        Some(AnswerRule::MultipleChoice) if has_choices => {
+            match resolve_choices(&parts, &choices) {
+                Ok(indexes) => ValidatedAnswer::valid(join_choices(&indexes)),
+                Err(part) => ValidatedAnswer::invalid(answer, format!("答案“{}”不在选项中", part)),
+            }
+        }
+        Some(AnswerRule::Judgement) => {
+            if parts.len() > 1 {
+                return ValidatedAnswer::invalid(answer, "判断题给出了多个答案".to_string());
+            }
+            // 选项为“A. 对 B. 错”时，答案可能是选项字母
+            let from_choice = || {
+                let indexes = resolve_choices(&parts, &choices).ok()?;
+                match indexes.as_slice() {
+                    [index] if has_choices => normalize_judgement(&display[*index]),
+                    _ => None,
+                }
+            };
+            match normalize_judgement(answer).or_else(from_choice) {
+                Some(value) => ValidatedAnswer::valid(judgement_text(value).to_string()),
+                None => ValidatedAnswer::invalid(
+                    answer,
+                    format!("判断题答案不是“{}”或“{}”", JUDGEMENT_TRUE, JUDGEMENT_FALSE),
+                ),
+            }
+        }
+        Some(AnswerRule::Ordering) if has_choices => match resolve_in_order(&parts, &choices) {
+            Ok(indexes) => {
+                let mut unique = indexes.clone();
+                unique.sort_unstable();
+                unique.dedup();
+                if unique.len() != indexes.len() {
+                    ValidatedAnswer::invalid(answer, "排序题答案中有重复的选项".to_string())
+                } else if indexes.len() != choices.len() {
+                    ValidatedAnswer::invalid(answer, "排序题答案没有包含全部选项".to_string())
+                } else {
+                    ValidatedAnswer::valid(join_choices(&indexes))
+                }
+            }
+            Err(part) => ValidatedAnswer::invalid(answer, format!("答案“{}”不在选项中", part)),
+        },
+        Some(AnswerRule::FreeText) => ValidatedAnswer::valid(answer.to_string()),
+        None if has_choices => match resolve_choices(&parts, &choices) {
+            Ok(indexes) => ValidatedAnswer::valid(join_choices(&indexes)),
+            Err(_) => ValidatedAnswer::valid(answer.to_string()),
+        },
+        _ => ValidatedAnswer::valid(parts.join(ANSWER_SEPARATOR)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_single_or_packed_capital_letters_are_choice_letters() {
+        assert_eq!(choice_letters("A、b、D", 4), Some(vec![0, 1, 3]));
+        assert_eq!(choice_letters("选 ABD", 4), Some(vec![0, 1, 3]));
+        assert_eq!(choice_letters("bad", 4), None);
+        assert_eq!(choice_letters("Ace", 5), None);
+        assert_eq!(choice_letters("AAB", 4), None);
+        assert_eq!(choice_letters("E", 4), None);
+        assert_eq!(choice_letters("A E", 4), None);
+
+        // 英文单词答案按选项内容匹配，不会被当作选项字母 B、A、D
+        let options = "A. bed\nB. bad\nC. bid\nD. bud";
+        let single = validate_answer("bad", Some(options), Some(AnswerRule::SingleChoice));
+        assert_eq!(single.answer, "bad");
+        assert_eq!(single.problem, None);
+        let multiple = validate_answer("Dab", Some(options), Some(AnswerRule::MultipleChoice));
+        assert!(multiple.problem.is_some());
+    }
+
+    #[test]
+    fn validation_is_off_by_default() {
+        let config = AnswerValidationConfig::default();
+        assert!(!config.enabled);
+        assert_eq!(config.retry_limit(), 0);
+    }
+
+    #[test]
+    fn validates_ai_answers_against_options_and_kind() {
+        let options = "A. 北京\nB. 上海\nC. 广州\nD. DNA 聚合酶";
+        let check =
+            |answer: &str, rule: AnswerRule| validate_answer(answer, Some(options), Some(rule));
+
+        // 单选：字母还原为选项内容，选项内容原样接受，不在选项中或多个答案时无效
+        assert_eq!(check("B", AnswerRule::SingleChoice).answer, "上海");
+        assert_eq!(check("选 C。", AnswerRule::SingleChoice).answer, "广州");
+        assert_eq!(
+            check("D. dna 聚合酶", AnswerRule::SingleChoice).answer,
+            "DNA 聚合酶"
+        );
+        assert_eq!(check("北京", AnswerRule::SingleChoice).problem, None);
+        assert!(check("深圳", AnswerRule::SingleChoice).problem.is_some());
+        assert!(check("北京###上海", AnswerRule::SingleChoice)
+            .problem
+            .is_some());
+        assert!(check("AB", AnswerRule::SingleChoice).problem.is_some());
+        assert!(check("E", AnswerRule::SingleChoice).problem.is_some());
+
+        // 多选：每一段都必须在选项中，字母与内容混用时按选项顺序去重
+        let multiple = check("C###A、B###上海", AnswerRule::MultipleChoice);
+        assert_eq!(multiple.answer, "北京###上海###广州");
+        assert_eq!(multiple.problem, None);
+        assert_eq!(
+            check("ABD", AnswerRule::MultipleChoice).answer,
+            "北京###上海###DNA 聚合酶"
+        );
+        let invalid = check("北京###杭州", AnswerRule::MultipleChoice);
+        assert_eq!(invalid.problem.as_deref(), Some("答案“杭州”不在选项中"));
+        assert_eq!(invalid.answer, "北京###杭州");
+
+        // 判断：统一为正确/错误，选项字母按选项内容判断，长段文字无效
+        let judge = |answer: &str, options: Option<&str>| {
+            validate_answer(answer, options, Some(AnswerRule::Judgement))
+        };
+        assert_eq!(judge("√", None).answer, "正确");
+        assert_eq!(judge("False.", None).answer, "错误");
+        assert_eq!(judge("B", Some("A. 对\nB. 错")).answer, "错误");
+        assert!(judge("这个说法基本正确，但忽略了例外情况", None)
+            .problem
+            .is_some());
+        assert_eq!(normalize_judgement("**对**"), Some(true));
+        assert_eq!(normalize_judgement("不一定"), None);
+
+        // 没有选项或题型未知时不判为无效
+        assert_eq!(
+            validate_answer("C", None, Some(AnswerRule::SingleChoice)).problem,
+            None
+        );
+        assert_eq!(validate_answer("深圳", Some(options), None).problem, None);
+        assert_eq!(validate_answer("A", Some(options), None).answer, "北京");
+        assert_eq!(
+            validate_answer(" 氧气 ### 氢气 ", None, Some(AnswerRule::Blanks)).answer,
+            "氧气###氢气"
+        );
+    }
+
+    #[test]
+    fn ordering_answers_must_use_every_option_once() {
+        let options = "A. 播种\nB. 发芽\nC. 开花\nD. 结果";
+        let check =
+            |answer: &str| validate_answer(answer, Some(options), Some(AnswerRule::Ordering));
+
+        assert_eq!(check("ABCD").answer, "播种###发芽###开花###结果");
+        assert_eq!(
+            check("发芽###播种###D###C").answer,
+            "发芽###播种###结果###开花"
+        );
+        assert!(check("ABC").problem.is_some());
+        assert!(check("AABC").problem.is_some());
+        assert!(check("播种###发芽###开花###枯萎").problem.is_some());
+    }
+}
@@ -1,12 +1,27 @@
-use jieba_rs::Jieba;
 use regex::Regex;
 use rusqlite::{Connection, OptionalExtension};
+use crate::embeddings::{
+    cosine_similarity, decode_vector, embed_query, embeddings_enabled, encode_vector,
+    QueryEmbedding,
+};
+use crate::image_hash::{hamming_distance, hash_query_images};
 use crate::logger::RequestLog;
+use crate::matching_profile::{MatchingProfile, MatchingProfiles};
+use crate::question_kind::{question_kind_id, KindRegistry};
+use crate::question_options::{
+    choice_set_similarity, choices_from_normalized, normalize_options, parse_choices,
+    same_choice_set,
+};
+use crate::ranking_policy::{RankInput, RankKey, RankingPolicy};
+use crate::search_query::{FieldLengths, HighlightSpan, SearchQuery};
+use crate::segmenter::{refresh_segmenter_if_changed, segmenter, Segmenter};
+use crate::text_normalize::{clean_stored_text, contains_html, html_to_text, normalize_text};
+use crate::url_canonical::UrlCanonicalizer;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
 use std::env;
-use std::sync::OnceLock;
+use std::sync::Arc;
 use strsim::normalized_levenshtein;
 
 /// 提取字符串中所有 URL（http/https），返回排序后的列表
@@ -31,6 +46,22 @@ fn extract_urls(text: &str) -> Vec<String> {
     urls
 }
 
+/// 提取字符串中所有 URL 并转换为规范形式，返回排序后的列表
+fn canonical_urls(canonicalizer: &UrlCanonicalizer, text: &str) -> Vec<String> {
+    let mut urls: Vec<String> = extract_urls(text)
+        .iter()
+        .map(|url| canonicalizer.canonicalize(url))
+        .collect();
+    urls.sort();
+    urls
+}
+
+// CanonicalUrls 列的存储形式：规范 URL 按行拼接，题目不含 URL 时为空串。
+// 写入时同时把 canonicalizer.fingerprint() 写入 CanonicalUrlsRules
+fn stored_canonical_urls(canonicalizer: &UrlCanonicalizer, question: &str) -> String {
+    canonical_urls(canonicalizer, question).join("\n")
+}
+
 /// 将字符串中所有 URL 替换为统一占位符，用于相似度比较
 fn normalize_urls(text: &str) -> String {
     let re = Regex::new(r"https?://[^\s]+").unwrap();
@@ -38,71 +69,6 @@ fn normalize_urls(text: &str) -> String {
     re.replace_all(text, "__URL__").to_string()
 }
 
-static JIEBA: OnceLock<Jieba> = OnceLock::new();
-
-const QUERY_STOPWORDS: &[&str] = &[
-    "的",
-    "地",
-    "得",
-    "了",
-    "着",
-    "吗",
-    "呢",
-    "啊",
-    "呀",
-    "吧",
-    "么",
-    "嘛",
-    "在",
-    "是",
-    "和",
-    "与",
-    "及",
-    "或",
-    "并",
-    "且",
-    "将",
-    "把",
-    "被",
-    "由",
-    "对",
-    "于",
-    "中",
-    "上",
-    "下",
-    "请问",
-    "哪里",
-    "哪儿",
-    "哪个",
-    "哪种",
-    "哪项",
-    "哪些",
-    "什么",
-    "怎么",
-    "怎样",
-    "如何",
-    "为何",
-    "为什么",
-    "多少",
-    "几",
-    "一下",
-    "以下",
-    "下列",
-    "题目",
-    "选项",
-    "答案",
-    "内容",
-    "说法",
-    "图片",
-    "图中",
-    "名字",
-    "名称",
-    "城市",
-    "国家",
-    "地区",
-    "地方",
-];
-
 const QUESTION_AND_OPTIONS_MATCH_KEYWORDS: &[&str] = &["以下", "下列", "下面", "下叙"];
 
 fn should_require_option_match(title: &str) -> bool {
@@ -144,9 +110,9 @@ fn is_punctuation_or_space(c: char) -> bool {
         )
 }
 
-fn is_meaningful_query_token(token: &str) -> bool {
+fn is_meaningful_query_token(segmenter: &Segmenter, token: &str) -> bool {
     let trimmed = token.trim();
-    if trimmed.is_empty() || QUERY_STOPWORDS.contains(&trimmed) {
+    if trimmed.is_empty() || segmenter.is_stopword(trimmed) {
         return false;
     }
     if trimmed.chars().all(is_punctuation_or_space) {
@@ -165,12 +131,13 @@ fn is_meaningful_query_token(token: &str) -> bool {
 }
 
 fn extract_query_keywords(text: &str) -> HashSet<String> {
-    let jieba = JIEBA.get_or_init(Jieba::new);
-    let mut keywords: Vec<String> = jieba
-        .cut_for_search(text, false)
+    let segmenter = segmenter();
+    let text = normalize_text(text);
+    let mut keywords: Vec<String> = segmenter
+        .cut_for_search(&text)
         .into_iter()
         .map(|token| token.trim().to_lowercase())
-        .filter(|token| is_meaningful_query_token(token))
+        .filter(|token| is_meaningful_query_token(&segmenter, token))
         .collect();
 
     keywords.sort_by(|a, b| {
@@ -196,57 +163,69 @@ fn keyword_coverage(query_keywords: &HashSet<String>, candidate_keywords: &HashS
     matched as f64 / query_keywords.len() as f64
 }
 
-fn min_keyword_coverage(query_keywords_len: usize, char_similarity: f64) -> f64 {
-    if query_keywords_len <= 2 {
-        return 1.0;
-    }
-
-    // 高字符相似但关键词数量不多时，要求所有关键词都命中，
-    // 避免“题干几乎一致但核心词不同”的题目误匹配。
-    if char_similarity >= 0.88 && query_keywords_len <= 8 {
-        return 1.0;
-    }
-
-    if query_keywords_len <= 4 {
-        return 1.0;
-    }
-
-    if query_keywords_len <= 8 {
-        return 0.9;
-    }
-
-    0.75
+/// 题目匹配分数的计算过程，用于匹配解释
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct QueryMatchDetail {
+    pub char_similarity: Option<f64>,
+    pub keyword_coverage: Option<f64>,
+    pub min_keyword_coverage: Option<f64>,
+    pub score: Option<f64>,
+    pub rejection: Option<String>,
 }
 
-fn compute_query_match_score(query: &str, candidate: &str) -> Option<f64> {
-    let normalized_query = normalize_urls(query).trim().to_lowercase();
-    let normalized_candidate = normalize_urls(candidate).trim().to_lowercase();
+fn explain_query_match_score(
+    query: &str,
+    candidate: &str,
+    profile: &MatchingProfile,
+) -> QueryMatchDetail {
+    let normalized_query = normalize_urls(&normalize_text(query)).to_lowercase();
+    let normalized_candidate = normalize_urls(&normalize_text(candidate)).to_lowercase();
+    let mut detail = QueryMatchDetail::default();
 
     if normalized_query.is_empty() || normalized_candidate.is_empty() {
-        return None;
+        detail.rejection = Some("文本为空".to_string());
+        return detail;
     }
     if normalized_query == normalized_candidate {
-        return Some(1.0);
+        detail.char_similarity = Some(1.0);
+        detail.score = Some(1.0);
+        return detail;
     }
 
     let char_similarity = normalized_levenshtein(&normalized_query, &normalized_candidate);
-    if char_similarity < 0.72 {
-        return None;
+    detail.char_similarity = Some(char_similarity);
+    if char_similarity < profile.min_char_similarity {
+        detail.rejection = Some(format!(
+            "字符相似度 {:.3} 低于 {}",
+            char_similarity, profile.min_char_similarity
+        ));
+        return detail;
     }
 
     let query_keywords = extract_query_keywords(&normalized_query);
     if query_keywords.is_empty() {
-        return Some(char_similarity);
+        detail.score = Some(char_similarity);
+        return detail;
     }
 
     let candidate_keywords = extract_query_keywords(&normalized_candidate);
     let coverage = keyword_coverage(&query_keywords, &candidate_keywords);
-    let min_coverage = min_keyword_coverage(query_keywords.len(), char_similarity);
+    let min_coverage = profile.min_keyword_coverage(query_keywords.len(), char_similarity);
+    detail.keyword_coverage = Some(coverage);
+    detail.min_keyword_coverage = Some(min_coverage);
     if coverage + f64::EPSILON < min_coverage {
-        return None;
+        detail.rejection = Some(format!(
+            "关键词覆盖率 {:.3} 低于要求的 {:.3}",
+            coverage, min_coverage
+        ));
+        return detail;
     }
 
-    Some(char_similarity * 0.7 + coverage * 0.3)
+    detail.score = Some(
+        char_similarity * profile.char_similarity_weight
+            + coverage * (1.0 - profile.char_similarity_weight),
+    );
+    detail
 }
 
 fn is_exact_match_score(score: f64) -> bool {
@@ -265,6 +244,11 @@ pub struct AIResponse {
     pub create_time: Option<String>,
     pub is_ai: bool,
     pub is_pending_correction: bool,
+    /// 人工核实过的题目：排序时总是优先，且不会被 AI 重新作答覆盖
+    pub is_verified: bool,
+    /// 由 question_type 识别出的题型 id，无法识别时为空
+    #[serde(default)]
+    pub question_kind: Option<String>,
 }
 
 fn map_ai_response_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<AIResponse> {
@@ -279,6 +263,8 @@ fn map_ai_response_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<AIResponse>
         create_time: row.get(7)?,
         is_ai: row.get(8)?,
         is_pending_correction: row.get(9)?,
+        is_verified: row.get(10)?,
+        question_kind: row.get(11)?,
     })
 }
 
@@ -297,6 +283,28 @@ pub struct FolderStat {
     pub question_count: i64,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SearchHit {
+    #[serde(flatten)]
+    pub item: AIResponse,
+    pub score: f64,
+    pub highlights: Vec<HighlightSpan>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PaginatedSearchResults {
+    pub items: Vec<SearchHit>,
+    pub total: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Tag {
+    pub id: i64,
+    pub name: String,
+    pub question_count: i64,
+    pub create_time: Option<String>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct FolderPathItem {
     pub id: i64,
@@ -326,6 +334,14 @@ fn get_db_path() -> String {
     }
 }
 
+/// 数据目录（数据库所在目录），用户词典等配置文件也放在这里
+pub(crate) fn get_data_dir() -> std::path::PathBuf {
+    std::path::Path::new(&get_db_path())
+        .parent()
+        .map(|dir| dir.to_path_buf())
+        .unwrap_or_default()
+}
+
 fn get_conn() -> Result<Connection, String> {
     let db_path = get_db_path();
     Connection::open(&db_path).map_err(|e| format!("{}", e))
@@ -353,6 +369,7 @@ fn map_request_log_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<RequestLog>
         ip: row.get(9)?,
         user_agent: row.get(10)?,
         stage: row.get(11)?,
+        matching_profile: row.get(12)?,
     })
 }
 
@@ -366,8 +383,8 @@ pub fn insert_request_log(log: &RequestLog, max_logs: usize) -> Result<(), Strin
         .map_err(|e| format!("{}", e))?;
 
     conn.execute(
-        "INSERT INTO RequestLogs (RequestId, Timestamp, Method, Path, Status, ResponseTime, RequestBody, ResponseBody, Headers, Ip, UserAgent, Stage)
-         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        "INSERT INTO RequestLogs (RequestId, Timestamp, Method, Path, Status, ResponseTime, RequestBody, ResponseBody, Headers, Ip, UserAgent, Stage, MatchingProfile)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
         rusqlite::params![
             log.id,
             log.timestamp.to_rfc3339(),
@@ -381,6 +398,7 @@ pub fn insert_request_log(log: &RequestLog, max_logs: usize) -> Result<(), Strin
             log.ip,
             log.user_agent,
             log.stage,
+            log.matching_profile,
         ],
     )
     .map_err(|e| format!("{}", e))?;
@@ -401,7 +419,7 @@ pub fn load_persisted_request_logs(limit: Option<usize>) -> Result<Vec<RequestLo
     let limit = limit.unwrap_or(1000).max(1) as i64;
     let mut stmt = conn
         .prepare(
-            "SELECT RequestId, Timestamp, Method, Path, Status, ResponseTime, RequestBody, ResponseBody, Headers, Ip, UserAgent, Stage
+            "SELECT RequestId, Timestamp, Method, Path, Status, ResponseTime, RequestBody, ResponseBody, Headers, Ip, UserAgent, Stage, MatchingProfile
              FROM RequestLogs
              ORDER BY LogId DESC
              LIMIT ?",
@@ -496,15 +514,16 @@ pub async fn get_folders() -> Result<Vec<Folder>, String> {
 pub async fn get_ai_responses(folder_id: Option<i64>) -> Result<Vec<AIResponse>, String> {
     let conn = get_conn()?;
     let query = if folder_id.is_some() {
-        "SELECT ar.Id, ar.Question, ar.Options, ar.Answer, ar.QuestionType, ar.FolderId, f.Name as FolderName, ar.CreateTime, ar.IsAi, COALESCE(ar.IsPendingCorrection, 0)
+        "SELECT ar.Id, ar.Question, ar.Options, ar.Answer, ar.QuestionType, ar.FolderId, f.Name as FolderName, ar.CreateTime, ar.IsAi, COALESCE(ar.IsPendingCorrection, 0), COALESCE(ar.IsVerified, 0), ar.QuestionKind
          FROM AIResponses ar
          LEFT JOIN Folders f ON ar.FolderId = f.Id
-         WHERE ar.FolderId = ?
+         WHERE ar.FolderId = ? AND ar.DeletedAt IS NULL
          ORDER BY ar.CreateTime DESC"
     } else {
-        "SELECT ar.Id, ar.Question, ar.Options, ar.Answer, ar.QuestionType, ar.FolderId, f.Name as FolderName, ar.CreateTime, ar.IsAi, COALESCE(ar.IsPendingCorrection, 0)
+        "SELECT ar.Id, ar.Question, ar.Options, ar.Answer, ar.QuestionType, ar.FolderId, f.Name as FolderName, ar.CreateTime, ar.IsAi, COALESCE(ar.IsPendingCorrection, 0), COALESCE(ar.IsVerified, 0), ar.QuestionKind
          FROM AIResponses ar
          LEFT JOIN Folders f ON ar.FolderId = f.Id
+         WHERE ar.DeletedAt IS NULL
          ORDER BY ar.CreateTime DESC"
     };
 
@@ -533,8 +552,10 @@ pub async fn get_paginated_questions(
     page: i64,
     page_size: i64,
     sort_order: Option<String>,
+    tag_ids: Option<Vec<i64>>,
 ) -> Result<PaginatedAIResponses, String> {
     let conn = get_conn()?;
+    let tag_filter = tag_filter_clause(tag_ids.as_deref().unwrap_or_default());
     let page = page.max(1);
     let page_size = page_size.clamp(1, 200);
     let offset = (page - 1) * page_size;
@@ -547,20 +568,23 @@ pub async fn get_paginated_questions(
     let (total, items) = if pending_correction_only {
         let total: i64 = conn
             .query_row(
-                "SELECT COUNT(*) FROM AIResponses WHERE COALESCE(IsPendingCorrection, 0) = 1",
+                &format!(
+                    "SELECT COUNT(*) FROM AIResponses ar WHERE COALESCE(ar.IsPendingCorrection, 0) = 1 AND ar.DeletedAt IS NULL{}",
+                    tag_filter
+                ),
                 [],
                 |row| row.get(0),
             )
             .map_err(|e| format!("{}", e))?;
 
         let data_query = format!(
-            "SELECT ar.Id, ar.Question, ar.Options, ar.Answer, ar.QuestionType, ar.FolderId, f.Name as FolderName, ar.CreateTime, ar.IsAi, COALESCE(ar.IsPendingCorrection, 0)
+            "SELECT ar.Id, ar.Question, ar.Options, ar.Answer, ar.QuestionType, ar.FolderId, f.Name as FolderName, ar.CreateTime, ar.IsAi, COALESCE(ar.IsPendingCorrection, 0), COALESCE(ar.IsVerified, 0), ar.QuestionKind
              FROM AIResponses ar
              LEFT JOIN Folders f ON ar.FolderId = f.Id
-             WHERE COALESCE(ar.IsPendingCorrection, 0) = 1
+             WHERE COALESCE(ar.IsPendingCorrection, 0) = 1 AND ar.DeletedAt IS NULL{}
              ORDER BY ar.CreateTime {}
              LIMIT ? OFFSET ?",
-            sort_direction
+            tag_filter, sort_direction
         );
 
         let mut stmt = conn.prepare(&data_query).map_err(|e| format!("{}", e))?;
@@ -578,20 +602,23 @@ pub async fn get_paginated_questions(
         if folder_id == 0 {
             let total: i64 = conn
                 .query_row(
-                    "SELECT COUNT(*) FROM AIResponses WHERE FolderId = 0",
+                    &format!(
+                        "SELECT COUNT(*) FROM AIResponses ar WHERE ar.FolderId = 0 AND ar.DeletedAt IS NULL{}",
+                        tag_filter
+                    ),
                     [],
                     |row| row.get(0),
                 )
                 .map_err(|e| format!("{}", e))?;
 
             let data_query = format!(
-                "SELECT ar.Id, ar.Question, ar.Options, ar.Answer, ar.QuestionType, ar.FolderId, f.Name as FolderName, ar.CreateTime, ar.IsAi, COALESCE(ar.IsPendingCorrection, 0)
+                "SELECT ar.Id, ar.Question, ar.Options, ar.Answer, ar.QuestionType, ar.FolderId, f.Name as FolderName, ar.CreateTime, ar.IsAi, COALESCE(ar.IsPendingCorrection, 0), COALESCE(ar.IsVerified, 0), ar.QuestionKind
                  FROM AIResponses ar
                  INNER JOIN Folders f ON ar.FolderId = f.Id
-                 WHERE ar.FolderId = 0
+                 WHERE ar.FolderId = 0 AND ar.DeletedAt IS NULL{}
                  ORDER BY ar.CreateTime {}
                  LIMIT ? OFFSET ?",
-                sort_direction
+                tag_filter, sort_direction
             );
 
             let mut stmt = conn.prepare(&data_query).map_err(|e| format!("{}", e))?;
@@ -608,15 +635,19 @@ pub async fn get_paginated_questions(
         } else {
             let total: i64 = conn
                 .query_row(
-                    "WITH RECURSIVE folder_tree AS (
-                       SELECT Id, Name, ParentId FROM Folders WHERE Id = ?
-                       UNION ALL
-                       SELECT f.Id, f.Name, f.ParentId FROM Folders f
-                       INNER JOIN folder_tree ft ON f.ParentId = ft.Id
-                     )
-                     SELECT COUNT(*)
-                     FROM AIResponses ar
-                     INNER JOIN folder_tree ft ON ar.FolderId = ft.Id",
+                    &format!(
+                        "WITH RECURSIVE folder_tree AS (
+                           SELECT Id, Name, ParentId FROM Folders WHERE Id = ?
+                           UNION
+                           SELECT f.Id, f.Name, f.ParentId FROM Folders f
+                           INNER JOIN folder_tree ft ON f.ParentId = ft.Id
+                         )
+                         SELECT COUNT(*)
+                         FROM AIResponses ar
+                         INNER JOIN folder_tree ft ON ar.FolderId = ft.Id
+                         WHERE ar.DeletedAt IS NULL{}",
+                        tag_filter
+                    ),
                     rusqlite::params![folder_id],
                     |row| row.get(0),
                 )
@@ -625,19 +656,20 @@ pub async fn get_paginated_questions(
             let data_query = format!(
                 "WITH RECURSIVE folder_tree AS (
                    SELECT Id, Name, ParentId FROM Folders WHERE Id = ?
-                   UNION ALL
+                   UNION
                    SELECT f.Id, f.Name, f.ParentId FROM Folders f
                    INNER JOIN folder_tree ft ON f.ParentId = ft.Id
                  )
                  SELECT
                    ar.Id, ar.Question, ar.Options, ar.Answer, ar.QuestionType,
-                   ar.FolderId, f.Name as FolderName, ar.CreateTime, ar.IsAi, COALESCE(ar.IsPendingCorrection, 0)
+                   ar.FolderId, f.Name as FolderName, ar.CreateTime, ar.IsAi, COALESCE(ar.IsPendingCorrection, 0), COALESCE(ar.IsVerified, 0), ar.QuestionKind
                  FROM AIResponses ar
                  INNER JOIN folder_tree ft ON ar.FolderId = ft.Id
                  INNER JOIN Folders f ON ar.FolderId = f.Id
+                 WHERE ar.DeletedAt IS NULL{}
                  ORDER BY ar.CreateTime {}
                  LIMIT ? OFFSET ?",
-                sort_direction
+                tag_filter, sort_direction
             );
 
             let mut stmt = conn.prepare(&data_query).map_err(|e| format!("{}", e))?;
@@ -654,16 +686,24 @@ pub async fn get_paginated_questions(
         }
     } else {
         let total: i64 = conn
-            .query_row("SELECT COUNT(*) FROM AIResponses", [], |row| row.get(0))
+            .query_row(
+                &format!(
+                    "SELECT COUNT(*) FROM AIResponses ar WHERE ar.DeletedAt IS NULL{}",
+                    tag_filter
+                ),
+                [],
+                |row| row.get(0),
+            )
             .map_err(|e| format!("{}", e))?;
 
         let data_query = format!(
-            "SELECT ar.Id, ar.Question, ar.Options, ar.Answer, ar.QuestionType, ar.FolderId, f.Name as FolderName, ar.CreateTime, ar.IsAi, COALESCE(ar.IsPendingCorrection, 0)
+            "SELECT ar.Id, ar.Question, ar.Options, ar.Answer, ar.QuestionType, ar.FolderId, f.Name as FolderName, ar.CreateTime, ar.IsAi, COALESCE(ar.IsPendingCorrection, 0), COALESCE(ar.IsVerified, 0), ar.QuestionKind
              FROM AIResponses ar
              LEFT JOIN Folders f ON ar.FolderId = f.Id
+             WHERE ar.DeletedAt IS NULL{}
              ORDER BY ar.CreateTime {}
              LIMIT ? OFFSET ?",
-            sort_direction
+            tag_filter, sort_direction
         );
 
         let mut stmt = conn.prepare(&data_query).map_err(|e| format!("{}", e))?;
@@ -689,24 +729,25 @@ pub async fn get_questions_recursive(folder_id: i64) -> Result<Vec<AIResponse>,
     let query = if folder_id == 0 {
         "SELECT
           ar.Id, ar.Question, ar.Options, ar.Answer, ar.QuestionType,
-          ar.FolderId, f.Name as FolderName, ar.CreateTime, ar.IsAi, COALESCE(ar.IsPendingCorrection, 0)
+          ar.FolderId, f.Name as FolderName, ar.CreateTime, ar.IsAi, COALESCE(ar.IsPendingCorrection, 0), COALESCE(ar.IsVerified, 0), ar.QuestionKind
         FROM AIResponses ar
         INNER JOIN Folders f ON ar.FolderId = f.Id
-        WHERE ar.FolderId = 0
+        WHERE ar.FolderId = 0 AND ar.DeletedAt IS NULL
         ORDER BY ar.CreateTime DESC"
     } else {
         "WITH RECURSIVE folder_tree AS (
           SELECT Id, Name, ParentId FROM Folders WHERE Id = ?
-          UNION ALL
+          UNION
           SELECT f.Id, f.Name, f.ParentId FROM Folders f
           INNER JOIN folder_tree ft ON f.ParentId = ft.Id
         )
         SELECT
           ar.Id, ar.Question, ar.Options, ar.Answer, ar.QuestionType,
-          ar.FolderId, f.Name as FolderName, ar.CreateTime, ar.IsAi, COALESCE(ar.IsPendingCorrection, 0)
+          ar.FolderId, f.Name as FolderName, ar.CreateTime, ar.IsAi, COALESCE(ar.IsPendingCorrection, 0), COALESCE(ar.IsVerified, 0), ar.QuestionKind
         FROM AIResponses ar
         INNER JOIN folder_tree ft ON ar.FolderId = ft.Id
         INNER JOIN Folders f ON ar.FolderId = f.Id
+        WHERE ar.DeletedAt IS NULL
         ORDER BY ar.CreateTime DESC"
     };
 
@@ -733,10 +774,10 @@ pub async fn get_pending_correction_questions() -> Result<Vec<AIResponse>, Strin
     let conn = get_conn()?;
     let mut stmt = conn
         .prepare(
-            "SELECT ar.Id, ar.Question, ar.Options, ar.Answer, ar.QuestionType, ar.FolderId, f.Name as FolderName, ar.CreateTime, ar.IsAi, COALESCE(ar.IsPendingCorrection, 0)
+            "SELECT ar.Id, ar.Question, ar.Options, ar.Answer, ar.QuestionType, ar.FolderId, f.Name as FolderName, ar.CreateTime, ar.IsAi, COALESCE(ar.IsPendingCorrection, 0), COALESCE(ar.IsVerified, 0), ar.QuestionKind
              FROM AIResponses ar
              LEFT JOIN Folders f ON ar.FolderId = f.Id
-             WHERE COALESCE(ar.IsPendingCorrection, 0) = 1
+             WHERE COALESCE(ar.IsPendingCorrection, 0) = 1 AND ar.DeletedAt IS NULL
              ORDER BY ar.CreateTime DESC",
         )
         .map_err(|e| format!("{}", e))?;
@@ -756,7 +797,7 @@ pub async fn get_pending_correction_questions() -> Result<Vec<AIResponse>, Strin
 pub async fn get_pending_correction_question_count() -> Result<i64, String> {
     let conn = get_conn()?;
     conn.query_row(
-        "SELECT COUNT(*) FROM AIResponses WHERE COALESCE(IsPendingCorrection, 0) = 1",
+        "SELECT COUNT(*) FROM AIResponses WHERE COALESCE(IsPendingCorrection, 0) = 1 AND DeletedAt IS NULL",
         [],
         |row| row.get(0),
     )
@@ -765,19 +806,87 @@ pub async fn get_pending_correction_question_count() -> Result<i64, String> {
 
 #[tauri::command]
 pub async fn set_question_pending_correction(id: i64, pending: bool) -> Result<(), String> {
-    let conn = get_conn()?;
-    let affected = conn
-        .execute(
-            "UPDATE AIResponses SET IsPendingCorrection = ? WHERE Id = ?",
-            rusqlite::params![pending, id],
-        )
-        .map_err(|e| format!("{}", e))?;
+    let mut conn = get_conn()?;
+    set_question_pending_correction_with_conn(&mut conn, id, pending, RevisionSource::Ui)
+}
 
-    if affected == 0 {
-        return Err("题目不存在".to_string());
+/// OCS 通过 HTTP 接口把题目标记为待修正
+pub(crate) fn mark_question_pending_correction(id: i64) -> Result<(), String> {
+    let mut conn = get_conn()?;
+    set_question_pending_correction_with_conn(&mut conn, id, true, RevisionSource::Api)
+}
+
+fn set_question_pending_correction_with_conn(
+    conn: &mut Connection,
+    id: i64,
+    pending: bool,
+    source: RevisionSource,
+) -> Result<(), String> {
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("开启事务失败: {}", e))?;
+    let old = load_question_snapshot(&tx, id)
+        .map_err(|e| format!("读取题目失败: {}", e))?
+        .ok_or_else(|| "题目不存在".to_string())?;
+    if old.is_pending_correction == pending {
+        return Ok(());
+    }
+    // 已核实的题目只能在界面中人工标记为待修正
+    if pending && source == RevisionSource::Api && is_question_verified(&tx, id)? {
+        return Err("题目已人工核实，不能通过接口标记为待修正".to_string());
     }
 
-    Ok(())
+    tx.execute(
+        "UPDATE AIResponses SET IsPendingCorrection = ? WHERE Id = ?",
+        rusqlite::params![pending, id],
+    )
+    .map_err(|e| format!("{}", e))?;
+    let new = QuestionSnapshot {
+        is_pending_correction: pending,
+        ..old.clone()
+    };
+    record_question_revision(&tx, id, Some(&old), &new, source)?;
+
+    tx.commit().map_err(|e| format!("提交事务失败: {}", e))
+}
+
+/// 标记题目已人工核实；核实即视为答案正确，同时清除待修正标记
+#[tauri::command]
+pub async fn set_question_verified(id: i64, verified: bool) -> Result<(), String> {
+    let mut conn = get_conn()?;
+    set_question_verified_with_conn(&mut conn, id, verified)
+}
+
+fn set_question_verified_with_conn(
+    conn: &mut Connection,
+    id: i64,
+    verified: bool,
+) -> Result<(), String> {
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("开启事务失败: {}", e))?;
+    let old = load_question_snapshot(&tx, id)
+        .map_err(|e| format!("读取题目失败: {}", e))?
+        .ok_or_else(|| "题目不存在".to_string())?;
+
+    tx.execute(
+        "UPDATE AIResponses
+         SET IsVerified = ?1,
+             IsPendingCorrection = CASE WHEN ?1 THEN 0 ELSE IsPendingCorrection END
+         WHERE Id = ?2",
+        rusqlite::params![verified, id],
+    )
+    .map_err(|e| format!("{}", e))?;
+    // 清除待修正标记属于答案状态的变化，记录到修订历史
+    if verified && old.is_pending_correction {
+        let new = QuestionSnapshot {
+            is_pending_correction: false,
+            ..old.clone()
+        };
+        record_question_revision(&tx, id, Some(&old), &new, RevisionSource::Ui)?;
+    }
+
+    tx.commit().map_err(|e| format!("提交事务失败: {}", e))
 }
 
 #[tauri::command]
@@ -785,7 +894,7 @@ pub async fn get_folder_question_count(folder_id: i64) -> Result<i64, String> {
     let conn = get_conn()?;
     let count: i64 = conn
         .query_row(
-            "SELECT COUNT(*) FROM AIResponses WHERE FolderId = ?",
+            "SELECT COUNT(*) FROM AIResponses WHERE FolderId = ? AND DeletedAt IS NULL",
             [&folder_id],
             |row| row.get(0),
         )
@@ -835,17 +944,32 @@ pub async fn get_folder_path(folder_id: i64) -> Result<Vec<FolderPathItem>, Stri
 #[tauri::command]
 pub async fn get_folder_stats() -> Result<Vec<FolderStat>, String> {
     let conn = get_conn()?;
+    get_folder_stats_with_conn(&conn, &[])
+}
+
+/// 按标签统计各文件夹题目数量，只计入同时带有全部指定标签的题目
+#[tauri::command]
+pub async fn get_folder_stats_by_tags(tag_ids: Vec<i64>) -> Result<Vec<FolderStat>, String> {
+    let conn = get_conn()?;
+    get_folder_stats_with_conn(&conn, &tag_ids)
+}
+
+fn get_folder_stats_with_conn(
+    conn: &Connection,
+    tag_ids: &[i64],
+) -> Result<Vec<FolderStat>, String> {
     let mut stmt = conn
-        .prepare(
+        .prepare(&format!(
             "SELECT 
           f.Id,
           COALESCE(f.Name, '[未分类]'),
           COUNT(ar.Id) as questionCount
         FROM Folders f
-        LEFT JOIN AIResponses ar ON f.Id = ar.FolderId
+        LEFT JOIN AIResponses ar ON f.Id = ar.FolderId AND ar.DeletedAt IS NULL{}
         GROUP BY f.Id, f.Name
         ORDER BY questionCount DESC, f.Name",
-        )
+            tag_filter_clause(tag_ids)
+        ))
         .map_err(|e| format!("{}", e))?;
 
     let rows = stmt
@@ -865,779 +989,3672 @@ pub async fn get_folder_stats() -> Result<Vec<FolderStat>, String> {
     Ok(stats)
 }
 
-// 辅助函数：获取目标文件夹（智能归类）
-fn get_target_folder_id(conn: &Connection, parent_folder_id: i64) -> Result<i64, rusqlite::Error> {
-    if parent_folder_id == 0 {
-        return Ok(0);
+// 标签过滤条件：题目需同时带有全部指定标签；标签 ID 为整数，可直接拼入 SQL
+fn tag_filter_clause(tag_ids: &[i64]) -> String {
+    let tag_ids: HashSet<i64> = tag_ids.iter().copied().collect();
+    if tag_ids.is_empty() {
+        return String::new();
     }
 
-    let sub_folders_exist: bool = conn.query_row(
-        "SELECT EXISTS(SELECT 1 FROM Folders WHERE ParentId = ?)",
-        [&parent_folder_id],
-        |row| row.get(0),
-    )?;
-
-    if !sub_folders_exist {
-        return Ok(parent_folder_id);
-    }
+    let id_list = tag_ids
+        .iter()
+        .map(|id| id.to_string())
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!(
+        " AND ar.Id IN (SELECT QuestionId FROM QuestionTags WHERE TagId IN ({}) GROUP BY QuestionId HAVING COUNT(DISTINCT TagId) = {})",
+        id_list,
+        tag_ids.len()
+    )
+}
 
-    let uncategorized_id: Option<i64> = conn
-        .query_row(
-            "SELECT Id FROM Folders WHERE ParentId = ? AND Name = '[未分类]'",
-            [&parent_folder_id],
-            |row| Ok(row.get(0)?),
-        )
-        .optional()?;
+fn map_tag_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<Tag> {
+    Ok(Tag {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        question_count: row.get(2)?,
+        create_time: row.get(3)?,
+    })
+}
 
-    if let Some(id) = uncategorized_id {
-        Ok(id)
-    } else {
-        conn.execute(
-            "INSERT INTO Folders (Name, ParentId, CreateTime) VALUES (?, ?, datetime('now'))",
-            rusqlite::params!["[未分类]", parent_folder_id],
-        )?;
-        Ok(conn.last_insert_rowid())
+fn normalize_tag_name(name: &str) -> Result<String, String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("标签名称不能为空".to_string());
     }
+    Ok(name.to_string())
 }
 
-fn get_configured_save_folder_id(conn: &Connection) -> i64 {
-    let config_path = std::env::current_exe()
-        .ok()
-        .and_then(|p| p.parent().map(|d| d.to_path_buf()))
-        .unwrap_or_else(|| std::path::PathBuf::from("."))
-        .join("config.json");
+fn tag_name_exists(conn: &Connection, name: &str, exclude_id: Option<i64>) -> Result<bool, String> {
+    conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM Tags WHERE Name = ? AND Id != COALESCE(?, -1))",
+        rusqlite::params![name, exclude_id],
+        |row| row.get(0),
+    )
+    .map_err(|e| format!("{}", e))
+}
 
-    let configured_folder_id = std::fs::read_to_string(&config_path)
-        .ok()
-        .and_then(|content| serde_json::from_str::<serde_json::Value>(&content).ok())
-        .and_then(|config| {
-            config.get("questionSaveFolderId").and_then(|value| {
-                value
-                    .as_i64()
-                    .or_else(|| value.as_str().and_then(|raw| raw.parse::<i64>().ok()))
-            })
-        })
-        .unwrap_or(0);
+#[tauri::command]
+pub async fn get_tags() -> Result<Vec<Tag>, String> {
+    let conn = get_conn()?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT t.Id, t.Name, COUNT(ar.Id), t.CreateTime
+             FROM Tags t
+             LEFT JOIN QuestionTags qt ON qt.TagId = t.Id
+             LEFT JOIN AIResponses ar ON ar.Id = qt.QuestionId AND ar.DeletedAt IS NULL
+             GROUP BY t.Id, t.Name, t.CreateTime
+             ORDER BY t.Name",
+        )
+        .map_err(|e| format!("{}", e))?;
 
-    if configured_folder_id <= 0 {
-        return 0;
+    let rows = stmt
+        .query_map([], map_tag_row)
+        .map_err(|e| format!("{}", e))?;
+
+    let mut tags = Vec::new();
+    for row in rows {
+        tags.push(row.map_err(|e| format!("{}", e))?);
     }
+    Ok(tags)
+}
 
-    let folder_exists: bool = conn
-        .query_row(
-            "SELECT EXISTS(SELECT 1 FROM Folders WHERE Id = ?)",
-            [configured_folder_id],
-            |row| row.get(0),
+#[tauri::command]
+pub async fn get_question_tags(question_id: i64) -> Result<Vec<Tag>, String> {
+    let conn = get_conn()?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT t.Id, t.Name,
+               (SELECT COUNT(*) FROM QuestionTags c
+                INNER JOIN AIResponses ar ON ar.Id = c.QuestionId
+                WHERE c.TagId = t.Id AND ar.DeletedAt IS NULL),
+               t.CreateTime
+             FROM Tags t
+             INNER JOIN QuestionTags qt ON qt.TagId = t.Id
+             WHERE qt.QuestionId = ?
+             ORDER BY t.Name",
         )
-        .unwrap_or(false);
+        .map_err(|e| format!("{}", e))?;
 
-    if !folder_exists {
-        println!(
-            "⚠️ 配置的题目保存文件夹不存在，已回退到默认文件夹: {}",
-            configured_folder_id
-        );
-        return 0;
-    }
+    let rows = stmt
+        .query_map([question_id], map_tag_row)
+        .map_err(|e| format!("{}", e))?;
 
-    match get_target_folder_id(conn, configured_folder_id) {
-        Ok(folder_id) => folder_id,
-        Err(error) => {
-            println!("⚠️ 解析题目保存文件夹失败，已回退到默认文件夹: {}", error);
-            0
-        }
+    let mut tags = Vec::new();
+    for row in rows {
+        tags.push(row.map_err(|e| format!("{}", e))?);
     }
+    Ok(tags)
 }
 
 #[tauri::command]
-pub async fn add_question(
-    content: String,
-    options: Option<String>,
-    answer: String,
-    question_type: Option<String>,
-    folder_id: i64,
-    is_ai: Option<bool>,
-) -> Result<AIResponse, String> {
+pub async fn add_tag(name: String) -> Result<i64, String> {
     let conn = get_conn()?;
-    let is_ai = is_ai.unwrap_or(false);
+    add_tag_with_conn(&conn, &name)
+}
 
-    if is_ai && answer.trim().is_empty() {
-        return Err("AI处理结果答案为空，不保存题目".to_string());
+fn add_tag_with_conn(conn: &Connection, name: &str) -> Result<i64, String> {
+    let name = normalize_tag_name(name)?;
+    if tag_name_exists(conn, &name, None)? {
+        return Err("标签已存在".to_string());
     }
 
-    let target_folder_id = get_target_folder_id(&conn, folder_id).map_err(|e| format!("{}", e))?;
-
     conn.execute(
-        "INSERT INTO AIResponses (Question, Options, Answer, QuestionType, FolderId, IsAi, CreateTime)
-        VALUES (?, ?, ?, ?, ?, ?, datetime('now'))",
-        rusqlite::params![content, options, answer, question_type, target_folder_id, is_ai],
-    ).map_err(|e| format!("{}", e))?;
-
-    let id = conn.last_insert_rowid();
-
-    // 获取完整的插入数据返回
-    let response = conn.query_row(
-        "SELECT ar.Id, ar.Question, ar.Options, ar.Answer, ar.QuestionType, ar.FolderId, f.Name as FolderName, ar.CreateTime, ar.IsAi, COALESCE(ar.IsPendingCorrection, 0)
-         FROM AIResponses ar
-         LEFT JOIN Folders f ON ar.FolderId = f.Id
-         WHERE ar.Id = ?",
-        [id],
-        map_ai_response_row,
-    ).map_err(|e| format!("{}", e))?;
-
-    Ok(response)
+        "INSERT INTO Tags (Name, CreateTime) VALUES (?, datetime('now'))",
+        [&name],
+    )
+    .map_err(|e| format!("创建标签失败: {}", e))?;
+    Ok(conn.last_insert_rowid())
 }
 
 #[tauri::command]
-pub async fn update_question(
-    id: i64,
-    question: Option<String>,
-    options: Option<String>,
-    answer: Option<String>,
-    question_type: Option<String>,
-) -> Result<(), String> {
+pub async fn rename_tag(id: i64, new_name: String) -> Result<(), String> {
     let conn = get_conn()?;
-    let mut has_updates = false;
-
-    if let Some(q) = question {
-        conn.execute(
-            "UPDATE AIResponses SET Question = ? WHERE Id = ?",
-            rusqlite::params![q, id],
-        )
-        .map_err(|e| format!("{}", e))?;
-        has_updates = true;
-    }
-    if let Some(o) = options {
-        conn.execute(
-            "UPDATE AIResponses SET Options = ? WHERE Id = ?",
-            rusqlite::params![o, id],
-        )
-        .map_err(|e| format!("{}", e))?;
-        has_updates = true;
-    }
-    if let Some(a) = answer {
-        conn.execute(
-            "UPDATE AIResponses SET Answer = ? WHERE Id = ?",
-            rusqlite::params![a, id],
-        )
-        .map_err(|e| format!("{}", e))?;
-        has_updates = true;
-    }
-    if let Some(t) = question_type {
-        conn.execute(
-            "UPDATE AIResponses SET QuestionType = ? WHERE Id = ?",
-            rusqlite::params![t, id],
-        )
-        .map_err(|e| format!("{}", e))?;
-        has_updates = true;
+    let name = normalize_tag_name(&new_name)?;
+    if tag_name_exists(&conn, &name, Some(id))? {
+        return Err("标签已存在".to_string());
     }
 
-    if has_updates {
-        conn.execute(
-            "UPDATE AIResponses SET IsPendingCorrection = 0 WHERE Id = ?",
-            rusqlite::params![id],
+    let affected = conn
+        .execute(
+            "UPDATE Tags SET Name = ? WHERE Id = ?",
+            rusqlite::params![name, id],
         )
-        .map_err(|e| format!("{}", e))?;
+        .map_err(|e| format!("重命名标签失败: {}", e))?;
+    if affected == 0 {
+        return Err("标签不存在".to_string());
     }
-
     Ok(())
 }
 
 #[tauri::command]
-pub async fn move_question(question_id: i64, target_folder_id: i64) -> Result<(), String> {
-    let conn = get_conn()?;
-    let actual_target_id =
-        get_target_folder_id(&conn, target_folder_id).map_err(|e| format!("{}", e))?;
-
-    conn.execute(
-        "UPDATE AIResponses SET FolderId = ? WHERE Id = ?",
-        rusqlite::params![actual_target_id, question_id],
-    )
-    .map_err(|e| format!("{}", e))?;
-
+pub async fn delete_tag(id: i64) -> Result<(), String> {
+    let mut conn = get_conn()?;
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("开启事务失败: {}", e))?;
+    tx.execute("DELETE FROM QuestionTags WHERE TagId = ?", [id])
+        .map_err(|e| format!("删除标签关联失败: {}", e))?;
+    tx.execute("DELETE FROM Tags WHERE Id = ?", [id])
+        .map_err(|e| format!("删除标签失败: {}", e))?;
+    tx.commit().map_err(|e| format!("提交事务失败: {}", e))?;
     Ok(())
 }
 
 #[tauri::command]
-pub async fn copy_question(question_id: i64, target_folder_id: i64) -> Result<(), String> {
+pub async fn tag_questions(question_ids: Vec<i64>, tag_ids: Vec<i64>) -> Result<usize, String> {
     let conn = get_conn()?;
-    let actual_target_id =
-        get_target_folder_id(&conn, target_folder_id).map_err(|e| format!("{}", e))?;
-
-    // 获取原题
-    let (q, o, a, qt, ia, ipc): (String, Option<String>, String, Option<String>, bool, bool) = conn
-        .query_row(
-            "SELECT Question, Options, Answer, QuestionType, IsAi, COALESCE(IsPendingCorrection, 0) FROM AIResponses WHERE Id = ?",
-            [question_id],
-            |row| {
-                Ok((
-                    row.get(0)?,
-                    row.get(1)?,
-                    row.get(2)?,
-                    row.get(3)?,
-                    row.get(4)?,
-                    row.get(5)?,
-                ))
-            },
-        )
-        .map_err(|e| format!("{}", e))?;
-
-    conn.execute(
-        "INSERT INTO AIResponses (Question, Options, Answer, QuestionType, FolderId, IsAi, IsPendingCorrection, CreateTime)
-        VALUES (?, ?, ?, ?, ?, ?, ?, datetime('now'))",
-        rusqlite::params![q, o, a, qt, actual_target_id, ia, ipc],
-    ).map_err(|e| format!("{}", e))?;
-
-    Ok(())
+    tag_questions_with_conn(&conn, &question_ids, &tag_ids)
 }
 
 #[tauri::command]
-pub async fn delete_question(id: i64) -> Result<(), String> {
+pub async fn untag_questions(question_ids: Vec<i64>, tag_ids: Vec<i64>) -> Result<usize, String> {
     let conn = get_conn()?;
-    conn.execute("DELETE FROM AIResponses WHERE Id = ?", [id])
-        .map_err(|e| format!("{}", e))?;
-    Ok(())
+    untag_questions_with_conn(&conn, &question_ids, &tag_ids)
 }
 
+/// 为搜索结果中的全部题目添加标签，参数与 search_questions_fuzzy 一致
 #[tauri::command]
-pub async fn delete_questions(ids: Vec<i64>) -> Result<(), String> {
+pub async fn tag_search_results(
+    keyword: String,
+    folder_id: Option<i64>,
+    filter_tag_ids: Option<Vec<i64>>,
+    tag_ids: Vec<i64>,
+) -> Result<usize, String> {
     let conn = get_conn()?;
-    // 由于 rusqlite 批量删除比较麻烦，这里采用循环方式
-    for id in ids {
-        conn.execute("DELETE FROM AIResponses WHERE Id = ?", [id])
-            .map_err(|e| format!("{}", e))?;
-    }
-    Ok(())
+    let question_ids = search_result_ids(&conn, &keyword, folder_id, filter_tag_ids)?;
+    tag_questions_with_conn(&conn, &question_ids, &tag_ids)
 }
 
+/// 移除搜索结果中全部题目的指定标签，参数与 search_questions_fuzzy 一致
 #[tauri::command]
-pub async fn clear_folder_questions(id: i64) -> Result<(), String> {
+pub async fn untag_search_results(
+    keyword: String,
+    folder_id: Option<i64>,
+    filter_tag_ids: Option<Vec<i64>>,
+    tag_ids: Vec<i64>,
+) -> Result<usize, String> {
     let conn = get_conn()?;
+    let question_ids = search_result_ids(&conn, &keyword, folder_id, filter_tag_ids)?;
+    untag_questions_with_conn(&conn, &question_ids, &tag_ids)
+}
 
-    let mut stmt = conn
-        .prepare(
-            "WITH RECURSIVE folder_tree AS (
-          SELECT Id FROM Folders WHERE Id = ?
-          UNION ALL
-          SELECT f.Id FROM Folders f
-          INNER JOIN folder_tree ft ON f.ParentId = ft.Id
-        )
-        SELECT Id FROM folder_tree",
-        )
-        .map_err(|e| format!("{}", e))?;
-
-    let folder_ids_iter = stmt
-        .query_map([id], |row| row.get::<_, i64>(0))
-        .map_err(|e| format!("{}", e))?;
+fn search_result_ids(
+    conn: &Connection,
+    keyword: &str,
+    folder_id: Option<i64>,
+    filter_tag_ids: Option<Vec<i64>>,
+) -> Result<Vec<i64>, String> {
+    let results = search_questions_fuzzy_with_conn(
+        conn,
+        keyword,
+        folder_id,
+        filter_tag_ids.as_deref().unwrap_or_default(),
+    )?;
+    Ok(results.into_iter().map(|item| item.id).collect())
+}
 
-    let mut folder_ids = Vec::new();
-    for fid in folder_ids_iter {
-        folder_ids.push(fid.map_err(|e| format!("{}", e))?);
+// 批量打标签，返回新增的关联数量；已存在的关联、不存在的题目或标签会被忽略
+fn tag_questions_with_conn(
+    conn: &Connection,
+    question_ids: &[i64],
+    tag_ids: &[i64],
+) -> Result<usize, String> {
+    if question_ids.is_empty() || tag_ids.is_empty() {
+        return Ok(0);
     }
 
-    for fid in &folder_ids {
-        conn.execute("DELETE FROM AIResponses WHERE FolderId = ?", [fid])
-            .map_err(|e| format!("{}", e))?;
+    let question_ids_json = serde_json::to_string(question_ids).map_err(|e| format!("{}", e))?;
+    let tag_ids_json = serde_json::to_string(tag_ids).map_err(|e| format!("{}", e))?;
+    conn.execute(
+        "INSERT OR IGNORE INTO QuestionTags (QuestionId, TagId)
+         SELECT ar.Id, t.Id
+         FROM AIResponses ar
+         INNER JOIN Tags t ON t.Id IN (SELECT value FROM json_each(?2))
+         WHERE ar.Id IN (SELECT value FROM json_each(?1))",
+        rusqlite::params![question_ids_json, tag_ids_json],
+    )
+    .map_err(|e| format!("添加标签失败: {}", e))
+}
+
+fn untag_questions_with_conn(
+    conn: &Connection,
+    question_ids: &[i64],
+    tag_ids: &[i64],
+) -> Result<usize, String> {
+    if question_ids.is_empty() || tag_ids.is_empty() {
+        return Ok(0);
     }
 
-    Ok(())
+    let question_ids_json = serde_json::to_string(question_ids).map_err(|e| format!("{}", e))?;
+    let tag_ids_json = serde_json::to_string(tag_ids).map_err(|e| format!("{}", e))?;
+    conn.execute(
+        "DELETE FROM QuestionTags
+         WHERE QuestionId IN (SELECT value FROM json_each(?1))
+           AND TagId IN (SELECT value FROM json_each(?2))",
+        rusqlite::params![question_ids_json, tag_ids_json],
+    )
+    .map_err(|e| format!("移除标签失败: {}", e))
 }
 
-#[tauri::command]
-pub async fn delete_folder(id: i64, delete_questions: bool) -> Result<(), String> {
-    let conn = get_conn()?;
-
-    // 递归获取所有子文件夹 ID
-    let mut stmt = conn
-        .prepare(
-            "WITH RECURSIVE folder_tree AS (
-          SELECT Id FROM Folders WHERE Id = ?
-          UNION ALL
-          SELECT f.Id FROM Folders f
-          INNER JOIN folder_tree ft ON f.ParentId = ft.Id
-        )
-        SELECT Id FROM folder_tree",
-        )
-        .map_err(|e| format!("{}", e))?;
+const UNCATEGORIZED_FOLDER_NAME: &str = "[未分类]";
 
-    let folder_ids_iter = stmt
-        .query_map([id], |row| row.get::<_, i64>(0))
-        .map_err(|e| format!("{}", e))?;
-    let mut folder_ids = Vec::new();
-    for fid in folder_ids_iter {
-        folder_ids.push(fid.map_err(|e| format!("{}", e))?);
+// 辅助函数：获取目标文件夹（智能归类）
+fn get_target_folder_id(conn: &Connection, parent_folder_id: i64) -> Result<i64, rusqlite::Error> {
+    if parent_folder_id == 0 {
+        return Ok(0);
     }
 
-    if delete_questions {
-        // 删除所有这些文件夹中的题目
-        for fid in &folder_ids {
-            conn.execute("DELETE FROM AIResponses WHERE FolderId = ?", [fid])
-                .map_err(|e| format!("{}", e))?;
-        }
-    } else {
-        // 将题目移到父文件夹或默认文件夹 (0)
-        let parent_id: i64 = conn
-            .query_row("SELECT ParentId FROM Folders WHERE Id = ?", [id], |row| {
-                row.get(0)
-            })
-            .unwrap_or(0);
+    let sub_folders_exist: bool = conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM Folders WHERE ParentId = ?)",
+        [&parent_folder_id],
+        |row| row.get(0),
+    )?;
 
-        for fid in &folder_ids {
-            conn.execute(
-                "UPDATE AIResponses SET FolderId = ? WHERE FolderId = ?",
-                [parent_id, *fid],
-            )
-            .map_err(|e| format!("{}", e))?;
-        }
+    if !sub_folders_exist {
+        return Ok(parent_folder_id);
     }
 
-    // 删除所有文件夹
-    for fid in &folder_ids {
-        conn.execute("DELETE FROM Folders WHERE Id = ?", [fid])
-            .map_err(|e| format!("{}", e))?;
-    }
+    get_or_create_uncategorized_folder(conn, parent_folder_id)
+}
 
-    Ok(())
+fn get_or_create_uncategorized_folder(
+    conn: &Connection,
+    parent_folder_id: i64,
+) -> Result<i64, rusqlite::Error> {
+    let uncategorized_id: Option<i64> = conn
+        .query_row(
+            "SELECT Id FROM Folders WHERE ParentId = ? AND Name = ?",
+            rusqlite::params![parent_folder_id, UNCATEGORIZED_FOLDER_NAME],
+            |row| row.get(0),
+        )
+        .optional()?;
+
+    if let Some(id) = uncategorized_id {
+        Ok(id)
+    } else {
+        conn.execute(
+            "INSERT INTO Folders (Name, ParentId, CreateTime) VALUES (?, ?, datetime('now'))",
+            rusqlite::params![UNCATEGORIZED_FOLDER_NAME, parent_folder_id],
+        )?;
+        Ok(conn.last_insert_rowid())
+    }
 }
 
-#[tauri::command]
-pub async fn rename_folder(id: i64, new_name: String) -> Result<(), String> {
-    let conn = get_conn()?;
-    conn.execute(
-        "UPDATE Folders SET Name = ? WHERE Id = ?",
-        rusqlite::params![new_name, id],
+fn folder_exists(conn: &Connection, folder_id: i64) -> Result<bool, rusqlite::Error> {
+    conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM Folders WHERE Id = ?)",
+        [folder_id],
+        |row| row.get(0),
     )
-    .map_err(|e| format!("{}", e))?;
-    Ok(())
 }
 
-#[tauri::command]
-pub async fn move_folder(id: i64, parent_id: i64) -> Result<(), String> {
-    let conn = get_conn()?;
-    // 检查防止循环嵌套（虽然前端会有检查，但后端建议也做简单保护）
-    if id == parent_id {
-        return Err("Cannot move folder to itself".to_string());
+// 沿目标父文件夹的祖先链向上查找，判断移动后是否会形成循环嵌套
+fn is_folder_ancestor_of(
+    conn: &Connection,
+    folder_id: i64,
+    descendant_id: i64,
+) -> Result<bool, rusqlite::Error> {
+    let mut visited = HashSet::new();
+    let mut current = descendant_id;
+    while current != 0 && visited.insert(current) {
+        if current == folder_id {
+            return Ok(true);
+        }
+        let parent_id: Option<Option<i64>> = conn
+            .query_row(
+                "SELECT ParentId FROM Folders WHERE Id = ?",
+                [current],
+                |row| row.get(0),
+            )
+            .optional()?;
+        current = parent_id.flatten().unwrap_or(0);
+    }
+    Ok(false)
+}
+
+// 添加文件夹逻辑：父文件夹没有子文件夹但已有题目时，
+// 先创建 [未分类] 文件夹并把父文件夹的题目移入其中
+fn move_questions_into_uncategorized_before_adding_child(
+    conn: &Connection,
+    parent_folder_id: i64,
+) -> Result<(), rusqlite::Error> {
+    if parent_folder_id == 0 {
+        return Ok(());
+    }
+
+    let (has_sub_folders, has_questions): (bool, bool) = conn.query_row(
+        "SELECT
+           EXISTS(SELECT 1 FROM Folders WHERE ParentId = ?1),
+           EXISTS(SELECT 1 FROM AIResponses WHERE FolderId = ?1 AND DeletedAt IS NULL)",
+        [parent_folder_id],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+    if has_sub_folders || !has_questions {
+        return Ok(());
     }
 
+    let uncategorized_id = get_or_create_uncategorized_folder(conn, parent_folder_id)?;
     conn.execute(
-        "UPDATE Folders SET ParentId = ? WHERE Id = ?",
-        [parent_id, id],
-    )
-    .map_err(|e| format!("{}", e))?;
+        "UPDATE AIResponses SET FolderId = ? WHERE FolderId = ? AND DeletedAt IS NULL",
+        [uncategorized_id, parent_folder_id],
+    )?;
     Ok(())
 }
 
-#[tauri::command]
-pub async fn add_folder(name: String, parent_id: i64) -> Result<i64, String> {
-    let conn = get_conn()?;
-    conn.execute(
-        "INSERT INTO Folders (Name, ParentId, CreateTime) VALUES (?, ?, datetime('now'))",
-        rusqlite::params![name, parent_id],
-    )
-    .map_err(|e| format!("{}", e))?;
-
-    Ok(conn.last_insert_rowid())
+// 读取 exe 同级目录下 config.json 中的整数配置项（兼容字符串形式的数字）
+/// 读取 config.json 中的单个配置项
+fn config_path() -> std::path::PathBuf {
+    std::env::current_exe()
+        .ok()
+        .and_then(|p| p.parent().map(|d| d.to_path_buf()))
+        .unwrap_or_else(|| std::path::PathBuf::from("."))
+        .join("config.json")
 }
 
-#[tauri::command]
-pub async fn search_questions_fuzzy(
-    keyword: String,
-    folder_id: Option<i64>,
-) -> Result<Vec<AIResponse>, String> {
-    let db_path = get_db_path();
-    let keyword_clone = keyword.clone();
-
-    let result = tokio::task::spawn_blocking(move || -> Result<Vec<AIResponse>, String> {
-        let conn = Connection::open(&db_path).map_err(|e| format!("{}", e))?;
-
-        // 1. 获取候选题目（根据文件夹过滤）
-        let query = if let Some(fid) = folder_id {
-            if fid == 0 {
-                // 默认文件夹仅显示自身题目
-                "SELECT
-                  ar.Id, ar.Question, ar.Options, ar.Answer, ar.QuestionType,
-                  ar.FolderId, f.Name as FolderName, ar.CreateTime, ar.IsAi, COALESCE(ar.IsPendingCorrection, 0)
-                FROM AIResponses ar
-                INNER JOIN Folders f ON ar.FolderId = f.Id
-                WHERE ar.FolderId = 0"
-            } else {
-                // 指定文件夹及其子文件夹
-                "WITH RECURSIVE folder_tree AS (
-                  SELECT Id, Name, ParentId FROM Folders WHERE Id = ?
-                  UNION ALL
-                  SELECT f.Id, f.Name, f.ParentId FROM Folders f
-                  INNER JOIN folder_tree ft ON f.ParentId = ft.Id
-                )
-                SELECT
-                  ar.Id, ar.Question, ar.Options, ar.Answer, ar.QuestionType,
-                  ar.FolderId, f.Name as FolderName, ar.CreateTime, ar.IsAi, COALESCE(ar.IsPendingCorrection, 0)
-                FROM AIResponses ar
-                INNER JOIN folder_tree ft ON ar.FolderId = ft.Id
-                INNER JOIN Folders f ON ar.FolderId = f.Id"
-            }
-        } else {
-            // 所有文件夹
-            "SELECT
-              ar.Id, ar.Question, ar.Options, ar.Answer, ar.QuestionType,
-              ar.FolderId, f.Name as FolderName, ar.CreateTime, ar.IsAi, COALESCE(ar.IsPendingCorrection, 0)
-            FROM AIResponses ar
-            LEFT JOIN Folders f ON ar.FolderId = f.Id"
-        };
+pub(crate) fn read_config_value(key: &str) -> Option<serde_json::Value> {
+    std::fs::read_to_string(config_path())
+        .ok()
+        .and_then(|content| serde_json::from_str::<serde_json::Value>(&content).ok())
+        .and_then(|mut config| config.get_mut(key).map(serde_json::Value::take))
+}
 
-        let mut stmt = conn.prepare(query).map_err(|e| format!("{}", e))?;
+// config.json 的修改时间与大小，文件不存在时为空
+type ConfigStamp = Option<(std::time::SystemTime, u64)>;
 
-        let params_vec: Vec<&dyn rusqlite::ToSql> = if let Some(ref fid) = folder_id {
-            if *fid == 0 {
-                vec![]
-            } else {
-                vec![fid]
-            }
-        } else {
-            vec![]
-        };
-        let params = params_vec.as_slice();
+fn config_stamp() -> ConfigStamp {
+    let metadata = std::fs::metadata(config_path()).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
 
-        let rows = stmt
-            .query_map(params, map_ai_response_row)
-            .map_err(|e| format!("{}", e))?;
+/// 由 config.json 解析出的配置的缓存，config.json 修改后下次读取时重新解析
+pub(crate) struct ConfigCache<T> {
+    slot: std::sync::RwLock<Option<(ConfigStamp, Arc<T>)>>,
+}
 
-        let mut results = Vec::new();
-        let keyword_lower = keyword_clone.to_lowercase();
-        let keywords: Vec<&str> = keyword_lower.split_whitespace().collect();
+impl<T> ConfigCache<T> {
+    pub(crate) const fn new() -> Self {
+        ConfigCache {
+            slot: std::sync::RwLock::new(None),
+        }
+    }
 
-        if keywords.is_empty() {
-            // 如果关键词为空，返回所有结果
-            for row in rows {
-                if let Ok(item) = row {
-                    results.push((item, 1.0));
-                }
-            }
-        } else {
-            for row in rows {
-                if let Ok(item) = row {
-                    let q_lower = item.question.to_lowercase();
-                    let a_lower = item.answer.clone().unwrap_or_default().to_lowercase();
-                    let o_lower = item.options.clone().unwrap_or_default().to_lowercase();
-
-                    let mut all_terms_matched = true;
-
-                    for term in &keywords {
-                        let mut term_matched = false;
-                        if q_lower.contains(term)
-                            || a_lower.contains(term)
-                            || o_lower.contains(term)
-                        {
-                            term_matched = true;
-                        }
-                        if !term_matched {
-                            all_terms_matched = false;
-                            break;
-                        }
-                    }
-
-                    if all_terms_matched {
-                        results.push((item, 1.0));
-                    }
-                }
+    pub(crate) fn get(&self, load: impl FnOnce() -> T) -> Arc<T> {
+        let stamp = config_stamp();
+        if let Some((cached_stamp, value)) = self.slot.read().unwrap().as_ref() {
+            if *cached_stamp == stamp {
+                return value.clone();
             }
         }
+        let value = Arc::new(load());
+        *self.slot.write().unwrap() = Some((stamp, value.clone()));
+        value
+    }
+}
 
-        // 按分数降序排序，分数相同按时间倒序
-        results.sort_by(|a, b| {
-            b.1.partial_cmp(&a.1)
-                .unwrap_or(std::cmp::Ordering::Equal)
-                .then_with(|| b.0.create_time.cmp(&a.0.create_time))
-        });
-
-        Ok(results.into_iter().map(|(item, _)| item).collect())
+fn read_config_i64(key: &str) -> Option<i64> {
+    read_config_value(key).and_then(|value| {
+        value
+            .as_i64()
+            .or_else(|| value.as_str().and_then(|raw| raw.parse::<i64>().ok()))
     })
-    .await
-    .map_err(|e| format!("{}", e))??;
-
-    Ok(result)
 }
 
-pub async fn query_database(
-    title: &str,
-    options: Option<&str>,
-) -> Result<Vec<(i64, String, String, bool, bool)>, Box<dyn std::error::Error + Send + Sync>> {
-    let db_path = get_db_path();
-    let title_clone = title.to_string();
-    let query_options = normalize_optional_query_text(options);
-    let require_option_match = should_require_option_match(&title_clone);
-
-    let result = tokio::task::spawn_blocking(
-        move || -> Result<Vec<(i64, String, String, bool, bool)>, Box<dyn std::error::Error + Send + Sync>> {
-            let conn = match Connection::open(&db_path) {
-                Ok(c) => c,
-                Err(e) => {
-                    return Err(Box::new(e) as Box<dyn std::error::Error + Send + Sync>);
-                }
-            };
-
-            let mut stmt = conn.prepare(
-                "SELECT Id, Question, Options, Answer, IsAi, COALESCE(IsPendingCorrection, 0) FROM AIResponses",
-            )?;
+/// 题目所属文件夹（查询范围中的第一个文件夹）从根到自身的各级文件夹 ID 与
+/// "父文件夹/子文件夹" 形式的路径，供提示词模板按文件夹选择；没有查询范围时为空
+pub(crate) fn scope_folder_chain(scope: Option<&QueryScope>) -> Vec<(i64, String)> {
+    let Some(folder_id) = scope.and_then(|scope| scope.roots.first().copied()) else {
+        return Vec::new();
+    };
+    get_conn()
+        .and_then(|conn| folder_chain_with_conn(&conn, folder_id))
+        .unwrap_or_else(|e| {
+            println!("⚠️ 读取题目保存文件夹路径失败: {}", e);
+            Vec::new()
+        })
+}
 
-            let rows = stmt.query_map([], |row| {
-                Ok((
-                    row.get::<_, i64>(0)?,
-                    row.get::<_, String>(1)?,
-                    row.get::<_, Option<String>>(2)?,
-                    row.get::<_, String>(3)?,
-                    row.get::<_, bool>(4)?,
-                    row.get::<_, bool>(5)?,
-                ))
-            })?;
+fn folder_chain_with_conn(conn: &Connection, folder_id: i64) -> Result<Vec<(i64, String)>, String> {
+    let mut stmt = conn
+        .prepare(
+            "WITH RECURSIVE folder_path AS (
+          SELECT Id as id, Name as name, ParentId, 0 as level
+          FROM Folders
+          WHERE Id = ?
 
-            let mut results = Vec::new();
-            for row in rows {
-                match row {
-                    Ok((id, question, db_options, answer, is_ai, is_pending_correction)) => {
-                        // 如果查询题目包含 URL，要求数据库记录的 URL 集合完全一致
-                        // 避免"设A图那么B图"误匹配"设C图那么B图"
-                        let query_urls = extract_urls(&title_clone);
-                        if !query_urls.is_empty() {
-                            let db_urls = extract_urls(&question);
-                            if query_urls != db_urls {
-                                continue;
-                            }
-                        }
-
-                        let Some(title_similarity) =
-                            compute_query_match_score(&title_clone, &question)
-                        else {
-                            continue;
-                        };
-
-                        let has_query_options = query_options.is_some();
-                        let option_similarity = match (
-                            query_options.as_deref(),
-                            normalize_optional_query_text(db_options.as_deref()),
-                        ) {
-                            (Some(query_options), Some(db_options)) => {
-                                compute_query_match_score(query_options, &db_options)
-                            }
-                            _ => None,
-                        };
-
-                        let is_exact_title_match = is_exact_match_score(title_similarity);
-                        if !is_exact_title_match
-                            && (require_option_match || has_query_options)
-                            && option_similarity.is_none()
-                        {
-                            continue;
-                        }
-
-                        let final_similarity = match option_similarity {
-                            Some(option_similarity) => title_similarity * 0.7 + option_similarity * 0.3,
-                            None => title_similarity,
-                        };
-
-                        results.push(((id, question, answer, is_ai, is_pending_correction), final_similarity));
-                    }
-                    Err(e) => return Err(Box::new(e) as Box<dyn std::error::Error + Send + Sync>),
-                }
-            }
+          UNION ALL
 
-            results.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+          SELECT f.Id as id, f.Name as name, f.ParentId, fp.level + 1 as level
+          FROM Folders f
+          INNER JOIN folder_path fp ON f.Id = fp.ParentId
+          WHERE f.Id != fp.id
+        )
+        SELECT id, name
+        FROM folder_path
+        ORDER BY level DESC",
+        )
+        .map_err(|e| format!("{}", e))?;
+    let rows = stmt
+        .query_map([folder_id], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+        })
+        .map_err(|e| format!("{}", e))?;
 
-            let final_results: Vec<(i64, String, String, bool, bool)> =
-                results.into_iter().take(50).map(|(data, _)| data).collect();
+    let mut chain: Vec<(i64, String)> = Vec::new();
+    for row in rows {
+        let (id, name) = row.map_err(|e| format!("{}", e))?;
+        // 根文件夹（Id 为 0）不计入路径
+        if id == 0 {
+            continue;
+        }
+        let path = match chain.last() {
+            Some((_, parent)) => format!("{}/{}", parent, name),
+            None => name,
+        };
+        chain.push((id, path));
+    }
+    Ok(chain)
+}
 
-            Ok(final_results)
-        },
-    )
-    .await?;
+/// 返回全部匹配配置档（内置预设与 config.json 中的自定义配置）及默认 / 文件夹选择
+#[tauri::command]
+pub async fn get_matching_profiles() -> Result<MatchingProfiles, String> {
+    Ok(MatchingProfiles::load())
+}
 
-    result
+// 查询范围不包含配置的保存文件夹时，改为保存到范围内的第一个文件夹
+fn save_folder_in_scope(configured_folder_id: i64, scope: Option<&QueryScope>) -> i64 {
+    match scope {
+        Some(scope) if !scope.folder_ids.contains(&configured_folder_id) => {
+            scope.roots.first().copied().unwrap_or(configured_folder_id)
+        }
+        _ => configured_folder_id,
+    }
 }
 
-pub fn insert_ai_response(
-    question: &str,
-    answer: &str,
-    options: Option<String>,
-    question_type: Option<String>,
-    is_ai: bool,
-) -> Result<i64, String> {
-    if answer.trim().is_empty() {
-        return Err("AI处理结果答案为空，不保存题目".to_string());
+fn get_configured_save_folder_id(conn: &Connection, scope: Option<&QueryScope>) -> i64 {
+    let configured_folder_id =
+        save_folder_in_scope(read_config_i64("questionSaveFolderId").unwrap_or(0), scope);
+
+    if configured_folder_id <= 0 {
+        return 0;
     }
 
-    let conn = get_conn()?;
-    let folder_id = get_configured_save_folder_id(&conn);
-    let folder_name: String = conn
+    let folder_exists: bool = conn
         .query_row(
-            "SELECT Name FROM Folders WHERE Id = ?",
-            [folder_id],
+            "SELECT EXISTS(SELECT 1 FROM Folders WHERE Id = ?)",
+            [configured_folder_id],
             |row| row.get(0),
         )
-        .unwrap_or_else(|_| "默认文件夹".to_string());
+        .unwrap_or(false);
 
-    conn.execute(
-        "INSERT INTO AIResponses (Question, Answer, Options, QuestionType, IsAi, IsPendingCorrection, CreateTime, FolderId, FolderName) VALUES (?, ?, ?, ?, ?, 0, datetime('now'), ?, ?)",
-        rusqlite::params![question, answer, options, question_type, is_ai, folder_id, folder_name],
-    ).map_err(|e| format!("{}", e))?;
+    if !folder_exists {
+        println!(
+            "⚠️ 配置的题目保存文件夹不存在，已回退到默认文件夹: {}",
+            configured_folder_id
+        );
+        return 0;
+    }
 
-    Ok(conn.last_insert_rowid())
+    match get_target_folder_id(conn, configured_folder_id) {
+        Ok(folder_id) => folder_id,
+        Err(error) => {
+            println!("⚠️ 解析题目保存文件夹失败，已回退到默认文件夹: {}", error);
+            0
+        }
+    }
 }
 
-pub fn get_username() -> Result<String, String> {
-    if let Ok(userprofile) = env::var("USERPROFILE") {
-        if !userprofile.is_empty() {
-            let path = std::path::Path::new(&userprofile);
-            if let Some(name_os) = path.file_name() {
-                let name = name_os.to_string_lossy().into_owned();
-                if !name.is_empty() {
-                    return Ok(name);
-                }
-            }
-            // 回退：按分隔符截取最后一段
-            let extracted = userprofile
-                .rsplit(|c| c == '\\' || c == '/')
-                .next()
-                .unwrap_or(&userprofile);
-            if !extracted.is_empty() {
-                return Ok(extracted.to_string());
-            }
-        }
+#[tauri::command]
+pub async fn add_question(
+    content: String,
+    options: Option<String>,
+    answer: String,
+    question_type: Option<String>,
+    folder_id: i64,
+    is_ai: Option<bool>,
+) -> Result<AIResponse, String> {
+    let mut conn = get_conn()?;
+    let is_ai = is_ai.unwrap_or(false);
+
+    if is_ai && answer.trim().is_empty() {
+        return Err("AI处理结果答案为空，不保存题目".to_string());
     }
-    env::var("USERNAME")
-        .or_else(|_| env::var("USER"))
-        .map_err(|_| "Unable to get username".to_string())
+
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("开启事务失败: {}", e))?;
+    let source = if is_ai {
+        RevisionSource::AiAnswer
+    } else {
+        RevisionSource::Ui
+    };
+    let id = insert_question_with_conn(
+        &tx,
+        &NewQuestion {
+            content,
+            options,
+            answer,
+            question_type,
+            is_ai,
+        },
+        folder_id,
+        source,
+    )?;
+    tx.commit().map_err(|e| format!("提交事务失败: {}", e))?;
+
+    // 获取完整的插入数据返回
+    let response = conn.query_row(
+        "SELECT ar.Id, ar.Question, ar.Options, ar.Answer, ar.QuestionType, ar.FolderId, f.Name as FolderName, ar.CreateTime, ar.IsAi, COALESCE(ar.IsPendingCorrection, 0), COALESCE(ar.IsVerified, 0), ar.QuestionKind
+         FROM AIResponses ar
+         LEFT JOIN Folders f ON ar.FolderId = f.Id
+         WHERE ar.Id = ?",
+        [id],
+        map_ai_response_row,
+    ).map_err(|e| format!("{}", e))?;
+
+    Ok(response)
 }
 
-pub fn file_exists(path: &str) -> bool {
-    std::path::Path::new(path).exists()
+/// 手动新增、导入或 AI 作答保存的一道题目
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NewQuestion {
+    pub content: String,
+    pub options: Option<String>,
+    pub answer: String,
+    pub question_type: Option<String>,
+    #[serde(default)]
+    pub is_ai: bool,
 }
 
-fn get_table_columns(conn: &Connection, table_name: &str) -> Result<HashSet<String>, String> {
-    let pragma = format!("PRAGMA table_info('{table_name}')");
-    let mut stmt = conn.prepare(&pragma).map_err(|e| format!("{}", e))?;
-    let cols = stmt
-        .query_map([], |row| Ok(row.get::<_, String>(1)?))
-        .map_err(|e| format!("{}", e))?;
+// 读取题目是否已人工核实
+fn is_question_verified(conn: &Connection, id: i64) -> Result<bool, String> {
+    conn.query_row(
+        "SELECT COALESCE(IsVerified, 0) FROM AIResponses WHERE Id = ?",
+        [id],
+        |row| row.get(0),
+    )
+    .map_err(|e| format!("读取题目失败: {}", e))
+}
 
-    let mut names = HashSet::new();
-    for col in cols {
-        names.insert(col.map_err(|e| format!("{}", e))?);
-    }
+// 文件夹中题干与选项都相同的已核实题目；AI 答案不能与之重复入库
+fn find_verified_duplicate(
+    conn: &Connection,
+    question: &str,
+    normalized_options: Option<&str>,
+    folder_id: i64,
+) -> Result<Option<i64>, String> {
+    conn.query_row(
+        "SELECT Id FROM AIResponses
+         WHERE FolderId = ? AND Question = ? AND COALESCE(NormalizedOptions, '') = COALESCE(?, '')
+           AND COALESCE(IsVerified, 0) = 1 AND DeletedAt IS NULL
+         ORDER BY Id LIMIT 1",
+        rusqlite::params![folder_id, question, normalized_options],
+        |row| row.get(0),
+    )
+    .optional()
+    .map_err(|e| format!("查询已核实题目失败: {}", e))
+}
 
-    Ok(names)
+fn verified_duplicate_error(id: i64) -> String {
+    format!("题目已人工核实（ID {}），不保存 AI 答案", id)
 }
 
-fn ensure_column(
+// AI 题目与目标文件夹中已核实的题目重复时返回该题目 ID；target_folder_id 为智能归类后的文件夹
+fn verified_duplicate_of(
     conn: &Connection,
-    table_columns: &mut HashSet<String>,
-    column_name: &str,
-    alter_sql: &str,
-    backfill_sqls: &[&str],
-) -> Result<(), String> {
-    if table_columns.contains(column_name) {
-        return Ok(());
+    question: &NewQuestion,
+    target_folder_id: i64,
+) -> Result<Option<i64>, String> {
+    if !question.is_ai {
+        return Ok(None);
     }
+    let content = clean_stored_text(&question.content);
+    let options = question.options.as_deref().map(clean_stored_text);
+    let normalized_options = normalize_options(options.as_deref());
+    find_verified_duplicate(
+        conn,
+        &content,
+        normalized_options.as_deref(),
+        target_folder_id,
+    )
+}
 
-    conn.execute(alter_sql, []).map_err(|e| format!("{}", e))?;
-    for sql in backfill_sqls {
-        conn.execute(sql, []).map_err(|e| format!("{}", e))?;
+// 写入一道题目并记录新建修订，调用方负责事务；AI 题目与已核实题目重复时返回错误
+fn insert_question_with_conn(
+    conn: &Connection,
+    question: &NewQuestion,
+    folder_id: i64,
+    source: RevisionSource,
+) -> Result<i64, String> {
+    let target_folder_id =
+        get_target_folder_id(conn, folder_id).map_err(|e| format!("解析目标文件夹失败: {}", e))?;
+    if let Some(verified) = verified_duplicate_of(conn, question, target_folder_id)? {
+        return Err(verified_duplicate_error(verified));
     }
-
-    table_columns.insert(column_name.to_string());
-    Ok(())
+    insert_question_into_folder(conn, question, target_folder_id, source)
 }
 
-pub fn init_database_schema(db_path: &str) -> Result<(), String> {
-    let conn = Connection::open(db_path).map_err(|e| format!("{}", e))?;
+// 写入一道题目到已解析的目标文件夹并记录新建修订，不检查已核实题目
+fn insert_question_into_folder(
+    conn: &Connection,
+    question: &NewQuestion,
+    target_folder_id: i64,
+    source: RevisionSource,
+) -> Result<i64, String> {
+    let content = clean_stored_text(&question.content);
+    let options = question.options.as_deref().map(clean_stored_text);
+    let normalized_options = normalize_options(options.as_deref());
+    let canonicalizer = UrlCanonicalizer::cached();
+    let canonical_urls = stored_canonical_urls(&canonicalizer, &content);
+    let question_kind = question_kind_id(question.question_type.as_deref());
     conn.execute(
-        "CREATE TABLE IF NOT EXISTS Folders (
-          Id INTEGER PRIMARY KEY AUTOINCREMENT,
-          Name TEXT NOT NULL,
-          ParentId INTEGER DEFAULT 0,
-          CreateTime DATETIME DEFAULT CURRENT_TIMESTAMP
-        )",
-        [],
+        "INSERT INTO AIResponses (Question, Options, NormalizedOptions, CanonicalUrls, CanonicalUrlsRules, Answer, QuestionType, QuestionKind, FolderId, IsAi, CreateTime)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, datetime('now'))",
+        rusqlite::params![content, options, normalized_options, canonical_urls, canonicalizer.fingerprint(), question.answer, question.question_type, question_kind, target_folder_id, question.is_ai],
+    ).map_err(|e| format!("保存题目失败: {}", e))?;
+
+    let id = conn.last_insert_rowid();
+    record_question_creation(conn, id, source)?;
+    Ok(id)
+}
+
+/// 导入题目到指定文件夹，题干为空的条目和与已核实题目重复的 AI 题目会被跳过；返回导入的题目数量
+#[tauri::command]
+pub async fn import_questions(
+    questions: Vec<NewQuestion>,
+    folder_id: i64,
+) -> Result<usize, String> {
+    let mut conn = get_conn()?;
+    import_questions_with_conn(&mut conn, &questions, folder_id)
+}
+
+fn import_questions_with_conn(
+    conn: &mut Connection,
+    questions: &[NewQuestion],
+    folder_id: i64,
+) -> Result<usize, String> {
+    // 没有可导入的题目时不解析目标文件夹，避免创建空的 [未分类]
+    if questions
+        .iter()
+        .all(|question| question.content.trim().is_empty())
+    {
+        return Ok(0);
+    }
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("开启事务失败: {}", e))?;
+    let target_folder_id =
+        get_target_folder_id(&tx, folder_id).map_err(|e| format!("解析目标文件夹失败: {}", e))?;
+    let mut count = 0;
+    let mut skipped_verified = 0;
+    for question in questions {
+        if question.content.trim().is_empty() {
+            continue;
+        }
+        if verified_duplicate_of(&tx, question, target_folder_id)?.is_some() {
+            skipped_verified += 1;
+            continue;
+        }
+        insert_question_into_folder(&tx, question, target_folder_id, RevisionSource::Import)?;
+        count += 1;
+    }
+    tx.commit().map_err(|e| format!("提交事务失败: {}", e))?;
+    if skipped_verified > 0 {
+        println!("⚠️ 跳过 {} 道与已核实题目重复的 AI 题目", skipped_verified);
+    }
+    Ok(count)
+}
+
+/// 题目入库前的原始 HTML（来自 OCS 转发的 HTML 题目），没有时返回 None
+#[tauri::command]
+pub async fn get_question_raw_html(id: i64) -> Result<Option<String>, String> {
+    let conn = get_conn()?;
+    conn.query_row(
+        "SELECT RawQuestionHtml FROM AIResponses WHERE Id = ?",
+        [id],
+        |row| row.get::<_, Option<String>>(0),
     )
-    .map_err(|e| format!("{}", e))?;
+    .optional()
+    .map_err(|e| format!("读取原始题目失败: {}", e))?
+    .ok_or_else(|| "题目不存在".to_string())
+}
 
-    let mut folder_columns = get_table_columns(&conn, "Folders")?;
-    ensure_column(
-        &conn,
-        &mut folder_columns,
-        "ParentId",
-        "ALTER TABLE Folders ADD COLUMN ParentId INTEGER DEFAULT 0",
-        &["UPDATE Folders SET ParentId = 0 WHERE ParentId IS NULL"],
-    )?;
-    ensure_column(
-        &conn,
-        &mut folder_columns,
-        "CreateTime",
-        "ALTER TABLE Folders ADD COLUMN CreateTime DATETIME",
-        &["UPDATE Folders SET CreateTime = datetime('now') WHERE CreateTime IS NULL"],
-    )?;
+/// 题目修改来源，记录在修订历史中，由写入题目的代码路径决定
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RevisionSource {
+    /// 应用界面中的新增、编辑与标记
+    Ui,
+    /// HTTP 接口（如 OCS 标记待修正）
+    Api,
+    /// AI 答题结果入库
+    AiAnswer,
+    Import,
+    Rollback,
+}
 
-    let exists_default: i64 = conn
-        .query_row("SELECT COUNT(1) FROM Folders WHERE Id = 0", [], |row| {
-            row.get(0)
-        })
-        .unwrap_or(0);
-    if exists_default == 0 {
-        let _ = conn.execute(
-            "INSERT INTO Folders (Id, Name, ParentId) VALUES (0, '默认文件夹', 0)",
-            [],
-        );
+impl RevisionSource {
+    fn as_str(&self) -> &'static str {
+        match self {
+            RevisionSource::Ui => "ui",
+            RevisionSource::Api => "api",
+            RevisionSource::AiAnswer => "ai_answer",
+            RevisionSource::Import => "import",
+            RevisionSource::Rollback => "rollback",
+        }
     }
+}
+
+/// 题目在某一时刻的可编辑字段快照
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QuestionSnapshot {
+    pub question: String,
+    pub options: Option<String>,
+    pub answer: Option<String>,
+    pub question_type: Option<String>,
+    pub is_pending_correction: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct QuestionRevision {
+    pub id: i64,
+    pub question_id: i64,
+    /// 修改前的内容，新建题目的修订为空
+    pub old: Option<QuestionSnapshot>,
+    pub new: QuestionSnapshot,
+    pub source: String,
+    pub create_time: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct QuestionFieldDiff {
+    pub field: String,
+    pub from: Option<String>,
+    pub to: Option<String>,
+}
+
+fn load_question_snapshot(
+    conn: &Connection,
+    id: i64,
+) -> Result<Option<QuestionSnapshot>, rusqlite::Error> {
+    conn.query_row(
+        "SELECT Question, Options, Answer, QuestionType, COALESCE(IsPendingCorrection, 0)
+         FROM AIResponses
+         WHERE Id = ?",
+        [id],
+        |row| {
+            Ok(QuestionSnapshot {
+                question: row.get(0)?,
+                options: row.get(1)?,
+                answer: row.get(2)?,
+                question_type: row.get(3)?,
+                is_pending_correction: row.get(4)?,
+            })
+        },
+    )
+    .optional()
+}
 
+// 写入新的题目内容并记录一条修订，调用方负责事务
+fn apply_question_change(
+    conn: &Connection,
+    id: i64,
+    old: &QuestionSnapshot,
+    new: &QuestionSnapshot,
+    source: RevisionSource,
+) -> Result<(), String> {
+    let canonicalizer = UrlCanonicalizer::cached();
     conn.execute(
-        "CREATE TABLE IF NOT EXISTS AIResponses (
-          Id INTEGER PRIMARY KEY AUTOINCREMENT,
-          Question TEXT NOT NULL,
-          Options TEXT,
-          QuestionType TEXT,
-          Answer TEXT NOT NULL,
-          CreateTime DATETIME DEFAULT CURRENT_TIMESTAMP,
-          FolderId INTEGER DEFAULT 0,
-          FolderName TEXT DEFAULT '默认文件夹',
-          IsAi BOOLEAN DEFAULT 1,
-          IsPendingCorrection BOOLEAN DEFAULT 0
-        )",
-        [],
+        // 题干被修改后原始 HTML 不再对应当前题目，一并清除
+        "UPDATE AIResponses
+         SET RawQuestionHtml = CASE WHEN Question = ? THEN RawQuestionHtml END,
+             Question = ?, Options = ?, NormalizedOptions = ?, CanonicalUrls = ?, CanonicalUrlsRules = ?, Answer = ?, QuestionType = ?, QuestionKind = ?, IsPendingCorrection = ?
+         WHERE Id = ?",
+        rusqlite::params![
+            new.question,
+            new.question,
+            new.options,
+            normalize_options(new.options.as_deref()),
+            stored_canonical_urls(&canonicalizer, &new.question),
+            canonicalizer.fingerprint(),
+            new.answer,
+            new.question_type,
+            question_kind_id(new.question_type.as_deref()),
+            new.is_pending_correction,
+            id
+        ],
     )
-    .map_err(|e| format!("{}", e))?;
+    .map_err(|e| format!("更新题目失败: {}", e))?;
 
-    let mut ai_response_columns = get_table_columns(&conn, "AIResponses")?;
-    let had_folder_name = ai_response_columns.contains("FolderName");
+    record_question_revision(conn, id, Some(old), new, source)
+}
 
-    ensure_column(
-        &conn,
-        &mut ai_response_columns,
-        "QuestionType",
-        "ALTER TABLE AIResponses ADD COLUMN QuestionType TEXT",
-        &[],
-    )?;
-    ensure_column(
-        &conn,
-        &mut ai_response_columns,
-        "CreateTime",
-        "ALTER TABLE AIResponses ADD COLUMN CreateTime DATETIME",
-        &["UPDATE AIResponses SET CreateTime = datetime('now') WHERE CreateTime IS NULL"],
-    )?;
-    ensure_column(
-        &conn,
-        &mut ai_response_columns,
-        "FolderId",
-        "ALTER TABLE AIResponses ADD COLUMN FolderId INTEGER DEFAULT 0",
-        &["UPDATE AIResponses SET FolderId = 0 WHERE FolderId IS NULL"],
-    )?;
-    if had_folder_name {
-        conn.execute(
-            "UPDATE AIResponses
-             SET FolderId = COALESCE(
-               (
-                 SELECT Id
-                 FROM Folders
-                 WHERE Folders.Name = AIResponses.FolderName
-                 ORDER BY CASE WHEN Id = 0 THEN 0 ELSE 1 END, Id
-                 LIMIT 1
-               ),
+// 记录一条修订，old 为空表示新建题目；调用方负责事务
+fn record_question_revision(
+    conn: &Connection,
+    id: i64,
+    old: Option<&QuestionSnapshot>,
+    new: &QuestionSnapshot,
+    source: RevisionSource,
+) -> Result<(), String> {
+    conn.execute(
+        "INSERT INTO QuestionRevisions (
+           QuestionId,
+           OldQuestion, OldOptions, OldAnswer, OldQuestionType, OldIsPendingCorrection,
+           NewQuestion, NewOptions, NewAnswer, NewQuestionType, NewIsPendingCorrection,
+           Source, CreateTime
+         ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, datetime('now'))",
+        rusqlite::params![
+            id,
+            old.map(|old| &old.question),
+            old.and_then(|old| old.options.as_ref()),
+            old.and_then(|old| old.answer.as_ref()),
+            old.and_then(|old| old.question_type.as_ref()),
+            old.map(|old| old.is_pending_correction),
+            new.question,
+            new.options,
+            new.answer,
+            new.question_type,
+            new.is_pending_correction,
+            source.as_str(),
+        ],
+    )
+    .map_err(|e| format!("记录修订历史失败: {}", e))?;
+
+    Ok(())
+}
+
+// 为刚写入的题目记录一条新建修订；调用方负责事务
+fn record_question_creation(
+    conn: &Connection,
+    id: i64,
+    source: RevisionSource,
+) -> Result<(), String> {
+    let new = load_question_snapshot(conn, id)
+        .map_err(|e| format!("读取题目失败: {}", e))?
+        .ok_or_else(|| "题目不存在".to_string())?;
+    record_question_revision(conn, id, None, &new, source)
+}
+
+#[tauri::command]
+pub async fn update_question(
+    id: i64,
+    question: Option<String>,
+    options: Option<String>,
+    answer: Option<String>,
+    question_type: Option<String>,
+) -> Result<(), String> {
+    let mut conn = get_conn()?;
+    update_question_with_conn(
+        &mut conn,
+        id,
+        QuestionUpdate {
+            question,
+            options,
+            answer,
+            question_type,
+        },
+        RevisionSource::Ui,
+    )
+}
+
+/// update_question 的可选字段，None 表示保持原值
+#[derive(Debug, Default)]
+struct QuestionUpdate {
+    question: Option<String>,
+    options: Option<String>,
+    answer: Option<String>,
+    question_type: Option<String>,
+}
+
+fn update_question_with_conn(
+    conn: &mut Connection,
+    id: i64,
+    update: QuestionUpdate,
+    source: RevisionSource,
+) -> Result<(), String> {
+    let has_updates = update.question.is_some()
+        || update.options.is_some()
+        || update.answer.is_some()
+        || update.question_type.is_some();
+    if !has_updates {
+        return Ok(());
+    }
+
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("开启事务失败: {}", e))?;
+    let old = load_question_snapshot(&tx, id)
+        .map_err(|e| format!("读取题目失败: {}", e))?
+        .ok_or_else(|| "题目不存在".to_string())?;
+
+    // 修改题目即视为已修正，清除待修正标记（会一并记录到修订历史中）
+    let new = QuestionSnapshot {
+        question: update
+            .question
+            .as_deref()
+            .map(clean_stored_text)
+            .unwrap_or_else(|| old.question.clone()),
+        options: update
+            .options
+            .as_deref()
+            .map(clean_stored_text)
+            .or_else(|| old.options.clone()),
+        answer: update.answer.or_else(|| old.answer.clone()),
+        question_type: update.question_type.or_else(|| old.question_type.clone()),
+        is_pending_correction: false,
+    };
+
+    if new != old {
+        apply_question_change(&tx, id, &old, &new, source)?;
+    }
+
+    tx.commit().map_err(|e| format!("提交事务失败: {}", e))?;
+    Ok(())
+}
+
+fn map_question_revision_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<QuestionRevision> {
+    Ok(QuestionRevision {
+        id: row.get(0)?,
+        question_id: row.get(1)?,
+        old: match row.get::<_, Option<String>>(2)? {
+            Some(question) => Some(QuestionSnapshot {
+                question,
+                options: row.get(3)?,
+                answer: row.get(4)?,
+                question_type: row.get(5)?,
+                is_pending_correction: row.get(6)?,
+            }),
+            None => None,
+        },
+        new: QuestionSnapshot {
+            question: row.get(7)?,
+            options: row.get(8)?,
+            answer: row.get(9)?,
+            question_type: row.get(10)?,
+            is_pending_correction: row.get(11)?,
+        },
+        source: row.get(12)?,
+        create_time: row.get(13)?,
+    })
+}
+
+const QUESTION_REVISION_COLUMNS: &str = "RevisionId, QuestionId,
+  OldQuestion, OldOptions, OldAnswer, OldQuestionType, COALESCE(OldIsPendingCorrection, 0),
+  NewQuestion, NewOptions, NewAnswer, NewQuestionType, COALESCE(NewIsPendingCorrection, 0),
+  Source, CreateTime";
+
+fn load_question_revision(conn: &Connection, revision_id: i64) -> Result<QuestionRevision, String> {
+    conn.query_row(
+        &format!(
+            "SELECT {} FROM QuestionRevisions WHERE RevisionId = ?",
+            QUESTION_REVISION_COLUMNS
+        ),
+        [revision_id],
+        map_question_revision_row,
+    )
+    .optional()
+    .map_err(|e| format!("读取修订记录失败: {}", e))?
+    .ok_or_else(|| "修订记录不存在".to_string())
+}
+
+#[tauri::command]
+pub async fn get_question_revisions(question_id: i64) -> Result<Vec<QuestionRevision>, String> {
+    let conn = get_conn()?;
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {} FROM QuestionRevisions WHERE QuestionId = ? ORDER BY RevisionId DESC",
+            QUESTION_REVISION_COLUMNS
+        ))
+        .map_err(|e| format!("{}", e))?;
+
+    let rows = stmt
+        .query_map([question_id], map_question_revision_row)
+        .map_err(|e| format!("{}", e))?;
+
+    let mut revisions = Vec::new();
+    for row in rows {
+        revisions.push(row.map_err(|e| format!("{}", e))?);
+    }
+    Ok(revisions)
+}
+
+fn diff_question_snapshots(
+    from: &QuestionSnapshot,
+    to: &QuestionSnapshot,
+) -> Vec<QuestionFieldDiff> {
+    let fields = [
+        (
+            "question",
+            Some(from.question.clone()),
+            Some(to.question.clone()),
+        ),
+        ("options", from.options.clone(), to.options.clone()),
+        ("answer", from.answer.clone(), to.answer.clone()),
+        (
+            "question_type",
+            from.question_type.clone(),
+            to.question_type.clone(),
+        ),
+        (
+            "is_pending_correction",
+            Some(from.is_pending_correction.to_string()),
+            Some(to.is_pending_correction.to_string()),
+        ),
+    ];
+
+    fields
+        .into_iter()
+        .filter(|(_, from, to)| from != to)
+        .map(|(field, from, to)| QuestionFieldDiff {
+            field: field.to_string(),
+            from,
+            to,
+        })
+        .collect()
+}
+
+/// 比较两个修订完成后的题目内容；to_revision_id 为空时与题目当前内容比较
+#[tauri::command]
+pub async fn diff_question_revisions(
+    from_revision_id: i64,
+    to_revision_id: Option<i64>,
+) -> Result<Vec<QuestionFieldDiff>, String> {
+    let conn = get_conn()?;
+    let from = load_question_revision(&conn, from_revision_id)?;
+    let to = match to_revision_id {
+        Some(to_revision_id) => {
+            let to = load_question_revision(&conn, to_revision_id)?;
+            if to.question_id != from.question_id {
+                return Err("两个修订记录不属于同一道题目".to_string());
+            }
+            to.new
+        }
+        None => load_question_snapshot(&conn, from.question_id)
+            .map_err(|e| format!("读取题目失败: {}", e))?
+            .ok_or_else(|| "题目不存在".to_string())?,
+    };
+
+    Ok(diff_question_snapshots(&from.new, &to))
+}
+
+/// 回滚到指定修订：将题目恢复为该修订保存的内容（新建记录即题目最初的内容），并记录一条回滚修订
+#[tauri::command]
+pub async fn rollback_question_revision(revision_id: i64) -> Result<(), String> {
+    let mut conn = get_conn()?;
+    rollback_question_revision_with_conn(&mut conn, revision_id)
+}
+
+fn rollback_question_revision_with_conn(
+    conn: &mut Connection,
+    revision_id: i64,
+) -> Result<(), String> {
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("开启事务失败: {}", e))?;
+    let revision = load_question_revision(&tx, revision_id)?;
+    let current = load_question_snapshot(&tx, revision.question_id)
+        .map_err(|e| format!("读取题目失败: {}", e))?
+        .ok_or_else(|| "题目不存在".to_string())?;
+
+    if current != revision.new {
+        apply_question_change(
+            &tx,
+            revision.question_id,
+            &current,
+            &revision.new,
+            RevisionSource::Rollback,
+        )?;
+    }
+
+    tx.commit().map_err(|e| format!("提交事务失败: {}", e))?;
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn move_question(question_id: i64, target_folder_id: i64) -> Result<(), String> {
+    let mut conn = get_conn()?;
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("开启事务失败: {}", e))?;
+    let actual_target_id = get_target_folder_id(&tx, target_folder_id)
+        .map_err(|e| format!("解析目标文件夹失败: {}", e))?;
+
+    tx.execute(
+        "UPDATE AIResponses SET FolderId = ? WHERE Id = ?",
+        rusqlite::params![actual_target_id, question_id],
+    )
+    .map_err(|e| format!("移动题目失败: {}", e))?;
+
+    tx.commit().map_err(|e| format!("提交事务失败: {}", e))?;
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn copy_question(question_id: i64, target_folder_id: i64) -> Result<(), String> {
+    let mut conn = get_conn()?;
+    copy_question_with_conn(&mut conn, question_id, target_folder_id)
+}
+
+fn copy_question_with_conn(
+    conn: &mut Connection,
+    question_id: i64,
+    target_folder_id: i64,
+) -> Result<(), String> {
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("开启事务失败: {}", e))?;
+
+    let actual_target_id = get_target_folder_id(&tx, target_folder_id)
+        .map_err(|e| format!("解析目标文件夹失败: {}", e))?;
+
+    // 在同一语句中读取原题并插入副本，避免读写之间被其他修改打断
+    let affected = tx
+        .execute(
+            "INSERT INTO AIResponses (Question, RawQuestionHtml, Options, NormalizedOptions, CanonicalUrls, CanonicalUrlsRules, Answer, QuestionType, QuestionKind, FolderId, IsAi, IsPendingCorrection, IsVerified, CreateTime)
+             SELECT Question, RawQuestionHtml, Options, NormalizedOptions, CanonicalUrls, CanonicalUrlsRules, Answer, QuestionType, QuestionKind, ?, IsAi, COALESCE(IsPendingCorrection, 0), COALESCE(IsVerified, 0), datetime('now')
+             FROM AIResponses
+             WHERE Id = ? AND DeletedAt IS NULL",
+            rusqlite::params![actual_target_id, question_id],
+        )
+        .map_err(|e| format!("复制题目失败: {}", e))?;
+
+    if affected == 0 {
+        return Err("题目不存在".to_string());
+    }
+    record_question_creation(&tx, tx.last_insert_rowid(), RevisionSource::Ui)?;
+
+    tx.commit().map_err(|e| format!("提交事务失败: {}", e))?;
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn delete_question(id: i64) -> Result<(), String> {
+    let mut conn = get_conn()?;
+    delete_questions_with_conn(&mut conn, &[id])
+}
+
+#[tauri::command]
+pub async fn delete_questions(ids: Vec<i64>) -> Result<(), String> {
+    let mut conn = get_conn()?;
+    delete_questions_with_conn(&mut conn, &ids)
+}
+
+fn delete_questions_with_conn(conn: &mut Connection, ids: &[i64]) -> Result<(), String> {
+    if ids.is_empty() {
+        return Ok(());
+    }
+
+    // 通过 json_each 将 id 列表展开为集合，一条语句完成批量移入回收站
+    let ids_json = serde_json::to_string(ids).map_err(|e| format!("{}", e))?;
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("开启事务失败: {}", e))?;
+    tx.execute(
+        &format!(
+            "{} WHERE Id IN (SELECT value FROM json_each(?)) AND DeletedAt IS NULL",
+            TRASH_QUESTIONS_SQL
+        ),
+        [ids_json],
+    )
+    .map_err(|e| format!("批量删除题目失败: {}", e))?;
+    tx.commit().map_err(|e| format!("提交事务失败: {}", e))?;
+    Ok(())
+}
+
+/// 将题目移入回收站（软删除）并记录原文件夹，需拼接 WHERE 条件使用
+const TRASH_QUESTIONS_SQL: &str =
+    "UPDATE AIResponses SET DeletedAt = datetime('now'), DeletedFromFolderId = FolderId";
+
+/// 选出指定文件夹（参数）及其所有子文件夹 Id 的递归子查询
+/// 默认文件夹 0 与顶层文件夹同级，不向下展开
+const FOLDER_SUBTREE_IDS_SQL: &str = "WITH RECURSIVE folder_tree AS (
+      SELECT Id FROM Folders WHERE Id = ?
+      UNION
+      SELECT f.Id FROM Folders f
+      INNER JOIN folder_tree ft ON f.ParentId = ft.Id
+      WHERE ft.Id != 0
+    )
+    SELECT Id FROM folder_tree";
+
+#[tauri::command]
+pub async fn clear_folder_questions(id: i64) -> Result<(), String> {
+    let mut conn = get_conn()?;
+    clear_folder_questions_with_conn(&mut conn, id)
+}
+
+fn clear_folder_questions_with_conn(conn: &mut Connection, id: i64) -> Result<(), String> {
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("开启事务失败: {}", e))?;
+    tx.execute(
+        &format!(
+            "{} WHERE FolderId IN ({}) AND DeletedAt IS NULL",
+            TRASH_QUESTIONS_SQL, FOLDER_SUBTREE_IDS_SQL
+        ),
+        [id],
+    )
+    .map_err(|e| format!("清空文件夹题目失败: {}", e))?;
+    tx.commit().map_err(|e| format!("提交事务失败: {}", e))?;
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn delete_folder(id: i64, delete_questions: bool) -> Result<(), String> {
+    let mut conn = get_conn()?;
+    delete_folder_with_conn(&mut conn, id, delete_questions)
+}
+
+fn delete_folder_with_conn(
+    conn: &mut Connection,
+    id: i64,
+    delete_questions: bool,
+) -> Result<(), String> {
+    if id == 0 {
+        return Err("默认文件夹不能删除".to_string());
+    }
+
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("开启事务失败: {}", e))?;
+
+    if delete_questions {
+        // 将该文件夹及其所有子文件夹中的题目移入回收站
+        tx.execute(
+            &format!(
+                "{} WHERE FolderId IN ({}) AND DeletedAt IS NULL",
+                TRASH_QUESTIONS_SQL, FOLDER_SUBTREE_IDS_SQL
+            ),
+            [id],
+        )
+        .map_err(|e| format!("删除文件夹题目失败: {}", e))?;
+    } else {
+        let target_folder_id = resolve_deleted_folder_question_target(&tx, id)?;
+        if let Some(target_folder_id) = target_folder_id {
+            tx.execute(
+                &format!(
+                    "UPDATE AIResponses SET FolderId = ? WHERE FolderId IN ({}) AND DeletedAt IS NULL",
+                    FOLDER_SUBTREE_IDS_SQL
+                ),
+                [target_folder_id, id],
+            )
+            .map_err(|e| format!("转移文件夹题目失败: {}", e))?;
+        }
+    }
+
+    // 删除该文件夹及其所有子文件夹
+    tx.execute(
+        &format!(
+            "DELETE FROM Folders WHERE Id IN ({})",
+            FOLDER_SUBTREE_IDS_SQL
+        ),
+        [id],
+    )
+    .map_err(|e| format!("删除文件夹失败: {}", e))?;
+
+    tx.commit().map_err(|e| format!("提交事务失败: {}", e))?;
+    Ok(())
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TrashedAIResponse {
+    #[serde(flatten)]
+    pub response: AIResponse,
+    pub deleted_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PaginatedTrashedAIResponses {
+    pub items: Vec<TrashedAIResponse>,
+    pub total: i64,
+}
+
+/// 回收站题目默认保留天数，可通过 config.json 的 trashRetentionDays 配置，<= 0 表示不自动清理
+const DEFAULT_TRASH_RETENTION_DAYS: i64 = 30;
+
+#[tauri::command]
+pub async fn get_trash_questions(
+    page: i64,
+    page_size: i64,
+) -> Result<PaginatedTrashedAIResponses, String> {
+    let conn = get_conn()?;
+
+    let page = page.max(1);
+    let page_size = page_size.clamp(1, 200);
+    let offset = (page - 1) * page_size;
+
+    let total: i64 = conn
+        .query_row(
+            "SELECT COUNT(*) FROM AIResponses WHERE DeletedAt IS NOT NULL",
+            [],
+            |row| row.get(0),
+        )
+        .map_err(|e| format!("{}", e))?;
+
+    // folder_id / folder_name 返回题目删除前所在的原文件夹
+    let mut stmt = conn
+        .prepare(
+            "SELECT ar.Id, ar.Question, ar.Options, ar.Answer, ar.QuestionType,
+               COALESCE(ar.DeletedFromFolderId, ar.FolderId), f.Name as FolderName, ar.CreateTime, ar.IsAi,
+               COALESCE(ar.IsPendingCorrection, 0), COALESCE(ar.IsVerified, 0), ar.QuestionKind, ar.DeletedAt
+             FROM AIResponses ar
+             LEFT JOIN Folders f ON COALESCE(ar.DeletedFromFolderId, ar.FolderId) = f.Id
+             WHERE ar.DeletedAt IS NOT NULL
+             ORDER BY ar.DeletedAt DESC, ar.Id DESC
+             LIMIT ? OFFSET ?",
+        )
+        .map_err(|e| format!("{}", e))?;
+
+    let rows = stmt
+        .query_map(rusqlite::params![page_size, offset], |row| {
+            Ok(TrashedAIResponse {
+                response: map_ai_response_row(row)?,
+                deleted_at: row.get(12)?,
+            })
+        })
+        .map_err(|e| format!("{}", e))?;
+
+    let mut items = Vec::new();
+    for row in rows {
+        items.push(row.map_err(|e| format!("{}", e))?);
+    }
+
+    Ok(PaginatedTrashedAIResponses { items, total })
+}
+
+#[tauri::command]
+pub async fn restore_questions(ids: Vec<i64>, target_folder_id: Option<i64>) -> Result<(), String> {
+    let mut conn = get_conn()?;
+    restore_questions_with_conn(&mut conn, &ids, target_folder_id)
+}
+
+fn restore_questions_with_conn(
+    conn: &mut Connection,
+    ids: &[i64],
+    target_folder_id: Option<i64>,
+) -> Result<(), String> {
+    if ids.is_empty() {
+        return Ok(());
+    }
+
+    let ids_json = serde_json::to_string(ids).map_err(|e| format!("{}", e))?;
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("开启事务失败: {}", e))?;
+
+    // 未指定目标文件夹时按原文件夹分组恢复，原文件夹已被删除则恢复到默认文件夹
+    let source_folder_ids: Vec<i64> = if target_folder_id.is_some() {
+        Vec::new()
+    } else {
+        let mut stmt = tx
+            .prepare(
+                "SELECT DISTINCT COALESCE(DeletedFromFolderId, FolderId, 0)
+                 FROM AIResponses
+                 WHERE Id IN (SELECT value FROM json_each(?)) AND DeletedAt IS NOT NULL",
+            )
+            .map_err(|e| format!("读取原文件夹失败: {}", e))?;
+        let rows = stmt
+            .query_map([&ids_json], |row| row.get::<_, i64>(0))
+            .map_err(|e| format!("读取原文件夹失败: {}", e))?;
+        let mut folder_ids = Vec::new();
+        for row in rows {
+            folder_ids.push(row.map_err(|e| format!("读取原文件夹失败: {}", e))?);
+        }
+        folder_ids
+    };
+
+    if let Some(target_folder_id) = target_folder_id {
+        if target_folder_id != 0
+            && !folder_exists(&tx, target_folder_id)
+                .map_err(|e| format!("读取目标文件夹失败: {}", e))?
+        {
+            return Err("目标文件夹不存在".to_string());
+        }
+        let actual_target_id = get_target_folder_id(&tx, target_folder_id)
+            .map_err(|e| format!("解析目标文件夹失败: {}", e))?;
+        tx.execute(
+            "UPDATE AIResponses SET FolderId = ?, DeletedAt = NULL, DeletedFromFolderId = NULL
+             WHERE Id IN (SELECT value FROM json_each(?)) AND DeletedAt IS NOT NULL",
+            rusqlite::params![actual_target_id, ids_json],
+        )
+        .map_err(|e| format!("恢复题目失败: {}", e))?;
+    } else {
+        for source_folder_id in source_folder_ids {
+            let restore_folder_id = if folder_exists(&tx, source_folder_id)
+                .map_err(|e| format!("读取原文件夹失败: {}", e))?
+            {
+                get_target_folder_id(&tx, source_folder_id)
+                    .map_err(|e| format!("解析原文件夹失败: {}", e))?
+            } else {
+                0
+            };
+            tx.execute(
+                "UPDATE AIResponses SET FolderId = ?, DeletedAt = NULL, DeletedFromFolderId = NULL
+                 WHERE Id IN (SELECT value FROM json_each(?))
+                   AND DeletedAt IS NOT NULL
+                   AND COALESCE(DeletedFromFolderId, FolderId, 0) = ?",
+                rusqlite::params![restore_folder_id, ids_json, source_folder_id],
+            )
+            .map_err(|e| format!("恢复题目失败: {}", e))?;
+        }
+    }
+
+    tx.commit().map_err(|e| format!("提交事务失败: {}", e))?;
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn purge_questions(ids: Vec<i64>) -> Result<(), String> {
+    if ids.is_empty() {
+        return Ok(());
+    }
+
+    let mut conn = get_conn()?;
+    let ids_json = serde_json::to_string(&ids).map_err(|e| format!("{}", e))?;
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("开启事务失败: {}", e))?;
+    tx.execute(
+        "DELETE FROM AIResponses WHERE Id IN (SELECT value FROM json_each(?)) AND DeletedAt IS NOT NULL",
+        [ids_json],
+    )
+    .map_err(|e| format!("彻底删除题目失败: {}", e))?;
+    delete_orphan_question_data(&tx)?;
+    tx.commit().map_err(|e| format!("提交事务失败: {}", e))?;
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn empty_trash() -> Result<(), String> {
+    let mut conn = get_conn()?;
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("开启事务失败: {}", e))?;
+    tx.execute("DELETE FROM AIResponses WHERE DeletedAt IS NOT NULL", [])
+        .map_err(|e| format!("清空回收站失败: {}", e))?;
+    delete_orphan_question_data(&tx)?;
+    tx.commit().map_err(|e| format!("提交事务失败: {}", e))?;
+    Ok(())
+}
+
+/// 回收站过期清理任务：启动时执行一次，之后每 6 小时执行一次
+pub async fn trash_purge_loop() {
+    loop {
+        match purge_expired_trash() {
+            Ok(purged) if purged > 0 => println!("🗑️ 已清理 {} 道过期的回收站题目", purged),
+            Ok(_) => {}
+            Err(e) => println!("⚠️ 清理回收站失败: {}", e),
+        }
+        tokio::time::sleep(std::time::Duration::from_secs(6 * 60 * 60)).await;
+    }
+}
+
+// 彻底删除超过保留天数的回收站题目，返回删除的数量
+fn purge_expired_trash() -> Result<usize, String> {
+    let mut conn = get_conn()?;
+    let retention_days =
+        read_config_i64("trashRetentionDays").unwrap_or(DEFAULT_TRASH_RETENTION_DAYS);
+    purge_trash_older_than(&mut conn, retention_days)
+}
+
+fn purge_trash_older_than(conn: &mut Connection, retention_days: i64) -> Result<usize, String> {
+    if retention_days <= 0 {
+        return Ok(0);
+    }
+
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("开启事务失败: {}", e))?;
+    let purged = tx
+        .execute(
+            "DELETE FROM AIResponses WHERE DeletedAt IS NOT NULL AND DeletedAt < datetime('now', ?)",
+            [format!("-{} days", retention_days)],
+        )
+        .map_err(|e| format!("清理过期回收站题目失败: {}", e))?;
+    if purged > 0 {
+        delete_orphan_question_data(&tx)?;
+    }
+    tx.commit().map_err(|e| format!("提交事务失败: {}", e))?;
+    Ok(purged)
+}
+
+// 题目被彻底删除后，一并清理其修订历史、标签关联、语义向量和图片哈希
+fn delete_orphan_question_data(conn: &Connection) -> Result<(), String> {
+    conn.execute(
+        "DELETE FROM QuestionRevisions WHERE QuestionId NOT IN (SELECT Id FROM AIResponses)",
+        [],
+    )
+    .map_err(|e| format!("清理修订历史失败: {}", e))?;
+    conn.execute(
+        "DELETE FROM QuestionTags WHERE QuestionId NOT IN (SELECT Id FROM AIResponses)",
+        [],
+    )
+    .map_err(|e| format!("清理题目标签失败: {}", e))?;
+    conn.execute(
+        "DELETE FROM QuestionEmbeddings WHERE QuestionId NOT IN (SELECT Id FROM AIResponses)",
+        [],
+    )
+    .map_err(|e| format!("清理题目向量失败: {}", e))?;
+    conn.execute(
+        "DELETE FROM QuestionImageHashes WHERE QuestionId NOT IN (SELECT Id FROM AIResponses)",
+        [],
+    )
+    .map_err(|e| format!("清理图片哈希失败: {}", e))?;
+    Ok(())
+}
+
+// 删除文件夹逻辑（保留题目）：决定被删除文件夹（含子文件夹）中题目的去向。
+// 返回 None 表示被删除的是空的 [未分类] 文件夹，无需转移题目。
+fn resolve_deleted_folder_question_target(
+    conn: &Connection,
+    id: i64,
+) -> Result<Option<i64>, String> {
+    let folder: Option<(String, Option<i64>)> = conn
+        .query_row(
+            "SELECT Name, ParentId FROM Folders WHERE Id = ?",
+            [id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()
+        .map_err(|e| format!("读取文件夹失败: {}", e))?;
+    let Some((name, parent_id)) = folder else {
+        return Err("文件夹不存在".to_string());
+    };
+    let parent_id = parent_id.unwrap_or(0);
+
+    // 无父文件夹：题目转移到默认文件夹
+    if parent_id == 0 {
+        return Ok(Some(0));
+    }
+
+    let has_siblings: bool = conn
+        .query_row(
+            "SELECT EXISTS(SELECT 1 FROM Folders WHERE ParentId = ? AND Id != ?)",
+            [parent_id, id],
+            |row| row.get(0),
+        )
+        .map_err(|e| format!("读取同级文件夹失败: {}", e))?;
+
+    // 父文件夹只有这一个子文件夹：题目转移到父文件夹
+    if !has_siblings {
+        return Ok(Some(parent_id));
+    }
+
+    let question_count: i64 = conn
+        .query_row(
+            &format!(
+                "SELECT COUNT(*) FROM AIResponses WHERE FolderId IN ({}) AND DeletedAt IS NULL",
+                FOLDER_SUBTREE_IDS_SQL
+            ),
+            [id],
+            |row| row.get(0),
+        )
+        .map_err(|e| format!("统计文件夹题目失败: {}", e))?;
+
+    if name != UNCATEGORIZED_FOLDER_NAME {
+        // 没有需要转移的题目时不创建 [未分类] 文件夹
+        if question_count == 0 {
+            return Ok(None);
+        }
+        // 父文件夹还有其他子文件夹：题目转移到父文件夹的 [未分类] 文件夹
+        let uncategorized_id = get_or_create_uncategorized_folder(conn, parent_id)
+            .map_err(|e| format!("创建[未分类]文件夹失败: {}", e))?;
+        return Ok(Some(uncategorized_id));
+    }
+
+    if question_count > 0 {
+        return Err(format!(
+            "{}文件夹中还有 {} 道题目，无法删除",
+            UNCATEGORIZED_FOLDER_NAME, question_count
+        ));
+    }
+
+    Ok(None)
+}
+
+#[tauri::command]
+pub async fn rename_folder(id: i64, new_name: String) -> Result<(), String> {
+    let conn = get_conn()?;
+    conn.execute(
+        "UPDATE Folders SET Name = ? WHERE Id = ?",
+        rusqlite::params![new_name, id],
+    )
+    .map_err(|e| format!("{}", e))?;
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn move_folder(id: i64, parent_id: i64) -> Result<(), String> {
+    let mut conn = get_conn()?;
+    move_folder_with_conn(&mut conn, id, parent_id)
+}
+
+fn move_folder_with_conn(conn: &mut Connection, id: i64, parent_id: i64) -> Result<(), String> {
+    if id == 0 {
+        return Err("默认文件夹不能移动".to_string());
+    }
+    if id == parent_id {
+        return Err("Cannot move folder to itself".to_string());
+    }
+
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("开启事务失败: {}", e))?;
+
+    if !folder_exists(&tx, id).map_err(|e| format!("读取文件夹失败: {}", e))? {
+        return Err("文件夹不存在".to_string());
+    }
+    if parent_id != 0 {
+        if !folder_exists(&tx, parent_id).map_err(|e| format!("读取目标文件夹失败: {}", e))?
+        {
+            return Err("目标文件夹不存在".to_string());
+        }
+        // 检查防止循环嵌套：目标父文件夹不能是该文件夹的子孙文件夹
+        if is_folder_ancestor_of(&tx, id, parent_id)
+            .map_err(|e| format!("检查文件夹层级失败: {}", e))?
+        {
+            return Err("不能将文件夹移动到其子文件夹中".to_string());
+        }
+    }
+
+    move_questions_into_uncategorized_before_adding_child(&tx, parent_id)
+        .map_err(|e| format!("整理目标文件夹题目失败: {}", e))?;
+    tx.execute(
+        "UPDATE Folders SET ParentId = ? WHERE Id = ?",
+        [parent_id, id],
+    )
+    .map_err(|e| format!("移动文件夹失败: {}", e))?;
+    tx.commit().map_err(|e| format!("提交事务失败: {}", e))?;
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn add_folder(name: String, parent_id: i64) -> Result<i64, String> {
+    let mut conn = get_conn()?;
+    add_folder_with_conn(&mut conn, &name, parent_id)
+}
+
+fn add_folder_with_conn(conn: &mut Connection, name: &str, parent_id: i64) -> Result<i64, String> {
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("开启事务失败: {}", e))?;
+
+    if parent_id != 0
+        && !folder_exists(&tx, parent_id).map_err(|e| format!("读取父文件夹失败: {}", e))?
+    {
+        return Err("父文件夹不存在".to_string());
+    }
+
+    move_questions_into_uncategorized_before_adding_child(&tx, parent_id)
+        .map_err(|e| format!("整理父文件夹题目失败: {}", e))?;
+
+    tx.execute(
+        "INSERT INTO Folders (Name, ParentId, CreateTime) VALUES (?, ?, datetime('now'))",
+        rusqlite::params![name, parent_id],
+    )
+    .map_err(|e| format!("创建文件夹失败: {}", e))?;
+    let id = tx.last_insert_rowid();
+
+    tx.commit().map_err(|e| format!("提交事务失败: {}", e))?;
+    Ok(id)
+}
+
+// 将 "父文件夹/子文件夹" 形式的路径解析为对应文件夹及其全部子文件夹的 ID；
+// 同名的兄弟文件夹会一并计入，路径不存在时为空
+fn resolve_folder_path_scope(conn: &Connection, path: &str) -> Result<HashSet<i64>, String> {
+    let mut scope = HashSet::new();
+    for folder_id in resolve_folder_path_ids(conn, path)? {
+        scope.extend(folder_subtree_ids(conn, folder_id)?);
+    }
+    Ok(scope)
+}
+
+// 路径对应的文件夹 ID（不含子文件夹），路径不存在时为空
+fn resolve_folder_path_ids(conn: &Connection, path: &str) -> Result<Vec<i64>, String> {
+    let mut current: Vec<i64> = Vec::new();
+    for (depth, segment) in path.split('/').map(str::trim).enumerate() {
+        let mut next = Vec::new();
+        if depth == 0 {
+            let mut stmt = conn
+                .prepare("SELECT Id FROM Folders WHERE ParentId = 0 AND Name = ?")
+                .map_err(|e| format!("{}", e))?;
+            let rows = stmt
+                .query_map([segment], |row| row.get::<_, i64>(0))
+                .map_err(|e| format!("{}", e))?;
+            for row in rows {
+                next.push(row.map_err(|e| format!("{}", e))?);
+            }
+        } else {
+            let mut stmt = conn
+                .prepare("SELECT Id FROM Folders WHERE ParentId = ? AND Id != 0 AND Name = ?")
+                .map_err(|e| format!("{}", e))?;
+            for parent_id in &current {
+                // 默认文件夹没有子文件夹
+                if *parent_id == 0 {
+                    continue;
+                }
+                let rows = stmt
+                    .query_map(rusqlite::params![parent_id, segment], |row| {
+                        row.get::<_, i64>(0)
+                    })
+                    .map_err(|e| format!("{}", e))?;
+                for row in rows {
+                    next.push(row.map_err(|e| format!("{}", e))?);
+                }
+            }
+        }
+
+        if next.is_empty() {
+            return Ok(Vec::new());
+        }
+        current = next;
+    }
+    Ok(current)
+}
+
+fn folder_subtree_ids(conn: &Connection, folder_id: i64) -> Result<HashSet<i64>, String> {
+    let mut stmt = conn
+        .prepare(FOLDER_SUBTREE_IDS_SQL)
+        .map_err(|e| format!("{}", e))?;
+    let rows = stmt
+        .query_map([folder_id], |row| row.get::<_, i64>(0))
+        .map_err(|e| format!("{}", e))?;
+    let mut ids = HashSet::new();
+    for row in rows {
+        ids.insert(row.map_err(|e| format!("{}", e))?);
+    }
+    Ok(ids)
+}
+
+/// 题库查询范围：指定的文件夹及其全部子文件夹
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct QueryScope {
+    /// 指定的文件夹，按指定顺序排列；新题目保存到第一个文件夹
+    pub roots: Vec<i64>,
+    pub folder_ids: HashSet<i64>,
+}
+
+impl QueryScope {
+    /// 两个范围的交集。根文件夹取本范围中落在另一范围内的，没有时取另一范围中落在本范围内的
+    pub fn intersect(&self, other: &QueryScope) -> QueryScope {
+        let within = |roots: &[i64], scope: &QueryScope| -> Vec<i64> {
+            roots
+                .iter()
+                .copied()
+                .filter(|id| scope.folder_ids.contains(id))
+                .collect()
+        };
+        let mut roots = within(&self.roots, other);
+        if roots.is_empty() {
+            roots = within(&other.roots, self);
+        }
+        QueryScope {
+            roots,
+            folder_ids: self
+                .folder_ids
+                .intersection(&other.folder_ids)
+                .copied()
+                .collect(),
+        }
+    }
+}
+
+/// 把文件夹 ID 或 "父文件夹/子文件夹" 形式的路径解析为查询范围；纯数字按文件夹 ID 处理
+pub(crate) fn resolve_query_scope(folders: &[String]) -> Result<QueryScope, String> {
+    let conn = get_conn()?;
+    resolve_query_scope_with_conn(&conn, folders)
+}
+
+fn resolve_query_scope_with_conn(
+    conn: &Connection,
+    folders: &[String],
+) -> Result<QueryScope, String> {
+    let mut scope = QueryScope::default();
+    for folder in folders.iter().map(|folder| folder.trim()) {
+        let ids = match folder.parse::<i64>() {
+            Ok(id) => {
+                let exists: bool = conn
+                    .query_row(
+                        "SELECT EXISTS(SELECT 1 FROM Folders WHERE Id = ?)",
+                        [id],
+                        |row| row.get(0),
+                    )
+                    .map_err(|e| format!("{}", e))?;
+                if !exists {
+                    return Err(format!("文件夹不存在: {}", folder));
+                }
+                vec![id]
+            }
+            Err(_) => {
+                let ids = resolve_folder_path_ids(conn, folder)?;
+                if ids.is_empty() {
+                    return Err(format!("文件夹不存在: {}", folder));
+                }
+                ids
+            }
+        };
+        for id in ids {
+            if !scope.roots.contains(&id) {
+                scope.roots.push(id);
+            }
+            scope.folder_ids.extend(folder_subtree_ids(conn, id)?);
+        }
+    }
+    Ok(scope)
+}
+
+/// 搜索题目，keyword 支持 search_query 模块中描述的搜索语法
+#[tauri::command]
+pub async fn search_questions_fuzzy(
+    keyword: String,
+    folder_id: Option<i64>,
+    tag_ids: Option<Vec<i64>>,
+) -> Result<Vec<AIResponse>, String> {
+    let db_path = get_db_path();
+
+    let result = tokio::task::spawn_blocking(move || -> Result<Vec<AIResponse>, String> {
+        let conn = Connection::open(&db_path).map_err(|e| format!("{}", e))?;
+        search_questions_fuzzy_with_conn(
+            &conn,
+            &keyword,
+            folder_id,
+            tag_ids.as_deref().unwrap_or_default(),
+        )
+    })
+    .await
+    .map_err(|e| format!("{}", e))??;
+
+    Ok(result)
+}
+
+/// 分页搜索题目，结果带相关度分数和高亮区间。
+/// sort_by 可选 relevance（默认）、create_time、question；sort_order 为 asc / desc
+#[tauri::command]
+pub async fn search_questions_paginated(
+    keyword: String,
+    folder_id: Option<i64>,
+    tag_ids: Option<Vec<i64>>,
+    page: i64,
+    page_size: i64,
+    sort_by: Option<String>,
+    sort_order: Option<String>,
+) -> Result<PaginatedSearchResults, String> {
+    let db_path = get_db_path();
+
+    tokio::task::spawn_blocking(move || -> Result<PaginatedSearchResults, String> {
+        let conn = Connection::open(&db_path).map_err(|e| format!("{}", e))?;
+        let page = page.max(1);
+        let page_size = page_size.clamp(1, 200);
+        search_ranked_with_conn(
+            &conn,
+            &keyword,
+            folder_id,
+            tag_ids.as_deref().unwrap_or_default(),
+            search_order_clause(sort_by.as_deref(), sort_order.as_deref())?,
+            Some(((page - 1) * page_size, page_size)),
+        )
+    })
+    .await
+    .map_err(|e| format!("{}", e))?
+}
+
+fn search_questions_fuzzy_with_conn(
+    conn: &Connection,
+    keyword: &str,
+    folder_id: Option<i64>,
+    tag_ids: &[i64],
+) -> Result<Vec<AIResponse>, String> {
+    let order = search_order_clause(None, None)?;
+    let hits = search_ranked_with_conn(conn, keyword, folder_id, tag_ids, order, None)?;
+    Ok(hits.items.into_iter().map(|hit| hit.item).collect())
+}
+
+// 搜索结果的 ORDER BY 子句；相关度相同的按创建时间排列
+fn search_order_clause(sort_by: Option<&str>, sort_order: Option<&str>) -> Result<String, String> {
+    let direction = match sort_order {
+        None if matches!(sort_by, Some("question")) => "ASC",
+        None | Some("desc") => "DESC",
+        Some("asc") => "ASC",
+        Some(other) => return Err(format!("未知的排序方向: {}", other)),
+    };
+    let columns: &[&str] = match sort_by.unwrap_or("relevance") {
+        "relevance" => &["Relevance", "ar.CreateTime", "ar.Id"],
+        "create_time" => &["ar.CreateTime", "ar.Id"],
+        "question" => &["ar.Question", "ar.Id"],
+        other => return Err(format!("未知的排序字段: {}", other)),
+    };
+    Ok(columns
+        .iter()
+        .map(|column| format!("{} {}", column, direction))
+        .collect::<Vec<_>>()
+        .join(", "))
+}
+
+// 按搜索语法过滤候选题目，在数据库中按 order 排序并分页（page 为偏移量和数量，为空时返回全部）；
+// 相关度为各文本条件在题目、选项、答案中词频的 BM25 加权和，见 SearchQuery::to_sql
+fn search_ranked_with_conn(
+    conn: &Connection,
+    keyword: &str,
+    folder_id: Option<i64>,
+    tag_ids: &[i64],
+    order: String,
+    page: Option<(i64, i64)>,
+) -> Result<PaginatedSearchResults, String> {
+    let search_query = SearchQuery::parse(keyword).map_err(|e| e.to_string())?;
+    let mut folder_scopes: HashMap<&str, HashSet<i64>> = HashMap::new();
+    for path in search_query.folder_paths() {
+        if !folder_scopes.contains_key(path) {
+            folder_scopes.insert(path, resolve_folder_path_scope(conn, path)?);
+        }
+    }
+    let lengths = conn
+        .query_row(
+            "SELECT AVG(length(Question)), AVG(length(COALESCE(Options, ''))), AVG(length(COALESCE(Answer, '')))
+             FROM AIResponses WHERE DeletedAt IS NULL",
+            [],
+            |row| {
+                Ok(FieldLengths {
+                    question: row.get::<_, Option<f64>>(0)?.unwrap_or_default(),
+                    options: row.get::<_, Option<f64>>(1)?.unwrap_or_default(),
+                    answer: row.get::<_, Option<f64>>(2)?.unwrap_or_default(),
+                })
+            },
+        )
+        .map_err(|e| format!("{}", e))?;
+    search_query
+        .register_sql_functions(conn)
+        .map_err(|e| format!("注册搜索函数失败: {}", e))?;
+    let sql = search_query.to_sql(&folder_scopes, &lengths);
+
+    // 候选题目范围（根据文件夹、标签过滤）
+    let (folder_tree, from) = match folder_id {
+        // 默认文件夹仅显示自身题目
+        Some(0) => (
+            "",
+            "FROM AIResponses ar
+            INNER JOIN Folders f ON ar.FolderId = f.Id
+            WHERE ar.FolderId = 0 AND ar.DeletedAt IS NULL",
+        ),
+        // 指定文件夹及其子文件夹
+        Some(_) => (
+            "WITH RECURSIVE folder_tree AS (
+              SELECT Id, Name, ParentId FROM Folders WHERE Id = ?
+              UNION
+              SELECT f.Id, f.Name, f.ParentId FROM Folders f
+              INNER JOIN folder_tree ft ON f.ParentId = ft.Id
+            )",
+            "FROM AIResponses ar
+            INNER JOIN folder_tree ft ON ar.FolderId = ft.Id
+            INNER JOIN Folders f ON ar.FolderId = f.Id
+            WHERE ar.DeletedAt IS NULL",
+        ),
+        // 所有文件夹
+        None => (
+            "",
+            "FROM AIResponses ar
+            LEFT JOIN Folders f ON ar.FolderId = f.Id
+            WHERE ar.DeletedAt IS NULL",
+        ),
+    };
+    let filter = format!("{}{} AND {}", from, tag_filter_clause(tag_ids), sql.filter);
+
+    let mut params: Vec<rusqlite::types::Value> = Vec::new();
+    if let Some(fid) = folder_id.filter(|fid| *fid != 0) {
+        params.push(fid.into());
+    }
+    params.extend(sql.params);
+
+    let limit = page
+        .map(|(offset, limit)| format!(" LIMIT {} OFFSET {}", limit, offset))
+        .unwrap_or_default();
+    let query = format!(
+        "{}
+        SELECT
+          ar.Id, ar.Question, ar.Options, ar.Answer, ar.QuestionType,
+          ar.FolderId, f.Name as FolderName, ar.CreateTime, ar.IsAi, COALESCE(ar.IsPendingCorrection, 0), COALESCE(ar.IsVerified, 0), ar.QuestionKind,
+          {} AS Relevance
+        {}
+        ORDER BY {}{}",
+        folder_tree, sql.relevance, filter, order, limit
+    );
+    let mut stmt = conn.prepare(&query).map_err(|e| format!("{}", e))?;
+    let rows = stmt
+        .query_map(rusqlite::params_from_iter(&params), |row| {
+            Ok((map_ai_response_row(row)?, row.get::<_, f64>(12)?))
+        })
+        .map_err(|e| format!("{}", e))?;
+
+    let mut items = Vec::new();
+    for row in rows {
+        let (item, score) = row.map_err(|e| format!("{}", e))?;
+        let highlights = search_query.highlights(&item);
+        items.push(SearchHit {
+            item,
+            score,
+            highlights,
+        });
+    }
+
+    // 分页时另行统计满足条件的题目总数
+    let total = match page {
+        Some(_) => conn
+            .query_row(
+                &format!("{} SELECT COUNT(*) {}", folder_tree, filter),
+                rusqlite::params_from_iter(&params),
+                |row| row.get(0),
+            )
+            .map_err(|e| format!("{}", e))?,
+        None => items.len() as i64,
+    };
+
+    Ok(PaginatedSearchResults { items, total })
+}
+
+// 一次题库查询的公共参数，query_database 和匹配解释共用
+struct QueryMatchContext {
+    title: String,
+    options: Option<String>,
+    /// 拆分后的查询选项，用于与顺序无关的选项比较
+    option_choices: Vec<String>,
+    require_option_match: bool,
+    /// 查询题目中的规范 URL
+    urls: Vec<String>,
+    url_canonicalizer: Arc<UrlCanonicalizer>,
+    /// 启用语义匹配时查询题目的向量
+    query_embedding: Option<QueryEmbedding>,
+    /// 查询题目中的实体词，语义匹配时要求与候选题目一致
+    entity_terms: Vec<String>,
+    /// 启用图片哈希时查询题目中各图片的 dHash，键为规范 URL
+    image_hashes: HashMap<String, u64>,
+}
+
+/// 查询前需要访问网络获取的信息：题目向量和图片哈希。未启用相应功能时为空。
+#[derive(Debug, Default)]
+pub(crate) struct QueryFeatures {
+    pub embedding: Option<QueryEmbedding>,
+    pub image_hashes: HashMap<String, u64>,
+}
+
+impl QueryFeatures {
+    // 只获取图片哈希；题目向量按需另行请求
+    async fn fetch(title: &str) -> Self {
+        let canonicalizer = UrlCanonicalizer::cached();
+        let urls: Vec<(String, String)> = extract_urls(title)
+            .into_iter()
+            .map(|url| (canonicalizer.canonicalize(&url), url))
+            .collect();
+        QueryFeatures {
+            embedding: None,
+            image_hashes: hash_query_images(&urls).await,
+        }
+    }
+}
+
+impl QueryMatchContext {
+    fn new(title: &str, options: Option<&str>) -> Self {
+        let options = normalize_optional_query_text(options);
+        let url_canonicalizer = UrlCanonicalizer::cached();
+        QueryMatchContext {
+            title: title.to_string(),
+            option_choices: options.as_deref().map(parse_choices).unwrap_or_default(),
+            options,
+            require_option_match: should_require_option_match(title),
+            urls: canonical_urls(&url_canonicalizer, title),
+            url_canonicalizer,
+            query_embedding: None,
+            entity_terms: Vec::new(),
+            image_hashes: HashMap::new(),
+        }
+    }
+
+    // 有查询向量时启用语义匹配，有图片哈希时启用图片比较
+    fn with_features(mut self, features: QueryFeatures) -> Self {
+        if let Some(embedding) = features.embedding {
+            self = self.with_embedding(embedding);
+        }
+        self.image_hashes = features.image_hashes;
+        self
+    }
+
+    fn with_embedding(mut self, embedding: QueryEmbedding) -> Self {
+        self.entity_terms = segmenter().entity_terms(&normalize_text(&self.title));
+        self.query_embedding = Some(embedding);
+        self
+    }
+
+    // 查询中的实体词没有出现在候选题干中的部分；不为空时候选题目不会通过语义匹配
+    fn missing_entity_terms(&self, question: &str) -> Vec<String> {
+        if self.entity_terms.is_empty() {
+            return Vec::new();
+        }
+        let normalized = normalize_text(question).to_lowercase();
+        self.entity_terms
+            .iter()
+            .filter(|term| !normalized.contains(term.as_str()))
+            .cloned()
+            .collect()
+    }
+}
+
+// 参与匹配的一道题库题目
+struct QueryCandidate<'a> {
+    question: &'a str,
+    options: Option<&'a str>,
+    /// 入库时保存的规范化选项，旧数据可能为空
+    normalized_options: Option<&'a str>,
+    /// 入库时保存的规范 URL，旧数据可能为空
+    canonical_urls: Option<&'a str>,
+    embedding: Option<&'a [f32]>,
+    /// 题目图片的 dHash，键为规范 URL
+    image_hashes: Option<&'a HashMap<String, u64>>,
+}
+
+// 候选题目的图片哈希，只在查询需要时加载
+#[derive(Default)]
+struct CandidateFeatures {
+    image_hashes: HashMap<i64, HashMap<String, u64>>,
+}
+
+impl CandidateFeatures {
+    fn load(conn: &Connection, context: &QueryMatchContext) -> Result<Self, String> {
+        let mut features = CandidateFeatures::default();
+        if !context.image_hashes.is_empty() {
+            features.image_hashes = load_question_image_hashes(conn)?;
+        }
+        Ok(features)
+    }
+
+    fn candidate<'a>(
+        &'a self,
+        id: i64,
+        question: &'a str,
+        options: Option<&'a str>,
+        normalized_options: Option<&'a str>,
+        canonical_urls: Option<&'a str>,
+        embedding: Option<&'a [f32]>,
+    ) -> QueryCandidate<'a> {
+        QueryCandidate {
+            question,
+            options,
+            normalized_options,
+            canonical_urls,
+            embedding,
+            image_hashes: self.image_hashes.get(&id),
+        }
+    }
+}
+
+// 语义匹配时逐题读取候选题目的向量；查询中的实体词不全出现在题干中的题目不会通过语义匹配，
+// 不读取向量。题干在生成向量后被修改过或生成失败的题目没有可用的向量。
+fn load_candidate_embedding(
+    conn: &Connection,
+    context: &QueryMatchContext,
+    id: i64,
+    question: &str,
+) -> Result<Option<Vec<f32>>, String> {
+    let Some(query_embedding) = &context.query_embedding else {
+        return Ok(None);
+    };
+    if !context.missing_entity_terms(question).is_empty() {
+        return Ok(None);
+    }
+    let mut stmt = conn
+        .prepare_cached(
+            "SELECT Vector FROM QuestionEmbeddings
+             WHERE QuestionId = ? AND Model = ? AND SourceText = ? AND Error IS NULL",
+        )
+        .map_err(|e| format!("{}", e))?;
+    let bytes: Option<Vec<u8>> = stmt
+        .query_row(
+            rusqlite::params![id, query_embedding.model, question],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| format!("{}", e))?;
+    Ok(bytes.and_then(|bytes| decode_vector(&bytes)))
+}
+
+// URL 不一致时按图片哈希比较：两边图片数量相同，且查询中每张 URL 不同的图片都能与候选题目中
+// 一张尚未配对的图片在汉明距离门槛内配对。返回配对中最大的汉明距离，无法配对时返回 None。
+fn match_image_hashes(
+    context: &QueryMatchContext,
+    profile: &MatchingProfile,
+    db_urls: &[String],
+    db_hashes: Option<&HashMap<String, u64>>,
+) -> Option<u32> {
+    if context.image_hashes.is_empty() || context.urls.len() != db_urls.len() {
+        return None;
+    }
+    let db_hashes = db_hashes?;
+
+    let mut unmatched_db_urls: Vec<&String> = db_urls.iter().collect();
+    let mut pending_query_urls = Vec::new();
+    for url in &context.urls {
+        match unmatched_db_urls.iter().position(|db_url| *db_url == url) {
+            Some(index) => {
+                unmatched_db_urls.remove(index);
+            }
+            None => pending_query_urls.push(url),
+        }
+    }
+
+    let mut max_distance = 0;
+    for url in pending_query_urls {
+        let query_hash = *context.image_hashes.get(url)?;
+        let (index, distance) = unmatched_db_urls
+            .iter()
+            .enumerate()
+            .filter_map(|(index, db_url)| {
+                db_hashes
+                    .get(*db_url)
+                    .map(|&db_hash| (index, hamming_distance(query_hash, db_hash)))
+            })
+            .min_by_key(|&(_, distance)| distance)?;
+        if distance > profile.max_image_hash_distance {
+            return None;
+        }
+        unmatched_db_urls.remove(index);
+        max_distance = max_distance.max(distance);
+    }
+    Some(max_distance)
+}
+
+/// 语义匹配的计算过程
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SemanticMatchDetail {
+    pub cosine_similarity: Option<f64>,
+    pub min_similarity: f64,
+    /// 只出现在其中一方题目中的实体词
+    pub mismatched_entities: Vec<String>,
+    pub rejection: Option<String>,
+}
+
+// 题干未通过字符匹配时的语义匹配：向量余弦相似度达到门槛，且双方的实体词互相包含。
+// 未启用语义匹配时返回 None。
+fn explain_semantic_match(
+    context: &QueryMatchContext,
+    profile: &MatchingProfile,
+    question: &str,
+    candidate_embedding: Option<&[f32]>,
+) -> Option<SemanticMatchDetail> {
+    let query_embedding = context.query_embedding.as_ref()?;
+    let mut detail = SemanticMatchDetail {
+        min_similarity: profile.min_semantic_similarity,
+        ..Default::default()
+    };
+
+    let Some(candidate_embedding) = candidate_embedding else {
+        detail.mismatched_entities = context.missing_entity_terms(question);
+        detail.rejection = Some(if detail.mismatched_entities.is_empty() {
+            "题目尚未生成语义向量".to_string()
+        } else {
+            format!("实体词不一致：{}", detail.mismatched_entities.join("、"))
+        });
+        return Some(detail);
+    };
+    let Some(similarity) = cosine_similarity(&query_embedding.vector, candidate_embedding) else {
+        detail.rejection = Some("向量维度不一致".to_string());
+        return Some(detail);
+    };
+    detail.cosine_similarity = Some(similarity);
+    if similarity < profile.min_semantic_similarity {
+        detail.rejection = Some(format!(
+            "语义相似度 {:.3} 低于 {}",
+            similarity, profile.min_semantic_similarity
+        ));
+        return Some(detail);
+    }
+
+    let normalized_query = normalize_text(&context.title).to_lowercase();
+    let normalized_candidate = normalize_text(question);
+    let candidate_terms = segmenter().entity_terms(&normalized_candidate);
+    let normalized_candidate = normalized_candidate.to_lowercase();
+    detail.mismatched_entities = context
+        .entity_terms
+        .iter()
+        .filter(|term| !normalized_candidate.contains(term.as_str()))
+        .chain(
+            candidate_terms
+                .iter()
+                .filter(|term| !normalized_query.contains(term.as_str())),
+        )
+        .cloned()
+        .collect();
+    if !detail.mismatched_entities.is_empty() {
+        detail.rejection = Some(format!(
+            "实体词不一致：{}",
+            detail.mismatched_entities.join("、")
+        ));
+    }
+    Some(detail)
+}
+
+// 选项比较：两边都能拆出至少两个选项时按集合比较（与选项顺序无关），否则按整段文本比较
+fn explain_option_match(
+    query_options: &str,
+    query_choices: &[String],
+    db_options: &str,
+    db_choices: &[String],
+    profile: &MatchingProfile,
+) -> QueryMatchDetail {
+    if query_choices.len() < 2 || db_choices.len() < 2 {
+        return explain_query_match_score(query_options, db_options, profile);
+    }
+
+    let mut detail = QueryMatchDetail::default();
+    if same_choice_set(query_choices, db_choices) {
+        detail.char_similarity = Some(1.0);
+        detail.score = Some(1.0);
+        return detail;
+    }
+
+    let similarity = choice_set_similarity(query_choices, db_choices);
+    detail.char_similarity = Some(similarity);
+    if similarity < profile.min_char_similarity {
+        detail.rejection = Some(format!(
+            "选项集合相似度 {:.3} 低于 {}",
+            similarity, profile.min_char_similarity
+        ));
+        return detail;
+    }
+    detail.score = Some(similarity);
+    detail
+}
+
+// 各文件夹的上级文件夹 ID；ParentId 为 NULL 的文件夹按顶层文件夹处理
+fn load_folder_parents(conn: &Connection) -> Result<HashMap<i64, i64>, String> {
+    let mut stmt = conn
+        .prepare("SELECT Id, ParentId FROM Folders")
+        .map_err(|e| format!("{}", e))?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, Option<i64>>(1)?))
+        })
+        .map_err(|e| format!("{}", e))?;
+    let mut parents = HashMap::new();
+    for row in rows {
+        let (id, parent_id) = row.map_err(|e| format!("{}", e))?;
+        parents.insert(id, parent_id.unwrap_or(0));
+    }
+    Ok(parents)
+}
+
+// 一次查询中各候选题目使用的匹配配置档：请求指定的配置档优先，
+// 否则按题目所在文件夹（或最近的祖先文件夹）的配置，再退回默认配置档
+struct QueryProfileSelector {
+    profiles: Arc<MatchingProfiles>,
+    request_profile_name: String,
+    request_profile: MatchingProfile,
+    explicit: bool,
+    folder_parents: HashMap<i64, i64>,
+}
+
+impl QueryProfileSelector {
+    fn new(conn: &Connection, requested: Option<&str>) -> Result<Self, String> {
+        let profiles = MatchingProfiles::cached();
+        let explicit = requested.is_some_and(|name| !name.trim().is_empty());
+        let (request_profile_name, request_profile) = profiles.resolve_request(requested)?;
+
+        let folder_parents = if !explicit && !profiles.folder_profiles.is_empty() {
+            load_folder_parents(conn)?
+        } else {
+            HashMap::new()
+        };
+
+        Ok(QueryProfileSelector {
+            profiles,
+            request_profile_name,
+            request_profile,
+            explicit,
+            folder_parents,
+        })
+    }
+
+    fn for_folder(&self, folder_id: i64) -> (&str, &MatchingProfile) {
+        if !self.explicit {
+            if let Some(name) = self
+                .profiles
+                .folder_profile_name(folder_id, &self.folder_parents)
+            {
+                if let Some(profile) = self.profiles.profiles.get(name) {
+                    return (name, profile);
+                }
+            }
+        }
+        (&self.request_profile_name, &self.request_profile)
+    }
+
+    fn result_limit(&self) -> usize {
+        self.request_profile.result_limit
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct QueryCandidateEvaluation {
+    /// 查询题目不含 URL 时为空
+    pub urls_match: Option<bool>,
+    /// URL 不一致但图片哈希一致时，配对图片中最大的汉明距离
+    pub image_hash_distance: Option<u32>,
+    pub title_match: QueryMatchDetail,
+    /// 未启用语义匹配或题干已通过字符匹配时为空
+    pub semantic_match: Option<SemanticMatchDetail>,
+    /// 查询或题库记录没有选项，或题干未通过时为空
+    pub option_match: Option<QueryMatchDetail>,
+    pub final_score: Option<f64>,
+    pub rejection_reason: Option<String>,
+}
+
+fn evaluate_query_candidate(
+    context: &QueryMatchContext,
+    profile: &MatchingProfile,
+    candidate: &QueryCandidate<'_>,
+) -> QueryCandidateEvaluation {
+    let question = candidate.question;
+    let mut evaluation = QueryCandidateEvaluation {
+        title_match: explain_query_match_score(&context.title, question, profile),
+        ..Default::default()
+    };
+
+    // 如果查询题目包含 URL，要求数据库记录的规范 URL 集合完全一致
+    // 避免"设A图那么B图"误匹配"设C图那么B图"
+    if !context.urls.is_empty() {
+        // 优先使用入库时保存的规范 URL，旧数据尚未回填时现场计算
+        let db_urls: Vec<String> = match candidate.canonical_urls {
+            Some(stored) => stored
+                .split('\n')
+                .filter(|url| !url.is_empty())
+                .map(str::to_string)
+                .collect(),
+            None => canonical_urls(&context.url_canonicalizer, question),
+        };
+        // 同一张图片被重新上传后 URL 不同，再按图片哈希比较
+        if context.urls != db_urls {
+            evaluation.image_hash_distance =
+                match_image_hashes(context, profile, &db_urls, candidate.image_hashes);
+        }
+        let urls_match = context.urls == db_urls || evaluation.image_hash_distance.is_some();
+        evaluation.urls_match = Some(urls_match);
+        if !urls_match {
+            evaluation.rejection_reason = Some("题目中的 URL 与查询不一致".to_string());
+            return evaluation;
+        }
+    }
+
+    if evaluation.title_match.score.is_none() {
+        evaluation.semantic_match =
+            explain_semantic_match(context, profile, question, candidate.embedding);
+    }
+    let semantic_similarity = evaluation
+        .semantic_match
+        .as_ref()
+        .filter(|detail| detail.rejection.is_none())
+        .and_then(|detail| detail.cosine_similarity);
+    let Some(title_similarity) = evaluation.title_match.score.or(semantic_similarity) else {
+        let semantic_rejection = evaluation
+            .semantic_match
+            .as_ref()
+            .and_then(|detail| detail.rejection.as_ref());
+        evaluation.rejection_reason =
+            evaluation
+                .title_match
+                .rejection
+                .as_ref()
+                .map(|reason| match semantic_rejection {
+                    Some(semantic) => format!("题干不匹配：{}；{}", reason, semantic),
+                    None => format!("题干不匹配：{}", reason),
+                });
+        return evaluation;
+    };
+
+    let db_options = normalize_optional_query_text(candidate.options);
+    if let (Some(query_options), Some(db_options)) = (context.options.as_deref(), db_options) {
+        // 优先使用入库时保存的规范化选项，旧数据尚未回填时现场解析
+        let db_choices = match candidate
+            .normalized_options
+            .filter(|normalized| !normalized.is_empty())
+        {
+            Some(normalized) => choices_from_normalized(normalized),
+            None => parse_choices(&db_options),
+        };
+        evaluation.option_match = Some(explain_option_match(
+            query_options,
+            &context.option_choices,
+            &db_options,
+            &db_choices,
+            profile,
+        ));
+    }
+    let option_similarity = evaluation
+        .option_match
+        .as_ref()
+        .and_then(|detail| detail.score);
+
+    let is_exact_title_match = is_exact_match_score(title_similarity);
+    if !is_exact_title_match
+        && (context.require_option_match || context.options.is_some())
+        && option_similarity.is_none()
+    {
+        evaluation.rejection_reason = Some(match &evaluation.option_match {
+            Some(detail) => format!(
+                "题干未完全一致且选项不匹配：{}",
+                detail.rejection.as_deref().unwrap_or_default()
+            ),
+            None => "题干未完全一致且缺少可比较的选项".to_string(),
+        });
+        return evaluation;
+    }
+
+    evaluation.final_score = Some(match option_similarity {
+        Some(option_similarity) => {
+            title_similarity * profile.title_weight
+                + option_similarity * (1.0 - profile.title_weight)
+        }
+        None => title_similarity,
+    });
+    evaluation
+}
+
+/// query_database 匹配到的一道题目
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryMatch {
+    pub id: i64,
+    pub question: String,
+    pub answer: String,
+    pub is_ai: bool,
+    pub is_pending_correction: bool,
+    pub is_verified: bool,
+    pub folder_id: i64,
+    /// "父文件夹/子文件夹" 形式的文件夹路径，默认文件夹为空
+    pub folder: Option<String>,
+    /// 综合匹配得分
+    pub score: f64,
+}
+
+/// query_database 的结果：按排序策略（见 rankingPolicy）排列的匹配题目和本次使用的匹配配置档
+#[derive(Debug)]
+pub struct QueryMatchResult {
+    pub rows: Vec<QueryMatch>,
+    /// 请求级配置档名称；有题目按文件夹配置匹配时附带文件夹配置档名称
+    pub profile: String,
+}
+
+/// scope 为空时在整个题库中查询
+pub async fn query_database(
+    title: &str,
+    options: Option<&str>,
+    profile: Option<&str>,
+    scope: Option<&QueryScope>,
+) -> Result<QueryMatchResult, Box<dyn std::error::Error + Send + Sync>> {
+    let context =
+        QueryMatchContext::new(title, options).with_features(QueryFeatures::fetch(title).await);
+    let (result, context) = query_database_blocking(context, profile, scope).await?;
+    // 没有题目通过字符匹配时才请求查询题目的向量，再按语义匹配查一次
+    if !result.rows.is_empty() || !embeddings_enabled() {
+        return Ok(result);
+    }
+    match embed_query(title).await {
+        Some(embedding) => {
+            let (result, _) =
+                query_database_blocking(context.with_embedding(embedding), profile, scope).await?;
+            Ok(result)
+        }
+        None => Ok(result),
+    }
+}
+
+type QueryError = Box<dyn std::error::Error + Send + Sync>;
+
+// 在阻塞线程中执行匹配，返回结果和匹配上下文（供语义匹配时复用）
+async fn query_database_blocking(
+    context: QueryMatchContext,
+    profile: Option<&str>,
+    scope: Option<&QueryScope>,
+) -> Result<(QueryMatchResult, QueryMatchContext), QueryError> {
+    let db_path = get_db_path();
+    let requested_profile = profile.map(str::to_string);
+    let scope = scope.cloned();
+
+    tokio::task::spawn_blocking(
+        move || -> Result<(QueryMatchResult, QueryMatchContext), QueryError> {
+            let conn = match Connection::open(&db_path) {
+                Ok(c) => c,
+                Err(e) => {
+                    return Err(Box::new(e) as QueryError);
+                }
+            };
+            let result = query_database_with_conn(
+                &conn,
+                &context,
+                requested_profile.as_deref(),
+                scope.as_ref(),
+            )?;
+            Ok((result, context))
+        },
+    )
+    .await?
+}
+
+fn query_database_with_conn(
+    conn: &Connection,
+    context: &QueryMatchContext,
+    requested_profile: Option<&str>,
+    scope: Option<&QueryScope>,
+) -> Result<QueryMatchResult, Box<dyn std::error::Error + Send + Sync>> {
+    let selector = QueryProfileSelector::new(conn, requested_profile)?;
+    let policy = RankingPolicy::cached();
+    refresh_segmenter_if_changed();
+    let candidate_features = CandidateFeatures::load(conn, context)?;
+
+    // 按其他规则计算的规范 URL 已过期，不使用
+    let mut stmt = conn.prepare(
+        "SELECT Id, Question, Options, Answer, IsAi, COALESCE(IsPendingCorrection, 0), FolderId, NormalizedOptions,
+           CASE WHEN CanonicalUrls = '' OR CanonicalUrlsRules = ? THEN CanonicalUrls END,
+           COALESCE(IsVerified, 0), julianday('now') - julianday(CreateTime)
+         FROM AIResponses WHERE DeletedAt IS NULL",
+    )?;
+
+    let rows = stmt.query_map([context.url_canonicalizer.fingerprint()], |row| {
+        Ok((
+            row.get::<_, i64>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, Option<String>>(2)?,
+            row.get::<_, String>(3)?,
+            row.get::<_, bool>(4)?,
+            row.get::<_, bool>(5)?,
+            row.get::<_, i64>(6)?,
+            row.get::<_, Option<String>>(7)?,
+            row.get::<_, Option<String>>(8)?,
+            row.get::<_, bool>(9)?,
+            row.get::<_, Option<f64>>(10)?,
+        ))
+    })?;
+
+    let mut results: Vec<(RankKey, QueryMatch)> = Vec::new();
+    let mut folder_chains: HashMap<i64, Vec<(i64, String)>> = HashMap::new();
+    let mut folder_profile_names = std::collections::BTreeSet::new();
+    for row in rows {
+        let (
+            id,
+            question,
+            db_options,
+            answer,
+            is_ai,
+            is_pending_correction,
+            folder_id,
+            normalized_options,
+            db_canonical_urls,
+            is_verified,
+            age_days,
+        ) = row?;
+        if scope.is_some_and(|scope| !scope.folder_ids.contains(&folder_id)) {
+            continue;
+        }
+        let (profile_name, profile) = selector.for_folder(folder_id);
+        let embedding = load_candidate_embedding(conn, context, id, &question)?;
+        let candidate = candidate_features.candidate(
+            id,
+            &question,
+            db_options.as_deref(),
+            normalized_options.as_deref(),
+            db_canonical_urls.as_deref(),
+            embedding.as_deref(),
+        );
+        let evaluation = evaluate_query_candidate(context, profile, &candidate);
+        let Some(score) = evaluation.final_score else {
+            continue;
+        };
+        let folders = if policy.uses_folders() {
+            cached_folder_chain(conn, &mut folder_chains, folder_id)?
+        } else {
+            &[]
+        };
+        let Some(rank) = policy.rank(&RankInput {
+            score,
+            is_ai,
+            is_pending_correction,
+            is_verified,
+            folders,
+            age_days,
+        }) else {
+            continue;
+        };
+        if profile_name != selector.request_profile_name {
+            folder_profile_names.insert(profile_name.to_string());
+        }
+        results.push((
+            rank,
+            QueryMatch {
+                id,
+                question,
+                answer,
+                is_ai,
+                is_pending_correction,
+                is_verified,
+                folder_id,
+                folder: None,
+                score,
+            },
+        ));
+    }
+
+    // 稳定排序，排序键相同的题目保持入库顺序
+    results.sort_by(|(a, _), (b, _)| a.compare(b));
+    results.truncate(selector.result_limit());
+
+    // 只为返回的题目查询文件夹路径
+    let mut rows = Vec::with_capacity(results.len());
+    for (_, mut result) in results {
+        result.folder = cached_folder_chain(conn, &mut folder_chains, result.folder_id)?
+            .last()
+            .map(|(_, path)| path.clone());
+        rows.push(result);
+    }
+
+    let mut profile = selector.request_profile_name.clone();
+    if !folder_profile_names.is_empty() {
+        let names: Vec<String> = folder_profile_names.into_iter().collect();
+        profile.push_str(&format!("（文件夹配置: {}）", names.join(", ")));
+    }
+
+    Ok(QueryMatchResult { rows, profile })
+}
+
+// 按文件夹 ID 缓存的文件夹链，同一次查询中每个文件夹只查询一次
+fn cached_folder_chain<'a>(
+    conn: &Connection,
+    cache: &'a mut HashMap<i64, Vec<(i64, String)>>,
+    folder_id: i64,
+) -> Result<&'a [(i64, String)], String> {
+    Ok(match cache.entry(folder_id) {
+        std::collections::hash_map::Entry::Occupied(entry) => entry.into_mut(),
+        std::collections::hash_map::Entry::Vacant(entry) => {
+            entry.insert(folder_chain_with_conn(conn, folder_id)?)
+        }
+    })
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct QueryCandidateExplanation {
+    pub id: i64,
+    pub question: String,
+    pub options: Option<String>,
+    /// 入库时保存的规范化选项（排序后按行拼接）
+    pub normalized_options: Option<String>,
+    /// 入库时保存的规范 URL（按行拼接），按其他规则计算的已过期记录为空
+    pub canonical_urls: Option<String>,
+    pub answer: String,
+    pub folder_id: i64,
+    /// 评估该候选时使用的匹配配置档
+    pub profile: String,
+    /// 在 query_database 结果中的名次，未通过匹配时为空
+    pub rank: Option<usize>,
+    /// 按排序策略调整后的得分，未通过匹配时为空
+    pub rank_score: Option<f64>,
+    #[serde(flatten)]
+    pub evaluation: QueryCandidateEvaluation,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct QueryExplanation {
+    pub title: String,
+    pub options: Option<String>,
+    pub require_option_match: bool,
+    pub profile: String,
+    pub total_candidates: usize,
+    pub accepted_count: usize,
+    pub result_limit: usize,
+    pub candidates: Vec<QueryCandidateExplanation>,
+}
+
+const DEFAULT_EXPLAIN_TOP_K: usize = 10;
+
+/// 解释一次题库查询的匹配过程：返回前 top_k 个候选（先通过匹配的，再按字符相似度排列被拒绝的）及各项得分；
+/// folders 与 /query 的 folders 相同，只解释这些文件夹及其子文件夹中的候选
+#[tauri::command]
+pub async fn explain_query_match(
+    title: String,
+    options: Option<String>,
+    top_k: Option<usize>,
+    profile: Option<String>,
+    folders: Option<Vec<String>>,
+) -> Result<QueryExplanation, String> {
+    let scope = match folders.filter(|folders| !folders.is_empty()) {
+        Some(folders) => Some(resolve_query_scope(&folders)?),
+        None => None,
+    };
+    explain_query_match_in_scope(title, options, top_k, profile, scope).await
+}
+
+/// 与 explain_query_match 相同，scope 为空时解释整个题库中的候选；
+/// 与 /query 一样，只有没有候选通过字符匹配时才给出语义匹配的详情
+pub(crate) async fn explain_query_match_in_scope(
+    title: String,
+    options: Option<String>,
+    top_k: Option<usize>,
+    profile: Option<String>,
+    scope: Option<QueryScope>,
+) -> Result<QueryExplanation, String> {
+    let features = QueryFeatures::fetch(&title).await;
+    let image_hashes = features.image_hashes.clone();
+    let explanation = explain_query_blocking(
+        title.clone(),
+        options.clone(),
+        top_k,
+        profile.clone(),
+        features,
+        scope.clone(),
+    )
+    .await?;
+    // 与 query_database 相同：没有候选通过字符匹配时才请求查询题目的向量，再按语义匹配解释一次
+    if explanation.accepted_count > 0 || !embeddings_enabled() {
+        return Ok(explanation);
+    }
+    match embed_query(&title).await {
+        Some(embedding) => {
+            let features = QueryFeatures {
+                embedding: Some(embedding),
+                image_hashes,
+            };
+            explain_query_blocking(title, options, top_k, profile, features, scope).await
+        }
+        None => Ok(explanation),
+    }
+}
+
+async fn explain_query_blocking(
+    title: String,
+    options: Option<String>,
+    top_k: Option<usize>,
+    profile: Option<String>,
+    features: QueryFeatures,
+    scope: Option<QueryScope>,
+) -> Result<QueryExplanation, String> {
+    let db_path = get_db_path();
+    tokio::task::spawn_blocking(move || -> Result<QueryExplanation, String> {
+        let conn = Connection::open(&db_path).map_err(|e| format!("{}", e))?;
+        explain_query_with_conn(
+            &conn,
+            &title,
+            options.as_deref(),
+            top_k,
+            profile.as_deref(),
+            features,
+            scope.as_ref(),
+        )
+    })
+    .await
+    .map_err(|e| format!("{}", e))?
+}
+
+fn explain_query_with_conn(
+    conn: &Connection,
+    title: &str,
+    options: Option<&str>,
+    top_k: Option<usize>,
+    profile: Option<&str>,
+    features: QueryFeatures,
+    scope: Option<&QueryScope>,
+) -> Result<QueryExplanation, String> {
+    let context = QueryMatchContext::new(title, options).with_features(features);
+    let selector = QueryProfileSelector::new(conn, profile)?;
+    let policy = RankingPolicy::cached();
+    refresh_segmenter_if_changed();
+    let candidate_features = CandidateFeatures::load(conn, &context)?;
+    let top_k = top_k.unwrap_or(DEFAULT_EXPLAIN_TOP_K).clamp(1, 100);
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT Id, Question, Options, Answer, FolderId, NormalizedOptions,
+               CASE WHEN CanonicalUrls = '' OR CanonicalUrlsRules = ? THEN CanonicalUrls END,
+               IsAi, COALESCE(IsPendingCorrection, 0), COALESCE(IsVerified, 0), julianday('now') - julianday(CreateTime)
+             FROM AIResponses WHERE DeletedAt IS NULL",
+        )
+        .map_err(|e| format!("{}", e))?;
+    let rows = stmt
+        .query_map([context.url_canonicalizer.fingerprint()], |row| {
+            let candidate = QueryCandidateExplanation {
+                id: row.get(0)?,
+                question: row.get(1)?,
+                options: row.get(2)?,
+                normalized_options: row.get(5)?,
+                canonical_urls: row.get(6)?,
+                answer: row.get(3)?,
+                folder_id: row.get(4)?,
+                profile: String::new(),
+                rank: None,
+                rank_score: None,
+                evaluation: QueryCandidateEvaluation::default(),
+            };
+            Ok((
+                candidate,
+                row.get::<_, bool>(7)?,
+                row.get::<_, bool>(8)?,
+                row.get::<_, bool>(9)?,
+                row.get::<_, Option<f64>>(10)?,
+            ))
+        })
+        .map_err(|e| format!("{}", e))?;
+
+    let mut folder_chains: HashMap<i64, Vec<(i64, String)>> = HashMap::new();
+    let mut candidates = Vec::new();
+    for row in rows {
+        let (mut candidate, is_ai, is_pending_correction, is_verified, age_days) =
+            row.map_err(|e| format!("{}", e))?;
+        if scope.is_some_and(|scope| !scope.folder_ids.contains(&candidate.folder_id)) {
+            continue;
+        }
+        let (profile_name, profile) = selector.for_folder(candidate.folder_id);
+        candidate.profile = profile_name.to_string();
+        let embedding =
+            load_candidate_embedding(conn, &context, candidate.id, &candidate.question)?;
+        candidate.evaluation = evaluate_query_candidate(
+            &context,
+            profile,
+            &candidate_features.candidate(
+                candidate.id,
+                &candidate.question,
+                candidate.options.as_deref(),
+                candidate.normalized_options.as_deref(),
+                candidate.canonical_urls.as_deref(),
+                embedding.as_deref(),
+            ),
+        );
+        let mut rank = None;
+        if let Some(score) = candidate.evaluation.final_score {
+            let folders = if policy.uses_folders() {
+                cached_folder_chain(conn, &mut folder_chains, candidate.folder_id)?
+            } else {
+                &[]
+            };
+            rank = policy.rank(&RankInput {
+                score,
+                is_ai,
+                is_pending_correction,
+                is_verified,
+                folders,
+                age_days,
+            });
+            match rank {
+                Some(key) => candidate.rank_score = Some(key.score),
+                None => {
+                    candidate.evaluation.final_score = None;
+                    candidate.evaluation.rejection_reason =
+                        Some("待修正题目按排序策略被排除".to_string());
+                }
+            }
+        }
+        candidates.push((rank, candidate));
+    }
+    let total_candidates = candidates.len();
+
+    // 与 query_database 相同的排序：通过匹配的按排序策略排列（稳定排序），其余按字符相似度降序
+    let sort_key = |candidate: &QueryCandidateExplanation| {
+        candidate
+            .evaluation
+            .title_match
+            .char_similarity
+            .unwrap_or(0.0)
+    };
+    candidates.sort_by(|(a_rank, a), (b_rank, b)| match (a_rank, b_rank) {
+        (Some(a_rank), Some(b_rank)) => a_rank.compare(b_rank),
+        (Some(_), None) => std::cmp::Ordering::Less,
+        (None, Some(_)) => std::cmp::Ordering::Greater,
+        (None, None) => sort_key(b)
+            .partial_cmp(&sort_key(a))
+            .unwrap_or(std::cmp::Ordering::Equal),
+    });
+
+    let mut accepted_count = 0;
+    let mut candidates: Vec<QueryCandidateExplanation> = candidates
+        .into_iter()
+        .map(|(rank, mut candidate)| {
+            if rank.is_some() {
+                accepted_count += 1;
+                candidate.rank = Some(accepted_count);
+            }
+            candidate
+        })
+        .collect();
+    candidates.truncate(top_k);
+
+    Ok(QueryExplanation {
+        title: context.title,
+        options: context.options,
+        require_option_match: context.require_option_match,
+        profile: selector.request_profile_name.clone(),
+        total_candidates,
+        accepted_count,
+        result_limit: selector.result_limit(),
+        candidates,
+    })
+}
+
+/// 需要（重新）生成向量的题目：没有向量、向量由其他模型生成或题干已修改。
+/// 生成失败的题目在题干修改或更换模型前不再重试。
+pub(crate) fn load_questions_needing_embeddings(
+    model: &str,
+    limit: usize,
+) -> Result<Vec<(i64, String)>, String> {
+    let conn = get_conn()?;
+    load_questions_needing_embeddings_with_conn(&conn, model, limit)
+}
+
+fn load_questions_needing_embeddings_with_conn(
+    conn: &Connection,
+    model: &str,
+    limit: usize,
+) -> Result<Vec<(i64, String)>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT a.Id, a.Question FROM AIResponses a
+             LEFT JOIN QuestionEmbeddings e ON e.QuestionId = a.Id
+             WHERE a.DeletedAt IS NULL
+               AND (e.QuestionId IS NULL OR e.Model != ? OR e.SourceText != a.Question)
+             ORDER BY a.Id
+             LIMIT ?",
+        )
+        .map_err(|e| format!("{}", e))?;
+    let rows = stmt
+        .query_map(rusqlite::params![model, limit as i64], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+        })
+        .map_err(|e| format!("{}", e))?;
+    rows.collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("{}", e))
+}
+
+/// 一道题目的向量生成结果：(题目 Id, 生成向量时的题干, 向量或生成失败的原因)
+pub(crate) type QuestionEmbeddingResult = (i64, String, Result<Vec<f32>, String>);
+
+/// 保存题目向量
+pub(crate) fn save_question_embeddings(
+    model: &str,
+    items: &[QuestionEmbeddingResult],
+) -> Result<(), String> {
+    let mut conn = get_conn()?;
+    save_question_embeddings_with_conn(&mut conn, model, items)
+}
+
+fn save_question_embeddings_with_conn(
+    conn: &mut Connection,
+    model: &str,
+    items: &[QuestionEmbeddingResult],
+) -> Result<(), String> {
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("开启事务失败: {}", e))?;
+    for (id, source_text, result) in items {
+        // 生成失败时 Dim 为 0、Vector 为空并记录 Error
+        let (vector, error) = match result {
+            Ok(vector) => (vector.as_slice(), None),
+            Err(e) => (&[][..], Some(e.as_str())),
+        };
+        tx.execute(
+            "INSERT OR REPLACE INTO QuestionEmbeddings (QuestionId, Model, Dim, Vector, SourceText, Error, UpdateTime)
+             VALUES (?, ?, ?, ?, ?, ?, datetime('now'))",
+            rusqlite::params![
+                id,
+                model,
+                vector.len() as i64,
+                encode_vector(vector),
+                source_text,
+                error
+            ],
+        )
+        .map_err(|e| format!("保存题目向量失败: {}", e))?;
+    }
+    tx.commit().map_err(|e| format!("提交事务失败: {}", e))
+}
+
+/// 未删除的题目总数，以及其中已有当前模型、当前题干向量的题目数量和生成失败的题目数量
+pub(crate) fn count_question_embeddings(model: &str) -> Result<(i64, i64, i64), String> {
+    let conn = get_conn()?;
+    let total: i64 = conn
+        .query_row(
+            "SELECT COUNT(*) FROM AIResponses WHERE DeletedAt IS NULL",
+            [],
+            |row| row.get(0),
+        )
+        .map_err(|e| format!("{}", e))?;
+    let (embedded, failed): (i64, i64) = conn
+        .query_row(
+            "SELECT COUNT(*) FILTER (WHERE e.Error IS NULL), COUNT(*) FILTER (WHERE e.Error IS NOT NULL)
+             FROM QuestionEmbeddings e
+             JOIN AIResponses a ON a.Id = e.QuestionId
+             WHERE e.Model = ? AND e.SourceText = a.Question AND a.DeletedAt IS NULL",
+            [model],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .map_err(|e| format!("{}", e))?;
+    Ok((total, embedded, failed))
+}
+
+// 已计算的题目图片哈希：题目 Id -> (规范 URL -> dHash)
+fn load_question_image_hashes(
+    conn: &Connection,
+) -> Result<HashMap<i64, HashMap<String, u64>>, String> {
+    let mut stmt = conn
+        .prepare("SELECT QuestionId, Url, Hash FROM QuestionImageHashes WHERE Hash IS NOT NULL")
+        .map_err(|e| format!("{}", e))?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, i64>(2)?,
+            ))
+        })
+        .map_err(|e| format!("{}", e))?;
+
+    let mut hashes: HashMap<i64, HashMap<String, u64>> = HashMap::new();
+    for row in rows {
+        let (question_id, url, hash) = row.map_err(|e| format!("{}", e))?;
+        // SQLite 只有有符号整数，按位保存 u64
+        hashes
+            .entry(question_id)
+            .or_default()
+            .insert(url, hash as u64);
+    }
+    Ok(hashes)
+}
+
+/// 需要计算哈希的题目图片：(题目 Id, 规范 URL, 原始 URL)。失败的图片一天后重试。
+pub(crate) fn load_pending_image_hashes(
+    limit: usize,
+) -> Result<Vec<(i64, String, String)>, String> {
+    let conn = get_conn()?;
+    load_pending_image_hashes_with_conn(&conn, limit)
+}
+
+fn load_pending_image_hashes_with_conn(
+    conn: &Connection,
+    limit: usize,
+) -> Result<Vec<(i64, String, String)>, String> {
+    let mut done: HashSet<(i64, String)> = HashSet::new();
+    {
+        let mut stmt = conn
+            .prepare(
+                "SELECT QuestionId, Url FROM QuestionImageHashes
+                 WHERE Hash IS NOT NULL OR UpdateTime > datetime('now', '-1 day')",
+            )
+            .map_err(|e| format!("{}", e))?;
+        let rows = stmt
+            .query_map([], |row| {
+                Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+            })
+            .map_err(|e| format!("{}", e))?;
+        for row in rows {
+            done.insert(row.map_err(|e| format!("{}", e))?);
+        }
+    }
+
+    let canonicalizer = UrlCanonicalizer::cached();
+    let mut stmt = conn
+        .prepare(
+            "SELECT Id, Question FROM AIResponses
+             WHERE DeletedAt IS NULL AND CanonicalUrls IS NOT NULL AND CanonicalUrls <> ''
+             ORDER BY Id",
+        )
+        .map_err(|e| format!("{}", e))?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+        })
+        .map_err(|e| format!("{}", e))?;
+
+    let mut pending = Vec::new();
+    for row in rows {
+        let (id, question) = row.map_err(|e| format!("{}", e))?;
+        for url in extract_urls(&question) {
+            let canonical = canonicalizer.canonicalize(&url);
+            if done.insert((id, canonical.clone())) {
+                pending.push((id, canonical, url));
+                if pending.len() >= limit {
+                    return Ok(pending);
+                }
+            }
+        }
+    }
+    Ok(pending)
+}
+
+/// 保存图片哈希计算结果，失败的图片记录错误信息
+pub(crate) fn save_image_hashes(
+    items: &[(i64, String, Result<u64, String>)],
+) -> Result<(), String> {
+    let mut conn = get_conn()?;
+    save_image_hashes_with_conn(&mut conn, items)
+}
+
+fn save_image_hashes_with_conn(
+    conn: &mut Connection,
+    items: &[(i64, String, Result<u64, String>)],
+) -> Result<(), String> {
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("开启事务失败: {}", e))?;
+    for (question_id, url, result) in items {
+        let (hash, error) = match result {
+            Ok(hash) => (Some(*hash as i64), None),
+            Err(e) => (None, Some(e.as_str())),
+        };
+        tx.execute(
+            "INSERT OR REPLACE INTO QuestionImageHashes (QuestionId, Url, Hash, Error, UpdateTime)
+             VALUES (?, ?, ?, ?, datetime('now'))",
+            rusqlite::params![question_id, url, hash, error],
+        )
+        .map_err(|e| format!("保存图片哈希失败: {}", e))?;
+    }
+    tx.commit().map_err(|e| format!("提交事务失败: {}", e))
+}
+
+/// 含图片的题目数量、已计算哈希的图片数量与失败的图片数量
+pub(crate) fn count_image_hashes() -> Result<(i64, i64, i64), String> {
+    let conn = get_conn()?;
+    conn.query_row(
+        "SELECT
+           (SELECT COUNT(*) FROM AIResponses
+            WHERE DeletedAt IS NULL AND CanonicalUrls IS NOT NULL AND CanonicalUrls <> ''),
+           (SELECT COUNT(*) FROM QuestionImageHashes WHERE Hash IS NOT NULL),
+           (SELECT COUNT(*) FROM QuestionImageHashes WHERE Hash IS NULL)",
+        [],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+    )
+    .map_err(|e| format!("{}", e))
+}
+
+/// 保存 AI 答题结果。raw_question_html 为题目清理前的原始 HTML，单独保存用于展示；
+/// 调用方未清理的 HTML 题目会在这里转为纯文本。题目保存到 questionSaveFolderId，
+/// 该文件夹不在查询范围 scope 内时保存到范围内的第一个文件夹。
+/// is_ai 的题目在目标文件夹中已有题干与选项相同的已核实题目时不保存。
+#[allow(clippy::too_many_arguments)]
+pub fn insert_ai_response(
+    question: &str,
+    raw_question_html: Option<&str>,
+    answer: &str,
+    options: Option<String>,
+    question_type: Option<String>,
+    is_ai: bool,
+    is_pending_correction: bool,
+    scope: Option<&QueryScope>,
+) -> Result<i64, String> {
+    let mut conn = get_conn()?;
+    let response = NewQuestion {
+        content: question.to_string(),
+        options,
+        answer: answer.to_string(),
+        question_type,
+        is_ai,
+    };
+    insert_ai_response_with_conn(
+        &mut conn,
+        &response,
+        raw_question_html,
+        is_pending_correction,
+        scope,
+    )
+}
+
+fn insert_ai_response_with_conn(
+    conn: &mut Connection,
+    response: &NewQuestion,
+    raw_question_html: Option<&str>,
+    is_pending_correction: bool,
+    scope: Option<&QueryScope>,
+) -> Result<i64, String> {
+    let question = response.content.as_str();
+    let answer = response.answer.as_str();
+    let question_type = &response.question_type;
+    if answer.trim().is_empty() {
+        return Err("AI处理结果答案为空，不保存题目".to_string());
+    }
+
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("开启事务失败: {}", e))?;
+    let folder_id = get_configured_save_folder_id(&tx, scope);
+    let folder_name: String = tx
+        .query_row(
+            "SELECT Name FROM Folders WHERE Id = ?",
+            [folder_id],
+            |row| row.get(0),
+        )
+        .unwrap_or_else(|_| "默认文件夹".to_string());
+
+    let (question, raw_question_html) = if contains_html(question) {
+        (
+            html_to_text(question),
+            Some(raw_question_html.unwrap_or(question)),
+        )
+    } else {
+        (clean_stored_text(question), raw_question_html)
+    };
+    let options = response.options.as_deref().map(clean_stored_text);
+    let normalized_options = normalize_options(options.as_deref());
+    if response.is_ai {
+        if let Some(verified) =
+            find_verified_duplicate(&tx, &question, normalized_options.as_deref(), folder_id)?
+        {
+            return Err(verified_duplicate_error(verified));
+        }
+    }
+    let canonicalizer = UrlCanonicalizer::cached();
+    let canonical_urls = stored_canonical_urls(&canonicalizer, &question);
+    let question_kind = question_kind_id(question_type.as_deref());
+    tx.execute(
+        "INSERT INTO AIResponses (Question, RawQuestionHtml, Answer, Options, NormalizedOptions, CanonicalUrls, CanonicalUrlsRules, QuestionType, QuestionKind, IsAi, IsPendingCorrection, CreateTime, FolderId, FolderName) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, datetime('now'), ?, ?)",
+        rusqlite::params![question, raw_question_html, answer, options, normalized_options, canonical_urls, canonicalizer.fingerprint(), question_type, question_kind, response.is_ai, is_pending_correction, folder_id, folder_name],
+    ).map_err(|e| format!("保存题目失败: {}", e))?;
+
+    let id = tx.last_insert_rowid();
+    record_question_creation(&tx, id, RevisionSource::AiAnswer)?;
+    tx.commit().map_err(|e| format!("提交事务失败: {}", e))?;
+    Ok(id)
+}
+
+pub fn get_username() -> Result<String, String> {
+    if let Ok(userprofile) = env::var("USERPROFILE") {
+        if !userprofile.is_empty() {
+            let path = std::path::Path::new(&userprofile);
+            if let Some(name_os) = path.file_name() {
+                let name = name_os.to_string_lossy().into_owned();
+                if !name.is_empty() {
+                    return Ok(name);
+                }
+            }
+            // 回退：按分隔符截取最后一段
+            let extracted = userprofile
+                .rsplit(|c| c == '\\' || c == '/')
+                .next()
+                .unwrap_or(&userprofile);
+            if !extracted.is_empty() {
+                return Ok(extracted.to_string());
+            }
+        }
+    }
+    env::var("USERNAME")
+        .or_else(|_| env::var("USER"))
+        .map_err(|_| "Unable to get username".to_string())
+}
+
+pub fn file_exists(path: &str) -> bool {
+    std::path::Path::new(path).exists()
+}
+
+fn get_table_columns(conn: &Connection, table_name: &str) -> Result<HashSet<String>, String> {
+    let pragma = format!("PRAGMA table_info('{table_name}')");
+    let mut stmt = conn.prepare(&pragma).map_err(|e| format!("{}", e))?;
+    let cols = stmt
+        .query_map([], |row| Ok(row.get::<_, String>(1)?))
+        .map_err(|e| format!("{}", e))?;
+
+    let mut names = HashSet::new();
+    for col in cols {
+        names.insert(col.map_err(|e| format!("{}", e))?);
+    }
+
+    Ok(names)
+}
+
+fn ensure_column(
+    conn: &Connection,
+    table_columns: &mut HashSet<String>,
+    column_name: &str,
+    alter_sql: &str,
+    backfill_sqls: &[&str],
+) -> Result<(), String> {
+    if table_columns.contains(column_name) {
+        return Ok(());
+    }
+
+    conn.execute(alter_sql, []).map_err(|e| format!("{}", e))?;
+    for sql in backfill_sqls {
+        conn.execute(sql, []).map_err(|e| format!("{}", e))?;
+    }
+
+    table_columns.insert(column_name.to_string());
+    Ok(())
+}
+
+// 为旧数据补齐规范化选项（选项解析无法用 SQL 完成，只处理尚未填写的记录）
+fn backfill_normalized_options(conn: &Connection) -> Result<(), String> {
+    let pending: Vec<(i64, String)> = {
+        let mut stmt = conn
+            .prepare(
+                "SELECT Id, Options FROM AIResponses
+                 WHERE NormalizedOptions IS NULL AND Options IS NOT NULL AND trim(Options) <> ''",
+            )
+            .map_err(|e| format!("{}", e))?;
+        let rows = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .map_err(|e| format!("{}", e))?;
+        rows.collect::<Result<_, _>>()
+            .map_err(|e| format!("{}", e))?
+    };
+    if pending.is_empty() {
+        return Ok(());
+    }
+
+    let tx = conn
+        .unchecked_transaction()
+        .map_err(|e| format!("开启事务失败: {}", e))?;
+    for (id, options) in &pending {
+        // 解析不出选项的记录写入空串，避免每次启动重复处理
+        let normalized = normalize_options(Some(options)).unwrap_or_default();
+        tx.execute(
+            "UPDATE AIResponses SET NormalizedOptions = ? WHERE Id = ?",
+            rusqlite::params![normalized, id],
+        )
+        .map_err(|e| format!("{}", e))?;
+    }
+    tx.commit().map_err(|e| format!("提交事务失败: {}", e))?;
+    println!("✅ 已为 {} 道题目生成规范化选项", pending.len());
+    Ok(())
+}
+
+// 按当前规则重新计算规范 URL，返回规范 URL 有变化的题目数量。
+// 只处理尚未计算，或含 URL 且按其他规则（CanonicalUrlsRules 不同）计算的题目。
+fn refresh_canonical_urls(conn: &Connection) -> Result<usize, String> {
+    let canonicalizer = UrlCanonicalizer::cached();
+    let fingerprint = canonicalizer.fingerprint();
+    let stale: Vec<(i64, String, bool)> = {
+        let mut stmt = conn
+            .prepare(
+                "SELECT Id, Question, CanonicalUrls FROM AIResponses
+                 WHERE CanonicalUrls IS NULL
+                    OR ((CanonicalUrls <> '' OR Question LIKE '%://%')
+                        AND CanonicalUrlsRules IS NOT ?)",
+            )
+            .map_err(|e| format!("{}", e))?;
+        let rows = stmt
+            .query_map([fingerprint], |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, Option<String>>(2)?,
+                ))
+            })
+            .map_err(|e| format!("{}", e))?;
+        let mut stale = Vec::new();
+        for row in rows {
+            let (id, question, stored) = row.map_err(|e| format!("{}", e))?;
+            let canonical = stored_canonical_urls(&canonicalizer, &question);
+            let changed = stored.as_deref() != Some(canonical.as_str());
+            stale.push((id, canonical, changed));
+        }
+        stale
+    };
+    if stale.is_empty() {
+        return Ok(0);
+    }
+
+    let tx = conn
+        .unchecked_transaction()
+        .map_err(|e| format!("开启事务失败: {}", e))?;
+    for (id, canonical, _) in &stale {
+        tx.execute(
+            "UPDATE AIResponses SET CanonicalUrls = ?, CanonicalUrlsRules = ? WHERE Id = ?",
+            rusqlite::params![canonical, fingerprint, id],
+        )
+        .map_err(|e| format!("{}", e))?;
+    }
+    tx.commit().map_err(|e| format!("提交事务失败: {}", e))?;
+    Ok(stale.iter().filter(|(_, _, changed)| *changed).count())
+}
+
+// 按当前题型配置重新计算派生的 QuestionKind 列，返回有变化的题目数量；原始的 QuestionType 保持不变。
+// 题型配置可能在两次启动之间增加别名，所以每次启动都检查一遍。
+fn refresh_question_kinds(conn: &Connection) -> Result<usize, String> {
+    let registry = KindRegistry::load();
+    let values: Vec<String> = {
+        let mut stmt = conn
+            .prepare("SELECT DISTINCT QuestionType FROM AIResponses WHERE QuestionType IS NOT NULL")
+            .map_err(|e| format!("{}", e))?;
+        let rows = stmt
+            .query_map([], |row| row.get::<_, String>(0))
+            .map_err(|e| format!("{}", e))?;
+        rows.collect::<Result<_, _>>()
+            .map_err(|e| format!("{}", e))?
+    };
+
+    let tx = conn
+        .unchecked_transaction()
+        .map_err(|e| format!("开启事务失败: {}", e))?;
+    let mut changed = 0;
+    for value in values {
+        let kind = registry.kind_id(Some(&value));
+        changed += tx
+            .execute(
+                "UPDATE AIResponses SET QuestionKind = ? WHERE QuestionType = ? AND QuestionKind IS NOT ?",
+                rusqlite::params![kind, value, kind],
+            )
+            .map_err(|e| format!("{}", e))?;
+    }
+    tx.commit().map_err(|e| format!("提交事务失败: {}", e))?;
+    Ok(changed)
+}
+
+/// 修改 URL 规范化规则后，重新计算所有题目的规范 URL，返回有变化的题目数量
+#[tauri::command]
+pub async fn rebuild_canonical_urls() -> Result<usize, String> {
+    let db_path = get_db_path();
+    tokio::task::spawn_blocking(move || -> Result<usize, String> {
+        let conn = Connection::open(&db_path).map_err(|e| format!("{}", e))?;
+        refresh_canonical_urls(&conn)
+    })
+    .await
+    .map_err(|e| format!("{}", e))?
+}
+
+pub fn init_database_schema(db_path: &str) -> Result<(), String> {
+    let conn = Connection::open(db_path).map_err(|e| format!("{}", e))?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS Folders (
+          Id INTEGER PRIMARY KEY AUTOINCREMENT,
+          Name TEXT NOT NULL,
+          ParentId INTEGER DEFAULT 0,
+          CreateTime DATETIME DEFAULT CURRENT_TIMESTAMP
+        )",
+        [],
+    )
+    .map_err(|e| format!("{}", e))?;
+
+    let mut folder_columns = get_table_columns(&conn, "Folders")?;
+    ensure_column(
+        &conn,
+        &mut folder_columns,
+        "ParentId",
+        "ALTER TABLE Folders ADD COLUMN ParentId INTEGER DEFAULT 0",
+        &["UPDATE Folders SET ParentId = 0 WHERE ParentId IS NULL"],
+    )?;
+    ensure_column(
+        &conn,
+        &mut folder_columns,
+        "CreateTime",
+        "ALTER TABLE Folders ADD COLUMN CreateTime DATETIME",
+        &["UPDATE Folders SET CreateTime = datetime('now') WHERE CreateTime IS NULL"],
+    )?;
+
+    let exists_default: i64 = conn
+        .query_row("SELECT COUNT(1) FROM Folders WHERE Id = 0", [], |row| {
+            row.get(0)
+        })
+        .unwrap_or(0);
+    if exists_default == 0 {
+        let _ = conn.execute(
+            "INSERT INTO Folders (Id, Name, ParentId) VALUES (0, '默认文件夹', 0)",
+            [],
+        );
+    }
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS AIResponses (
+          Id INTEGER PRIMARY KEY AUTOINCREMENT,
+          Question TEXT NOT NULL,
+          RawQuestionHtml TEXT,
+          Options TEXT,
+          NormalizedOptions TEXT,
+          CanonicalUrls TEXT,
+          CanonicalUrlsRules TEXT,
+          QuestionType TEXT,
+          QuestionKind TEXT,
+          Answer TEXT NOT NULL,
+          CreateTime DATETIME DEFAULT CURRENT_TIMESTAMP,
+          FolderId INTEGER DEFAULT 0,
+          FolderName TEXT DEFAULT '默认文件夹',
+          IsAi BOOLEAN DEFAULT 1,
+          IsPendingCorrection BOOLEAN DEFAULT 0,
+          IsVerified BOOLEAN DEFAULT 0,
+          DeletedAt DATETIME,
+          DeletedFromFolderId INTEGER
+        )",
+        [],
+    )
+    .map_err(|e| format!("{}", e))?;
+
+    let mut ai_response_columns = get_table_columns(&conn, "AIResponses")?;
+    let had_folder_name = ai_response_columns.contains("FolderName");
+
+    ensure_column(
+        &conn,
+        &mut ai_response_columns,
+        "QuestionType",
+        "ALTER TABLE AIResponses ADD COLUMN QuestionType TEXT",
+        &[],
+    )?;
+    ensure_column(
+        &conn,
+        &mut ai_response_columns,
+        "QuestionKind",
+        "ALTER TABLE AIResponses ADD COLUMN QuestionKind TEXT",
+        &[],
+    )?;
+    ensure_column(
+        &conn,
+        &mut ai_response_columns,
+        "CreateTime",
+        "ALTER TABLE AIResponses ADD COLUMN CreateTime DATETIME",
+        &["UPDATE AIResponses SET CreateTime = datetime('now') WHERE CreateTime IS NULL"],
+    )?;
+    ensure_column(
+        &conn,
+        &mut ai_response_columns,
+        "FolderId",
+        "ALTER TABLE AIResponses ADD COLUMN FolderId INTEGER DEFAULT 0",
+        &["UPDATE AIResponses SET FolderId = 0 WHERE FolderId IS NULL"],
+    )?;
+    if had_folder_name {
+        conn.execute(
+            "UPDATE AIResponses
+             SET FolderId = COALESCE(
+               (
+                 SELECT Id
+                 FROM Folders
+                 WHERE Folders.Name = AIResponses.FolderName
+                 ORDER BY CASE WHEN Id = 0 THEN 0 ELSE 1 END, Id
+                 LIMIT 1
+               ),
                0
              )
              WHERE FolderId IS NULL OR FolderId = 0",
@@ -1676,138 +4693,1816 @@ pub fn init_database_schema(db_path: &str) -> Result<(), String> {
         "ALTER TABLE AIResponses ADD COLUMN IsPendingCorrection BOOLEAN DEFAULT 0",
         &["UPDATE AIResponses SET IsPendingCorrection = 0 WHERE IsPendingCorrection IS NULL"],
     )?;
+    ensure_column(
+        &conn,
+        &mut ai_response_columns,
+        "IsVerified",
+        "ALTER TABLE AIResponses ADD COLUMN IsVerified BOOLEAN DEFAULT 0",
+        &["UPDATE AIResponses SET IsVerified = 0 WHERE IsVerified IS NULL"],
+    )?;
+    ensure_column(
+        &conn,
+        &mut ai_response_columns,
+        "DeletedAt",
+        "ALTER TABLE AIResponses ADD COLUMN DeletedAt DATETIME",
+        &[],
+    )?;
+    ensure_column(
+        &conn,
+        &mut ai_response_columns,
+        "DeletedFromFolderId",
+        "ALTER TABLE AIResponses ADD COLUMN DeletedFromFolderId INTEGER",
+        &[],
+    )?;
+    ensure_column(
+        &conn,
+        &mut ai_response_columns,
+        "NormalizedOptions",
+        "ALTER TABLE AIResponses ADD COLUMN NormalizedOptions TEXT",
+        &[],
+    )?;
+    backfill_normalized_options(&conn)?;
+    ensure_column(
+        &conn,
+        &mut ai_response_columns,
+        "RawQuestionHtml",
+        "ALTER TABLE AIResponses ADD COLUMN RawQuestionHtml TEXT",
+        &[],
+    )?;
+    ensure_column(
+        &conn,
+        &mut ai_response_columns,
+        "CanonicalUrls",
+        "ALTER TABLE AIResponses ADD COLUMN CanonicalUrls TEXT",
+        &[],
+    )?;
+    // 计算 CanonicalUrls 时所用规则的指纹，规则修改后据此判断哪些记录已过期
+    ensure_column(
+        &conn,
+        &mut ai_response_columns,
+        "CanonicalUrlsRules",
+        "ALTER TABLE AIResponses ADD COLUMN CanonicalUrlsRules TEXT",
+        &[],
+    )?;
+    match refresh_canonical_urls(&conn)? {
+        0 => {}
+        count => println!("✅ 已为 {} 道题目更新规范 URL", count),
+    }
+    match refresh_question_kinds(&conn)? {
+        0 => {}
+        count => println!("✅ 已为 {} 道题目更新题型", count),
+    }
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_ai_responses_deleted_at ON AIResponses(DeletedAt)",
+        [],
+    )
+    .map_err(|e| format!("{}", e))?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS RequestLogs (
+          LogId INTEGER PRIMARY KEY AUTOINCREMENT,
+          RequestId TEXT NOT NULL,
+          Timestamp TEXT NOT NULL,
+          Method TEXT NOT NULL,
+          Path TEXT NOT NULL,
+          Status INTEGER,
+          ResponseTime INTEGER,
+          RequestBody TEXT,
+          ResponseBody TEXT,
+          Headers TEXT,
+          Ip TEXT,
+          UserAgent TEXT,
+          Stage TEXT NOT NULL,
+          MatchingProfile TEXT
+        )",
+        [],
+    )
+    .map_err(|e| format!("{}", e))?;
+
+    let mut request_log_columns = get_table_columns(&conn, "RequestLogs")?;
+    ensure_column(
+        &conn,
+        &mut request_log_columns,
+        "MatchingProfile",
+        "ALTER TABLE RequestLogs ADD COLUMN MatchingProfile TEXT",
+        &[],
+    )?;
+
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_request_logs_request_id ON RequestLogs(RequestId)",
+        [],
+    )
+    .map_err(|e| format!("{}", e))?;
+
+    // 题目修订历史：每次修改记录修改前后的内容与来源
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS QuestionRevisions (
+          RevisionId INTEGER PRIMARY KEY AUTOINCREMENT,
+          QuestionId INTEGER NOT NULL,
+          OldQuestion TEXT,
+          OldOptions TEXT,
+          OldAnswer TEXT,
+          OldQuestionType TEXT,
+          OldIsPendingCorrection BOOLEAN,
+          NewQuestion TEXT,
+          NewOptions TEXT,
+          NewAnswer TEXT,
+          NewQuestionType TEXT,
+          NewIsPendingCorrection BOOLEAN,
+          Source TEXT NOT NULL,
+          CreateTime DATETIME DEFAULT CURRENT_TIMESTAMP
+        )",
+        [],
+    )
+    .map_err(|e| format!("{}", e))?;
+
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_question_revisions_question_id ON QuestionRevisions(QuestionId)",
+        [],
+    )
+    .map_err(|e| format!("{}", e))?;
+
+    // 标签表及题目-标签多对多关联表
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS Tags (
+          Id INTEGER PRIMARY KEY AUTOINCREMENT,
+          Name TEXT NOT NULL UNIQUE,
+          CreateTime DATETIME DEFAULT CURRENT_TIMESTAMP
+        )",
+        [],
+    )
+    .map_err(|e| format!("{}", e))?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS QuestionTags (
+          QuestionId INTEGER NOT NULL,
+          TagId INTEGER NOT NULL,
+          PRIMARY KEY (QuestionId, TagId)
+        )",
+        [],
+    )
+    .map_err(|e| format!("{}", e))?;
+
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_question_tags_tag_id ON QuestionTags(TagId)",
+        [],
+    )
+    .map_err(|e| format!("{}", e))?;
+
+    // 语义匹配向量：每道题一条，SourceText 为生成向量时的题干，题干修改后需要重新生成
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS QuestionEmbeddings (
+          QuestionId INTEGER PRIMARY KEY,
+          Model TEXT NOT NULL,
+          Dim INTEGER NOT NULL,
+          Vector BLOB NOT NULL,
+          SourceText TEXT NOT NULL,
+          Error TEXT,
+          UpdateTime DATETIME DEFAULT CURRENT_TIMESTAMP
+        )",
+        [],
+    )
+    .map_err(|e| format!("{}", e))?;
+
+    // 接口拒绝生成向量时记录原因，Dim 为 0、Vector 为空
+    let mut embedding_columns = get_table_columns(&conn, "QuestionEmbeddings")?;
+    ensure_column(
+        &conn,
+        &mut embedding_columns,
+        "Error",
+        "ALTER TABLE QuestionEmbeddings ADD COLUMN Error TEXT",
+        &[],
+    )?;
+
+    // 题目图片的感知哈希（dHash），Url 为规范 URL；下载失败时 Hash 为空并记录 Error
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS QuestionImageHashes (
+          QuestionId INTEGER NOT NULL,
+          Url TEXT NOT NULL,
+          Hash INTEGER,
+          Error TEXT,
+          UpdateTime DATETIME DEFAULT CURRENT_TIMESTAMP,
+          PRIMARY KEY (QuestionId, Url)
+        )",
+        [],
+    )
+    .map_err(|e| format!("{}", e))?;
 
+    // 每天请求计数表：仅展存 date + 计数，不保存请求详情
     conn.execute(
-        "CREATE TABLE IF NOT EXISTS RequestLogs (
-          LogId INTEGER PRIMARY KEY AUTOINCREMENT,
-          RequestId TEXT NOT NULL,
-          Timestamp TEXT NOT NULL,
-          Method TEXT NOT NULL,
-          Path TEXT NOT NULL,
-          Status INTEGER,
-          ResponseTime INTEGER,
-          RequestBody TEXT,
-          ResponseBody TEXT,
-          Headers TEXT,
-          Ip TEXT,
-          UserAgent TEXT,
-          Stage TEXT NOT NULL
+        "CREATE TABLE IF NOT EXISTS DailyRequestCounts (
+          Day TEXT PRIMARY KEY,
+          Count INTEGER NOT NULL DEFAULT 0
         )",
         [],
     )
     .map_err(|e| format!("{}", e))?;
 
-    conn.execute(
-        "CREATE INDEX IF NOT EXISTS idx_request_logs_request_id ON RequestLogs(RequestId)",
-        [],
-    )
-    .map_err(|e| format!("{}", e))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        add_folder_with_conn, add_tag_with_conn, backfill_normalized_options,
+        clear_folder_questions_with_conn, copy_question_with_conn, delete_folder_with_conn,
+        delete_questions_with_conn, explain_query_match_score, explain_query_with_conn,
+        extract_urls, folder_chain_with_conn, get_folder_stats_with_conn, get_table_columns,
+        import_questions_with_conn, init_database_schema, insert_ai_response_with_conn,
+        insert_question_with_conn, is_exact_match_score, load_folder_parents,
+        load_pending_image_hashes_with_conn, load_question_revision,
+        load_questions_needing_embeddings_with_conn, move_folder_with_conn, purge_trash_older_than,
+        query_database_with_conn, refresh_canonical_urls, refresh_question_kinds,
+        resolve_query_scope_with_conn, restore_questions_with_conn,
+        rollback_question_revision_with_conn, save_folder_in_scope, save_image_hashes_with_conn,
+        save_question_embeddings_with_conn, search_order_clause, search_questions_fuzzy_with_conn,
+        search_ranked_with_conn, set_question_pending_correction_with_conn,
+        set_question_verified_with_conn, tag_questions_with_conn, untag_questions_with_conn,
+        update_question_with_conn, NewQuestion, QueryFeatures, QueryMatchContext, QueryScope,
+        QuestionUpdate, RevisionSource,
+    };
+    use crate::embeddings::QueryEmbedding;
+    use crate::image_hash::tests::encode_test_image;
+    use crate::image_hash::{hash_image_url, ImageHashConfig};
+    use crate::matching_profile::{MatchingProfile, MatchingProfiles};
+    use crate::text_normalize::html_to_text;
+    use crate::url_canonical::UrlCanonicalizer;
+    use rusqlite::Connection;
+    use uuid::Uuid;
+    use warp::Filter;
+
+    // 使用 default 预设计算匹配分数
+    fn compute_query_match_score(query: &str, candidate: &str) -> Option<f64> {
+        explain_query_match_score(query, candidate, &MatchingProfile::default()).score
+    }
+
+    fn open_temp_database() -> (String, Connection) {
+        let db_path = std::env::temp_dir().join(format!("zerror-test-{}.db", Uuid::new_v4()));
+        let db_path_str = db_path.to_string_lossy().to_string();
+        init_database_schema(&db_path_str).expect("init test database");
+        let conn = Connection::open(&db_path_str).expect("open test database");
+        (db_path_str, conn)
+    }
+
+    fn insert_folder(conn: &Connection, name: &str, parent_id: i64) -> i64 {
+        conn.execute(
+            "INSERT INTO Folders (Name, ParentId) VALUES (?, ?)",
+            rusqlite::params![name, parent_id],
+        )
+        .expect("insert folder");
+        conn.last_insert_rowid()
+    }
+
+    fn insert_question(conn: &Connection, question: &str, folder_id: i64) -> i64 {
+        conn.execute(
+            "INSERT INTO AIResponses (Question, Answer, FolderId, IsAi) VALUES (?, '答案', ?, 0)",
+            rusqlite::params![question, folder_id],
+        )
+        .expect("insert question");
+        conn.last_insert_rowid()
+    }
+
+    fn count(conn: &Connection, sql: &str) -> i64 {
+        conn.query_row(sql, [], |row| row.get(0))
+            .expect("count rows")
+    }
+
+    #[test]
+    fn rejects_same_template_with_different_entity() {
+        assert!(compute_query_match_score("韩国的首都在哪里", "美国的首都在哪里").is_none());
+    }
+
+    #[test]
+    fn keeps_same_question_with_small_wording_changes() {
+        assert!(compute_query_match_score("韩国的首都在哪里", "韩国首都是哪里").is_some());
+    }
+
+    #[test]
+    fn keeps_exact_match() {
+        assert_eq!(
+            compute_query_match_score("韩国的首都在哪里", "韩国的首都在哪里"),
+            Some(1.0)
+        );
+    }
+
+    #[test]
+    fn matching_profile_presets_change_thresholds() {
+        let loose = MatchingProfile::loose();
+        let strict = MatchingProfile::strict();
+        let query = "光合作用发生在细胞的哪个部位";
+        let reworded = "光合作用主要在细胞哪个部位进行";
+
+        let default_detail =
+            explain_query_match_score(query, reworded, &MatchingProfile::default());
+        assert!(default_detail.score.is_none());
+        assert!(explain_query_match_score(query, reworded, &loose)
+            .score
+            .is_some());
+        assert!(explain_query_match_score(query, reworded, &strict)
+            .score
+            .is_none());
+        assert!(
+            explain_query_match_score("中国的首都是哪座城市", "中国首都是哪个城市", &strict)
+                .score
+                .is_some()
+        );
+
+        let profiles = MatchingProfiles::default();
+        assert!(profiles.resolve_request(Some("strict")).is_ok());
+        assert!(profiles.resolve_request(Some("不存在")).is_err());
+        assert_eq!(
+            profiles.resolve_request(None).map(|(name, _)| name),
+            Ok("default".to_string())
+        );
+    }
+
+    #[test]
+    fn folder_parents_treat_null_parent_as_top_level() {
+        let (db_path, conn) = open_temp_database();
+        let semester = insert_folder(&conn, "2024春", 0);
+        let chapter = insert_folder(&conn, "第一章", semester);
+        conn.execute(
+            "UPDATE Folders SET ParentId = NULL WHERE Id = ?",
+            [semester],
+        )
+        .expect("clear parent");
+
+        let parents = load_folder_parents(&conn).expect("load folder parents");
+        assert_eq!(parents.get(&semester), Some(&0));
+        assert_eq!(parents.get(&chapter), Some(&semester));
+
+        let _ = std::fs::remove_file(&db_path);
+    }
+
+    #[test]
+    fn exact_match_score_is_detected() {
+        assert!(is_exact_match_score(1.0));
+        assert!(!is_exact_match_score(0.999));
+    }
+
+    #[test]
+    fn migrates_legacy_database_schema() {
+        let db_path = std::env::temp_dir().join(format!("zerror-migration-{}.db", Uuid::new_v4()));
+        let db_path_str = db_path.to_string_lossy().to_string();
+
+        {
+            let conn = Connection::open(&db_path_str).expect("create legacy database");
+            conn.execute(
+                "CREATE TABLE Folders (
+                  Id INTEGER PRIMARY KEY AUTOINCREMENT,
+                  Name TEXT NOT NULL
+                )",
+                [],
+            )
+            .expect("create legacy folders");
+            conn.execute(
+                "CREATE TABLE AIResponses (
+                  Id INTEGER PRIMARY KEY AUTOINCREMENT,
+                  Question TEXT NOT NULL,
+                  Options TEXT,
+                  Answer TEXT NOT NULL,
+                  FolderName TEXT DEFAULT '默认文件夹'
+                )",
+                [],
+            )
+            .expect("create legacy ai responses");
+            conn.execute("INSERT INTO Folders (Id, Name) VALUES (0, '默认文件夹')", [])
+                .expect("insert default folder");
+            conn.execute(
+                "INSERT INTO AIResponses (Question, Options, Answer, FolderName) VALUES ('题目', NULL, '答案', '默认文件夹')",
+                [],
+            )
+            .expect("insert legacy response");
+        }
+
+        init_database_schema(&db_path_str).expect("migrate legacy database");
+
+        let conn = Connection::open(&db_path_str).expect("reopen migrated database");
+        let folder_columns = get_table_columns(&conn, "Folders").expect("read folder columns");
+        assert!(folder_columns.contains("ParentId"));
+        assert!(folder_columns.contains("CreateTime"));
+
+        let response_columns = get_table_columns(&conn, "AIResponses").expect("read ai response columns");
+        assert!(response_columns.contains("QuestionType"));
+        assert!(response_columns.contains("CreateTime"));
+        assert!(response_columns.contains("FolderId"));
+        assert!(response_columns.contains("FolderName"));
+        assert!(response_columns.contains("IsAi"));
+        assert!(response_columns.contains("IsPendingCorrection"));
+        assert!(response_columns.contains("DeletedAt"));
+        assert!(response_columns.contains("DeletedFromFolderId"));
+
+        let (folder_id, folder_name, is_ai, is_pending): (i64, String, i64, i64) = conn
+            .query_row(
+                "SELECT FolderId, FolderName, IsAi, IsPendingCorrection FROM AIResponses LIMIT 1",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+            )
+            .expect("query migrated values");
+        assert_eq!(folder_id, 0);
+        assert_eq!(folder_name, "默认文件夹");
+        assert_eq!(is_ai, 1);
+        assert_eq!(is_pending, 0);
+
+        let _ = std::fs::remove_file(&db_path_str);
+    }
+
+    #[test]
+    fn delete_folder_removes_subtree_and_questions() {
+        let (db_path, mut conn) = open_temp_database();
+        let parent = insert_folder(&conn, "课程", 0);
+        let child = insert_folder(&conn, "第一章", parent);
+        let other = insert_folder(&conn, "其他", 0);
+        insert_question(&conn, "题目一", parent);
+        insert_question(&conn, "题目二", child);
+        insert_question(&conn, "题目三", other);
+
+        delete_folder_with_conn(&mut conn, parent, true).expect("delete folder");
+
+        assert_eq!(
+            count(
+                &conn,
+                "SELECT COUNT(*) FROM AIResponses WHERE DeletedAt IS NULL"
+            ),
+            1
+        );
+        assert_eq!(
+            count(
+                &conn,
+                &format!(
+                    "SELECT COUNT(*) FROM Folders WHERE Id IN ({}, {})",
+                    parent, child
+                )
+            ),
+            0
+        );
+
+        delete_folder_with_conn(&mut conn, other, false).expect("delete folder keeping questions");
+        assert_eq!(
+            count(&conn, "SELECT COUNT(*) FROM AIResponses WHERE FolderId = 0"),
+            1
+        );
+
+        let _ = std::fs::remove_file(&db_path);
+    }
+
+    #[test]
+    fn bulk_deletes_are_set_based() {
+        let (db_path, mut conn) = open_temp_database();
+        let folder = insert_folder(&conn, "课程", 0);
+        let sub = insert_folder(&conn, "第一章", folder);
+        let a = insert_question(&conn, "题目一", 0);
+        let b = insert_question(&conn, "题目二", 0);
+        insert_question(&conn, "题目三", 0);
+        insert_question(&conn, "题目四", sub);
+
+        delete_questions_with_conn(&mut conn, &[a, b]).expect("delete questions");
+        assert_eq!(
+            count(
+                &conn,
+                "SELECT COUNT(*) FROM AIResponses WHERE DeletedAt IS NULL"
+            ),
+            2
+        );
+
+        clear_folder_questions_with_conn(&mut conn, folder).expect("clear folder");
+        assert_eq!(
+            count(
+                &conn,
+                "SELECT COUNT(*) FROM AIResponses WHERE DeletedAt IS NULL"
+            ),
+            1
+        );
+
+        let _ = std::fs::remove_file(&db_path);
+    }
+
+    #[test]
+    fn copy_question_rolls_back_when_source_is_missing() {
+        let (db_path, mut conn) = open_temp_database();
+        let folder = insert_folder(&conn, "课程", 0);
+        insert_folder(&conn, "第一章", folder);
+
+        let error = copy_question_with_conn(&mut conn, 9999, folder).expect_err("missing source");
+        assert_eq!(error, "题目不存在");
+        // 复制失败时不应残留自动创建的 [未分类] 文件夹
+        assert_eq!(
+            count(
+                &conn,
+                "SELECT COUNT(*) FROM Folders WHERE Name = '[未分类]'"
+            ),
+            0
+        );
+
+        let _ = std::fs::remove_file(&db_path);
+    }
+
+    #[test]
+    fn move_folder_rejects_cycles_and_invalid_targets() {
+        let (db_path, mut conn) = open_temp_database();
+        let root = insert_folder(&conn, "课程", 0);
+        let child = insert_folder(&conn, "第一章", root);
+        let grandchild = insert_folder(&conn, "第一节", child);
+
+        assert!(move_folder_with_conn(&mut conn, root, grandchild).is_err());
+        assert!(move_folder_with_conn(&mut conn, root, child).is_err());
+        assert!(move_folder_with_conn(&mut conn, child, 9999).is_err());
+        assert!(move_folder_with_conn(&mut conn, 0, root).is_err());
+
+        move_folder_with_conn(&mut conn, grandchild, 0).expect("move to top level");
+        let parent_id: i64 = conn
+            .query_row(
+                "SELECT ParentId FROM Folders WHERE Id = ?",
+                [grandchild],
+                |row| row.get(0),
+            )
+            .expect("read parent");
+        assert_eq!(parent_id, 0);
+
+        let _ = std::fs::remove_file(&db_path);
+    }
+
+    #[test]
+    fn adding_first_sub_folder_moves_questions_into_uncategorized() {
+        let (db_path, mut conn) = open_temp_database();
+        let parent = insert_folder(&conn, "课程", 0);
+        insert_question(&conn, "题目一", parent);
+
+        let child = add_folder_with_conn(&mut conn, "第一章", parent).expect("add folder");
+
+        let uncategorized_id: i64 = conn
+            .query_row(
+                "SELECT Id FROM Folders WHERE ParentId = ? AND Name = '[未分类]'",
+                [parent],
+                |row| row.get(0),
+            )
+            .expect("uncategorized folder created");
+        assert_ne!(uncategorized_id, child);
+        assert_eq!(
+            count(
+                &conn,
+                &format!(
+                    "SELECT COUNT(*) FROM AIResponses WHERE FolderId = {}",
+                    uncategorized_id
+                )
+            ),
+            1
+        );
+
+        let _ = std::fs::remove_file(&db_path);
+    }
+
+    #[test]
+    fn delete_folder_follows_uncategorized_rules() {
+        let (db_path, mut conn) = open_temp_database();
+        let parent = insert_folder(&conn, "课程", 0);
+        let first = insert_folder(&conn, "第一章", parent);
+        let second = insert_folder(&conn, "第二章", parent);
+        let empty = insert_folder(&conn, "空章节", parent);
+        insert_question(&conn, "题目一", first);
+
+        // 没有题目的文件夹直接删除，不创建 [未分类]
+        delete_folder_with_conn(&mut conn, empty, false).expect("delete empty");
+        assert_eq!(
+            count(
+                &conn,
+                "SELECT COUNT(*) FROM Folders WHERE Name = '[未分类]'"
+            ),
+            0
+        );
+
+        // 父文件夹还有其他子文件夹：题目转移到 [未分类]
+        delete_folder_with_conn(&mut conn, first, false).expect("delete first");
+        let uncategorized_id: i64 = conn
+            .query_row(
+                "SELECT Id FROM Folders WHERE ParentId = ? AND Name = '[未分类]'",
+                [parent],
+                |row| row.get(0),
+            )
+            .expect("uncategorized folder created");
+        assert_eq!(
+            count(
+                &conn,
+                &format!(
+                    "SELECT COUNT(*) FROM AIResponses WHERE FolderId = {}",
+                    uncategorized_id
+                )
+            ),
+            1
+        );
+
+        // 有题目的 [未分类] 文件夹不能删除
+        assert!(delete_folder_with_conn(&mut conn, uncategorized_id, false).is_err());
+
+        // 父文件夹只剩这一个子文件夹：题目转移到父文件夹
+        delete_folder_with_conn(&mut conn, second, false).expect("delete second");
+        delete_folder_with_conn(&mut conn, uncategorized_id, false).expect("delete uncategorized");
+        assert_eq!(
+            count(
+                &conn,
+                &format!(
+                    "SELECT COUNT(*) FROM AIResponses WHERE FolderId = {}",
+                    parent
+                )
+            ),
+            1
+        );
+
+        assert!(delete_folder_with_conn(&mut conn, 0, true).is_err());
+
+        let _ = std::fs::remove_file(&db_path);
+    }
+
+    #[test]
+    fn trashed_questions_can_be_restored_and_purged() {
+        let (db_path, mut conn) = open_temp_database();
+        let folder = insert_folder(&conn, "课程", 0);
+        let other = insert_folder(&conn, "其他", 0);
+        let a = insert_question(&conn, "题目一", folder);
+        let b = insert_question(&conn, "题目二", folder);
+        let c = insert_question(&conn, "题目三", folder);
+
+        delete_questions_with_conn(&mut conn, &[a, b, c]).expect("trash questions");
+        assert_eq!(
+            count(
+                &conn,
+                "SELECT COUNT(*) FROM AIResponses WHERE DeletedAt IS NOT NULL"
+            ),
+            3
+        );
+
+        restore_questions_with_conn(&mut conn, &[a], None).expect("restore to original");
+        restore_questions_with_conn(&mut conn, &[b], Some(other)).expect("restore to chosen");
+        let folder_of = |id: i64| -> i64 {
+            conn.query_row(
+                "SELECT FolderId FROM AIResponses WHERE Id = ? AND DeletedAt IS NULL",
+                [id],
+                |row| row.get(0),
+            )
+            .expect("restored question")
+        };
+        assert_eq!(folder_of(a), folder);
+        assert_eq!(folder_of(b), other);
+
+        conn.execute(
+            "UPDATE AIResponses SET DeletedAt = datetime('now', '-40 days') WHERE Id = ?",
+            [c],
+        )
+        .expect("age trashed question");
+        assert_eq!(purge_trash_older_than(&mut conn, 30).expect("purge"), 1);
+        assert_eq!(count(&conn, "SELECT COUNT(*) FROM AIResponses"), 2);
+
+        let _ = std::fs::remove_file(&db_path);
+    }
+
+    #[test]
+    fn update_question_records_revision() {
+        let (db_path, mut conn) = open_temp_database();
+        let id = insert_question(&conn, "韩国的首都在哪里", 0);
+        conn.execute(
+            "UPDATE AIResponses SET IsPendingCorrection = 1 WHERE Id = ?",
+            [id],
+        )
+        .expect("mark pending");
+
+        update_question_with_conn(
+            &mut conn,
+            id,
+            QuestionUpdate {
+                answer: Some("首尔".to_string()),
+                ..Default::default()
+            },
+            RevisionSource::Ui,
+        )
+        .expect("update question");
+
+        let (old_answer, new_answer, old_pending, new_pending, source): (
+            String,
+            String,
+            bool,
+            bool,
+            String,
+        ) = conn
+            .query_row(
+                "SELECT OldAnswer, NewAnswer, OldIsPendingCorrection, NewIsPendingCorrection, Source
+                 FROM QuestionRevisions WHERE QuestionId = ?",
+                [id],
+                |row| {
+                    Ok((
+                        row.get(0)?,
+                        row.get(1)?,
+                        row.get(2)?,
+                        row.get(3)?,
+                        row.get(4)?,
+                    ))
+                },
+            )
+            .expect("revision recorded");
+        assert_eq!(old_answer, "答案");
+        assert_eq!(new_answer, "首尔");
+        assert!(old_pending);
+        assert!(!new_pending);
+        assert_eq!(source, "ui");
+
+        let _ = std::fs::remove_file(&db_path);
+    }
+
+    #[test]
+    fn rollback_restores_the_content_saved_by_a_revision() {
+        let (db_path, mut conn) = open_temp_database();
+        let id = insert_question_with_conn(
+            &conn,
+            &NewQuestion {
+                content: "日本的首都在哪里".to_string(),
+                answer: "京都".to_string(),
+                ..Default::default()
+            },
+            0,
+            RevisionSource::Ui,
+        )
+        .expect("insert question");
+        for answer in ["东京", "大阪"] {
+            update_question_with_conn(
+                &mut conn,
+                id,
+                QuestionUpdate {
+                    answer: Some(answer.to_string()),
+                    ..Default::default()
+                },
+                RevisionSource::Ui,
+            )
+            .expect("update question");
+        }
+        let revision_ids = |conn: &Connection| -> Vec<i64> {
+            let mut stmt = conn
+                .prepare("SELECT RevisionId FROM QuestionRevisions WHERE QuestionId = ? ORDER BY RevisionId")
+                .expect("prepare revisions");
+            stmt.query_map([id], |row| row.get(0))
+                .expect("query revisions")
+                .collect::<Result<_, _>>()
+                .expect("read revisions")
+        };
+        let answer = |conn: &Connection| -> String {
+            conn.query_row("SELECT Answer FROM AIResponses WHERE Id = ?", [id], |row| {
+                row.get(0)
+            })
+            .expect("read question")
+        };
+        let revisions = revision_ids(&conn);
+        assert_eq!(revisions.len(), 3);
+        assert_eq!(answer(&conn), "大阪");
+
+        // 回滚到中间的修订，恢复该次修改保存的内容
+        rollback_question_revision_with_conn(&mut conn, revisions[1]).expect("roll back");
+        assert_eq!(answer(&conn), "东京");
+
+        // 新建记录也可以作为回滚目标
+        rollback_question_revision_with_conn(&mut conn, revisions[0]).expect("roll back");
+        assert_eq!(answer(&conn), "京都");
+
+        // 回滚到当前内容不产生新的修订
+        let revisions = revision_ids(&conn);
+        assert_eq!(revisions.len(), 5);
+        rollback_question_revision_with_conn(&mut conn, revisions[4]).expect("roll back");
+        assert_eq!(revision_ids(&conn).len(), 5);
+        let sources: Vec<String> = conn
+            .prepare(
+                "SELECT Source FROM QuestionRevisions WHERE QuestionId = ? ORDER BY RevisionId",
+            )
+            .expect("prepare revisions")
+            .query_map([id], |row| row.get(0))
+            .expect("query revisions")
+            .collect::<Result<_, _>>()
+            .expect("read revisions");
+        assert_eq!(sources, ["ui", "ui", "ui", "rollback", "rollback"]);
+
+        let _ = std::fs::remove_file(&db_path);
+    }
+
+    #[test]
+    fn answer_writes_record_revisions_with_their_source() {
+        let (db_path, mut conn) = open_temp_database();
+        let sources = |conn: &Connection, id: i64| -> Vec<String> {
+            let mut stmt = conn
+                .prepare(
+                    "SELECT Source FROM QuestionRevisions WHERE QuestionId = ? ORDER BY RevisionId",
+                )
+                .expect("prepare revisions");
+            stmt.query_map([id], |row| row.get(0))
+                .expect("query revisions")
+                .collect::<Result<_, _>>()
+                .expect("read revisions")
+        };
+
+        let imported = import_questions_with_conn(
+            &mut conn,
+            &[
+                NewQuestion {
+                    content: "地球绕太阳一周需要多久".to_string(),
+                    answer: "一年".to_string(),
+                    ..Default::default()
+                },
+                NewQuestion {
+                    content: "  ".to_string(),
+                    ..Default::default()
+                },
+            ],
+            0,
+        )
+        .expect("import questions");
+        assert_eq!(imported, 1);
+        let id: i64 = conn
+            .query_row("SELECT MAX(Id) FROM AIResponses", [], |row| row.get(0))
+            .expect("imported id");
+
+        // OCS 标记待修正与界面核实都会留下修订，重复标记不产生新修订
+        set_question_pending_correction_with_conn(&mut conn, id, true, RevisionSource::Api)
+            .expect("mark pending");
+        set_question_pending_correction_with_conn(&mut conn, id, true, RevisionSource::Api)
+            .expect("mark pending again");
+        set_question_verified_with_conn(&mut conn, id, true).expect("verify");
+        assert_eq!(sources(&conn, id), vec!["import", "api", "ui"]);
+
+        // 新建记录没有修改前的内容，回滚到新建记录即恢复题目最初的内容
+        let creation_id: i64 = conn
+            .query_row(
+                "SELECT MIN(RevisionId) FROM QuestionRevisions WHERE QuestionId = ?",
+                [id],
+                |row| row.get(0),
+            )
+            .expect("creation revision");
+        let creation = load_question_revision(&conn, creation_id).expect("load creation");
+        assert_eq!(creation.old, None);
+        assert_eq!(creation.new.answer.as_deref(), Some("一年"));
+        // 核实时已清除待修正标记，内容与新建时相同，回滚不产生新修订
+        rollback_question_revision_with_conn(&mut conn, creation_id).expect("roll back");
+        assert_eq!(sources(&conn, id), vec!["import", "api", "ui"]);
+
+        copy_question_with_conn(&mut conn, id, 0).expect("copy question");
+        let copy_id: i64 = conn
+            .query_row("SELECT MAX(Id) FROM AIResponses", [], |row| row.get(0))
+            .expect("copied id");
+        assert_eq!(sources(&conn, copy_id), vec!["ui"]);
+
+        let _ = std::fs::remove_file(&db_path);
+    }
 
-    // 每天请求计数表：仅展存 date + 计数，不保存请求详情
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS DailyRequestCounts (
-          Day TEXT PRIMARY KEY,
-          Count INTEGER NOT NULL DEFAULT 0
-        )",
-        [],
-    )
-    .map_err(|e| format!("{}", e))?;
+    #[test]
+    fn tags_filter_search_and_folder_stats() {
+        let (db_path, conn) = open_temp_database();
+        let folder = insert_folder(&conn, "期中", 0);
+        let capital = insert_question(&conn, "中国的首都是哪里", folder);
+        let river = insert_question(&conn, "中国最长的河流是哪条", folder);
+        let other = insert_question(&conn, "日本的首都是哪里", 0);
 
-    Ok(())
-}
+        let verified = add_tag_with_conn(&conn, "已核对").expect("add tag");
+        let geography = add_tag_with_conn(&conn, " 地理 ").expect("add tag");
+        assert!(add_tag_with_conn(&conn, "地理").is_err());
 
-#[cfg(test)]
-mod tests {
-    use super::{compute_query_match_score, get_table_columns, init_database_schema, is_exact_match_score};
-    use rusqlite::Connection;
-    use uuid::Uuid;
+        assert_eq!(
+            tag_questions_with_conn(&conn, &[capital, river, other, 9999], &[geography])
+                .expect("tag"),
+            3
+        );
+        assert_eq!(
+            tag_questions_with_conn(&conn, &[capital], &[verified, 9999]).expect("tag"),
+            1
+        );
+
+        let both: Vec<i64> =
+            search_questions_fuzzy_with_conn(&conn, "中国", None, &[geography, verified])
+                .expect("search")
+                .into_iter()
+                .map(|item| item.id)
+                .collect();
+        assert_eq!(both, vec![capital]);
+
+        let stats = get_folder_stats_with_conn(&conn, &[verified]).expect("stats");
+        let folder_count = stats
+            .iter()
+            .find(|s| s.folder_id == folder)
+            .map(|s| s.question_count);
+        let default_count = stats
+            .iter()
+            .find(|s| s.folder_id == 0)
+            .map(|s| s.question_count);
+        assert_eq!(folder_count, Some(1));
+        assert_eq!(default_count, Some(0));
+
+        assert_eq!(
+            untag_questions_with_conn(&conn, &[capital, river], &[geography]).expect("untag"),
+            2
+        );
+        assert_eq!(count(&conn, "SELECT COUNT(*) FROM QuestionTags"), 2);
+
+        let _ = std::fs::remove_file(&db_path);
+    }
 
     #[test]
-    fn rejects_same_template_with_different_entity() {
-        assert!(compute_query_match_score("韩国的首都在哪里", "美国的首都在哪里").is_none());
+    fn search_query_language_filters_questions() {
+        let (db_path, conn) = open_temp_database();
+        let semester = insert_folder(&conn, "2024春", 0);
+        let chapter = insert_folder(&conn, "第一章", semester);
+        let capital = insert_question(&conn, "中国的首都是哪里", chapter);
+        let river = insert_question(&conn, "中国最长的河流 是哪条", semester);
+        let other = insert_question(&conn, "日本的首都是哪里", 0);
+        conn.execute(
+            "UPDATE AIResponses SET QuestionType = '单选', IsAi = 1, Options = 'A. 北京 B. 上海', CreateTime = '2024-03-01 08:00:00' WHERE Id = ?",
+            [capital],
+        )
+        .expect("update capital");
+        conn.execute(
+            "UPDATE AIResponses SET IsPendingCorrection = 1, CreateTime = '2024-05-20 12:00:00' WHERE Id = ?",
+            [river],
+        )
+        .expect("update river");
+        refresh_question_kinds(&conn).expect("refresh kinds");
+
+        let search = |query: &str| -> Result<Vec<i64>, String> {
+            let mut ids: Vec<i64> = search_questions_fuzzy_with_conn(&conn, query, None, &[])?
+                .into_iter()
+                .map(|item| item.id)
+                .collect();
+            ids.sort();
+            Ok(ids)
+        };
+
+        assert_eq!(search("首都 哪里").unwrap(), vec![capital, other]);
+        assert_eq!(search("q:中国 -is:ai").unwrap(), vec![river]);
+        // 类型字段不同但识别为同一题型（取自派生的 QuestionKind 列）
+        assert_eq!(search("type:single").unwrap(), vec![capital]);
+        assert_eq!(search("folder:\"2024春/第一章\"").unwrap(), vec![capital]);
+        assert_eq!(search("folder:2024春").unwrap(), vec![capital, river]);
+        assert_eq!(search("created:2024-03-01").unwrap(), vec![capital]);
+        assert_eq!(search("a:答案 -folder:2024春").unwrap(), vec![other]);
+        assert_eq!(search("folder:不存在").unwrap(), Vec::<i64>::new());
+        assert_eq!(
+            search("-folder:不存在").unwrap(),
+            vec![capital, river, other]
+        );
+        assert!(search("/[/").is_err());
+
+        let _ = std::fs::remove_file(&db_path);
     }
 
     #[test]
-    fn keeps_same_question_with_small_wording_changes() {
-        assert!(compute_query_match_score("韩国的首都在哪里", "韩国首都是哪里").is_some());
+    fn search_results_are_ranked_and_highlighted() {
+        let (db_path, conn) = open_temp_database();
+        let in_answer = insert_question(&conn, "下列哪座城市位于华北", 0);
+        conn.execute(
+            "UPDATE AIResponses SET Answer = '北京' WHERE Id = ?",
+            [in_answer],
+        )
+        .expect("update answer");
+        let partial = insert_question(&conn, "北京是哪个国家的城市", 0);
+        let exact = insert_question(&conn, "北京", 0);
+
+        let search = |keyword: &str, sort_by: Option<&str>, page: Option<(i64, i64)>| {
+            let order = search_order_clause(sort_by, None).expect("order");
+            search_ranked_with_conn(&conn, keyword, None, &[], order, page).expect("search")
+        };
+        let results = search("北京", None, None);
+        let hits = &results.items;
+        let ids: Vec<i64> = hits.iter().map(|hit| hit.item.id).collect();
+        assert_eq!(ids, vec![exact, partial, in_answer]);
+        assert!(hits[0].score > hits[1].score && hits[1].score > hits[2].score);
+
+        let highlights: Vec<(&str, usize, usize)> = hits[2]
+            .highlights
+            .iter()
+            .map(|span| (span.field.as_str(), span.start, span.end))
+            .collect();
+        assert_eq!(highlights, vec![("answer", 0, 2)]);
+        assert_eq!(
+            (hits[1].highlights[0].start, hits[1].highlights[0].end),
+            (0, 2)
+        );
+
+        // 词频越高越靠前
+        let repeated = insert_question(&conn, "北京、北京，哪个国家的城市", 0);
+        let results = search("北京", None, None);
+        assert_eq!(results.items[1].item.id, repeated);
+        assert_eq!(results.items[2].item.id, partial);
+
+        // 分页和总数在数据库中计算
+        let page = search("北京", None, Some((1, 2)));
+        assert_eq!(page.total, 4);
+        let ids: Vec<i64> = page.items.iter().map(|hit| hit.item.id).collect();
+        assert_eq!(ids, vec![repeated, partial]);
+        assert_eq!(search("北京 -q:国家", None, Some((0, 10))).total, 2);
+
+        let by_question = search("北京", Some("question"), None);
+        assert_eq!(by_question.items[0].item.id, in_answer);
+        assert!(search_order_clause(Some("folder"), None).is_err());
+        assert!(search_order_clause(None, Some("up")).is_err());
+
+        let _ = std::fs::remove_file(&db_path);
     }
 
     #[test]
-    fn keeps_exact_match() {
+    fn explain_query_reports_accepted_and_rejected_candidates() {
+        let (db_path, conn) = open_temp_database();
+        let exact = insert_question(&conn, "中华人民共和国成立于哪一年", 0);
+        let similar = insert_question(&conn, "中华人民共和国成立于哪一年？", 0);
+        let unrelated = insert_question(&conn, "水的化学式是什么", 0);
+        let with_url = insert_question(&conn, "看图 https://a.example/1.png 回答问题", 0);
+
+        let explanation = explain_query_with_conn(
+            &conn,
+            "中华人民共和国成立于哪一年",
+            None,
+            Some(10),
+            None,
+            QueryFeatures::default(),
+            None,
+        )
+        .expect("explain");
+        assert_eq!(explanation.total_candidates, 4);
+        assert_eq!(explanation.accepted_count, 2);
+        assert_eq!(explanation.candidates[0].id, exact);
+        assert_eq!(explanation.candidates[0].rank, Some(1));
+        assert_eq!(explanation.candidates[0].evaluation.final_score, Some(1.0));
+        assert_eq!(explanation.candidates[1].id, similar);
+        assert!(explanation.candidates[1]
+            .evaluation
+            .title_match
+            .keyword_coverage
+            .is_some());
+
+        let rejected = explanation
+            .candidates
+            .iter()
+            .find(|candidate| candidate.id == unrelated)
+            .expect("rejected candidate listed");
+        assert_eq!(rejected.rank, None);
+        assert!(rejected
+            .evaluation
+            .rejection_reason
+            .as_deref()
+            .is_some_and(|reason| reason.contains("字符相似度")));
+
+        let url_explanation = explain_query_with_conn(
+            &conn,
+            "看图 https://a.example/2.png 回答问题",
+            None,
+            Some(1),
+            None,
+            QueryFeatures::default(),
+            None,
+        )
+        .expect("explain url query");
+        assert_eq!(url_explanation.accepted_count, 0);
+        assert_eq!(url_explanation.candidates.len(), 1);
+        assert_eq!(url_explanation.candidates[0].id, with_url);
         assert_eq!(
-            compute_query_match_score("韩国的首都在哪里", "韩国的首都在哪里"),
+            url_explanation.candidates[0].evaluation.urls_match,
+            Some(false)
+        );
+
+        let _ = std::fs::remove_file(&db_path);
+    }
+
+    #[test]
+    fn shuffled_options_match_exactly() {
+        let (db_path, mut conn) = open_temp_database();
+        let capital = insert_question(&conn, "下列城市中哪一个是中国的首都", 0);
+        let other = insert_question(&conn, "下列城市中哪一个是日本的首都", 0);
+        // 模拟旧数据：只有原始选项，规范化选项由启动时回填
+        conn.execute(
+            "UPDATE AIResponses SET Options = 'A. 北京 B. 上海 C. 广州 D. 深圳' WHERE Id = ?",
+            [capital],
+        )
+        .expect("set options");
+        conn.execute(
+            "UPDATE AIResponses SET Options = 'A. 东京 B. 大阪 C. 京都 D. 札幌' WHERE Id = ?",
+            [other],
+        )
+        .expect("set options");
+        backfill_normalized_options(&conn).expect("backfill normalized options");
+        let normalized: String = conn
+            .query_row(
+                "SELECT NormalizedOptions FROM AIResponses WHERE Id = ?",
+                [capital],
+                |row| row.get(0),
+            )
+            .expect("read normalized options");
+        assert_eq!(normalized, "上海\n北京\n广州\n深圳");
+
+        let explanation = explain_query_with_conn(
+            &conn,
+            "下列城市中哪一个是中国的首都？",
+            Some("A. 深圳 B. 广州 C. 北京 D. 上海"),
+            Some(10),
+            None,
+            QueryFeatures::default(),
+            None,
+        )
+        .expect("explain shuffled options");
+        assert_eq!(explanation.accepted_count, 1);
+        let matched = &explanation.candidates[0];
+        assert_eq!(matched.id, capital);
+        assert_eq!(
+            matched
+                .evaluation
+                .option_match
+                .as_ref()
+                .and_then(|detail| detail.score),
             Some(1.0)
         );
+
+        // 通过 update_question 修改选项时同步更新规范化选项
+        update_question_with_conn(
+            &mut conn,
+            capital,
+            QuestionUpdate {
+                options: Some("A. 上海 B. 北京".to_string()),
+                ..Default::default()
+            },
+            RevisionSource::Ui,
+        )
+        .expect("update options");
+        let normalized: String = conn
+            .query_row(
+                "SELECT NormalizedOptions FROM AIResponses WHERE Id = ?",
+                [capital],
+                |row| row.get(0),
+            )
+            .expect("read normalized options");
+        assert_eq!(normalized, "上海\n北京");
+
+        let _ = std::fs::remove_file(&db_path);
     }
 
     #[test]
-    fn exact_match_score_is_detected() {
-        assert!(is_exact_match_score(1.0));
-        assert!(!is_exact_match_score(0.999));
+    fn normalized_variants_match_exactly() {
+        assert_eq!(
+            compute_query_match_score(
+                "中華人民共和國成立於哪一年？",
+                "中华人民共和国成立于哪一年?"
+            ),
+            Some(1.0)
+        );
+        assert_eq!(
+            compute_query_match_score(
+                "“光合作用”发生在\u{200B}哪里",
+                "&quot;光合作用&quot;发生在哪里"
+            ),
+            Some(1.0)
+        );
     }
 
     #[test]
-    fn migrates_legacy_database_schema() {
-        let db_path = std::env::temp_dir().join(format!("zerror-migration-{}.db", Uuid::new_v4()));
-        let db_path_str = db_path.to_string_lossy().to_string();
+    fn image_urls_survive_html_stripping() {
+        let html = "<p><img src=\"https://a.example/1.png?x=1&amp;y=2\">图中是什么动物？</p>";
+        assert_eq!(
+            extract_urls(&html_to_text(html)),
+            vec!["https://a.example/1.png?x=1&y=2"]
+        );
+    }
 
-        {
-            let conn = Connection::open(&db_path_str).expect("create legacy database");
-            conn.execute(
-                "CREATE TABLE Folders (
-                  Id INTEGER PRIMARY KEY AUTOINCREMENT,
-                  Name TEXT NOT NULL
-                )",
-                [],
+    #[test]
+    fn editing_question_clears_stale_raw_html() {
+        let (db_path, mut conn) = open_temp_database();
+        let id = insert_question(&conn, "看图回答", 0);
+        conn.execute(
+            "UPDATE AIResponses SET RawQuestionHtml = '<p>看图回答</p>' WHERE Id = ?",
+            [id],
+        )
+        .expect("set raw html");
+        let raw_html = |conn: &Connection| -> Option<String> {
+            conn.query_row(
+                "SELECT RawQuestionHtml FROM AIResponses WHERE Id = ?",
+                [id],
+                |row| row.get(0),
             )
-            .expect("create legacy folders");
+            .expect("read raw html")
+        };
+
+        update_question_with_conn(
+            &mut conn,
+            id,
+            QuestionUpdate {
+                answer: Some("猫".to_string()),
+                ..Default::default()
+            },
+            RevisionSource::Ui,
+        )
+        .expect("update answer");
+        assert_eq!(raw_html(&conn).as_deref(), Some("<p>看图回答</p>"));
+
+        update_question_with_conn(
+            &mut conn,
+            id,
+            QuestionUpdate {
+                question: Some("看图回答：图中是什么动物".to_string()),
+                ..Default::default()
+            },
+            RevisionSource::Ui,
+        )
+        .expect("update question");
+        assert_eq!(raw_html(&conn), None);
+
+        let _ = std::fs::remove_file(&db_path);
+    }
+
+    #[test]
+    fn stored_question_types_keep_raw_value_and_derive_kind() {
+        let (db_path, conn) = open_temp_database();
+        for question_type in ["single", "0", "单选题", "编程问题"] {
+            let id = insert_question(&conn, "题目", 0);
             conn.execute(
-                "CREATE TABLE AIResponses (
-                  Id INTEGER PRIMARY KEY AUTOINCREMENT,
-                  Question TEXT NOT NULL,
-                  Options TEXT,
-                  Answer TEXT NOT NULL,
-                  FolderName TEXT DEFAULT '默认文件夹'
-                )",
-                [],
+                "UPDATE AIResponses SET QuestionType = ? WHERE Id = ?",
+                rusqlite::params![question_type, id],
             )
-            .expect("create legacy ai responses");
-            conn.execute("INSERT INTO Folders (Id, Name) VALUES (0, '默认文件夹')", [])
-                .expect("insert default folder");
-            conn.execute(
-                "INSERT INTO AIResponses (Question, Options, Answer, FolderName) VALUES ('题目', NULL, '答案', '默认文件夹')",
-                [],
+            .expect("set question type");
+        }
+
+        // 题型配置变化后启动时只重新计算 QuestionKind，原始的类型字段不变，也不记录修订
+        assert_eq!(refresh_question_kinds(&conn).expect("refresh kinds"), 3);
+        assert_eq!(refresh_question_kinds(&conn).expect("refresh kinds"), 0);
+        let added = insert_question_with_conn(
+            &conn,
+            &NewQuestion {
+                content: "新题目".to_string(),
+                answer: "答案".to_string(),
+                question_type: Some("Multiple-Choice".to_string()),
+                ..NewQuestion::default()
+            },
+            0,
+            RevisionSource::Ui,
+        )
+        .expect("add question");
+        assert!(added > 0);
+
+        let mut stmt = conn
+            .prepare("SELECT QuestionType, QuestionKind FROM AIResponses ORDER BY Id")
+            .expect("prepare");
+        let types: Vec<(String, Option<String>)> = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .expect("query")
+            .map(|row| row.expect("row"))
+            .collect();
+        let kind = |value: &str| Some(value.to_string());
+        assert_eq!(
+            types,
+            vec![
+                ("single".to_string(), kind("single")),
+                ("0".to_string(), kind("single")),
+                ("单选题".to_string(), kind("single")),
+                ("编程问题".to_string(), None),
+                ("Multiple-Choice".to_string(), kind("multiple")),
+            ]
+        );
+        // 只有新增题目的修订
+        assert_eq!(count(&conn, "SELECT COUNT(*) FROM QuestionRevisions"), 1);
+
+        drop(stmt);
+        drop(conn);
+        let _ = std::fs::remove_file(&db_path);
+    }
+
+    #[test]
+    fn rejected_embeddings_are_not_retried_until_question_changes() {
+        let (db_path, mut conn) = open_temp_database();
+        let id = insert_question(&conn, "过长的题目", 0);
+        save_question_embeddings_with_conn(
+            &mut conn,
+            "test",
+            &[(id, "过长的题目".to_string(), Err("输入过长".to_string()))],
+        )
+        .expect("save failed embedding");
+        assert!(
+            load_questions_needing_embeddings_with_conn(&conn, "test", 10)
+                .expect("load pending")
+                .is_empty()
+        );
+        assert_eq!(
+            count(
+                &conn,
+                "SELECT COUNT(*) FROM QuestionEmbeddings WHERE Error IS NOT NULL AND Dim = 0"
+            ),
+            1
+        );
+
+        conn.execute(
+            "UPDATE AIResponses SET Question = '缩短后的题目' WHERE Id = ?",
+            [id],
+        )
+        .expect("edit question");
+        assert_eq!(
+            load_questions_needing_embeddings_with_conn(&conn, "test", 10).expect("load pending"),
+            vec![(id, "缩短后的题目".to_string())]
+        );
+
+        let _ = std::fs::remove_file(&db_path);
+    }
+
+    #[test]
+    fn semantic_match_accepts_paraphrases_but_not_other_entities() {
+        let (db_path, mut conn) = open_temp_database();
+        let mammal = insert_question(&conn, "下列不属于哺乳动物的是", 0);
+        let korea = insert_question(&conn, "韩国的首都是哪里", 0);
+        conn.execute(
+            "UPDATE AIResponses SET Options = 'A. 鲸 B. 蝙蝠 C. 鲨鱼 D. 海豚' WHERE Id = ?",
+            [mammal],
+        )
+        .expect("set options");
+        backfill_normalized_options(&conn).expect("backfill normalized options");
+
+        let pending =
+            load_questions_needing_embeddings_with_conn(&conn, "test", 10).expect("load pending");
+        assert_eq!(
+            pending.iter().map(|(id, _)| *id).collect::<Vec<_>>(),
+            vec![mammal, korea]
+        );
+        save_question_embeddings_with_conn(
+            &mut conn,
+            "test",
+            &[
+                (
+                    mammal,
+                    "下列不属于哺乳动物的是".to_string(),
+                    Ok(vec![1.0, 0.0, 0.0]),
+                ),
+                (
+                    korea,
+                    "韩国的首都是哪里".to_string(),
+                    Ok(vec![0.0, 1.0, 0.0]),
+                ),
+            ],
+        )
+        .expect("save embeddings");
+        assert!(
+            load_questions_needing_embeddings_with_conn(&conn, "test", 10)
+                .expect("load pending")
+                .is_empty()
+        );
+        // 换用其他模型时需要重新生成
+        assert_eq!(
+            load_questions_needing_embeddings_with_conn(&conn, "other", 10)
+                .expect("load pending")
+                .len(),
+            2
+        );
+
+        let embedding = |vector: Vec<f32>| QueryFeatures {
+            embedding: Some(QueryEmbedding {
+                model: "test".to_string(),
+                vector,
+            }),
+            ..Default::default()
+        };
+
+        // 字符相似度不够，但语义相近且选项一致
+        let explanation = explain_query_with_conn(
+            &conn,
+            "以下哪项不是哺乳动物",
+            Some("A. 海豚 B. 鲨鱼 C. 蝙蝠 D. 鲸"),
+            Some(10),
+            None,
+            embedding(vec![0.98, 0.1, 0.0]),
+            None,
+        )
+        .expect("explain paraphrase");
+        assert_eq!(explanation.accepted_count, 1);
+        let matched = &explanation.candidates[0];
+        assert_eq!(matched.id, mammal);
+        assert!(matched.evaluation.title_match.score.is_none());
+        let semantic = matched
+            .evaluation
+            .semantic_match
+            .as_ref()
+            .expect("semantic detail");
+        assert!(semantic.rejection.is_none());
+        assert!(semantic
+            .cosine_similarity
+            .is_some_and(|similarity| similarity > 0.99));
+
+        // 向量相同但实体不同的题目不匹配
+        let explanation = explain_query_with_conn(
+            &conn,
+            "美国的首都是哪里",
+            None,
+            Some(10),
+            None,
+            embedding(vec![0.0, 1.0, 0.0]),
+            None,
+        )
+        .expect("explain other entity");
+        assert_eq!(explanation.accepted_count, 0);
+        let rejected = explanation
+            .candidates
+            .iter()
+            .find(|candidate| candidate.id == korea)
+            .expect("candidate listed");
+        let semantic = rejected
+            .evaluation
+            .semantic_match
+            .as_ref()
+            .expect("semantic detail");
+        // 查询中的实体词不在题干中时不读取向量
+        assert_eq!(semantic.mismatched_entities, vec!["美国"]);
+        assert!(semantic.cosine_similarity.is_none());
+
+        // 题干修改后旧向量不再使用，需要重新生成
+        update_question_with_conn(
+            &mut conn,
+            mammal,
+            QuestionUpdate {
+                question: Some("下列动物中不属于哺乳动物的是".to_string()),
+                ..Default::default()
+            },
+            RevisionSource::Ui,
+        )
+        .expect("update question");
+        let pending =
+            load_questions_needing_embeddings_with_conn(&conn, "test", 10).expect("load pending");
+        assert_eq!(
+            pending,
+            vec![(mammal, "下列动物中不属于哺乳动物的是".to_string())]
+        );
+        let explanation = explain_query_with_conn(
+            &conn,
+            "以下哪项不是哺乳动物",
+            Some("A. 海豚 B. 鲨鱼 C. 蝙蝠 D. 鲸"),
+            Some(10),
+            None,
+            embedding(vec![0.98, 0.1, 0.0]),
+            None,
+        )
+        .expect("explain after update");
+        assert_eq!(explanation.accepted_count, 0);
+
+        let _ = std::fs::remove_file(&db_path);
+    }
+
+    #[test]
+    fn signed_image_urls_match_the_same_question() {
+        let (db_path, conn) = open_temp_database();
+        let image = insert_question(
+            &conn,
+            "看图 https://img.example.com/q/1.png?x-oss-process=image/resize,w_200 回答问题",
+            0,
+        );
+        let other = insert_question(&conn, "看图 https://img.example.com/q/2.png 回答问题", 0);
+
+        // 旧数据没有规范 URL 时现场计算
+        let query = "看图 http://img.example.com/q/1.png?Expires=1700000000&Signature=abc 回答问题";
+        let explanation = explain_query_with_conn(
+            &conn,
+            query,
+            None,
+            Some(10),
+            None,
+            QueryFeatures::default(),
+            None,
+        )
+        .expect("explain");
+        assert_eq!(explanation.accepted_count, 1);
+        assert_eq!(explanation.candidates[0].id, image);
+
+        assert_eq!(
+            refresh_canonical_urls(&conn).expect("refresh canonical urls"),
+            2
+        );
+        assert_eq!(
+            refresh_canonical_urls(&conn).expect("refresh canonical urls"),
+            0
+        );
+        let stored: String = conn
+            .query_row(
+                "SELECT CanonicalUrls FROM AIResponses WHERE Id = ?",
+                [image],
+                |row| row.get(0),
             )
-            .expect("insert legacy response");
+            .expect("read canonical urls");
+        assert_eq!(stored, "https://img.example.com/q/1.png");
+
+        let explanation = explain_query_with_conn(
+            &conn,
+            query,
+            None,
+            Some(10),
+            None,
+            QueryFeatures::default(),
+            None,
+        )
+        .expect("explain");
+        assert_eq!(explanation.accepted_count, 1);
+        assert_eq!(explanation.candidates[0].id, image);
+        let rejected = explanation
+            .candidates
+            .iter()
+            .find(|candidate| candidate.id == other)
+            .expect("candidate listed");
+        assert_eq!(rejected.evaluation.urls_match, Some(false));
+
+        // 按其他规则计算的规范 URL 不再使用，刷新时按当前规则重新计算
+        conn.execute(
+            "UPDATE AIResponses SET CanonicalUrls = 'https://stale/1.png', CanonicalUrlsRules = 'old'
+             WHERE Id = ?",
+            [image],
+        )
+        .expect("mark stale");
+        let explanation = explain_query_with_conn(
+            &conn,
+            query,
+            None,
+            Some(10),
+            None,
+            QueryFeatures::default(),
+            None,
+        )
+        .expect("explain");
+        assert_eq!(explanation.accepted_count, 1);
+        assert_eq!(explanation.candidates[0].id, image);
+        assert_eq!(explanation.candidates[0].canonical_urls, None);
+        assert_eq!(
+            refresh_canonical_urls(&conn).expect("refresh canonical urls"),
+            1
+        );
+
+        let _ = std::fs::remove_file(&db_path);
+    }
+
+    #[tokio::test]
+    async fn reuploaded_images_match_by_perceptual_hash() {
+        let original = encode_test_image(64, false, image::ImageFormat::Png);
+        let reuploaded = encode_test_image(48, false, image::ImageFormat::Jpeg);
+        let different = encode_test_image(64, true, image::ImageFormat::Png);
+
+        // 本地 HTTP 服务模拟图床
+        let images = std::sync::Arc::new(std::collections::HashMap::from([
+            ("a.png".to_string(), original),
+            ("b.jpg".to_string(), reuploaded),
+            ("c.png".to_string(), different),
+        ]));
+        let routes = warp::path::param::<String>().map(move |name: String| {
+            let response = warp::http::Response::builder();
+            match images.get(&name) {
+                Some(bytes) => response.body(bytes.clone()),
+                None => response.status(404).body(Vec::new()),
+            }
+            .unwrap()
+        });
+        let (addr, server) = warp::serve(routes).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+        let url = |name: &str| format!("http://{}/{}", addr, name);
+
+        // 测试图床在本机
+        let config = ImageHashConfig {
+            timeout_secs: 5,
+            allow_private_hosts: true,
+            ..ImageHashConfig::default()
+        };
+
+        let (db_path, mut conn) = open_temp_database();
+        let image_question = insert_question(&conn, &format!("看图回答 {}", url("a.png")), 0);
+        let other = insert_question(&conn, &format!("看图回答 {}", url("c.png")), 0);
+        let missing = insert_question(&conn, &format!("看图回答 {}", url("missing.png")), 0);
+        refresh_canonical_urls(&conn).expect("refresh canonical urls");
+
+        let pending = load_pending_image_hashes_with_conn(&conn, 10).expect("load pending");
+        assert_eq!(
+            pending.iter().map(|(id, _, _)| *id).collect::<Vec<_>>(),
+            vec![image_question, other, missing]
+        );
+        let mut results = Vec::new();
+        for (question_id, canonical, raw) in pending {
+            results.push((question_id, canonical, hash_image_url(&raw, &config).await));
         }
+        assert!(results[2].2.is_err());
+        save_image_hashes_with_conn(&mut conn, &results).expect("save image hashes");
+        // 失败的图片不会立即重试
+        assert!(load_pending_image_hashes_with_conn(&conn, 10)
+            .expect("load pending")
+            .is_empty());
+
+        let query = format!("看图回答 {}", url("b.jpg"));
+        let query_hash = hash_image_url(&url("b.jpg"), &config)
+            .await
+            .expect("hash query image");
+        let canonical = UrlCanonicalizer::load().canonicalize(&url("b.jpg"));
+        let features = QueryFeatures {
+            image_hashes: [(canonical, query_hash)].into_iter().collect(),
+            ..Default::default()
+        };
+        let explanation =
+            explain_query_with_conn(&conn, &query, None, Some(10), None, features, None)
+                .expect("explain");
+        assert_eq!(explanation.accepted_count, 1);
+        let matched = &explanation.candidates[0];
+        assert_eq!(matched.id, image_question);
+        assert!(matched
+            .evaluation
+            .image_hash_distance
+            .is_some_and(|distance| distance <= 6));
+        let rejected = explanation
+            .candidates
+            .iter()
+            .find(|candidate| candidate.id == other)
+            .expect("candidate listed");
+        assert_eq!(rejected.evaluation.urls_match, Some(false));
+
+        // 未启用图片哈希时仍按 URL 比较
+        let explanation = explain_query_with_conn(
+            &conn,
+            &query,
+            None,
+            Some(10),
+            None,
+            QueryFeatures::default(),
+            None,
+        )
+        .expect("explain");
+        assert_eq!(explanation.accepted_count, 0);
 
-        init_database_schema(&db_path_str).expect("migrate legacy database");
+        let _ = std::fs::remove_file(&db_path);
+    }
 
-        let conn = Connection::open(&db_path_str).expect("reopen migrated database");
-        let folder_columns = get_table_columns(&conn, "Folders").expect("read folder columns");
-        assert!(folder_columns.contains("ParentId"));
-        assert!(folder_columns.contains("CreateTime"));
+    #[test]
+    fn folder_chain_lists_ancestors_with_paths() {
+        let (db_path, conn) = open_temp_database();
+        let english = insert_folder(&conn, "英语", 0);
+        let cet4 = insert_folder(&conn, "四级", english);
+        assert_eq!(
+            folder_chain_with_conn(&conn, cet4).expect("folder chain"),
+            vec![
+                (english, "英语".to_string()),
+                (cet4, "英语/四级".to_string())
+            ]
+        );
+        assert!(folder_chain_with_conn(&conn, 9999)
+            .expect("missing folder")
+            .is_empty());
 
-        let response_columns = get_table_columns(&conn, "AIResponses").expect("read ai response columns");
-        assert!(response_columns.contains("QuestionType"));
-        assert!(response_columns.contains("CreateTime"));
-        assert!(response_columns.contains("FolderId"));
-        assert!(response_columns.contains("FolderName"));
-        assert!(response_columns.contains("IsAi"));
-        assert!(response_columns.contains("IsPendingCorrection"));
+        let _ = std::fs::remove_file(&db_path);
+    }
 
-        let (folder_id, folder_name, is_ai, is_pending): (i64, String, i64, i64) = conn
+    #[test]
+    fn query_matches_carry_score_and_folder() {
+        let (db_path, conn) = open_temp_database();
+        let biology = insert_folder(&conn, "生物", 0);
+        let cells = insert_folder(&conn, "细胞", biology);
+        let exact = insert_question(&conn, "细胞膜的主要成分是什么", cells);
+        let similar = insert_question(&conn, "细胞膜的主要成分是什么物质", 0);
+        insert_question(&conn, "光合作用发生在哪里", cells);
+
+        let context = QueryMatchContext::new("细胞膜的主要成分是什么", None)
+            .with_features(QueryFeatures::default());
+        let result = query_database_with_conn(&conn, &context, None, None).expect("query database");
+        let ids: Vec<i64> = result.rows.iter().map(|row| row.id).collect();
+        assert_eq!(ids, vec![exact, similar]);
+        assert!(result.rows[0].score > result.rows[1].score);
+        assert_eq!(result.rows[0].folder_id, cells);
+        assert_eq!(result.rows[0].folder.as_deref(), Some("生物/细胞"));
+        assert_eq!(result.rows[1].folder, None);
+
+        let _ = std::fs::remove_file(&db_path);
+    }
+
+    #[test]
+    fn ranking_prefers_verified_then_human_answers() {
+        let (db_path, mut conn) = open_temp_database();
+        let human = insert_question(&conn, "水的化学式是什么", 0);
+        let ai = insert_question(&conn, "水的化学式是什么", 0);
+        let pending = insert_question(&conn, "水的化学式是什么", 0);
+        let verified = insert_question(&conn, "水的化学式是什么物质", 0);
+        conn.execute(
+            "UPDATE AIResponses SET CreateTime = '2024-01-01 00:00:00' WHERE Id = ?",
+            [human],
+        )
+        .expect("age human answer");
+        conn.execute(
+            "UPDATE AIResponses SET IsAi = 1 WHERE Id IN (?, ?)",
+            [ai, verified],
+        )
+        .expect("mark ai answers");
+        conn.execute(
+            "UPDATE AIResponses SET IsPendingCorrection = 1 WHERE Id = ?",
+            [pending],
+        )
+        .expect("mark pending");
+        set_question_verified_with_conn(&mut conn, verified, true).expect("verify question");
+
+        let context = QueryMatchContext::new("水的化学式是什么", None)
+            .with_features(QueryFeatures::default());
+        let result = query_database_with_conn(&conn, &context, None, None).expect("query database");
+        let ids: Vec<i64> = result.rows.iter().map(|row| row.id).collect();
+        assert_eq!(ids, vec![verified, human, ai, pending]);
+        assert!(result.rows[0].is_verified);
+        assert!(result.rows[0].score < result.rows[1].score);
+
+        // AI 作答、添加和导入都不会写入与已核实题目重复的 AI 答案，接口也不能把它标记为待修正
+        let ai_answer = NewQuestion {
+            content: "水的化学式是什么物质".to_string(),
+            answer: "AI 答案".to_string(),
+            is_ai: true,
+            ..Default::default()
+        };
+        let err = insert_ai_response_with_conn(&mut conn, &ai_answer, None, false, None)
+            .expect_err("verified question is protected from ai answers");
+        assert!(err.contains("已人工核实"));
+        assert!(insert_question_with_conn(&conn, &ai_answer, 0, RevisionSource::AiAnswer).is_err());
+        let human_answer = NewQuestion {
+            is_ai: false,
+            ..ai_answer.clone()
+        };
+        let imported = import_questions_with_conn(&mut conn, &[ai_answer.clone(), human_answer], 0)
+            .expect("import questions");
+        assert_eq!(imported, 1);
+        let other = NewQuestion {
+            content: "氧气的化学式是什么".to_string(),
+            ..ai_answer.clone()
+        };
+        insert_ai_response_with_conn(&mut conn, &other, None, false, None)
+            .expect("unverified question is saved");
+        let err = set_question_pending_correction_with_conn(
+            &mut conn,
+            verified,
+            true,
+            RevisionSource::Api,
+        )
+        .expect_err("api cannot mark verified question");
+        assert!(err.contains("已人工核实"));
+        set_question_pending_correction_with_conn(&mut conn, verified, true, RevisionSource::Ui)
+            .expect("manual mark");
+
+        // 核实即清除待修正标记
+        set_question_verified_with_conn(&mut conn, pending, true).expect("verify pending question");
+        let (is_verified, is_pending): (bool, bool) = conn
             .query_row(
-                "SELECT FolderId, FolderName, IsAi, IsPendingCorrection FROM AIResponses LIMIT 1",
-                [],
-                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+                "SELECT IsVerified, IsPendingCorrection FROM AIResponses WHERE Id = ?",
+                [pending],
+                |row| Ok((row.get(0)?, row.get(1)?)),
             )
-            .expect("query migrated values");
-        assert_eq!(folder_id, 0);
-        assert_eq!(folder_name, "默认文件夹");
-        assert_eq!(is_ai, 1);
-        assert_eq!(is_pending, 0);
+            .expect("read flags");
+        assert!(is_verified && !is_pending);
+        assert!(set_question_verified_with_conn(&mut conn, 9999, true).is_err());
 
-        let _ = std::fs::remove_file(&db_path_str);
+        let _ = std::fs::remove_file(&db_path);
+    }
+
+    #[test]
+    fn query_scope_limits_matches_and_save_folder() {
+        let (db_path, conn) = open_temp_database();
+        let english = insert_folder(&conn, "英语", 0);
+        let cet4 = insert_folder(&conn, "四级", english);
+        let math = insert_folder(&conn, "数学", 0);
+        let in_cet4 = insert_question(&conn, "下列哪个选项是正确的", cet4);
+        let in_math = insert_question(&conn, "下列哪个选项是正确的", math);
+
+        let english_scope =
+            resolve_query_scope_with_conn(&conn, &["英语".to_string()]).expect("resolve path");
+        assert_eq!(english_scope.roots, vec![english]);
+        assert!(english_scope.folder_ids.contains(&cet4));
+        assert!(!english_scope.folder_ids.contains(&math));
+
+        let context = QueryMatchContext::new("下列哪个选项是正确的", None)
+            .with_features(QueryFeatures::default());
+        let ids = |scope: Option<&QueryScope>| -> Vec<i64> {
+            query_database_with_conn(&conn, &context, None, scope)
+                .expect("query database")
+                .rows
+                .iter()
+                .map(|row| row.id)
+                .collect()
+        };
+        assert_eq!(ids(Some(&english_scope)), vec![in_cet4]);
+        let math_scope =
+            resolve_query_scope_with_conn(&conn, &[format!(" {} ", math)]).expect("resolve id");
+        assert_eq!(ids(Some(&math_scope)), vec![in_math]);
+        assert_eq!(ids(None).len(), 2);
+        let explanation = explain_query_with_conn(
+            &conn,
+            "下列哪个选项是正确的",
+            None,
+            None,
+            None,
+            QueryFeatures::default(),
+            Some(&math_scope),
+        )
+        .expect("explain query");
+        let explained: Vec<i64> = explanation.candidates.iter().map(|c| c.id).collect();
+        assert_eq!(explained, vec![in_math]);
+
+        assert!(resolve_query_scope_with_conn(&conn, &["英语/六级".to_string()]).is_err());
+        assert!(resolve_query_scope_with_conn(&conn, &["9999".to_string()]).is_err());
+
+        // 请求的范围与 token 绑定的范围取交集
+        let cet4_scope =
+            resolve_query_scope_with_conn(&conn, &["英语/四级".to_string()]).expect("resolve path");
+        let narrowed = english_scope.intersect(&cet4_scope);
+        assert_eq!(narrowed.roots, vec![cet4]);
+        assert_eq!(narrowed.folder_ids, cet4_scope.folder_ids);
+        assert!(english_scope.intersect(&math_scope).folder_ids.is_empty());
+
+        // 保存文件夹在范围内时保持不变，否则改为范围内的第一个文件夹
+        assert_eq!(save_folder_in_scope(cet4, Some(&english_scope)), cet4);
+        assert_eq!(save_folder_in_scope(math, Some(&english_scope)), english);
+        assert_eq!(save_folder_in_scope(0, Some(&english_scope)), english);
+        assert_eq!(save_folder_in_scope(math, None), math);
+
+        let _ = std::fs::remove_file(&db_path);
     }
 }
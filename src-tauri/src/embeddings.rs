@@ -0,0 +1,397 @@
+//! 语义匹配：通过 OpenAI 兼容的 `/v1/embeddings` 接口（本地服务也可以）为题目生成向量，
+//! 题干字符相似度未达到门槛时，按余弦相似度接受语义相近的题目（例如“下列不属于…的是”与“以下哪项不是…”）。
+//!
+//! config.json 中的配置：
+//! ```json
+//! {
+//!   "embeddings": {
+//!     "enabled": true,
+//!     "baseUrl": "http://127.0.0.1:11434/v1",
+//!     "model": "bge-m3",
+//!     "apiKey": "sk-..."
+//!   }
+//! }
+//! ```
+//! 启用后后台任务定期为缺少向量或题干已修改的题目补全向量，保存在 QuestionEmbeddings 表中；
+//! 接口拒绝的题目记录错误后跳过，题干修改或更换模型后才会重试。
+//! 只有没有题目通过字符匹配时，/query 才会请求查询题目的向量；接口不可用时退回纯字符匹配。
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+use crate::database::{
+    count_question_embeddings, load_questions_needing_embeddings, read_config_value,
+    save_question_embeddings, QuestionEmbeddingResult,
+};
+use crate::text_normalize::normalize_text;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct EmbeddingConfig {
+    pub enabled: bool,
+    /// OpenAI 兼容接口的地址，请求发送到 `{baseUrl}/embeddings`
+    pub base_url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub api_key: Option<String>,
+    pub model: String,
+    /// 后台补全时每次请求包含的题目数量
+    pub batch_size: usize,
+    pub timeout_secs: u64,
+    /// 后台补全任务的运行间隔
+    pub backfill_interval_secs: u64,
+}
+
+impl Default for EmbeddingConfig {
+    fn default() -> Self {
+        EmbeddingConfig {
+            enabled: false,
+            base_url: "http://127.0.0.1:11434/v1".to_string(),
+            api_key: None,
+            model: "bge-m3".to_string(),
+            batch_size: 32,
+            timeout_secs: 10,
+            backfill_interval_secs: 300,
+        }
+    }
+}
+
+impl EmbeddingConfig {
+    /// 读取 config.json 中的 embeddings 配置，缺失或格式错误时不启用
+    pub fn load() -> Self {
+        let Some(value) = read_config_value("embeddings") else {
+            return EmbeddingConfig::default();
+        };
+        serde_json::from_value(value).unwrap_or_else(|e| {
+            println!("⚠️ 语义匹配配置格式错误，已忽略: {}", e);
+            EmbeddingConfig::default()
+        })
+    }
+}
+
+/// 查询题目的向量及生成它的模型，只与同一模型生成的题目向量比较
+#[derive(Debug, Clone)]
+pub struct QueryEmbedding {
+    pub model: String,
+    pub vector: Vec<f32>,
+}
+
+#[derive(Deserialize)]
+struct EmbeddingResponse {
+    data: Vec<EmbeddingItem>,
+}
+
+#[derive(Deserialize)]
+struct EmbeddingItem {
+    #[serde(default)]
+    index: usize,
+    embedding: Vec<f32>,
+}
+
+/// 生成向量时使用的文本：与字符匹配相同的规范化结果
+pub fn embedding_input(question: &str) -> String {
+    normalize_text(question)
+}
+
+/// 向量接口请求失败的原因
+#[derive(Debug, Clone, PartialEq)]
+pub enum EmbeddingError {
+    /// 接口无法访问、超时或服务端出错，稍后可以重试
+    Unavailable(String),
+    /// 接口拒绝了输入（4xx）或返回的向量无效，重试同样的输入也不会成功
+    Rejected(String),
+}
+
+impl EmbeddingError {
+    pub fn message(&self) -> &str {
+        match self {
+            EmbeddingError::Unavailable(message) | EmbeddingError::Rejected(message) => message,
+        }
+    }
+}
+
+static HTTP_CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+
+// 所有向量请求共用一个客户端以复用连接，超时按请求设置
+fn http_client() -> &'static reqwest::Client {
+    HTTP_CLIENT.get_or_init(reqwest::Client::new)
+}
+
+/// 调用 embeddings 接口，返回与 inputs 顺序一致的向量
+pub async fn request_embeddings(
+    config: &EmbeddingConfig,
+    inputs: &[String],
+) -> Result<Vec<Vec<f32>>, EmbeddingError> {
+    let url = format!("{}/embeddings", config.base_url.trim_end_matches('/'));
+    let mut request = http_client()
+        .post(&url)
+        .timeout(Duration::from_secs(config.timeout_secs.max(1)))
+        .json(&serde_json::json!({
+            "model": config.model,
+            "input": inputs,
+        }));
+    if let Some(api_key) = config.api_key.as_deref().filter(|key| !key.is_empty()) {
+        request = request.bearer_auth(api_key);
+    }
+
+    let response = request
+        .send()
+        .await
+        .map_err(|e| EmbeddingError::Unavailable(format!("请求向量接口失败: {}", e)))?;
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        let message = format!("向量接口返回 {}: {}", status, body);
+        // 401/403/404/429 多为配置或限流问题，与具体题目无关
+        return Err(
+            if status.is_client_error() && !matches!(status.as_u16(), 401 | 403 | 404 | 429) {
+                EmbeddingError::Rejected(message)
+            } else {
+                EmbeddingError::Unavailable(message)
+            },
+        );
+    }
+
+    let mut parsed: EmbeddingResponse = response
+        .json()
+        .await
+        .map_err(|e| EmbeddingError::Unavailable(format!("解析向量接口响应失败: {}", e)))?;
+    if parsed.data.len() != inputs.len() {
+        return Err(EmbeddingError::Rejected(format!(
+            "向量接口返回 {} 个向量，请求了 {} 个",
+            parsed.data.len(),
+            inputs.len()
+        )));
+    }
+    if parsed.data.iter().any(|item| item.embedding.is_empty()) {
+        return Err(EmbeddingError::Rejected("向量接口返回了空向量".to_string()));
+    }
+    parsed.data.sort_by_key(|item| item.index);
+    Ok(parsed.data.into_iter().map(|item| item.embedding).collect())
+}
+
+/// 语义匹配是否启用
+pub fn embeddings_enabled() -> bool {
+    EmbeddingConfig::load().enabled
+}
+
+/// 为查询题目生成向量；未启用或接口出错时返回 None，查询退回纯字符匹配
+pub async fn embed_query(title: &str) -> Option<QueryEmbedding> {
+    let config = EmbeddingConfig::load();
+    if !config.enabled {
+        return None;
+    }
+    match request_embeddings(&config, &[embedding_input(title)]).await {
+        Ok(mut vectors) => vectors.pop().map(|vector| QueryEmbedding {
+            model: config.model,
+            vector,
+        }),
+        Err(e) => {
+            println!("⚠️ 获取查询向量失败，跳过语义匹配: {}", e.message());
+            None
+        }
+    }
+}
+
+/// 余弦相似度；维度不一致或存在零向量时返回 None
+pub fn cosine_similarity(left: &[f32], right: &[f32]) -> Option<f64> {
+    if left.is_empty() || left.len() != right.len() {
+        return None;
+    }
+    let (mut dot, mut left_norm, mut right_norm) = (0.0f64, 0.0f64, 0.0f64);
+    for (&l, &r) in left.iter().zip(right) {
+        let (l, r) = (l as f64, r as f64);
+        dot += l * r;
+        left_norm += l * l;
+        right_norm += r * r;
+    }
+    if left_norm == 0.0 || right_norm == 0.0 {
+        return None;
+    }
+    Some(dot / (left_norm.sqrt() * right_norm.sqrt()))
+}
+
+/// 向量的存储格式：按 f32 小端字节依次拼接
+pub fn encode_vector(vector: &[f32]) -> Vec<u8> {
+    vector
+        .iter()
+        .flat_map(|value| value.to_le_bytes())
+        .collect()
+}
+
+pub fn decode_vector(bytes: &[u8]) -> Option<Vec<f32>> {
+    let chunks = bytes.chunks_exact(4);
+    if !chunks.remainder().is_empty() {
+        return None;
+    }
+    Some(
+        chunks
+            .map(|chunk| f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+            .collect(),
+    )
+}
+
+/// 语义匹配的配置与向量补全进度
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EmbeddingStatus {
+    pub enabled: bool,
+    pub base_url: String,
+    pub model: String,
+    /// 未删除的题目总数
+    pub total_questions: i64,
+    /// 已有当前模型、当前题干向量的题目数量
+    pub embedded_questions: i64,
+    /// 接口拒绝生成向量的题目数量，题干修改或更换模型后会重试
+    pub failed_questions: i64,
+    pub running: bool,
+    pub last_run_at: Option<DateTime<Utc>>,
+    /// 最近一次补全任务处理的题目数量（含失败的题目）
+    pub last_run_embedded: usize,
+    pub last_error: Option<String>,
+}
+
+#[derive(Default)]
+struct BackfillRecord {
+    last_run_at: Option<DateTime<Utc>>,
+    last_run_embedded: usize,
+    last_error: Option<String>,
+}
+
+static BACKFILL_RUNNING: AtomicBool = AtomicBool::new(false);
+static BACKFILL_RECORD: Mutex<BackfillRecord> = Mutex::new(BackfillRecord {
+    last_run_at: None,
+    last_run_embedded: 0,
+    last_error: None,
+});
+
+pub async fn embedding_status() -> Result<EmbeddingStatus, String> {
+    let config = EmbeddingConfig::load();
+    let model = config.model.clone();
+    let (total_questions, embedded_questions, failed_questions) =
+        tokio::task::spawn_blocking(move || count_question_embeddings(&model))
+            .await
+            .map_err(|e| format!("{}", e))??;
+
+    let record = BACKFILL_RECORD.lock().unwrap();
+    Ok(EmbeddingStatus {
+        enabled: config.enabled,
+        base_url: config.base_url,
+        model: config.model,
+        total_questions,
+        embedded_questions,
+        failed_questions,
+        running: BACKFILL_RUNNING.load(Ordering::SeqCst),
+        last_run_at: record.last_run_at,
+        last_run_embedded: record.last_run_embedded,
+        last_error: record.last_error.clone(),
+    })
+}
+
+/// 为缺少向量或题干已修改的题目补全向量，返回本次处理的题目数量。
+/// 接口拒绝的题目记录错误后跳过；接口不可用时中止，等下一轮再试。同一时间只运行一个补全任务。
+pub async fn run_embedding_backfill() -> Result<usize, String> {
+    let config = EmbeddingConfig::load();
+    if !config.enabled {
+        return Err("语义匹配未启用".to_string());
+    }
+    if BACKFILL_RUNNING.swap(true, Ordering::SeqCst) {
+        return Err("向量补全任务正在运行".to_string());
+    }
+
+    let mut processed = 0;
+    let result = backfill_pending(&config, &mut processed).await;
+    BACKFILL_RUNNING.store(false, Ordering::SeqCst);
+
+    let mut record = BACKFILL_RECORD.lock().unwrap();
+    record.last_run_at = Some(Utc::now());
+    record.last_run_embedded = processed;
+    record.last_error = result.as_ref().err().cloned();
+    result.map(|_| processed)
+}
+
+async fn backfill_pending(config: &EmbeddingConfig, processed: &mut usize) -> Result<(), String> {
+    let batch_size = config.batch_size.max(1);
+    loop {
+        let model = config.model.clone();
+        let pending = tokio::task::spawn_blocking(move || {
+            load_questions_needing_embeddings(&model, batch_size)
+        })
+        .await
+        .map_err(|e| format!("{}", e))??;
+        if pending.is_empty() {
+            return Ok(());
+        }
+
+        let inputs: Vec<String> = pending
+            .iter()
+            .map(|(_, question)| embedding_input(question))
+            .collect();
+        let results = match request_embeddings(config, &inputs).await {
+            Ok(vectors) => vectors.into_iter().map(Ok).collect(),
+            // 整批被拒绝时逐题重试，找出无法生成向量的题目
+            Err(EmbeddingError::Rejected(_)) => {
+                let mut results = Vec::with_capacity(inputs.len());
+                for (input, (id, _)) in inputs.into_iter().zip(&pending) {
+                    match request_embeddings(config, &[input]).await {
+                        Ok(mut vectors) => results.push(Ok(vectors.remove(0))),
+                        Err(EmbeddingError::Rejected(e)) => {
+                            println!("⚠️ 题目 {} 无法生成语义向量: {}", id, e);
+                            results.push(Err(e));
+                        }
+                        Err(EmbeddingError::Unavailable(e)) => return Err(e),
+                    }
+                }
+                results
+            }
+            Err(EmbeddingError::Unavailable(e)) => return Err(e),
+        };
+        let items: Vec<QuestionEmbeddingResult> = pending
+            .into_iter()
+            .zip(results)
+            .map(|((id, question), result)| (id, question, result))
+            .collect();
+        let count = items.len();
+
+        let model = config.model.clone();
+        tokio::task::spawn_blocking(move || save_question_embeddings(&model, &items))
+            .await
+            .map_err(|e| format!("{}", e))??;
+        *processed += count;
+    }
+}
+
+/// 后台补全任务：每隔 backfillIntervalSecs 检查一次，未启用时只等待。
+/// 每轮重新读取配置，修改 config.json 后无需重启。
+pub async fn embedding_backfill_loop() {
+    loop {
+        let config = EmbeddingConfig::load();
+        if config.enabled {
+            match run_embedding_backfill().await {
+                Ok(count) if count > 0 => println!("🧭 已处理 {} 道题目的语义向量", count),
+                Ok(_) => {}
+                Err(e) => println!("⚠️ 语义向量补全失败: {}", e),
+            }
+        }
+        tokio::time::sleep(Duration::from_secs(config.backfill_interval_secs.max(30))).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn embedding_vectors_round_trip_and_compare() {
+        let vector = vec![0.25f32, -1.5, 3.0];
+        assert_eq!(decode_vector(&encode_vector(&vector)), Some(vector.clone()));
+        assert_eq!(decode_vector(&[0, 0, 0]), None);
+
+        let similarity = cosine_similarity(&vector, &vector).expect("same dimension");
+        assert!((similarity - 1.0).abs() < 1e-9);
+        assert_eq!(cosine_similarity(&[1.0, 0.0], &[0.0, 1.0]), Some(0.0));
+        assert_eq!(cosine_similarity(&[1.0, 0.0], &[1.0, 0.0, 0.0]), None);
+        assert_eq!(cosine_similarity(&[0.0, 0.0], &[1.0, 0.0]), None);
+    }
+}
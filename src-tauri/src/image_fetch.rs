@@ -0,0 +1,467 @@
+//! 图片下载：前端显示题目图片（fetch_image_as_base64）与图片感知哈希共用的请求策略和磁盘缓存。
+//! 缓存以 data URL 形式保存在 `image_cache` 目录下，文件名为 URL 的哈希值。
+//! 图片 URL 可能来自 /query 的请求方，默认只允许 http / https，域名解析后拒绝回环、内网与链路本地地址
+//! （重定向后的地址同样检查），并限制图片大小；前端显示图片使用 `FetchPolicy::local()`，不做这些限制。
+
+use base64::{engine::general_purpose, Engine as _};
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+
+static IMAGE_CACHE_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+/// 下载图片时的限制
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FetchPolicy {
+    /// 允许访问回环、内网与链路本地地址
+    pub allow_private_hosts: bool,
+    /// 图片的最大字节数
+    pub max_bytes: u64,
+}
+
+impl Default for FetchPolicy {
+    fn default() -> Self {
+        FetchPolicy {
+            allow_private_hosts: false,
+            max_bytes: 10 * 1024 * 1024,
+        }
+    }
+}
+
+impl FetchPolicy {
+    /// 前端显示题目图片时使用：URL 来自本机界面，允许内网地址，大小上限放宽到 100 MiB
+    pub fn local() -> Self {
+        FetchPolicy {
+            allow_private_hosts: true,
+            max_bytes: 100 * 1024 * 1024,
+        }
+    }
+}
+
+/// 是否为公网地址：回环、内网、链路本地、运营商级 NAT、未指定与广播等地址均不是
+fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => {
+            let [a, b, ..] = v4.octets();
+            !(v4.is_loopback()
+                || v4.is_private()
+                || v4.is_link_local()
+                || v4.is_unspecified()
+                || v4.is_broadcast()
+                || v4.is_documentation()
+                || a == 0
+                || (a == 100 && (64..128).contains(&b)))
+        }
+        IpAddr::V6(v6) => {
+            if let Some(v4) = v6.to_ipv4_mapped() {
+                return is_public_ip(IpAddr::V4(v4));
+            }
+            let first = v6.segments()[0];
+            !(v6.is_loopback()
+                || v6.is_unspecified()
+                || (first & 0xfe00) == 0xfc00
+                || (first & 0xffc0) == 0xfe80)
+        }
+    }
+}
+
+/// 检查 URL 的协议与字面 IP 地址；域名在解析后由 check_resolved_host 与 PublicResolver 检查
+fn check_url(url: &url::Url, policy: &FetchPolicy) -> Result<(), String> {
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(format!("不支持的图片地址协议: {}", url.scheme()));
+    }
+    let ip = match url.host() {
+        Some(url::Host::Ipv4(ip)) => IpAddr::V4(ip),
+        Some(url::Host::Ipv6(ip)) => IpAddr::V6(ip),
+        Some(url::Host::Domain(_)) => return Ok(()),
+        None => return Err("图片地址缺少主机名".to_string()),
+    };
+    if !policy.allow_private_hosts && !is_public_ip(ip) {
+        return Err(format!("不允许访问内网地址: {}", ip));
+    }
+    Ok(())
+}
+
+/// 解析域名并确认所有地址都是公网地址
+async fn check_resolved_host(url: &url::Url, policy: &FetchPolicy) -> Result<(), String> {
+    check_url(url, policy)?;
+    if policy.allow_private_hosts {
+        return Ok(());
+    }
+    let Some(url::Host::Domain(domain)) = url.host() else {
+        return Ok(());
+    };
+    let port = url.port_or_known_default().unwrap_or(80);
+    let addrs: Vec<SocketAddr> = tokio::net::lookup_host((domain, port))
+        .await
+        .map_err(|e| format!("解析图片域名失败: {}", e))?
+        .collect();
+    if addrs.is_empty() {
+        return Err(format!("图片域名没有可用地址: {}", domain));
+    }
+    if let Some(addr) = addrs.iter().find(|addr| !is_public_ip(addr.ip())) {
+        return Err(format!("图片域名 {} 解析到内网地址: {}", domain, addr.ip()));
+    }
+    Ok(())
+}
+
+/// 只返回公网地址的 DNS 解析器，防止重定向或 DNS 重绑定绕过 check_resolved_host
+struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host((name.as_str(), 0))
+                .await?
+                .filter(|addr| is_public_ip(addr.ip()))
+                .collect();
+            if addrs.is_empty() {
+                return Err(format!("{} 没有可访问的公网地址", name.as_str()).into());
+            }
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// 按下载限制配置 HTTP 客户端：重定向最多 10 次，且每次跳转都检查目标地址
+fn client_builder(user_agent: &str, policy: &FetchPolicy) -> reqwest::ClientBuilder {
+    let policy = *policy;
+    let builder = reqwest::Client::builder().user_agent(user_agent).redirect(
+        reqwest::redirect::Policy::custom(move |attempt| {
+            if attempt.previous().len() >= 10 {
+                attempt.error("重定向次数过多")
+            } else if let Err(e) = check_url(attempt.url(), &policy) {
+                attempt.error(e)
+            } else {
+                attempt.follow()
+            }
+        }),
+    );
+    if policy.allow_private_hosts {
+        builder
+    } else {
+        builder.dns_resolver(Arc::new(PublicResolver))
+    }
+}
+
+/// 读取响应内容，超过 max_bytes 时停止读取
+async fn read_limited(mut response: reqwest::Response, max_bytes: u64) -> Result<Vec<u8>, String> {
+    if response
+        .content_length()
+        .is_some_and(|length| length > max_bytes)
+    {
+        return Err(format!("图片超过大小限制 ({} bytes)", max_bytes));
+    }
+    let mut bytes = Vec::new();
+    while let Some(chunk) = response.chunk().await.map_err(|e| e.to_string())? {
+        if (bytes.len() + chunk.len()) as u64 > max_bytes {
+            return Err(format!("图片超过大小限制 ({} bytes)", max_bytes));
+        }
+        bytes.extend_from_slice(&chunk);
+    }
+    Ok(bytes)
+}
+
+/// 应用启动时设置图片缓存目录，后台任务与 /query 获取图片时使用
+pub fn set_image_cache_dir(dir: PathBuf) {
+    let _ = IMAGE_CACHE_DIR.set(dir);
+}
+
+/// 未设置时（如测试中）不使用磁盘缓存
+pub fn image_cache_dir() -> Option<&'static Path> {
+    IMAGE_CACHE_DIR.get().map(PathBuf::as_path)
+}
+
+fn cache_file(cache_dir: &Path, url: &str) -> PathBuf {
+    // 用 URL 的哈希值作为文件名
+    let mut hasher = DefaultHasher::new();
+    url.hash(&mut hasher);
+    cache_dir.join(format!("{}.b64", hasher.finish()))
+}
+
+/// 获取图片并转换为 data URL，依次尝试多种请求策略；cache_dir 不为空时先查磁盘缓存，成功后写入缓存
+pub async fn fetch_image_data_url(
+    cache_dir: Option<&Path>,
+    url: &str,
+    policy: &FetchPolicy,
+) -> Result<String, String> {
+    // --- 磁盘缓存 ---
+    let cache_file = cache_dir.map(|dir| {
+        let _ = std::fs::create_dir_all(dir);
+        cache_file(dir, url)
+    });
+
+    // 命中缓存直接返回
+    if let Some(cached) = cache_file
+        .as_ref()
+        .and_then(|file| std::fs::read_to_string(file).ok())
+    {
+        if !cached.is_empty() {
+            return Ok(cached);
+        }
+    }
+
+    let parsed_url = url::Url::parse(url).map_err(|e| format!("URL解析失败: {}", e))?;
+    check_resolved_host(&parsed_url, policy).await?;
+
+    println!("🖼️ 开始获取图片: {}", url);
+
+    // 尝试多种请求策略
+    let strategies = vec![
+        ("完整浏览器伪装", create_full_browser_request(url, policy)?),
+        ("简化请求头", create_simple_request(url, policy)?),
+        ("移动端伪装", create_mobile_request(url, policy)?),
+    ];
+
+    for (strategy_name, request) in strategies {
+        println!("🔄 尝试策略: {}", strategy_name);
+
+        match request.send().await {
+            Ok(response) => {
+                println!("📊 响应状态: {}", response.status());
+
+                if response.status().is_success() {
+                    match read_limited(response, policy.max_bytes).await {
+                        Ok(bytes) => {
+                            // 转换为base64
+                            let base64_string = general_purpose::STANDARD.encode(&bytes);
+
+                            // 检测图片类型
+                            let content_type = detect_image_type(&bytes);
+                            let data_url =
+                                format!("data:{};base64,{}", content_type, base64_string);
+
+                            println!(
+                                "✅ 图片获取成功 ({}), 大小: {} bytes",
+                                strategy_name,
+                                bytes.len()
+                            );
+                            // 写入磁盘缓存
+                            if let Some(cache_file) = &cache_file {
+                                let _ = std::fs::write(cache_file, &data_url);
+                            }
+                            return Ok(data_url);
+                        }
+                        Err(e) => {
+                            println!("❌ 读取图片数据失败 ({}): {}", strategy_name, e);
+                            continue;
+                        }
+                    }
+                } else {
+                    println!(
+                        "❌ HTTP请求失败 ({}): {} {}",
+                        strategy_name,
+                        response.status(),
+                        response.status().canonical_reason().unwrap_or("Unknown")
+                    );
+                    continue;
+                }
+            }
+            Err(e) => {
+                println!("❌ 网络请求失败 ({}): {}", strategy_name, e);
+                continue;
+            }
+        }
+    }
+
+    Err("所有请求策略都失败了".to_string())
+}
+
+/// 获取图片的原始字节（经过与 fetch_image_data_url 相同的缓存）
+pub async fn fetch_image_bytes(
+    cache_dir: Option<&Path>,
+    url: &str,
+    policy: &FetchPolicy,
+) -> Result<Vec<u8>, String> {
+    let data_url = fetch_image_data_url(cache_dir, url, policy).await?;
+    decode_data_url(&data_url).ok_or_else(|| "图片缓存格式错误".to_string())
+}
+
+fn decode_data_url(data_url: &str) -> Option<Vec<u8>> {
+    let (_, encoded) = data_url.split_once(";base64,")?;
+    general_purpose::STANDARD.decode(encoded).ok()
+}
+
+// 创建完整浏览器伪装请求
+fn create_full_browser_request(
+    url: &str,
+    policy: &FetchPolicy,
+) -> Result<reqwest::RequestBuilder, String> {
+    let client = client_builder("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36", policy)
+        .cookie_store(true)
+        .build()
+        .map_err(|e| format!("创建HTTP客户端失败: {}", e))?;
+
+    let parsed_url = url::Url::parse(url).map_err(|e| format!("URL解析失败: {}", e))?;
+    let domain = parsed_url.host_str().unwrap_or("www.google.com");
+    let referer = if domain.contains("chaoxing.com") {
+        "https://mooc1-1.chaoxing.com/"
+    } else if domain.contains("zhihuishu.com") {
+        "https://www.zhihuishu.com/"
+    } else {
+        "https://www.google.com/"
+    };
+
+    Ok(client
+        .get(url)
+        .header(
+            "Accept",
+            "image/webp,image/apng,image/svg+xml,image/*,*/*;q=0.8",
+        )
+        .header(
+            "Accept-Language",
+            "zh-CN,zh;q=0.9,en;q=0.8,en-GB;q=0.7,en-US;q=0.6",
+        )
+        .header("Accept-Encoding", "gzip, deflate, br")
+        .header("Cache-Control", "no-cache")
+        .header("Pragma", "no-cache")
+        .header(
+            "Sec-Ch-Ua",
+            "\"Not_A Brand\";v=\"8\", \"Chromium\";v=\"120\", \"Google Chrome\";v=\"120\"",
+        )
+        .header("Sec-Ch-Ua-Mobile", "?0")
+        .header("Sec-Ch-Ua-Platform", "\"Windows\"")
+        .header("Sec-Fetch-Dest", "image")
+        .header("Sec-Fetch-Mode", "no-cors")
+        .header("Sec-Fetch-Site", "cross-site")
+        .header("Upgrade-Insecure-Requests", "1")
+        .header("DNT", "1")
+        .header("Connection", "keep-alive")
+        .header("Referer", referer)
+        .timeout(std::time::Duration::from_secs(30)))
+}
+
+// 创建简化请求
+fn create_simple_request(
+    url: &str,
+    policy: &FetchPolicy,
+) -> Result<reqwest::RequestBuilder, String> {
+    let client = client_builder(
+        "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36",
+        policy,
+    )
+    .build()
+    .map_err(|e| format!("创建HTTP客户端失败: {}", e))?;
+
+    Ok(client
+        .get(url)
+        .header("Accept", "image/*,*/*;q=0.8")
+        .header("Accept-Language", "zh-CN,zh;q=0.9")
+        .timeout(std::time::Duration::from_secs(30)))
+}
+
+// 创建移动端伪装请求
+fn create_mobile_request(
+    url: &str,
+    policy: &FetchPolicy,
+) -> Result<reqwest::RequestBuilder, String> {
+    let client = client_builder("Mozilla/5.0 (iPhone; CPU iPhone OS 17_0 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/17.0 Mobile/15E148 Safari/604.1", policy)
+        .build()
+        .map_err(|e| format!("创建HTTP客户端失败: {}", e))?;
+
+    Ok(client
+        .get(url)
+        .header("Accept", "image/webp,image/apng,image/*,*/*;q=0.8")
+        .header("Accept-Language", "zh-CN,zh-Hans;q=0.9")
+        .header("Accept-Encoding", "gzip, deflate, br")
+        .timeout(std::time::Duration::from_secs(30)))
+}
+
+// 检测图片类型
+fn detect_image_type(bytes: &[u8]) -> &'static str {
+    if bytes.len() < 4 {
+        return "image/png"; // 默认类型
+    }
+
+    // PNG: 89 50 4E 47
+    if bytes[0] == 0x89 && bytes[1] == 0x50 && bytes[2] == 0x4E && bytes[3] == 0x47 {
+        return "image/png";
+    }
+
+    // JPEG: FF D8 FF
+    if bytes[0] == 0xFF && bytes[1] == 0xD8 && bytes[2] == 0xFF {
+        return "image/jpeg";
+    }
+
+    // GIF: 47 49 46 38
+    if bytes[0] == 0x47 && bytes[1] == 0x49 && bytes[2] == 0x46 && bytes[3] == 0x38 {
+        return "image/gif";
+    }
+
+    // WebP: 52 49 46 46 (RIFF) + WebP signature
+    if bytes.len() >= 12
+        && bytes[0] == 0x52
+        && bytes[1] == 0x49
+        && bytes[2] == 0x46
+        && bytes[3] == 0x46
+        && bytes[8] == 0x57
+        && bytes[9] == 0x45
+        && bytes[10] == 0x42
+        && bytes[11] == 0x50
+    {
+        return "image/webp";
+    }
+
+    // BMP: 42 4D
+    if bytes[0] == 0x42 && bytes[1] == 0x4D {
+        return "image/bmp";
+    }
+
+    // 默认返回PNG
+    "image/png"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn private_and_non_http_urls_are_rejected() {
+        let policy = FetchPolicy::default();
+        let check = |url: &str| check_url(&url::Url::parse(url).unwrap(), &policy);
+        assert!(check("https://img.example.com/a.png").is_ok());
+        assert!(check("http://93.184.216.34/a.png").is_ok());
+        for url in [
+            "file:///etc/passwd",
+            "ftp://example.com/a.png",
+            "http://127.0.0.1:8080/a.png",
+            "http://10.1.2.3/a.png",
+            "http://192.168.0.1/a.png",
+            "http://169.254.169.254/latest/meta-data",
+            "http://100.64.0.1/a.png",
+            "http://0.0.0.0/a.png",
+            "http://[::1]/a.png",
+            "http://[fd00::1]/a.png",
+            "http://[fe80::1]/a.png",
+            "http://[::ffff:127.0.0.1]/a.png",
+        ] {
+            assert!(check(url).is_err(), "{} 应被拒绝", url);
+        }
+
+        let allow_private = FetchPolicy {
+            allow_private_hosts: true,
+            ..FetchPolicy::default()
+        };
+        assert!(check_url(
+            &url::Url::parse("http://127.0.0.1/a.png").unwrap(),
+            &allow_private
+        )
+        .is_ok());
+        assert!(check_url(&url::Url::parse("file:///a.png").unwrap(), &allow_private).is_err());
+    }
+
+    #[tokio::test]
+    async fn localhost_is_rejected_after_resolution() {
+        let url = url::Url::parse("http://localhost:9/a.png").unwrap();
+        assert!(check_resolved_host(&url, &FetchPolicy::default())
+            .await
+            .is_err());
+        // 前端显示图片的策略允许本机与内网地址
+        assert!(check_resolved_host(&url, &FetchPolicy::local())
+            .await
+            .is_ok());
+    }
+}
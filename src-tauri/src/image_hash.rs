@@ -0,0 +1,345 @@
+//! 图片感知哈希：平台重新上传同一张图片后 URL 会变化，改为比较图片内容的 dHash，
+//! 汉明距离不超过匹配配置档 maxImageHashDistance 的两张图片视为同一张。
+//!
+//! config.json 中的配置：
+//! ```json
+//! { "imageHashing": { "enabled": true, "timeoutSecs": 10, "maxImageBytes": 10485760 } }
+//! ```
+//! 启用后后台任务为题库中含图片的题目下载图片并计算哈希，保存在 QuestionImageHashes 表中；
+//! /query 中的图片在查询时并发下载（与前端共用图片缓存），全部图片共用 timeoutSecs 的时限。
+//! 默认不下载回环、内网与链路本地地址的图片，图床部署在内网时可设置 allowPrivateHosts。
+//! 目前支持 PNG 与 JPEG。
+
+use chrono::{DateTime, Utc};
+use futures_util::stream::{FuturesUnordered, StreamExt};
+use image::imageops::FilterType;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::database::{
+    count_image_hashes, load_pending_image_hashes, read_config_value, save_image_hashes,
+};
+use crate::image_fetch::{fetch_image_bytes, image_cache_dir, FetchPolicy};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ImageHashConfig {
+    pub enabled: bool,
+    /// 单张图片下载与计算的超时时间；/query 中的全部图片共用这一时限
+    pub timeout_secs: u64,
+    /// 后台任务每批处理的图片数量
+    pub batch_size: usize,
+    pub backfill_interval_secs: u64,
+    /// 允许下载回环、内网与链路本地地址的图片
+    pub allow_private_hosts: bool,
+    /// 图片的最大字节数，超过时视为下载失败
+    pub max_image_bytes: u64,
+}
+
+impl Default for ImageHashConfig {
+    fn default() -> Self {
+        ImageHashConfig {
+            enabled: false,
+            timeout_secs: 10,
+            batch_size: 16,
+            backfill_interval_secs: 600,
+            allow_private_hosts: false,
+            max_image_bytes: FetchPolicy::default().max_bytes,
+        }
+    }
+}
+
+impl ImageHashConfig {
+    /// 读取 config.json 中的 imageHashing 配置，缺失或格式错误时不启用
+    pub fn load() -> Self {
+        let Some(value) = read_config_value("imageHashing") else {
+            return ImageHashConfig::default();
+        };
+        serde_json::from_value(value).unwrap_or_else(|e| {
+            println!("⚠️ 图片哈希配置格式错误，已忽略: {}", e);
+            ImageHashConfig::default()
+        })
+    }
+
+    pub fn fetch_policy(&self) -> FetchPolicy {
+        FetchPolicy {
+            allow_private_hosts: self.allow_private_hosts,
+            max_bytes: self.max_image_bytes,
+        }
+    }
+}
+
+/// 64 位 dHash：缩放为 9×8 灰度图后比较每行相邻像素的亮度
+pub fn dhash(bytes: &[u8]) -> Result<u64, String> {
+    let image = image::load_from_memory(bytes).map_err(|e| format!("解析图片失败: {}", e))?;
+    let small = image
+        .grayscale()
+        .resize_exact(9, 8, FilterType::Triangle)
+        .to_luma8();
+
+    let mut hash = 0u64;
+    for y in 0..8 {
+        for x in 0..8 {
+            hash <<= 1;
+            if small.get_pixel(x, y)[0] < small.get_pixel(x + 1, y)[0] {
+                hash |= 1;
+            }
+        }
+    }
+    Ok(hash)
+}
+
+pub fn hamming_distance(left: u64, right: u64) -> u32 {
+    (left ^ right).count_ones()
+}
+
+/// 下载图片并计算 dHash
+pub async fn hash_image_url(url: &str, config: &ImageHashConfig) -> Result<u64, String> {
+    let bytes = tokio::time::timeout(
+        Duration::from_secs(config.timeout_secs.max(1)),
+        fetch_image_bytes(image_cache_dir(), url, &config.fetch_policy()),
+    )
+    .await
+    .map_err(|_| "下载图片超时".to_string())??;
+    tokio::task::spawn_blocking(move || dhash(&bytes))
+        .await
+        .map_err(|e| format!("{}", e))?
+}
+
+/// 计算查询题目中各图片的哈希，键为规范 URL；未启用或图片无法获取时不包含该图片。
+/// urls 为 (规范 URL, 原始 URL)，下载使用原始 URL（可能带有签名参数）。
+pub async fn hash_query_images(urls: &[(String, String)]) -> HashMap<String, u64> {
+    let mut hashes = HashMap::new();
+    if urls.is_empty() {
+        return hashes;
+    }
+    let config = ImageHashConfig::load();
+    if !config.enabled {
+        return hashes;
+    }
+    hash_images_within_deadline(urls, &config, &mut hashes).await;
+    hashes
+}
+
+/// 并发下载并计算各图片的哈希，全部图片共用 timeoutSecs 的时限，超时未完成的图片不包含在结果中
+async fn hash_images_within_deadline(
+    urls: &[(String, String)],
+    config: &ImageHashConfig,
+    hashes: &mut HashMap<String, u64>,
+) {
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(config.timeout_secs.max(1));
+    let mut pending: FuturesUnordered<_> = urls
+        .iter()
+        .map(|(canonical, raw)| async move { (canonical, raw, hash_image_url(raw, config).await) })
+        .collect();
+    loop {
+        match tokio::time::timeout_at(deadline, pending.next()).await {
+            Ok(Some((canonical, _, Ok(hash)))) => {
+                hashes.insert(canonical.clone(), hash);
+            }
+            Ok(Some((_, raw, Err(e)))) => println!("⚠️ 计算查询图片哈希失败 {}: {}", raw, e),
+            Ok(None) => break,
+            Err(_) => {
+                println!("⚠️ 计算查询图片哈希超时，跳过 {} 张图片", pending.len());
+                break;
+            }
+        }
+    }
+}
+
+/// 图片哈希的配置与补全进度
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ImageHashStatus {
+    pub enabled: bool,
+    /// 含图片的题目数量
+    pub image_questions: i64,
+    pub hashed_images: i64,
+    /// 下载或解析失败的图片，一天后重试
+    pub failed_images: i64,
+    pub running: bool,
+    pub last_run_at: Option<DateTime<Utc>>,
+    /// 最近一次补全任务处理的图片数量（含失败）
+    pub last_run_processed: usize,
+    pub last_error: Option<String>,
+}
+
+struct BackfillRecord {
+    last_run_at: Option<DateTime<Utc>>,
+    last_run_processed: usize,
+    last_error: Option<String>,
+}
+
+static BACKFILL_RUNNING: AtomicBool = AtomicBool::new(false);
+static BACKFILL_RECORD: Mutex<BackfillRecord> = Mutex::new(BackfillRecord {
+    last_run_at: None,
+    last_run_processed: 0,
+    last_error: None,
+});
+
+pub async fn image_hash_status() -> Result<ImageHashStatus, String> {
+    let config = ImageHashConfig::load();
+    let (image_questions, hashed_images, failed_images) =
+        tokio::task::spawn_blocking(count_image_hashes)
+            .await
+            .map_err(|e| format!("{}", e))??;
+
+    let record = BACKFILL_RECORD.lock().unwrap();
+    Ok(ImageHashStatus {
+        enabled: config.enabled,
+        image_questions,
+        hashed_images,
+        failed_images,
+        running: BACKFILL_RUNNING.load(Ordering::SeqCst),
+        last_run_at: record.last_run_at,
+        last_run_processed: record.last_run_processed,
+        last_error: record.last_error.clone(),
+    })
+}
+
+/// 为题库中尚未计算哈希的图片下载并计算哈希，返回本次处理的图片数量。
+/// 单张图片失败时记录错误并继续处理其他图片。同一时间只运行一个补全任务。
+pub async fn run_image_hash_backfill() -> Result<usize, String> {
+    let config = ImageHashConfig::load();
+    if !config.enabled {
+        return Err("图片哈希未启用".to_string());
+    }
+    if BACKFILL_RUNNING.swap(true, Ordering::SeqCst) {
+        return Err("图片哈希补全任务正在运行".to_string());
+    }
+
+    let mut processed = 0;
+    let result = backfill_pending(&config, &mut processed).await;
+    BACKFILL_RUNNING.store(false, Ordering::SeqCst);
+
+    let mut record = BACKFILL_RECORD.lock().unwrap();
+    record.last_run_at = Some(Utc::now());
+    record.last_run_processed = processed;
+    record.last_error = result.as_ref().err().cloned();
+    result.map(|_| processed)
+}
+
+async fn backfill_pending(config: &ImageHashConfig, processed: &mut usize) -> Result<(), String> {
+    let batch_size = config.batch_size.max(1);
+    loop {
+        let pending = tokio::task::spawn_blocking(move || load_pending_image_hashes(batch_size))
+            .await
+            .map_err(|e| format!("{}", e))??;
+        if pending.is_empty() {
+            return Ok(());
+        }
+
+        let mut results = Vec::with_capacity(pending.len());
+        for (question_id, canonical, raw) in pending {
+            let hash = hash_image_url(&raw, config).await;
+            if let Err(e) = &hash {
+                println!("⚠️ 计算题目 {} 图片哈希失败 {}: {}", question_id, raw, e);
+            }
+            results.push((question_id, canonical, hash));
+        }
+        let count = results.len();
+
+        tokio::task::spawn_blocking(move || save_image_hashes(&results))
+            .await
+            .map_err(|e| format!("{}", e))??;
+        *processed += count;
+    }
+}
+
+/// 后台补全任务：每隔 backfillIntervalSecs 检查一次，未启用时只等待
+pub async fn image_hash_backfill_loop() {
+    loop {
+        let config = ImageHashConfig::load();
+        if config.enabled {
+            match run_image_hash_backfill().await {
+                Ok(count) if count > 0 => println!("🖼️ 已为 {} 张题目图片计算哈希", count),
+                Ok(_) => {}
+                Err(e) => println!("⚠️ 图片哈希补全失败: {}", e),
+            }
+        }
+        tokio::time::sleep(Duration::from_secs(config.backfill_interval_secs.max(30))).await;
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use warp::Filter;
+
+    /// 灰度波纹测试图片，invert 时明暗反转
+    pub(crate) fn encode_test_image(
+        size: u32,
+        invert: bool,
+        format: image::ImageFormat,
+    ) -> Vec<u8> {
+        let image = image::RgbImage::from_fn(size, size, |x, y| {
+            let fx = x as f64 * 64.0 / size as f64;
+            let fy = y as f64 * 64.0 / size as f64;
+            let value = (128.0 + 100.0 * (fx / 9.0).sin() * (fy / 13.0).cos()) as u8;
+            let value = if invert { 255 - value } else { value };
+            image::Rgb([value, value, value])
+        });
+        let mut bytes = std::io::Cursor::new(Vec::new());
+        image.write_to(&mut bytes, format).expect("encode image");
+        bytes.into_inner()
+    }
+
+    #[test]
+    fn resized_reuploads_hash_close_to_the_original() {
+        let original = encode_test_image(64, false, image::ImageFormat::Png);
+        let reuploaded = encode_test_image(48, false, image::ImageFormat::Jpeg);
+        let different = encode_test_image(64, true, image::ImageFormat::Png);
+        let original_hash = dhash(&original).expect("hash original");
+        assert!(hamming_distance(original_hash, dhash(&reuploaded).expect("hash reupload")) <= 6);
+        assert!(hamming_distance(original_hash, dhash(&different).expect("hash different")) > 20);
+    }
+
+    #[tokio::test]
+    async fn query_images_share_one_deadline() {
+        let mut png = std::io::Cursor::new(Vec::new());
+        image::RgbImage::from_fn(16, 16, |x, y| {
+            image::Rgb([(x * 16) as u8, (y * 16) as u8, 0])
+        })
+        .write_to(&mut png, image::ImageFormat::Png)
+        .expect("encode image");
+        let png = png.into_inner();
+
+        // slow 开头的图片在时限内无法下载完成
+        let routes = warp::path::param::<String>().then(move |name: String| {
+            let png = png.clone();
+            async move {
+                if name.starts_with("slow") {
+                    tokio::time::sleep(Duration::from_secs(30)).await;
+                }
+                png
+            }
+        });
+        let (addr, server) = warp::serve(routes).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+        let urls: Vec<(String, String)> = ["fast.png", "slow-1.png", "slow-2.png"]
+            .iter()
+            .map(|name| (name.to_string(), format!("http://{}/{}", addr, name)))
+            .collect();
+
+        let config = ImageHashConfig {
+            enabled: true,
+            timeout_secs: 2,
+            allow_private_hosts: true,
+            ..ImageHashConfig::default()
+        };
+        let started = std::time::Instant::now();
+        let mut hashes = HashMap::new();
+        hash_images_within_deadline(&urls, &config, &mut hashes).await;
+        assert!(started.elapsed() < Duration::from_secs(4));
+        assert_eq!(hashes.keys().collect::<Vec<_>>(), vec!["fast.png"]);
+
+        // 默认不下载本机地址的图片
+        let strict = ImageHashConfig {
+            allow_private_hosts: false,
+            ..config
+        };
+        assert!(hash_image_url(&urls[0].1, &strict).await.is_err());
+    }
+}
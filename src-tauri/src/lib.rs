@@ -2,11 +2,26 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 // 模块声明
+pub mod answer_extraction;
+pub mod answer_format;
+pub mod answer_validation;
 pub mod commands;
 pub mod database;
+pub mod embeddings;
+pub mod image_fetch;
+pub mod image_hash;
 pub mod logger;
+pub mod matching_profile;
+pub mod prompt_template;
+pub mod question_kind;
+pub mod question_options;
+pub mod ranking_policy;
+pub mod search_query;
+pub mod segmenter;
 pub mod server;
+pub mod text_normalize;
 pub mod types;
+pub mod url_canonical;
 pub mod window_size;
 
 use crate::window_size::{resolve_window_size, MAIN_WINDOW_PRESET};
@@ -16,14 +31,18 @@ pub use commands::{
     get_daily_request_counts, get_request_logs, get_username, greet, open_cache_dir, open_devtools,
     open_url_content_window, read_config, read_doc_range, read_docx_range, read_excel_headers,
     read_excel_range, read_file_bytes, read_file_range, read_file_text, read_model_config,
-    request_admin_elevation, segment_text, write_config, write_model_config
+    request_admin_elevation, segment_text, get_segmenter_status, reload_segmenter_config,
+    get_embedding_status, start_embedding_backfill, get_image_hash_status,
+    start_image_hash_backfill, write_config, write_model_config
 };
 pub use database::*;
 pub use database::{
     clear_folder_questions, delete_folder, delete_question, delete_questions, move_folder,
     rename_folder,
 };
-pub use server::{get_server_status, start_server, stop_server};
+pub use server::{
+    get_server_status, preview_query_prompt, resolve_model_prompt, start_server, stop_server,
+};
 use tauri::Manager;
 pub use types::*;
 
@@ -90,6 +109,12 @@ pub fn run() {
             read_file_range,
             convert_doc_to_docx,
             segment_text,
+            get_segmenter_status,
+            reload_segmenter_config,
+            get_embedding_status,
+            start_embedding_backfill,
+            get_image_hash_status,
+            start_image_hash_backfill,
             read_config,
             write_config,
             read_model_config,
@@ -106,7 +131,9 @@ pub fn run() {
             get_folder_path,
             get_folder_stats,
             add_question,
+            import_questions,
             set_question_pending_correction,
+            set_question_verified,
             update_question,
             move_question,
             copy_question,
@@ -116,7 +143,31 @@ pub fn run() {
             clear_folder_questions,
             delete_folder,
             rename_folder,
-            move_folder
+            move_folder,
+            get_trash_questions,
+            restore_questions,
+            purge_questions,
+            empty_trash,
+            get_question_revisions,
+            diff_question_revisions,
+            rollback_question_revision,
+            get_folder_stats_by_tags,
+            get_tags,
+            get_question_tags,
+            add_tag,
+            rename_tag,
+            delete_tag,
+            tag_questions,
+            untag_questions,
+            tag_search_results,
+            untag_search_results,
+            search_questions_paginated,
+            explain_query_match,
+            get_matching_profiles,
+            get_question_raw_html,
+            rebuild_canonical_urls,
+            preview_query_prompt,
+            resolve_model_prompt
         ])
         .setup(|app| {
             // 初始化 ServerState 并注入 app_handle
@@ -193,6 +244,18 @@ pub fn run() {
                 }
             }
 
+            // 定期清理超过保留天数的回收站题目
+            tauri::async_runtime::spawn(crate::database::trash_purge_loop());
+
+            // 语义匹配向量的后台补全任务，未启用时只定期检查配置
+            tauri::async_runtime::spawn(crate::embeddings::embedding_backfill_loop());
+
+            // 图片哈希与前端共用图片缓存目录
+            if let Ok(dir) = app.path().app_local_data_dir() {
+                crate::image_fetch::set_image_cache_dir(dir.join("image_cache"));
+            }
+            tauri::async_runtime::spawn(crate::image_hash::image_hash_backfill_loop());
+
             let is_dev = cfg!(debug_assertions);
             let url = if is_dev {
                 tauri::WebviewUrl::External("http://localhost:1420".parse().unwrap())
@@ -21,6 +21,8 @@ pub struct RequestLog {
     pub ip: Option<String>,
     pub user_agent: Option<String>,
     pub stage: String, // "started" 或 "completed"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub matching_profile: Option<String>, // 题库查询完成时使用的匹配配置档
 }
 
 // 新增：模型调用请求事件
@@ -28,6 +30,9 @@ pub struct RequestLog {
 pub struct ModelCallRequest {
     pub request_id: String, // 关联的请求ID
     pub query: String,      // 需要查询的内容
+    // 按模型配置的提示词（键为模型名规则），前端按实际调用的模型选用，未匹配时使用 query
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub model_queries: HashMap<String, String>,
     pub timestamp: DateTime<Utc>,
 }
 
@@ -46,10 +51,19 @@ pub struct ModelCallResponse {
     pub content: String,    // 模型返回的内容
     #[serde(skip_serializing_if = "Option::is_none")]
     pub reasoning_content: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>, // 给出答案的模型
     pub is_success: bool,
     pub timestamp: DateTime<Utc>,
 }
 
+// 等待到的模型最终响应
+#[derive(Debug, Clone)]
+pub struct ModelReply {
+    pub content: String,
+    pub model: Option<String>,
+}
+
 // 统一的SSE事件类型
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type")]
@@ -69,7 +83,7 @@ pub struct RequestLogger {
     logs: Arc<Mutex<VecDeque<RequestLog>>>,
     max_logs: usize,
     broadcaster: broadcast::Sender<SSEEvent>, // 修改为SSEEvent类型
-    pending_responses: Arc<Mutex<HashMap<String, tokio::sync::oneshot::Sender<String>>>>, // 等待模型响应的通道
+    pending_responses: Arc<Mutex<HashMap<String, tokio::sync::oneshot::Sender<ModelReply>>>>, // 等待模型响应的通道
     pending_error_responses: Arc<Mutex<HashMap<String, (String, std::time::Instant)>>>, // 暂存错误响应，给成功回调留一个兜底窗口
 }
 
@@ -125,6 +139,7 @@ impl RequestLogger {
             ip,
             user_agent,
             stage: "started".to_string(),
+            matching_profile: None,
         };
 
         let mut logs = self.logs.lock().unwrap();
@@ -172,8 +187,40 @@ impl RequestLogger {
             ip: None,
             user_agent: None,
             stage: "completed".to_string(),
+            matching_profile: None,
+        };
+        self.record_request_complete(log);
+    }
+
+    // 记录题库查询完成，附带本次使用的匹配配置档
+    pub fn log_query_complete(
+        &self,
+        id: String,
+        method: String,
+        status: u16,
+        response_time: u64,
+        response_body: Option<String>,
+        matching_profile: Option<String>,
+    ) {
+        let log = RequestLog {
+            id,
+            timestamp: Utc::now(),
+            method,
+            path: "/query".to_string(),
+            status: Some(status),
+            response_time: Some(response_time),
+            request_body: None,
+            response_body,
+            headers: None,
+            ip: None,
+            user_agent: None,
+            stage: "completed".to_string(),
+            matching_profile,
         };
+        self.record_request_complete(log);
+    }
 
+    fn record_request_complete(&self, log: RequestLog) {
         let mut logs = self.logs.lock().unwrap();
         logs.push_back(log.clone());
 
@@ -222,6 +269,7 @@ impl RequestLogger {
             ip,
             user_agent,
             stage: "completed".to_string(), // 一次性记录视为已完成
+            matching_profile: None,
         };
 
         let mut logs = self.logs.lock().unwrap();
@@ -247,10 +295,16 @@ impl RequestLogger {
     }
 
     // 新增：发送模型调用请求事件
-    pub fn send_model_call_request(&self, request_id: String, query: String) {
+    pub fn send_model_call_request(
+        &self,
+        request_id: String,
+        query: String,
+        model_queries: HashMap<String, String>,
+    ) {
         let event = ModelCallRequest {
             request_id,
             query,
+            model_queries,
             timestamp: Utc::now(),
         };
 
@@ -288,6 +342,7 @@ impl RequestLogger {
         request_id: String,
         content: String,
         reasoning_content: Option<String>,
+        model: Option<String>,
         is_success: bool,
     ) {
         if is_success {
@@ -301,7 +356,10 @@ impl RequestLogger {
                 let mut pending = self.pending_responses.lock().unwrap();
                 pending.remove(&request_id)
             } {
-                let _ = sender.send(content.clone());
+                let _ = sender.send(ModelReply {
+                    content: content.clone(),
+                    model: model.clone(),
+                });
             }
         } else {
             // 错误响应先暂存，给其他可能成功的消费者留出覆盖窗口
@@ -315,6 +373,7 @@ impl RequestLogger {
             request_id,
             content,
             reasoning_content,
+            model,
             is_success,
             timestamp: Utc::now(),
         };
@@ -352,7 +411,7 @@ impl RequestLogger {
         &self,
         request_id: String,
         inactivity_seconds: u64,
-    ) -> Result<String, String> {
+    ) -> Result<ModelReply, String> {
         let (sender, mut final_receiver) = tokio::sync::oneshot::channel();
 
         // 注册等待最终响应的通道
@@ -373,9 +432,9 @@ impl RequestLogger {
                 // 最终成功响应到达
                 res = &mut final_receiver => {
                     match res {
-                        Ok(content) => {
+                        Ok(reply) => {
                             self.pending_error_responses.lock().unwrap().remove(&request_id);
-                            return Ok(content)
+                            return Ok(reply)
                         },
                         Err(_) => {
                             let mut pending = self.pending_responses.lock().unwrap();
@@ -410,7 +469,10 @@ impl RequestLogger {
                         if error_at.elapsed() >= error_grace && last_activity.elapsed() >= error_grace {
                             self.pending_error_responses.lock().unwrap().remove(&request_id);
                             self.pending_responses.lock().unwrap().remove(&request_id);
-                            return Ok(error_content);
+                            return Ok(ModelReply {
+                                content: error_content,
+                                model: None,
+                            });
                         }
                     }
 
@@ -0,0 +1,267 @@
+//! 题库匹配配置档：把 compute_query_match_score / query_database 中的阈值与权重集中管理。
+//!
+//! 内置 default / strict / loose 三个预设，config.json 中可以覆盖或新增：
+//! ```json
+//! {
+//!   "matchingProfiles": { "course-a": { "minCharSimilarity": 0.8, "resultLimit": 20 } },
+//!   "defaultMatchingProfile": "course-a",
+//!   "folderMatchingProfiles": { "12": "strict" }
+//! }
+//! ```
+//! 自定义配置档中未填写的字段取 default 预设的值。
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::database::{read_config_value, ConfigCache};
+
+pub const DEFAULT_PROFILE_NAME: &str = "default";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CoverageTier {
+    /// 查询关键词数量不超过该值时适用
+    pub max_keywords: usize,
+    pub min_coverage: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct MatchingProfile {
+    /// 题干最低字符相似度（归一化 Levenshtein），低于该值直接淘汰
+    pub min_char_similarity: f64,
+    /// 字符相似度达到该值且关键词不多于 high_similarity_max_keywords 时，要求关键词全部命中
+    pub high_similarity: f64,
+    pub high_similarity_max_keywords: usize,
+    /// 按关键词数量从少到多排列的覆盖率要求
+    pub coverage_tiers: Vec<CoverageTier>,
+    /// 关键词数量超过所有档位时的覆盖率要求
+    pub default_min_coverage: f64,
+    /// 题干得分中字符相似度的权重，其余为关键词覆盖率
+    pub char_similarity_weight: f64,
+    /// 有选项时最终得分中题干的权重，其余为选项得分
+    pub title_weight: f64,
+    /// 单次查询最多返回的匹配数量
+    pub result_limit: usize,
+    /// 启用语义匹配时，题干未通过字符匹配的候选至少需要的向量余弦相似度
+    pub min_semantic_similarity: f64,
+    /// 启用图片哈希时，两张图片的 dHash 汉明距离（0~64）不超过该值视为同一张
+    pub max_image_hash_distance: u32,
+}
+
+impl Default for MatchingProfile {
+    fn default() -> Self {
+        MatchingProfile {
+            min_char_similarity: 0.72,
+            high_similarity: 0.88,
+            high_similarity_max_keywords: 8,
+            coverage_tiers: vec![
+                CoverageTier {
+                    max_keywords: 4,
+                    min_coverage: 1.0,
+                },
+                CoverageTier {
+                    max_keywords: 8,
+                    min_coverage: 0.9,
+                },
+            ],
+            default_min_coverage: 0.75,
+            char_similarity_weight: 0.7,
+            title_weight: 0.7,
+            result_limit: 50,
+            min_semantic_similarity: 0.86,
+            max_image_hash_distance: 6,
+        }
+    }
+}
+
+impl MatchingProfile {
+    /// 更严格：提高字符相似度门槛，关键词基本都要命中
+    pub fn strict() -> Self {
+        MatchingProfile {
+            min_char_similarity: 0.8,
+            coverage_tiers: vec![CoverageTier {
+                max_keywords: 8,
+                min_coverage: 1.0,
+            }],
+            default_min_coverage: 0.85,
+            min_semantic_similarity: 0.9,
+            max_image_hash_distance: 4,
+            ..Default::default()
+        }
+    }
+
+    /// 更宽松：适合题干措辞差异较大的课程
+    pub fn loose() -> Self {
+        MatchingProfile {
+            min_char_similarity: 0.65,
+            high_similarity: 0.92,
+            coverage_tiers: vec![
+                CoverageTier {
+                    max_keywords: 2,
+                    min_coverage: 1.0,
+                },
+                CoverageTier {
+                    max_keywords: 4,
+                    min_coverage: 0.75,
+                },
+                CoverageTier {
+                    max_keywords: 8,
+                    min_coverage: 0.7,
+                },
+            ],
+            default_min_coverage: 0.6,
+            min_semantic_similarity: 0.82,
+            max_image_hash_distance: 10,
+            ..Default::default()
+        }
+    }
+
+    pub fn min_keyword_coverage(&self, query_keywords_len: usize, char_similarity: f64) -> f64 {
+        // 高字符相似但关键词数量不多时，要求所有关键词都命中，
+        // 避免“题干几乎一致但核心词不同”的题目误匹配。
+        if char_similarity >= self.high_similarity
+            && query_keywords_len <= self.high_similarity_max_keywords
+        {
+            return 1.0;
+        }
+
+        self.coverage_tiers
+            .iter()
+            .find(|tier| query_keywords_len <= tier.max_keywords)
+            .map(|tier| tier.min_coverage)
+            .unwrap_or(self.default_min_coverage)
+    }
+}
+
+/// 全部可用配置档以及默认 / 按文件夹的选择
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MatchingProfiles {
+    pub profiles: HashMap<String, MatchingProfile>,
+    pub default_profile: String,
+    pub folder_profiles: HashMap<i64, String>,
+}
+
+impl Default for MatchingProfiles {
+    fn default() -> Self {
+        let profiles = HashMap::from([
+            (DEFAULT_PROFILE_NAME.to_string(), MatchingProfile::default()),
+            ("strict".to_string(), MatchingProfile::strict()),
+            ("loose".to_string(), MatchingProfile::loose()),
+        ]);
+        MatchingProfiles {
+            profiles,
+            default_profile: DEFAULT_PROFILE_NAME.to_string(),
+            folder_profiles: HashMap::new(),
+        }
+    }
+}
+
+impl MatchingProfiles {
+    /// 读取内置预设并合并 config.json 中的配置；配置有误的条目会被忽略并打印警告
+    pub fn load() -> Self {
+        let mut profiles = MatchingProfiles::default();
+
+        if let Some(custom) =
+            read_config_value("matchingProfiles").and_then(|v| v.as_object().cloned())
+        {
+            for (name, value) in custom {
+                match serde_json::from_value::<MatchingProfile>(value) {
+                    Ok(profile) => {
+                        profiles.profiles.insert(name, profile);
+                    }
+                    Err(e) => println!("⚠️ 匹配配置 {} 格式错误，已忽略: {}", name, e),
+                }
+            }
+        }
+
+        if let Some(name) =
+            read_config_value("defaultMatchingProfile").and_then(|v| v.as_str().map(str::to_string))
+        {
+            if profiles.profiles.contains_key(&name) {
+                profiles.default_profile = name;
+            } else {
+                println!(
+                    "⚠️ 默认匹配配置 {} 不存在，使用 {}",
+                    name, DEFAULT_PROFILE_NAME
+                );
+            }
+        }
+
+        if let Some(mapping) =
+            read_config_value("folderMatchingProfiles").and_then(|v| v.as_object().cloned())
+        {
+            for (folder_id, name) in mapping {
+                let (Ok(folder_id), Some(name)) = (folder_id.parse::<i64>(), name.as_str()) else {
+                    println!("⚠️ 文件夹匹配配置 {} 格式错误，已忽略", folder_id);
+                    continue;
+                };
+                if profiles.profiles.contains_key(name) {
+                    profiles.folder_profiles.insert(folder_id, name.to_string());
+                } else {
+                    println!("⚠️ 文件夹 {} 的匹配配置 {} 不存在，已忽略", folder_id, name);
+                }
+            }
+        }
+
+        profiles
+    }
+
+    /// 与 load 相同，但在 config.json 未修改时复用上次的结果
+    pub fn cached() -> Arc<Self> {
+        static CACHE: ConfigCache<MatchingProfiles> = ConfigCache::new();
+        CACHE.get(MatchingProfiles::load)
+    }
+
+    /// 确定本次请求使用的配置档：请求指定的优先，否则使用默认配置档
+    pub fn resolve_request(
+        &self,
+        requested: Option<&str>,
+    ) -> Result<(String, MatchingProfile), String> {
+        let name = requested
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .unwrap_or(&self.default_profile);
+        self.profiles
+            .get(name)
+            .map(|profile| (name.to_string(), profile.clone()))
+            .ok_or_else(|| format!("未知的匹配配置: {}", name))
+    }
+
+    /// 文件夹（或其最近的祖先文件夹）配置的配置档名称
+    pub fn folder_profile_name(&self, folder_id: i64, parents: &HashMap<i64, i64>) -> Option<&str> {
+        let mut current = folder_id;
+        // 防御异常数据中的循环
+        for _ in 0..=parents.len() {
+            if let Some(name) = self.folder_profiles.get(&current) {
+                return Some(name.as_str());
+            }
+            // 顶层文件夹的 ParentId 为 0，但默认文件夹 0 与顶层文件夹同级，不是它们的祖先
+            match parents.get(&current) {
+                Some(&parent) if parent != 0 && parent != current => current = parent,
+                _ => break,
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn folder_profiles_follow_nearest_ancestor() {
+        let mut profiles = MatchingProfiles::default();
+        profiles.folder_profiles.insert(1, "strict".to_string());
+        profiles.folder_profiles.insert(0, "loose".to_string());
+        let parents = std::collections::HashMap::from([(0, 0), (1, 0), (2, 1), (3, 2), (4, 0)]);
+
+        assert_eq!(profiles.folder_profile_name(3, &parents), Some("strict"));
+        assert_eq!(profiles.folder_profile_name(0, &parents), Some("loose"));
+        // 默认文件夹不是顶层文件夹的祖先
+        assert_eq!(profiles.folder_profile_name(4, &parents), None);
+    }
+}
@@ -0,0 +1,450 @@
+//! 提示词模板：数据库中没有答案时，按模板生成发送给模型的提示词。
+//!
+//! config.json 中的配置（均可省略，未配置时使用内置模板）：
+//! ```json
+//! {
+//!   "promptTemplates": {
+//!     "default": "Answer the question below.\n{{title}}\n{{#options}}Options:\n{{options}}\n{{/options}}",
+//!     "kinds": { "judgement": "..." },
+//!     "folders": { "英语/四级": "...", "12": "..." },
+//!     "models": { "deepseek-r1*": "...", "gpt-4o": "..." },
+//!     "retry": "{{prompt}}\n\nYour previous answer \"{{answer}}\" was rejected ({{problem}}). Answer again."
+//!   }
+//! }
+//! ```
+//! 模板选择顺序（越具体越优先）：文件夹 > 题型 > 模型（模型名不区分大小写，以 `*` 结尾时按前缀匹配）>
+//! default > 内置模板。folders 的键为文件夹 ID 或 "父文件夹/子文件夹" 形式的路径，按题目所属文件夹
+//! （查询范围中的第一个文件夹）自身、上级文件夹的顺序查找；kinds 的键为题型 id（见 questionKinds）。
+//!
+//! 模板变量：`{{title}}` 题目、`{{options}}` 选项、`{{kind}}` 识别出的题型名称、
+//! `{{unknown_type}}` 无法识别题型时的原始类型字段、`{{hint}}` 题型的答题要求、`{{folder}}` 题目所属文件夹的路径。
+//! `{{#options}}…{{/options}}` 中的内容只在变量非空时保留；未知的变量原样保留。
+//!
+//! retry 为答案未通过校验后重新询问模型的模板，变量为 `{{prompt}}` 上一次的提示词、`{{answer}}` 上一次的答案、
+//! `{{problem}}` 未通过校验的原因。
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::answer_extraction::best_model_match;
+use crate::database::read_config_value;
+use crate::question_kind::KindRegistry;
+
+/// 内置模板：先分析题目，再只输出带 answer 字段的 JSON
+pub const BUILTIN_TEMPLATE: &str = "你是一个专业的答题助手。我会给你一道题目，请先分别做两件事：
+1. 仔细分析题目，理解题目在问什么，如果有选项则逐一分析每个选项是否正确。
+2. 输出最终答案，**只输出一个JSON对象**，不要加代码块标记，不要加任何其他文字。
+
+格式：{\"answer\": \"你的最终答案\"}
+
+{{#kind}}【题目类型：{{kind}}】
+{{/kind}}{{#unknown_type}}【题目类型字段：{{unknown_type}}】
+{{/unknown_type}}{{#hint}}提示：{{hint}}
+{{/hint}}【题目】
+{{title}}
+{{#options}}【选项】
+{{options}}
+{{/options}}
+请先分析，再输出答案JSON：";
+
+/// 内置的重新询问模板
+pub const BUILTIN_RETRY_TEMPLATE: &str = "{{prompt}}
+
+注意：你上一次给出的答案“{{answer}}”不符合要求（{{problem}}）。请重新作答，仍然按上面的格式输出答案JSON：";
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PromptTemplateConfig {
+    /// 替换内置模板
+    pub default: Option<String>,
+    /// 键为题型 id
+    pub kinds: HashMap<String, String>,
+    /// 键为文件夹 ID 或文件夹路径
+    pub folders: HashMap<String, String>,
+    /// 键为模型名规则
+    pub models: HashMap<String, String>,
+    /// 替换内置的重新询问模板
+    pub retry: Option<String>,
+}
+
+/// 生成提示词所需的题目信息
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PromptContext {
+    pub title: String,
+    pub options: Option<String>,
+    pub query_type: Option<String>,
+    /// 题目所属文件夹从根到自身的各级文件夹 ID 与路径
+    pub folders: Vec<(i64, String)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RenderedPrompt {
+    pub prompt: String,
+    /// 使用的模板："builtin"、"default"、"kind:<题型 id>"、"folder:<配置的键>" 或 "model:<模型名规则>"
+    pub source: String,
+}
+
+/// 一道题目的全部提示词
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryPrompts {
+    /// 没有按模型配置模板时使用的提示词
+    pub prompt: RenderedPrompt,
+    /// 按模型配置的提示词，键为模型名规则；题目有文件夹或题型模板时为空
+    pub models: HashMap<String, String>,
+}
+
+impl PromptTemplateConfig {
+    /// 读取 config.json 中的 promptTemplates 配置，缺失或格式错误时只使用内置模板
+    pub fn load() -> Self {
+        let Some(value) = read_config_value("promptTemplates") else {
+            return PromptTemplateConfig::default();
+        };
+        serde_json::from_value(value).unwrap_or_else(|e| {
+            println!("⚠️ 提示词模板配置格式错误，使用内置模板: {}", e);
+            PromptTemplateConfig::default()
+        })
+    }
+
+    /// 按文件夹或题型配置的模板及其来源，比按模型配置的模板优先
+    fn specific_template(
+        &self,
+        kind_id: Option<&str>,
+        context: &PromptContext,
+    ) -> Option<(&str, String)> {
+        for (id, path) in context.folders.iter().rev() {
+            for key in [id.to_string(), path.clone()] {
+                if let Some(template) = self.folders.get(&key) {
+                    return Some((template, format!("folder:{}", key)));
+                }
+            }
+        }
+        kind_id
+            .and_then(|id| self.kinds.get_key_value(id))
+            .map(|(id, template)| (template.as_str(), format!("kind:{}", id)))
+    }
+
+    /// 没有更具体的模板时使用的模板及其来源
+    fn fallback_template(&self) -> (&str, String) {
+        match &self.default {
+            Some(template) => (template, "default".to_string()),
+            None => (BUILTIN_TEMPLATE, "builtin".to_string()),
+        }
+    }
+
+    /// 渲染一道题目的提示词，model 为空时不考虑按模型配置的模板
+    pub fn render(
+        &self,
+        registry: &KindRegistry,
+        context: &PromptContext,
+        model: Option<&str>,
+    ) -> RenderedPrompt {
+        let (kind_id, variables) = prompt_variables(registry, context);
+        let (template, source) = self
+            .specific_template(kind_id.as_deref(), context)
+            .or_else(|| {
+                model
+                    .and_then(|m| best_model_match(&self.models, m))
+                    .map(|(pattern, template)| (template.as_str(), format!("model:{}", pattern)))
+            })
+            .unwrap_or_else(|| self.fallback_template());
+        RenderedPrompt {
+            prompt: render_template(template, &variables),
+            source,
+        }
+    }
+
+    /// 答案未通过校验后重新询问模型的提示词
+    pub fn render_retry(&self, prompt: &str, answer: &str, problem: &str) -> String {
+        let variables = HashMap::from([
+            ("prompt", prompt.to_string()),
+            ("answer", answer.to_string()),
+            ("problem", problem.to_string()),
+        ]);
+        render_template(
+            self.retry.as_deref().unwrap_or(BUILTIN_RETRY_TEMPLATE),
+            &variables,
+        )
+    }
+
+    /// 渲染默认提示词以及每条模型规则的提示词，由前端调用 resolve_model_prompt 按实际调用的模型选择；
+    /// 题目有文件夹或题型模板时不使用按模型配置的模板
+    pub fn render_all(&self, registry: &KindRegistry, context: &PromptContext) -> QueryPrompts {
+        let (kind_id, variables) = prompt_variables(registry, context);
+        let models = if self
+            .specific_template(kind_id.as_deref(), context)
+            .is_some()
+        {
+            HashMap::new()
+        } else {
+            self.models
+                .iter()
+                .map(|(pattern, template)| (pattern.clone(), render_template(template, &variables)))
+                .collect()
+        };
+        QueryPrompts {
+            prompt: self.render(registry, context, None),
+            models,
+        }
+    }
+}
+
+/// 题目的模板变量以及识别出的题型 id
+fn prompt_variables(
+    registry: &KindRegistry,
+    context: &PromptContext,
+) -> (Option<String>, HashMap<&'static str, String>) {
+    let raw_type = context
+        .query_type
+        .as_deref()
+        .map(str::trim)
+        .unwrap_or_default();
+    let kind = registry.detect(raw_type);
+    let (kind_name, unknown_type, hint) = match kind {
+        Some(kind) => (kind.name.clone(), String::new(), kind.prompt_hint.clone()),
+        None => (String::new(), raw_type.to_string(), String::new()),
+    };
+    let options = context
+        .options
+        .as_deref()
+        .map(str::trim)
+        .unwrap_or_default()
+        .to_string();
+    let folder = context
+        .folders
+        .last()
+        .map(|(_, path)| path.clone())
+        .unwrap_or_default();
+
+    let variables = HashMap::from([
+        ("title", context.title.clone()),
+        ("options", options),
+        ("kind", kind_name),
+        ("unknown_type", unknown_type),
+        ("hint", hint),
+        ("folder", folder),
+    ]);
+    (kind.map(|kind| kind.id.clone()), variables)
+}
+
+/// 按变量渲染模板：先处理 `{{#name}}…{{/name}}` 段落，再替换 `{{name}}`；变量的值不会再被当作模板解析
+pub fn render_template(template: &str, variables: &HashMap<&str, String>) -> String {
+    let expanded = render_sections(template, variables);
+    let placeholder = Regex::new(r"\{\{\s*([a-z_]+)\s*\}\}").unwrap();
+    placeholder
+        .replace_all(&expanded, |caps: &regex::Captures| {
+            variables
+                .get(&caps[1])
+                .cloned()
+                .unwrap_or_else(|| caps[0].to_string())
+        })
+        .into_owned()
+}
+
+// 变量非空时保留段落内容，否则去掉整个段落；没有结束标记的段落原样保留
+fn render_sections(template: &str, variables: &HashMap<&str, String>) -> String {
+    let open = Regex::new(r"\{\{#\s*([a-z_]+)\s*\}\}").unwrap();
+    let mut output = String::new();
+    let mut rest = template;
+    while let Some(caps) = open.captures(rest) {
+        let start = caps.get(0).unwrap();
+        let name = &caps[1];
+        let close = Regex::new(&format!(r"\{{\{{/\s*{}\s*\}}\}}", regex::escape(name))).unwrap();
+        let Some(end) = close.find(&rest[start.end()..]) else {
+            break;
+        };
+        output.push_str(&rest[..start.start()]);
+        if variables.get(name).is_some_and(|value| !value.is_empty()) {
+            let body = &rest[start.end()..start.end() + end.start()];
+            output.push_str(&render_sections(body, variables));
+        }
+        rest = &rest[start.end() + end.end()..];
+    }
+    output.push_str(rest);
+    output
+}
+
+/// 按实际调用的模型从 render_all 的结果中选择提示词：完全匹配优先，其次是最长的前缀规则，
+/// 都没有时使用默认提示词
+pub fn resolve_model_prompt<'a>(
+    prompt: &'a str,
+    model_prompts: &'a HashMap<String, String>,
+    model: &str,
+) -> &'a str {
+    best_model_match(model_prompts, model)
+        .map(|(_, prompt)| prompt.as_str())
+        .unwrap_or(prompt)
+}
+
+/// 按当前配置生成一道题目的提示词
+pub fn build_query_prompts(context: &PromptContext) -> QueryPrompts {
+    PromptTemplateConfig::load().render_all(&KindRegistry::load(), context)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retry_prompt_uses_the_configured_template() {
+        let builtin =
+            PromptTemplateConfig::default().render_retry("原题", "E", "答案“E”不在选项中");
+        assert!(builtin.starts_with("原题\n\n注意：你上一次给出的答案“E”不符合要求"));
+
+        let config = PromptTemplateConfig {
+            retry: Some("{{prompt}}\nRejected {{answer}}: {{problem}}".to_string()),
+            ..PromptTemplateConfig::default()
+        };
+        // 上一次的答案不会被当作模板解析
+        assert_eq!(
+            config.render_retry("Q", "{{prompt}}", "not an option"),
+            "Q\nRejected {{prompt}}: not an option"
+        );
+    }
+
+    #[test]
+    fn renders_prompt_template_sections_and_variables() {
+        let variables = HashMap::from([
+            ("title", "1+1={{options}}".to_string()),
+            ("options", String::new()),
+            ("kind", "填空题".to_string()),
+        ]);
+        let rendered = render_template(
+            "{{#kind}}[{{ kind }}] {{/kind}}{{title}}{{#options}}\n选项：{{options}}{{/options}}{{#hint}}提示{{/hint}} {{unknown}}",
+            &variables,
+        );
+        // 变量的值不会再被当作模板解析，未知变量原样保留
+        assert_eq!(rendered, "[填空题] 1+1={{options}} {{unknown}}");
+        assert_eq!(
+            render_template("{{#kind}}未闭合", &variables),
+            "{{#kind}}未闭合"
+        );
+    }
+
+    #[test]
+    fn builtin_prompt_template_matches_previous_prompt() {
+        let registry = KindRegistry::default();
+        let context = PromptContext {
+            title: "地球是圆的".to_string(),
+            options: Some(" A. 对\nB. 错 ".to_string()),
+            query_type: Some("judgement".to_string()),
+            folders: Vec::new(),
+        };
+        let rendered = PromptTemplateConfig::default().render(&registry, &context, None);
+        assert_eq!(rendered.source, "builtin");
+        assert_eq!(
+            rendered.prompt,
+            "你是一个专业的答题助手。我会给你一道题目，请先分别做两件事：\n\
+             1. 仔细分析题目，理解题目在问什么，如果有选项则逐一分析每个选项是否正确。\n\
+             2. 输出最终答案，**只输出一个JSON对象**，不要加代码块标记，不要加任何其他文字。\n\n\
+             格式：{\"answer\": \"你的最终答案\"}\n\n\
+             【题目类型：判断题】\n\
+             提示：这是判断题,请只回答正确或错误,不要添加任何其他文字或标点.\n\
+             【题目】\n地球是圆的\n\
+             【选项】\nA. 对\nB. 错\n\n\
+             请先分析，再输出答案JSON："
+        );
+
+        let untyped = PromptContext {
+            options: None,
+            query_type: None,
+            ..context
+        };
+        let prompt = PromptTemplateConfig::default()
+            .render(&registry, &untyped, None)
+            .prompt;
+        assert!(prompt.ends_with("【题目】\n地球是圆的\n\n请先分析，再输出答案JSON："));
+
+        // 无法识别的题型保留原始类型字段
+        let unknown = PromptContext {
+            query_type: Some("编程问题".to_string()),
+            ..untyped
+        };
+        let prompt = PromptTemplateConfig::default()
+            .render(&registry, &unknown, None)
+            .prompt;
+        assert!(prompt.contains("\n\n【题目类型字段：编程问题】\n【题目】\n"));
+        assert!(!prompt.contains("【题目类型："));
+    }
+
+    #[test]
+    fn most_specific_prompt_template_wins() {
+        let config: PromptTemplateConfig = serde_json::from_value(serde_json::json!({
+            "default": "默认 {{title}}",
+            "kinds": { "judgement": "判断 {{kind}} {{title}}" },
+            "folders": { "英语": "English ({{folder}}): {{title}}{{#options}} / {{options}}{{/options}}" },
+            "models": { "deepseek*": "DS {{title}}", "deepseek-r1": "R1 {{title}}" }
+        }))
+        .expect("prompt templates");
+        let registry = KindRegistry::default();
+        let mut context = PromptContext {
+            title: "apple".to_string(),
+            options: None,
+            query_type: Some("3".to_string()),
+            folders: Vec::new(),
+        };
+
+        let render = |context: &PromptContext, model: Option<&str>| {
+            let rendered = config.render(&registry, context, model);
+            (rendered.source, rendered.prompt)
+        };
+        // 题型模板比模型模板更具体
+        assert_eq!(
+            render(&context, Some("deepseek-r1")),
+            (
+                "kind:judgement".to_string(),
+                "判断 判断题 apple".to_string()
+            )
+        );
+        context.query_type = Some("编程问题".to_string());
+        assert_eq!(
+            render(&context, Some("gpt-4o")),
+            ("default".to_string(), "默认 apple".to_string())
+        );
+
+        // 完全匹配的模型规则优先于前缀规则
+        assert_eq!(
+            render(&context, Some("DeepSeek-R1")),
+            ("model:deepseek-r1".to_string(), "R1 apple".to_string())
+        );
+        assert_eq!(
+            render(&context, Some("deepseek-v3")),
+            ("model:deepseek*".to_string(), "DS apple".to_string())
+        );
+        let prompts = config.render_all(&registry, &context);
+        assert_eq!(prompts.prompt.source, "default");
+        assert_eq!(prompts.models.len(), 2);
+        assert_eq!(
+            resolve_model_prompt(&prompts.prompt.prompt, &prompts.models, "deepseek-v3"),
+            "DS apple"
+        );
+        assert_eq!(
+            resolve_model_prompt(&prompts.prompt.prompt, &prompts.models, "gpt-4o"),
+            "默认 apple"
+        );
+
+        // 子文件夹没有配置时使用上级文件夹的模板，文件夹优先于题型
+        context.query_type = Some("judgement".to_string());
+        context.folders = vec![(1, "英语".to_string()), (2, "英语/四级".to_string())];
+        assert_eq!(
+            render(&context, None),
+            (
+                "folder:英语".to_string(),
+                "English (英语/四级): apple".to_string()
+            )
+        );
+
+        // 文件夹模板比模型模板更具体，此时不再下发按模型配置的提示词
+        assert_eq!(
+            render(&context, Some("DeepSeek-R1")),
+            (
+                "folder:英语".to_string(),
+                "English (英语/四级): apple".to_string()
+            )
+        );
+        let prompts = config.render_all(&registry, &context);
+        assert_eq!(prompts.prompt.source, "folder:英语");
+        assert!(prompts.models.is_empty());
+    }
+}
@@ -0,0 +1,364 @@
+//! 题型注册表：OCS 传入的题目类型字段（single、多选题、平台的数字类型代码等）识别为规范题型，
+//! 供提示词、答案校验与搜索使用。入库时原样保存类型字段（QuestionType），识别出的题型 id 另存在
+//! QuestionKind 列中；题型配置变化后启动时只重新计算 QuestionKind。
+//!
+//! config.json 中可以补充或覆盖内置题型：
+//! ```json
+//! {
+//!   "questionKinds": [
+//!     { "id": "single", "aliases": ["单择题"], "codes": ["10"] },
+//!     { "id": "case", "name": "案例分析题", "aliases": ["案例分析"], "promptHint": "...", "answerRule": "freeText" }
+//!   ]
+//! }
+//! ```
+//! id 与内置题型相同时，aliases 与 codes 追加到内置题型，其余填写的字段替换内置值；
+//! 新的 id 作为新题型加入，未填写 name 时使用 id，未填写 answerRule 时按自由作答处理。
+
+use serde::{Deserialize, Serialize};
+
+use crate::database::read_config_value;
+
+/// 题型对答案的要求，决定答案的校验与规范化方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum AnswerRule {
+    /// 恰好一个选项
+    SingleChoice,
+    /// 一个或多个选项，按选项顺序用 ### 连接
+    MultipleChoice,
+    /// 正确或错误
+    Judgement,
+    /// 多个部分用 ### 连接（填空、连线等），不对照选项
+    Blanks,
+    /// 全部选项的一个排列，按作答顺序用 ### 连接
+    Ordering,
+    /// 自由作答，不做校验
+    FreeText,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QuestionKind {
+    /// 稳定的题型标识，用于配置
+    pub id: String,
+    /// 显示与入库使用的名称
+    pub name: String,
+    /// 类型字段与其中任意一个相同时识别为该题型，名称与 id 总是可以识别。
+    /// 比较时不区分大小写，并忽略空白、连字符、下划线、括号与末尾的“题”字
+    pub aliases: Vec<String>,
+    /// 平台使用的数字类型代码，需要完全一致
+    pub codes: Vec<String>,
+    /// 附加到提示词中的答题要求
+    pub prompt_hint: String,
+    pub answer_rule: AnswerRule,
+}
+
+impl QuestionKind {
+    fn builtin(
+        id: &str,
+        name: &str,
+        aliases: &[&str],
+        codes: &[&str],
+        prompt_hint: &str,
+        answer_rule: AnswerRule,
+    ) -> Self {
+        QuestionKind {
+            id: id.to_string(),
+            name: name.to_string(),
+            aliases: aliases.iter().map(|alias| alias.to_string()).collect(),
+            codes: codes.iter().map(|code| code.to_string()).collect(),
+            prompt_hint: prompt_hint.to_string(),
+            answer_rule,
+        }
+    }
+
+    // 名称与 id 也作为别名
+    fn matches_alias(&self, normalized: &str) -> bool {
+        self.aliases
+            .iter()
+            .chain([&self.name, &self.id])
+            .any(|alias| normalize_kind_token(alias) == normalized)
+    }
+}
+
+/// 类型字段的比较形式：小写，去掉空白、连字符、下划线、括号与末尾的“题”字（“单项选择题”与“单项选择”相同）
+fn normalize_kind_token(value: &str) -> String {
+    let token: String = value
+        .chars()
+        .filter(|c| {
+            !c.is_whitespace()
+                && !matches!(
+                    c,
+                    '-' | '_' | '(' | ')' | '（' | '）' | '[' | ']' | '【' | '】'
+                )
+        })
+        .flat_map(char::to_lowercase)
+        .collect();
+    token.strip_suffix('题').unwrap_or(&token).to_string()
+}
+
+/// config.json 中的题型配置项
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct KindOverride {
+    id: String,
+    name: Option<String>,
+    aliases: Vec<String>,
+    codes: Vec<String>,
+    prompt_hint: Option<String>,
+    answer_rule: Option<AnswerRule>,
+}
+
+/// 内置题型，数字代码采用常见平台的题型编号（0 单选、1 多选、2 填空、3 判断、4 简答……）
+fn builtin_kinds() -> Vec<QuestionKind> {
+    use AnswerRule::*;
+    vec![
+        QuestionKind::builtin(
+            "single",
+            "单选题",
+            &["single", "singlechoice", "单选", "单项选择"],
+            &["0"],
+            "这是单选题,只有一个正确答案.请返回正确选项的完整文字内容,不要返回A/B/C/D等选项字母或序号.",
+            SingleChoice,
+        ),
+        QuestionKind::builtin(
+            "multiple",
+            "多选题",
+            &["multiple", "multiplechoice", "多选", "多项选择", "不定项", "不定项选择"],
+            &["1"],
+            "这是多选题,可能有多个正确答案.请返回所有正确选项的完整文字内容,多个答案用\"###\"连接,不要返回A/B/C/D等选项字母.",
+            MultipleChoice,
+        ),
+        QuestionKind::builtin(
+            "completion",
+            "填空题",
+            &["completion", "fill", "fillblank", "填空"],
+            &["2"],
+            "这是填空题.如果有多空请用\"###\"连接每个空的答案,只有一个空则直接返回答案内容,不要加序号.",
+            Blanks,
+        ),
+        QuestionKind::builtin(
+            "judgement",
+            "判断题",
+            &["judgement", "judgment", "truefalse", "判断", "是非"],
+            &["3"],
+            "这是判断题,请只回答正确或错误,不要添加任何其他文字或标点.",
+            Judgement,
+        ),
+        QuestionKind::builtin(
+            "short_answer",
+            "简答题",
+            &["short", "shortanswer", "简答", "问答"],
+            &["4"],
+            "这是简答题.请直接给出简明扼要的答案要点,不要重复题目.",
+            FreeText,
+        ),
+        QuestionKind::builtin(
+            "term",
+            "名词解释",
+            &["term", "名词解释"],
+            &["5"],
+            "这是名词解释题.请用一两句话给出该名词的准确定义.",
+            FreeText,
+        ),
+        QuestionKind::builtin(
+            "essay",
+            "论述题",
+            &["essay", "论述"],
+            &["6"],
+            "这是论述题.请分点论述主要观点,条理清晰.",
+            FreeText,
+        ),
+        QuestionKind::builtin(
+            "calculation",
+            "计算题",
+            &["calculation", "计算"],
+            &["7"],
+            "这是计算题.答案只返回最终计算结果(含单位),不要返回计算过程.",
+            FreeText,
+        ),
+        QuestionKind::builtin(
+            "matching",
+            "连线题",
+            &["line", "matching", "连线", "匹配"],
+            &["11"],
+            "这是连线题.请按左侧顺序给出每一项对应的右侧内容,格式为\"左项=右项\",多组用\"###\"连接.",
+            Blanks,
+        ),
+        QuestionKind::builtin(
+            "ordering",
+            "排序题",
+            &["sort", "ordering", "排序"],
+            &["13"],
+            "这是排序题.请按正确顺序返回各选项的完整文字内容,用\"###\"连接,不要返回选项字母.",
+            Ordering,
+        ),
+        QuestionKind::builtin(
+            "cloze",
+            "完形填空",
+            &["cloze", "完形填空", "完型填空"],
+            &["14"],
+            "这是完形填空题.请按空的顺序返回每个空应填入的内容,用\"###\"连接.",
+            Blanks,
+        ),
+        QuestionKind::builtin(
+            "reading",
+            "阅读理解",
+            &["reader", "reading", "阅读理解"],
+            &["15"],
+            "这是阅读理解题.请根据材料作答,如有多个小题,按顺序用\"###\"连接每个小题的答案.",
+            Blanks,
+        ),
+    ]
+}
+
+#[derive(Debug, Clone)]
+pub struct KindRegistry {
+    kinds: Vec<QuestionKind>,
+}
+
+impl Default for KindRegistry {
+    fn default() -> Self {
+        KindRegistry {
+            kinds: builtin_kinds(),
+        }
+    }
+}
+
+impl KindRegistry {
+    /// 内置题型加上 config.json 中 questionKinds 的补充，格式错误的配置会被忽略
+    pub fn load() -> Self {
+        let mut registry = KindRegistry::default();
+        let Some(value) = read_config_value("questionKinds") else {
+            return registry;
+        };
+        match serde_json::from_value::<Vec<KindOverride>>(value) {
+            Ok(overrides) => {
+                for item in overrides {
+                    registry.apply(item);
+                }
+            }
+            Err(e) => println!("⚠️ 题型配置格式错误，使用内置题型: {}", e),
+        }
+        registry
+    }
+
+    fn apply(&mut self, item: KindOverride) {
+        let id = item.id.trim().to_string();
+        if id.is_empty() {
+            return;
+        }
+        match self.kinds.iter_mut().find(|kind| kind.id == id) {
+            Some(kind) => {
+                kind.aliases.extend(item.aliases);
+                kind.codes.extend(item.codes);
+                if let Some(name) = item.name {
+                    kind.name = name;
+                }
+                if let Some(prompt_hint) = item.prompt_hint {
+                    kind.prompt_hint = prompt_hint;
+                }
+                if let Some(answer_rule) = item.answer_rule {
+                    kind.answer_rule = answer_rule;
+                }
+            }
+            None => self.kinds.push(QuestionKind {
+                name: item.name.unwrap_or_else(|| id.clone()),
+                id,
+                aliases: item.aliases,
+                codes: item.codes,
+                prompt_hint: item.prompt_hint.unwrap_or_default(),
+                answer_rule: item.answer_rule.unwrap_or(AnswerRule::FreeText),
+            }),
+        }
+    }
+
+    pub fn kinds(&self) -> &[QuestionKind] {
+        &self.kinds
+    }
+
+    pub fn get(&self, id: &str) -> Option<&QuestionKind> {
+        self.kinds.iter().find(|kind| kind.id == id)
+    }
+
+    /// 识别类型字段：纯数字按类型代码完全匹配；否则按别名整体匹配（“判断说理题”不是判断题），
+    /// 多个题型的别名相同时取先定义的题型
+    pub fn detect(&self, query_type: &str) -> Option<&QuestionKind> {
+        let trimmed = query_type.trim();
+        if trimmed.is_empty() {
+            return None;
+        }
+        if trimmed.chars().all(|c| c.is_ascii_digit()) {
+            return self
+                .kinds
+                .iter()
+                .find(|kind| kind.codes.iter().any(|code| code.trim() == trimmed));
+        }
+
+        let normalized = normalize_kind_token(trimmed);
+        if normalized.is_empty() {
+            return None;
+        }
+        self.kinds
+            .iter()
+            .find(|kind| kind.matches_alias(&normalized))
+    }
+
+    /// 类型字段识别出的题型 id，保存在 QuestionKind 列中
+    pub fn kind_id(&self, query_type: Option<&str>) -> Option<String> {
+        query_type
+            .and_then(|value| self.detect(value))
+            .map(|kind| kind.id.clone())
+    }
+}
+
+/// 根据 OCS 传入的题目类型字段判断题型，无法识别时返回 None
+pub fn detect_question_kind(query_type: &str) -> Option<QuestionKind> {
+    KindRegistry::load().detect(query_type).cloned()
+}
+
+/// 类型字段识别出的题型 id，无法识别时为 None
+pub fn question_kind_id(query_type: Option<&str>) -> Option<String> {
+    KindRegistry::load().kind_id(query_type)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_question_kinds_by_alias_and_code() {
+        let registry = KindRegistry::default();
+        let detect = |value: &str| registry.detect(value).map(|kind| kind.id.as_str());
+
+        assert_eq!(detect("single"), Some("single"));
+        assert_eq!(detect("单项选择题"), Some("single"));
+        assert_eq!(detect("不定项选择题"), Some("multiple"));
+        assert_eq!(detect("Judgment"), Some("judgement"));
+        assert_eq!(detect("名词解释"), Some("term"));
+        assert_eq!(detect("阅读理解"), Some("reading"));
+        assert_eq!(detect("line"), Some("matching"));
+        // 别名整体匹配：完形填空不是普通填空，只包含别名的类型字段不识别
+        assert_eq!(detect("完形填空"), Some("cloze"));
+        assert_eq!(detect("【单选题】"), Some("single"));
+        assert_eq!(detect("short_answer"), Some("short_answer"));
+        assert_eq!(detect("判断说理题"), None);
+        assert_eq!(detect("简答与论述"), None);
+        assert_eq!(detect("3"), Some("judgement"));
+        assert_eq!(detect(" 4 "), Some("short_answer"));
+        assert_eq!(detect("99"), None);
+        assert_eq!(detect("编程问题"), None);
+        assert_eq!(detect("  "), None);
+
+        assert_eq!(
+            registry.kind_id(Some("多选题")).as_deref(),
+            Some("multiple")
+        );
+        assert_eq!(registry.kind_id(Some(" 编程问题 ")), None);
+        assert_eq!(registry.kind_id(None), None);
+        assert_eq!(
+            registry.get("ordering").map(|kind| kind.answer_rule),
+            Some(AnswerRule::Ordering)
+        );
+    }
+}
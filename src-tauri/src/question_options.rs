@@ -0,0 +1,213 @@
+//! 选项解析：把 "A. 北京 B. 上海"、按行或竖线分隔等形式的选项拆成单个选项，
+//! 去掉 A./B./①/(A) 等序号后按集合比较。平台打乱选项顺序时，
+//! 同一题目的选项规范化后完全一致，可以精确匹配。
+
+use regex::Regex;
+use std::sync::OnceLock;
+use strsim::normalized_levenshtein;
+
+use crate::text_normalize::normalize_text_keep_lines;
+
+/// 规范化选项中各选项之间的分隔符
+const CHOICE_SEPARATOR: char = '\n';
+
+static CHOICE_LABEL: OnceLock<Regex> = OnceLock::new();
+
+fn choice_label_regex() -> &'static Regex {
+    CHOICE_LABEL
+        .get_or_init(|| Regex::new(r"[(（]\s*([A-Z])\s*[)）]|([A-Z])\s*[.．、,:：)）]").unwrap())
+}
+
+// 序号在字母表中的位置：A 为 0，B 为 1……
+fn label_index(captures: &regex::Captures<'_>) -> Option<u32> {
+    captures
+        .get(1)
+        .or_else(|| captures.get(2))
+        .and_then(|letter| letter.as_str().chars().next())
+        .map(|c| c as u32 - 'A' as u32)
+}
+
+// ①②… 在 NFKC 后会变成普通数字，规范化前先换成对应的字母序号
+fn replace_circled_labels(segment: &str) -> String {
+    segment
+        .chars()
+        .map(|c| match c {
+            '①'..='⑳' => {
+                let letter = char::from_u32('A' as u32 + (c as u32 - '①' as u32)).unwrap_or(c);
+                format!("({})", letter)
+            }
+            _ => c.to_string(),
+        })
+        .collect()
+}
+
+// 在一段文本中按连续递增的序号切分；段首不是序号时返回 None
+fn split_labeled_segment(segment: &str) -> Option<Vec<&str>> {
+    let mut markers: Vec<(usize, usize)> = Vec::new();
+    let mut expected: Option<u32> = None;
+
+    for captures in choice_label_regex().captures_iter(segment) {
+        let whole = captures.get(0)?;
+        // 序号前必须是开头、空白或标点，避免把 "DNA." 之类的单词结尾当成序号
+        let preceded_by_word = segment[..whole.start()]
+            .chars()
+            .next_back()
+            .is_some_and(|c| c.is_ascii_alphanumeric());
+        if preceded_by_word {
+            continue;
+        }
+        let Some(index) = label_index(&captures) else {
+            continue;
+        };
+        match expected {
+            None if !segment[..whole.start()].trim().is_empty() => return None,
+            Some(next) if index != next => continue,
+            _ => {}
+        }
+        markers.push((whole.start(), whole.end()));
+        expected = Some(index + 1);
+    }
+
+    if markers.is_empty() {
+        return None;
+    }
+
+    let mut parts = Vec::with_capacity(markers.len());
+    for (i, &(_, content_start)) in markers.iter().enumerate() {
+        let content_end = markers
+            .get(i + 1)
+            .map(|&(next_start, _)| next_start)
+            .unwrap_or(segment.len());
+        parts.push(&segment[content_start..content_end]);
+    }
+    Some(parts)
+}
+
+fn trim_choice(choice: &str) -> String {
+    choice
+        .trim()
+        .trim_end_matches([';', '；', ',', '，', '。'])
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn clean_choice(choice: &str) -> String {
+    trim_choice(choice).to_lowercase()
+}
+
+fn split_choices(text: &str, clean: fn(&str) -> String) -> Vec<String> {
+    let mut choices = Vec::new();
+    for segment in text.split(['\n', '\r', '|', '｜']) {
+        // 先统一全角/半角与标点样式，"Ａ．"、"（A）" 等序号才能被识别
+        let segment = normalize_text_keep_lines(&replace_circled_labels(segment));
+        match split_labeled_segment(&segment) {
+            Some(parts) => choices.extend(parts.into_iter().map(clean)),
+            None => choices.push(clean(&segment)),
+        }
+    }
+    choices.retain(|choice| !choice.is_empty());
+    choices
+}
+
+/// 拆分选项文本，返回去掉序号、经过文本规范化与统一大小写后的各个选项（保持原顺序）
+pub fn parse_choices(text: &str) -> Vec<String> {
+    split_choices(text, clean_choice)
+}
+
+/// 与 parse_choices 相同，但保留大小写，用于把选项字母还原为选项内容
+pub fn choice_texts(text: &str) -> Vec<String> {
+    split_choices(text, trim_choice)
+}
+
+/// 选项的规范化存储形式：各选项排序后按行拼接，选项顺序不同的同一组选项结果相同。
+/// 空选项返回 None。
+pub fn normalize_options(text: Option<&str>) -> Option<String> {
+    let mut choices = parse_choices(text?);
+    if choices.is_empty() {
+        return None;
+    }
+    choices.sort();
+    Some(choices.join(&CHOICE_SEPARATOR.to_string()))
+}
+
+/// 从 normalize_options 的结果还原选项列表
+pub fn choices_from_normalized(normalized: &str) -> Vec<String> {
+    normalized
+        .split(CHOICE_SEPARATOR)
+        .filter(|choice| !choice.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// 两组选项作为集合的相似度：每个选项取另一组中最相近选项的字符相似度，
+/// 两个方向取平均，多出或缺少的选项会拉低结果。选项完全相同（不论顺序）时为 1.0。
+pub fn choice_set_similarity(left: &[String], right: &[String]) -> f64 {
+    if left.is_empty() || right.is_empty() {
+        return 0.0;
+    }
+
+    let best_total = |from: &[String], to: &[String]| -> f64 {
+        from.iter()
+            .map(|choice| {
+                to.iter()
+                    .map(|other| normalized_levenshtein(choice, other))
+                    .fold(0.0, f64::max)
+            })
+            .sum()
+    };
+
+    (best_total(left, right) + best_total(right, left)) / (left.len() + right.len()) as f64
+}
+
+/// 两组选项是否为同一组选项的不同排列
+pub fn same_choice_set(left: &[String], right: &[String]) -> bool {
+    let mut left = left.to_vec();
+    let mut right = right.to_vec();
+    left.sort();
+    right.sort();
+    left == right
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn comma_after_label_is_a_separator() {
+        // 全角逗号经文本规范化后是半角逗号
+        let expected = vec!["北京".to_string(), "上海".to_string()];
+        assert_eq!(parse_choices("A，北京 B，上海"), expected);
+        assert_eq!(parse_choices("A,北京\nB,上海"), expected);
+    }
+
+    #[test]
+    fn parses_choices_in_common_formats() {
+        let expected = vec!["北京".to_string(), "上海".to_string(), "广州".to_string()];
+        assert_eq!(parse_choices("A. 北京 B. 上海 C. 广州"), expected);
+        assert_eq!(parse_choices("A.北京B.上海C.广州"), expected);
+        assert_eq!(parse_choices("A、北京\nB、上海\nC、广州"), expected);
+        assert_eq!(parse_choices("①北京 ②上海 ③广州"), expected);
+        assert_eq!(parse_choices("(A) 北京；(B) 上海；(C) 广州"), expected);
+        assert_eq!(parse_choices("北京|上海|广州"), expected);
+        // 选项内容里的字母不当作序号
+        assert_eq!(
+            parse_choices("A. 维生素C. B. DNA. C. 铁"),
+            vec!["维生素c.".to_string(), "dna.".to_string(), "铁".to_string()]
+        );
+
+        assert_eq!(
+            normalize_options(Some("A. 北京 B. 上海 C. 广州")),
+            normalize_options(Some("A. 广州\nB. 北京\nC. 上海"))
+        );
+        assert_eq!(normalize_options(Some("  ")), None);
+    }
+
+    #[test]
+    fn full_width_labels_normalize_like_ascii_labels() {
+        assert_eq!(
+            normalize_options(Some("Ａ．北京　Ｂ．上海")),
+            normalize_options(Some("A. 上海 B. 北京"))
+        );
+    }
+}
@@ -0,0 +1,239 @@
+//! 题库匹配结果的排序策略：在匹配得分之外考虑人工核实、人工 / AI 来源、待修正标记、文件夹优先级和入库时间。
+//!
+//! config.json 中的配置（均可省略）：
+//! ```json
+//! {
+//!   "rankingPolicy": {
+//!     "pendingCorrection": "deprioritize",
+//!     "pendingPenalty": 0.1,
+//!     "humanBonus": 0.02,
+//!     "folderPriorities": { "教材/官方题库": 0.05, "12": 0.03 },
+//!     "recencyBonus": 0.01,
+//!     "recencyHalfLifeDays": 30
+//!   }
+//! }
+//! ```
+//! 已核实的题目总是排在未核实的题目之前；同一组内按调整后的得分排序：
+//! 匹配得分 + 人工答案加成 − 待修正扣减 + 文件夹加成 + 新近度加成。调整后得分相同时，
+//! 人工答案优先，其次是较新的题目。
+//! folderPriorities 的键为文件夹 ID 或 "父文件夹/子文件夹" 形式的路径，对子文件夹同样生效，
+//! 按题目所在文件夹自身、上级文件夹的顺序取第一个配置的值。
+
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::database::{read_config_value, ConfigCache};
+
+/// 待修正题目的处理方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PendingCorrectionRule {
+    /// 扣减 pendingPenalty 后参与排序
+    #[default]
+    Deprioritize,
+    /// 不返回（已核实的题目除外）
+    Exclude,
+    /// 与其他题目同等对待
+    Keep,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct RankingPolicy {
+    pub pending_correction: PendingCorrectionRule,
+    /// pendingCorrection 为 deprioritize 时从得分中扣减的值
+    pub pending_penalty: f64,
+    /// 人工录入（非 AI）答案的得分加成
+    pub human_bonus: f64,
+    /// 键为文件夹 ID 或文件夹路径，值为得分加成（可为负数）
+    pub folder_priorities: HashMap<String, f64>,
+    /// 刚入库的题目获得的最大得分加成，随时间按半衰期衰减
+    pub recency_bonus: f64,
+    pub recency_half_life_days: f64,
+}
+
+impl Default for RankingPolicy {
+    fn default() -> Self {
+        RankingPolicy {
+            pending_correction: PendingCorrectionRule::Deprioritize,
+            pending_penalty: 0.1,
+            human_bonus: 0.02,
+            folder_priorities: HashMap::new(),
+            recency_bonus: 0.01,
+            recency_half_life_days: 30.0,
+        }
+    }
+}
+
+/// 参与排序的一道题目
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RankInput<'a> {
+    /// 匹配得分
+    pub score: f64,
+    pub is_ai: bool,
+    pub is_pending_correction: bool,
+    pub is_verified: bool,
+    /// 题目所在文件夹从根到自身的各级文件夹 ID 与路径
+    pub folders: &'a [(i64, String)],
+    /// 入库至今的天数，未知时为空
+    pub age_days: Option<f64>,
+}
+
+/// 排序键，用 RankKey::compare 比较
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RankKey {
+    pub is_verified: bool,
+    /// 调整后的得分
+    pub score: f64,
+    pub is_ai: bool,
+    pub age_days: f64,
+}
+
+impl RankKey {
+    /// 排名靠前的题目比较结果为 Less，可直接用于 sort_by
+    pub fn compare(&self, other: &Self) -> Ordering {
+        other
+            .is_verified
+            .cmp(&self.is_verified)
+            .then_with(|| other.score.total_cmp(&self.score))
+            .then_with(|| self.is_ai.cmp(&other.is_ai))
+            .then_with(|| self.age_days.total_cmp(&other.age_days))
+    }
+}
+
+impl RankingPolicy {
+    /// 读取 config.json 中的 rankingPolicy 配置，缺失或格式错误时使用默认策略
+    pub fn load() -> Self {
+        let Some(value) = read_config_value("rankingPolicy") else {
+            return RankingPolicy::default();
+        };
+        serde_json::from_value(value).unwrap_or_else(|e| {
+            println!("⚠️ 排序策略配置格式错误，使用默认策略: {}", e);
+            RankingPolicy::default()
+        })
+    }
+
+    /// 与 load 相同，但在 config.json 未修改时复用上次的结果
+    pub fn cached() -> Arc<Self> {
+        static CACHE: ConfigCache<RankingPolicy> = ConfigCache::new();
+        CACHE.get(RankingPolicy::load)
+    }
+
+    /// 排序是否需要题目所在文件夹的路径
+    pub fn uses_folders(&self) -> bool {
+        !self.folder_priorities.is_empty()
+    }
+
+    fn folder_bonus(&self, folders: &[(i64, String)]) -> f64 {
+        for (id, path) in folders.iter().rev() {
+            for key in [id.to_string(), path.clone()] {
+                if let Some(bonus) = self.folder_priorities.get(&key) {
+                    return *bonus;
+                }
+            }
+        }
+        0.0
+    }
+
+    fn recency(&self, age_days: Option<f64>) -> f64 {
+        match age_days {
+            Some(age) if self.recency_half_life_days > 0.0 => {
+                self.recency_bonus * 0.5_f64.powf(age.max(0.0) / self.recency_half_life_days)
+            }
+            _ => 0.0,
+        }
+    }
+
+    /// 计算题目的排序键；按策略应排除的题目返回 None
+    pub fn rank(&self, input: &RankInput) -> Option<RankKey> {
+        let pending = input.is_pending_correction && !input.is_verified;
+        let mut score = input.score;
+        match self.pending_correction {
+            PendingCorrectionRule::Exclude if pending => return None,
+            PendingCorrectionRule::Deprioritize if pending => score -= self.pending_penalty,
+            _ => {}
+        }
+        if !input.is_ai {
+            score += self.human_bonus;
+        }
+        score += self.folder_bonus(input.folders) + self.recency(input.age_days);
+
+        Some(RankKey {
+            is_verified: input.is_verified,
+            score,
+            is_ai: input.is_ai,
+            age_days: input.age_days.unwrap_or(f64::MAX),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ranking_policy_applies_folder_priority_and_pending_rule() {
+        let folders = vec![(3, "教材".to_string()), (5, "教材/第一章".to_string())];
+        let policy = RankingPolicy {
+            pending_correction: PendingCorrectionRule::Exclude,
+            human_bonus: 0.0,
+            recency_bonus: 0.0,
+            folder_priorities: HashMap::from([("教材".to_string(), 0.05)]),
+            ..Default::default()
+        };
+        let base = RankInput {
+            score: 0.9,
+            is_ai: true,
+            ..Default::default()
+        };
+
+        // 上级文件夹的优先级对子文件夹生效
+        let in_folder = policy
+            .rank(&RankInput {
+                folders: &folders,
+                ..base.clone()
+            })
+            .expect("ranked");
+        let outside = policy.rank(&base).expect("ranked");
+        assert!((in_folder.score - 0.95).abs() < 1e-9);
+        assert_eq!(in_folder.compare(&outside), std::cmp::Ordering::Less);
+
+        let pending = RankInput {
+            is_pending_correction: true,
+            ..base.clone()
+        };
+        assert_eq!(policy.rank(&pending), None);
+        assert!(policy
+            .rank(&RankInput {
+                is_verified: true,
+                ..pending.clone()
+            })
+            .is_some());
+
+        // 调整后得分相同时人工答案优先，其次是较新的题目
+        let human = policy
+            .rank(&RankInput {
+                is_ai: false,
+                age_days: Some(100.0),
+                ..base.clone()
+            })
+            .expect("ranked");
+        let new_ai = policy
+            .rank(&RankInput {
+                age_days: Some(1.0),
+                ..base.clone()
+            })
+            .expect("ranked");
+        let old_ai = policy
+            .rank(&RankInput {
+                age_days: Some(10.0),
+                ..base
+            })
+            .expect("ranked");
+        let mut keys = [old_ai, new_ai, human];
+        keys.sort_by(|a, b| a.compare(b));
+        assert_eq!(keys, [human, new_ai, old_ai]);
+    }
+}
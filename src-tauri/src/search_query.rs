@@ -0,0 +1,819 @@
+//! 题库搜索语法解析。
+//!
+//! 支持的语法（多个条件之间为 AND 关系）：
+//! - `关键词` / `"带空格的短语"`：在题目、答案、选项中查找
+//! - `q:` / `a:` / `opt:`：仅在题目 / 答案 / 选项中查找
+//! - `/正则/`：正则匹配，可与字段前缀组合，如 `q:/^中国.*首都/`；没有闭合的 `/`
+//!   或闭合后紧跟其他字符时（如 `/usr/bin`）按普通关键词处理
+//! - `type:单选`：题型
+//! - `is:ai` / `is:pending`：AI 生成 / 待修正
+//! - `folder:"路径/子文件夹"`：指定文件夹及其子文件夹
+//! - `created:2024-01-01..2024-06-30`：创建日期范围，任一端可省略；只写一个日期表示当天
+//! - 任意条件前加 `-` 表示取反，如 `-is:ai`、`-"错误短语"`；单独的 `-` 按普通关键词处理
+//!
+//! 解析结果通过 `SearchQuery::to_sql` 编译成 SQL 条件和相关度表达式，在数据库中过滤、排序和分页。
+
+use chrono::NaiveDate;
+use regex::{Regex, RegexBuilder};
+use rusqlite::functions::FunctionFlags;
+use rusqlite::types::Value;
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use crate::database::AIResponse;
+use crate::question_kind::KindRegistry;
+
+// 正则编译结果的大小上限，避免恶意表达式占用过多内存
+const REGEX_SIZE_LIMIT: usize = 1 << 20;
+
+// 文本条件命中次数的 SQL 函数，参数为文本条件的序号和字段内容
+const TERM_COUNT_FUNCTION: &str = "search_term_count";
+
+// 相关度（BM25）的词频饱和参数与长度归一化参数
+const BM25_K1: f64 = 1.2;
+const BM25_B: f64 = 0.75;
+
+// 相关度中各字段的权重：题目 > 选项 > 答案
+const QUESTION_WEIGHT: f64 = 1.0;
+const OPTIONS_WEIGHT: f64 = 0.7;
+const ANSWER_WEIGHT: f64 = 0.5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextField {
+    Any,
+    Question,
+    Answer,
+    Options,
+}
+
+#[derive(Debug, Clone)]
+pub enum TextPattern {
+    /// 已转为小写的子串
+    Contains(String),
+    Regex(Regex),
+}
+
+impl TextPattern {
+    // 命中次数；正则只匹配到空串时按命中一次计算
+    fn count_matches(&self, text: &str) -> usize {
+        match self {
+            TextPattern::Contains(needle) => text.to_lowercase().matches(needle.as_str()).count(),
+            TextPattern::Regex(regex) => {
+                let count = regex.find_iter(text).filter(|m| !m.is_empty()).count();
+                if count == 0 && regex.is_match(text) {
+                    1
+                } else {
+                    count
+                }
+            }
+        }
+    }
+
+    // 返回所有命中位置（原文中的字符下标，左闭右开）
+    fn find_spans(&self, text: &str) -> Vec<(usize, usize)> {
+        match self {
+            TextPattern::Contains(needle) => {
+                // 部分字符（如 'İ'）转小写后长度会变化，需要把小写文本中的位置换算回原文
+                let (lower, char_at) = lowercase_with_char_map(text);
+                lower
+                    .match_indices(needle.as_str())
+                    .map(|(start, matched)| {
+                        (char_at[start], char_at[start + matched.len() - 1] + 1)
+                    })
+                    .collect()
+            }
+            TextPattern::Regex(regex) => regex
+                .find_iter(text)
+                .filter(|m| !m.is_empty())
+                .map(|m| {
+                    let start_char = text[..m.start()].chars().count();
+                    (start_char, start_char + m.as_str().chars().count())
+                })
+                .collect(),
+        }
+    }
+}
+
+// 转为小写，同时返回小写文本中每个字节对应的原文字符下标
+fn lowercase_with_char_map(text: &str) -> (String, Vec<usize>) {
+    let mut lower = String::with_capacity(text.len());
+    let mut char_at = Vec::with_capacity(text.len());
+    for (index, c) in text.chars().enumerate() {
+        lower.extend(c.to_lowercase());
+        char_at.resize(lower.len(), index);
+    }
+    (lower, char_at)
+}
+
+/// 搜索结果中的高亮区间，start/end 为字段内的字符下标（左闭右开）
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HighlightSpan {
+    pub field: String,
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(Debug, Clone)]
+pub enum SearchCondition {
+    Text {
+        field: TextField,
+        pattern: TextPattern,
+    },
+    /// 题型：与输入的类型字段相同，或识别出的题型 id 与题目的 QuestionKind 相同即满足
+    QuestionType {
+        raw: String,
+        kind: Option<String>,
+    },
+    IsAi,
+    IsPending,
+    Folder(String),
+    Created {
+        from: Option<NaiveDate>,
+        to: Option<NaiveDate>,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct SearchClause {
+    pub condition: SearchCondition,
+    pub negated: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SearchQuery {
+    pub clauses: Vec<SearchClause>,
+}
+
+/// 题库中各字段的平均长度（字符数），用于相关度的长度归一化
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FieldLengths {
+    pub question: f64,
+    pub options: f64,
+    pub answer: f64,
+}
+
+/// 编译成 SQL 的搜索条件，列名使用 AIResponses 表的别名 `ar`
+#[derive(Debug)]
+pub struct SearchSql {
+    /// 以 AND 连接的过滤条件，没有条件时为 `1`
+    pub filter: String,
+    /// filter 中 `?` 占位符对应的参数
+    pub params: Vec<Value>,
+    /// 相关度表达式：各文本条件在题目、选项、答案中的词频按 BM25 饱和、按字段长度归一化后加权求和；
+    /// 没有文本条件时为常数 1
+    pub relevance: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchQueryError {
+    /// 出错位置（从 1 开始的字符序号）
+    pub position: usize,
+    pub message: String,
+}
+
+impl fmt::Display for SearchQueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "搜索语法错误（第 {} 个字符）: {}",
+            self.position, self.message
+        )
+    }
+}
+
+impl std::error::Error for SearchQueryError {}
+
+fn error_at(position: usize, message: impl Into<String>) -> SearchQueryError {
+    SearchQueryError {
+        position: position + 1,
+        message: message.into(),
+    }
+}
+
+// 原始值：引号内的短语、正则或普通单词
+enum RawValue {
+    Word(String),
+    Phrase(String),
+    Regex(String),
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.pos += 1;
+        }
+    }
+
+    // 读取 `field:` 前缀；只有已知字段名才视为前缀，其余情况（如 `10:30`）按普通文本处理
+    fn read_field(&mut self) -> Option<String> {
+        let mut end = self.pos;
+        while end < self.chars.len() && self.chars[end].is_ascii_alphabetic() {
+            end += 1;
+        }
+        if end == self.pos || self.chars.get(end) != Some(&':') {
+            return None;
+        }
+
+        let name: String = self.chars[self.pos..end]
+            .iter()
+            .collect::<String>()
+            .to_lowercase();
+        if !matches!(
+            name.as_str(),
+            "q" | "a" | "opt" | "type" | "is" | "folder" | "created"
+        ) {
+            return None;
+        }
+        self.pos = end + 1;
+        Some(name)
+    }
+
+    // 读取到下一个未转义的 terminator，支持 `\` 转义 terminator 本身
+    fn read_delimited(&mut self, terminator: char, what: &str) -> Result<String, SearchQueryError> {
+        let start = self.pos;
+        self.pos += 1;
+        let mut value = String::new();
+        while let Some(c) = self.peek() {
+            self.pos += 1;
+            if c == '\\' && self.peek() == Some(terminator) {
+                value.push(terminator);
+                self.pos += 1;
+            } else if c == '\\' && terminator == '/' {
+                // 正则中的其它转义原样保留
+                value.push(c);
+                if let Some(next) = self.peek() {
+                    value.push(next);
+                    self.pos += 1;
+                }
+            } else if c == terminator {
+                return Ok(value);
+            } else {
+                value.push(c);
+            }
+        }
+        Err(error_at(
+            start,
+            format!("{}缺少结尾的 {}", what, terminator),
+        ))
+    }
+
+    fn read_word(&mut self) -> String {
+        let mut value = String::new();
+        while let Some(c) = self.peek() {
+            if c.is_whitespace() {
+                break;
+            }
+            value.push(c);
+            self.pos += 1;
+        }
+        value
+    }
+
+    fn read_value(&mut self) -> Result<RawValue, SearchQueryError> {
+        match self.peek() {
+            Some('"') => Ok(RawValue::Phrase(self.read_delimited('"', "短语")?)),
+            Some('/') => {
+                let start = self.pos;
+                match self.read_delimited('/', "正则表达式") {
+                    Ok(source) if self.peek().is_none_or(char::is_whitespace) => {
+                        Ok(RawValue::Regex(source))
+                    }
+                    // 不是完整的 /正则/，按普通单词处理
+                    _ => {
+                        self.pos = start;
+                        Ok(RawValue::Word(self.read_word()))
+                    }
+                }
+            }
+            _ => Ok(RawValue::Word(self.read_word())),
+        }
+    }
+
+    fn parse_clause(&mut self) -> Result<SearchClause, SearchQueryError> {
+        // 单独的 `-` 不是取反，作为普通单词读取
+        let negated = self.peek() == Some('-')
+            && self
+                .chars
+                .get(self.pos + 1)
+                .is_some_and(|c| !c.is_whitespace());
+        if negated {
+            self.pos += 1;
+        }
+
+        let field = self.read_field();
+        let value_start = self.pos;
+        let value = self.read_value()?;
+        if let RawValue::Word(word) = &value {
+            if word.is_empty() {
+                return Err(error_at(
+                    value_start,
+                    format!("{}: 后缺少内容", field.as_deref().unwrap_or_default()),
+                ));
+            }
+        }
+
+        let condition = match field.as_deref() {
+            None => text_condition(TextField::Any, value, value_start)?,
+            Some("q") => text_condition(TextField::Question, value, value_start)?,
+            Some("a") => text_condition(TextField::Answer, value, value_start)?,
+            Some("opt") => text_condition(TextField::Options, value, value_start)?,
+            Some("type") => {
+                let raw = plain_value(value, "type", value_start)?;
+                let kind = KindRegistry::load().kind_id(Some(&raw));
+                SearchCondition::QuestionType { raw, kind }
+            }
+            Some("is") => {
+                let flag = plain_value(value, "is", value_start)?.to_lowercase();
+                match flag.as_str() {
+                    "ai" => SearchCondition::IsAi,
+                    "pending" => SearchCondition::IsPending,
+                    _ => {
+                        return Err(error_at(
+                            value_start,
+                            format!("未知的 is: 条件 \"{}\"，可选值为 ai、pending", flag),
+                        ))
+                    }
+                }
+            }
+            Some("folder") => {
+                let path = plain_value(value, "folder", value_start)?;
+                if path.split('/').any(|segment| segment.trim().is_empty()) {
+                    return Err(error_at(value_start, "文件夹路径中存在空的层级"));
+                }
+                SearchCondition::Folder(path)
+            }
+            Some("created") => {
+                let range = plain_value(value, "created", value_start)?;
+                parse_date_range(&range, value_start)?
+            }
+            Some(other) => unreachable!("未处理的字段 {}", other),
+        };
+
+        Ok(SearchClause { condition, negated })
+    }
+}
+
+fn text_condition(
+    field: TextField,
+    value: RawValue,
+    position: usize,
+) -> Result<SearchCondition, SearchQueryError> {
+    let pattern = match value {
+        RawValue::Word(text) | RawValue::Phrase(text) => {
+            if text.is_empty() {
+                return Err(error_at(position, "短语不能为空"));
+            }
+            TextPattern::Contains(text.to_lowercase())
+        }
+        RawValue::Regex(source) => {
+            if source.is_empty() {
+                return Err(error_at(position, "正则表达式不能为空"));
+            }
+            let regex = RegexBuilder::new(&source)
+                .case_insensitive(true)
+                .size_limit(REGEX_SIZE_LIMIT)
+                .build()
+                .map_err(|e| error_at(position, format!("无效的正则表达式: {}", e)))?;
+            TextPattern::Regex(regex)
+        }
+    };
+    Ok(SearchCondition::Text { field, pattern })
+}
+
+fn plain_value(value: RawValue, field: &str, position: usize) -> Result<String, SearchQueryError> {
+    match value {
+        RawValue::Word(text) | RawValue::Phrase(text) if !text.trim().is_empty() => {
+            Ok(text.trim().to_string())
+        }
+        RawValue::Regex(_) => Err(error_at(position, format!("{}: 不支持正则表达式", field))),
+        _ => Err(error_at(position, format!("{}: 后缺少内容", field))),
+    }
+}
+
+fn parse_date(text: &str, position: usize) -> Result<NaiveDate, SearchQueryError> {
+    NaiveDate::parse_from_str(text, "%Y-%m-%d").map_err(|_| {
+        error_at(
+            position,
+            format!("无效的日期 \"{}\"，格式应为 YYYY-MM-DD", text),
+        )
+    })
+}
+
+fn parse_date_range(range: &str, position: usize) -> Result<SearchCondition, SearchQueryError> {
+    let (from, to) = match range.split_once("..") {
+        Some((from, to)) => {
+            let from = (!from.is_empty())
+                .then(|| parse_date(from, position))
+                .transpose()?;
+            let to = (!to.is_empty())
+                .then(|| parse_date(to, position))
+                .transpose()?;
+            if from.is_none() && to.is_none() {
+                return Err(error_at(position, "日期范围至少需要一个端点"));
+            }
+            (from, to)
+        }
+        None => {
+            let day = parse_date(range, position)?;
+            (Some(day), Some(day))
+        }
+    };
+
+    if let (Some(from), Some(to)) = (from, to) {
+        if from > to {
+            return Err(error_at(position, "起始日期不能晚于结束日期"));
+        }
+    }
+    Ok(SearchCondition::Created { from, to })
+}
+
+impl SearchQuery {
+    pub fn parse(input: &str) -> Result<Self, SearchQueryError> {
+        let mut parser = Parser {
+            chars: input.chars().collect(),
+            pos: 0,
+        };
+        let mut clauses = Vec::new();
+
+        loop {
+            parser.skip_whitespace();
+            if parser.peek().is_none() {
+                break;
+            }
+            clauses.push(parser.parse_clause()?);
+        }
+
+        Ok(SearchQuery { clauses })
+    }
+
+    /// 查询中引用的文件夹路径，需要调用方解析为文件夹 ID 集合
+    pub fn folder_paths(&self) -> impl Iterator<Item = &str> {
+        self.clauses
+            .iter()
+            .filter_map(|clause| match &clause.condition {
+                SearchCondition::Folder(path) => Some(path.as_str()),
+                _ => None,
+            })
+    }
+
+    /// 非取反的文本条件，用于高亮
+    pub fn text_terms(&self) -> impl Iterator<Item = (TextField, &TextPattern)> {
+        self.clauses
+            .iter()
+            .filter(|clause| !clause.negated)
+            .filter_map(|clause| match &clause.condition {
+                SearchCondition::Text { field, pattern } => Some((*field, pattern)),
+                _ => None,
+            })
+    }
+
+    // 全部文本条件（含取反的），下标即 TERM_COUNT_FUNCTION 中的条件序号
+    fn text_patterns(&self) -> impl Iterator<Item = &TextPattern> {
+        self.clauses
+            .iter()
+            .filter_map(|clause| match &clause.condition {
+                SearchCondition::Text { pattern, .. } => Some(pattern),
+                _ => None,
+            })
+    }
+
+    /// 在连接上注册 to_sql 生成的 SQL 中用到的文本匹配函数，每次搜索前都需要调用
+    pub fn register_sql_functions(&self, conn: &Connection) -> rusqlite::Result<()> {
+        let patterns: Vec<TextPattern> = self.text_patterns().cloned().collect();
+        conn.create_scalar_function(
+            TERM_COUNT_FUNCTION,
+            2,
+            FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+            move |ctx| {
+                let index = ctx.get::<i64>(0)?;
+                let text = ctx.get::<Option<String>>(1)?;
+                let count = usize::try_from(index)
+                    .ok()
+                    .and_then(|index| patterns.get(index))
+                    .zip(text)
+                    .map_or(0, |(pattern, text)| pattern.count_matches(&text));
+                Ok(count as i64)
+            },
+        )
+    }
+
+    /// 编译成 SQL 条件和相关度表达式。folder_scopes 为 folder: 条件中各路径对应的文件夹 ID 集合
+    /// （含子文件夹），不存在的路径视为空集合
+    pub fn to_sql(
+        &self,
+        folder_scopes: &HashMap<&str, HashSet<i64>>,
+        lengths: &FieldLengths,
+    ) -> SearchSql {
+        let mut filters = Vec::new();
+        let mut params = Vec::new();
+        let mut relevance = Vec::new();
+        let mut term_index = 0;
+
+        for clause in &self.clauses {
+            let condition = match &clause.condition {
+                SearchCondition::Text { field, .. } => {
+                    let fields = match field {
+                        TextField::Any => vec![
+                            ("ar.Question", QUESTION_WEIGHT, lengths.question),
+                            ("ar.Options", OPTIONS_WEIGHT, lengths.options),
+                            ("ar.Answer", ANSWER_WEIGHT, lengths.answer),
+                        ],
+                        TextField::Question => {
+                            vec![("ar.Question", QUESTION_WEIGHT, lengths.question)]
+                        }
+                        TextField::Options => vec![("ar.Options", OPTIONS_WEIGHT, lengths.options)],
+                        TextField::Answer => vec![("ar.Answer", ANSWER_WEIGHT, lengths.answer)],
+                    };
+                    let count = |column: &str| {
+                        format!("{}({}, {})", TERM_COUNT_FUNCTION, term_index, column)
+                    };
+                    if !clause.negated {
+                        relevance.extend(fields.iter().map(|(column, weight, average)| {
+                            format!(
+                                "{weight:?} * {count} * {k1_plus_1:?} / ({count} + {k1:?} * ({keep:?} + {b:?} * length(COALESCE({column}, '')) / {average:?}))",
+                                weight = weight,
+                                count = count(column),
+                                k1_plus_1 = BM25_K1 + 1.0,
+                                k1 = BM25_K1,
+                                keep = 1.0 - BM25_B,
+                                b = BM25_B,
+                                column = column,
+                                average = average.max(1.0),
+                            )
+                        }));
+                    }
+                    let matched = fields
+                        .iter()
+                        .map(|(column, ..)| format!("{} > 0", count(column)))
+                        .collect::<Vec<_>>()
+                        .join(" OR ");
+                    term_index += 1;
+                    matched
+                }
+                SearchCondition::QuestionType { raw, kind } => {
+                    params.push(Value::Text(raw.clone()));
+                    match kind {
+                        Some(kind) => {
+                            params.push(Value::Text(kind.clone()));
+                            "TRIM(ar.QuestionType) = ? COLLATE NOCASE OR ar.QuestionKind = ?"
+                                .to_string()
+                        }
+                        None => "TRIM(ar.QuestionType) = ? COLLATE NOCASE".to_string(),
+                    }
+                }
+                SearchCondition::IsAi => "ar.IsAi".to_string(),
+                SearchCondition::IsPending => "ar.IsPendingCorrection".to_string(),
+                SearchCondition::Folder(path) => {
+                    let ids = folder_scopes
+                        .get(path.as_str())
+                        .map(|scope| {
+                            let mut ids: Vec<i64> = scope.iter().copied().collect();
+                            ids.sort_unstable();
+                            ids.iter()
+                                .map(|id| id.to_string())
+                                .collect::<Vec<_>>()
+                                .join(", ")
+                        })
+                        .unwrap_or_default();
+                    format!("ar.FolderId IN ({})", ids)
+                }
+                SearchCondition::Created { from, to } => {
+                    // CreateTime 形如 "YYYY-MM-DD HH:MM:SS"，取日期部分比较；无法解析的日期为 NULL
+                    let date = "date(substr(ar.CreateTime, 1, 10))";
+                    let mut bounds = Vec::new();
+                    if let Some(from) = from {
+                        bounds.push(format!("{} >= '{}'", date, from.format("%Y-%m-%d")));
+                    }
+                    if let Some(to) = to {
+                        bounds.push(format!("{} <= '{}'", date, to.format("%Y-%m-%d")));
+                    }
+                    bounds.join(" AND ")
+                }
+            };
+            // NULL（如字段为空）按不满足处理，取反后为满足
+            let condition = format!("COALESCE({}, 0)", condition);
+            filters.push(if clause.negated {
+                format!("NOT {}", condition)
+            } else {
+                condition
+            });
+        }
+
+        SearchSql {
+            filter: if filters.is_empty() {
+                "1".to_string()
+            } else {
+                filters.join(" AND ")
+            },
+            params,
+            relevance: if relevance.is_empty() {
+                "1.0".to_string()
+            } else {
+                relevance.join(" + ")
+            },
+        }
+    }
+
+    /// 计算文本条件在题目、答案、选项中的命中区间，重叠区间会被合并
+    pub fn highlights(&self, item: &AIResponse) -> Vec<HighlightSpan> {
+        let fields = [
+            (
+                TextField::Question,
+                "question",
+                Some(item.question.as_str()),
+            ),
+            (TextField::Answer, "answer", item.answer.as_deref()),
+            (TextField::Options, "options", item.options.as_deref()),
+        ];
+
+        let mut highlights = Vec::new();
+        for (field, name, text) in fields {
+            let Some(text) = text else {
+                continue;
+            };
+
+            let mut spans: Vec<(usize, usize)> = self
+                .text_terms()
+                .filter(|(term_field, _)| *term_field == TextField::Any || *term_field == field)
+                .flat_map(|(_, pattern)| pattern.find_spans(text))
+                .collect();
+            spans.sort();
+
+            let mut merged: Vec<(usize, usize)> = Vec::new();
+            for (start, end) in spans {
+                match merged.last_mut() {
+                    Some(last) if start <= last.1 => last.1 = last.1.max(end),
+                    _ => merged.push((start, end)),
+                }
+            }
+
+            highlights.extend(merged.into_iter().map(|(start, end)| HighlightSpan {
+                field: name.to_string(),
+                start,
+                end,
+            }));
+        }
+        highlights
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(question: &str) -> AIResponse {
+        AIResponse {
+            id: 1,
+            question: question.to_string(),
+            options: None,
+            answer: None,
+            question_type: None,
+            folder_id: 0,
+            folder_name: None,
+            create_time: None,
+            is_ai: false,
+            is_pending_correction: false,
+            is_verified: false,
+            question_kind: None,
+        }
+    }
+
+    // 在内存数据库中按编译出的 SQL 条件搜索，返回满足条件的题目 ID
+    fn search(items: &[AIResponse], query: &str) -> Vec<i64> {
+        let conn = Connection::open_in_memory().expect("open database");
+        conn.execute_batch(
+            "CREATE TABLE AIResponses (
+               Id INTEGER PRIMARY KEY, Question TEXT, Options TEXT, Answer TEXT,
+               QuestionType TEXT, QuestionKind TEXT, FolderId INTEGER, CreateTime TEXT,
+               IsAi BOOLEAN, IsPendingCorrection BOOLEAN
+             )",
+        )
+        .expect("create table");
+        for item in items {
+            conn.execute(
+                "INSERT INTO AIResponses VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+                rusqlite::params![
+                    item.id,
+                    item.question,
+                    item.options,
+                    item.answer,
+                    item.question_type,
+                    item.question_kind,
+                    item.folder_id,
+                    item.create_time,
+                    item.is_ai,
+                    item.is_pending_correction
+                ],
+            )
+            .expect("insert item");
+        }
+
+        let query = SearchQuery::parse(query).expect("parse query");
+        query
+            .register_sql_functions(&conn)
+            .expect("register functions");
+        let sql = query.to_sql(&HashMap::new(), &FieldLengths::default());
+        let mut stmt = conn
+            .prepare(&format!(
+                "SELECT ar.Id FROM AIResponses ar WHERE {} ORDER BY ar.Id",
+                sql.filter
+            ))
+            .expect("prepare search");
+        stmt.query_map(rusqlite::params_from_iter(sql.params), |row| row.get(0))
+            .expect("search")
+            .collect::<Result<_, _>>()
+            .expect("read ids")
+    }
+
+    #[test]
+    fn incomplete_syntax_is_read_as_literal_terms() {
+        let query = SearchQuery::parse("/usr/bin - /tmp").expect("parse literal terms");
+        assert_eq!(query.clauses.len(), 3);
+        assert!(query.clauses.iter().all(|clause| !clause.negated));
+        assert_eq!(
+            search(&[item("cd /usr/bin - /tmp")], "/usr/bin - /tmp"),
+            vec![1]
+        );
+        assert!(search(&[item("cd /usr/bin /tmp")], "/usr/bin - /tmp").is_empty());
+
+        assert_eq!(search(&[item("1 - 2")], "1 -"), vec![1]);
+
+        assert_eq!(search(&[item("北京")], "/北京/ -/上海/"), vec![1]);
+        assert!(search(&[item("北京 上海")], "/北京/ -/上海/").is_empty());
+    }
+
+    #[test]
+    fn clauses_filter_by_field_flag_type_and_date() {
+        let capital = AIResponse {
+            id: 1,
+            options: Some("A. 北京 B. 上海".to_string()),
+            question_type: Some("单选".to_string()),
+            is_ai: true,
+            create_time: Some("2024-03-01 08:00:00".to_string()),
+            ..item("中国的首都是哪里")
+        };
+        let river = AIResponse {
+            id: 2,
+            is_pending_correction: true,
+            create_time: Some("2024-05-20 12:00:00".to_string()),
+            ..item("中国最长的河流 是哪条")
+        };
+        let other = AIResponse {
+            id: 3,
+            question_type: Some("单项选择".to_string()),
+            question_kind: Some("single".to_string()),
+            ..item("日本的首都是哪里")
+        };
+        let items = [capital, river, other];
+        let search = |query: &str| search(&items, query);
+
+        assert_eq!(search("首都 哪里"), vec![1, 3]);
+        assert_eq!(search("q:中国 -is:ai"), vec![2]);
+        assert_eq!(search("opt:北京 type:单选"), vec![1]);
+        // 类型字段原样匹配，或与识别出的题型相同
+        assert_eq!(search("type:single"), vec![3]);
+        assert_eq!(search("\"河流 是\""), vec![2]);
+        assert_eq!(search("q:/^(中国|日本).*首都/"), vec![1, 3]);
+        assert_eq!(search("is:pending"), vec![2]);
+        assert_eq!(search("created:2024-03-01"), vec![1]);
+        assert_eq!(search("created:2024-04-01..2024-12-31"), vec![2]);
+        assert_eq!(search("created:..2024-04-01"), vec![1]);
+    }
+
+    #[test]
+    fn malformed_queries_are_rejected() {
+        for malformed in [
+            "q:\"未闭合",
+            "/[/",
+            "is:manual",
+            "created:2024-13-01",
+            "created:2024-05-01..2024-04-01",
+            "type:",
+        ] {
+            assert!(
+                SearchQuery::parse(malformed).is_err(),
+                "{} should be rejected",
+                malformed
+            );
+        }
+    }
+
+    #[test]
+    fn highlights_use_character_offsets_of_the_original_text() {
+        let query = SearchQuery::parse("STANBUL 北京").expect("parse query");
+        let spans = query.highlights(&item("İstanbul 和北京"));
+        let ranges: Vec<(usize, usize)> = spans.iter().map(|span| (span.start, span.end)).collect();
+        assert_eq!(ranges, vec![(1, 8), (10, 12)]);
+    }
+}
@@ -0,0 +1,322 @@
+//! 分词配置：题库匹配（extract_query_keywords）与前端搜索（segment_text）共用的 jieba 分词器和停用词。
+//!
+//! 数据目录（数据库所在目录）下的两个文件：
+//! - `user_dict.txt`：用户词典，每行 `词语 [词频] [词性]`，未写词频时由 jieba 推荐，`#` 开头为注释；
+//! - `stopwords.txt`：停用词，每行一个，`#` 开头为注释。文件存在时替换内置停用词表。
+//!
+//! 文件修改后，下一次查询或分词时自动重新加载，也可以调用 reload_segmenter_config 立即重新加载。
+
+use jieba_rs::Jieba;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, OnceLock, RwLock};
+use std::time::SystemTime;
+
+use crate::database::get_data_dir;
+
+pub const USER_DICT_FILE: &str = "user_dict.txt";
+pub const STOPWORDS_FILE: &str = "stopwords.txt";
+
+/// 内置停用词，没有用户停用词文件时使用
+pub const DEFAULT_STOPWORDS: &[&str] = &[
+    "的",
+    "地",
+    "得",
+    "了",
+    "着",
+    "吗",
+    "呢",
+    "啊",
+    "呀",
+    "吧",
+    "么",
+    "嘛",
+    "在",
+    "是",
+    "和",
+    "与",
+    "及",
+    "或",
+    "并",
+    "且",
+    "将",
+    "把",
+    "被",
+    "由",
+    "对",
+    "于",
+    "中",
+    "上",
+    "下",
+    "请问",
+    "哪里",
+    "哪儿",
+    "哪个",
+    "哪种",
+    "哪项",
+    "哪些",
+    "什么",
+    "怎么",
+    "怎样",
+    "如何",
+    "为何",
+    "为什么",
+    "多少",
+    "几",
+    "一下",
+    "以下",
+    "下列",
+    "题目",
+    "选项",
+    "答案",
+    "内容",
+    "说法",
+    "图片",
+    "图中",
+    "名字",
+    "名称",
+    "城市",
+    "国家",
+    "地区",
+    "地方",
+];
+
+/// 分词器当前加载的配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SegmenterStatus {
+    pub user_dict_path: String,
+    /// 用户词典中成功加载的词语数量，文件不存在时为 0
+    pub user_dict_words: usize,
+    /// 用户词典中无法解析而被跳过的行
+    pub skipped_dict_lines: Vec<String>,
+    pub stopwords_path: String,
+    pub stopword_count: usize,
+    /// 没有用户停用词文件，使用内置停用词
+    pub using_default_stopwords: bool,
+}
+
+pub struct Segmenter {
+    jieba: Jieba,
+    stopwords: HashSet<String>,
+    status: SegmenterStatus,
+    // 加载时两个文件的修改时间，用于判断是否需要重新加载
+    dict_modified: Option<SystemTime>,
+    stopwords_modified: Option<SystemTime>,
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
+
+fn meaningful_lines(content: &str) -> impl Iterator<Item = &str> {
+    content
+        .lines()
+        .map(|line| line.trim_start_matches('\u{feff}').trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+}
+
+impl Segmenter {
+    /// 从指定目录加载用户词典与停用词，文件不存在时使用默认配置
+    pub fn load_from(dir: &Path) -> Result<Self, String> {
+        let dict_path = dir.join(USER_DICT_FILE);
+        let stopwords_path = dir.join(STOPWORDS_FILE);
+
+        let mut jieba = Jieba::new();
+        let mut user_dict_words = 0;
+        let mut skipped_dict_lines = Vec::new();
+        if dict_path.exists() {
+            let content =
+                fs::read_to_string(&dict_path).map_err(|e| format!("读取用户词典失败: {}", e))?;
+            for line in meaningful_lines(&content) {
+                let mut parts = line.split_whitespace();
+                let Some(word) = parts.next() else {
+                    continue;
+                };
+                let freq = match parts.next().map(str::parse::<usize>) {
+                    Some(Ok(freq)) => Some(freq),
+                    Some(Err(_)) => {
+                        skipped_dict_lines.push(line.to_string());
+                        continue;
+                    }
+                    None => None,
+                };
+                jieba.add_word(word, freq, parts.next());
+                user_dict_words += 1;
+            }
+        }
+
+        let using_default_stopwords = !stopwords_path.exists();
+        let stopwords: HashSet<String> = if using_default_stopwords {
+            DEFAULT_STOPWORDS
+                .iter()
+                .map(|word| word.to_string())
+                .collect()
+        } else {
+            let content = fs::read_to_string(&stopwords_path)
+                .map_err(|e| format!("读取停用词文件失败: {}", e))?;
+            meaningful_lines(&content)
+                .map(|word| word.to_lowercase())
+                .collect()
+        };
+
+        Ok(Segmenter {
+            jieba,
+            status: SegmenterStatus {
+                user_dict_path: dict_path.to_string_lossy().to_string(),
+                user_dict_words,
+                skipped_dict_lines,
+                stopwords_path: stopwords_path.to_string_lossy().to_string(),
+                stopword_count: stopwords.len(),
+                using_default_stopwords,
+            },
+            stopwords,
+            dict_modified: modified_time(&dict_path),
+            stopwords_modified: modified_time(&stopwords_path),
+        })
+    }
+
+    fn is_stale(&self) -> bool {
+        modified_time(Path::new(&self.status.user_dict_path)) != self.dict_modified
+            || modified_time(Path::new(&self.status.stopwords_path)) != self.stopwords_modified
+    }
+
+    /// 搜索引擎模式分词
+    pub fn cut_for_search<'a>(&self, text: &'a str) -> Vec<&'a str> {
+        self.jieba.cut_for_search(text, false)
+    }
+
+    /// 文本中的实体词：人名、地名、机构名等专有名词，英文词以及含数字的数词（已转为小写）。
+    /// 语义匹配用它防止“韩国的首都”与“美国的首都”这类只差实体的题目互相匹配。
+    pub fn entity_terms(&self, text: &str) -> Vec<String> {
+        let mut terms: Vec<String> = Vec::new();
+        for tag in self.jieba.tag(text, true) {
+            let word = tag.word.trim();
+            let is_entity = match tag.tag {
+                "nr" | "ns" | "nt" | "nz" | "eng" => true,
+                "m" => word.chars().any(|c| c.is_ascii_digit()),
+                _ => false,
+            };
+            if !is_entity || word.is_empty() || self.is_stopword(word) {
+                continue;
+            }
+            let word = word.to_lowercase();
+            if !terms.contains(&word) {
+                terms.push(word);
+            }
+        }
+        terms
+    }
+
+    pub fn is_stopword(&self, token: &str) -> bool {
+        self.stopwords.contains(token)
+    }
+
+    pub fn status(&self) -> &SegmenterStatus {
+        &self.status
+    }
+}
+
+static SEGMENTER: OnceLock<RwLock<Option<Arc<Segmenter>>>> = OnceLock::new();
+
+fn segmenter_slot() -> &'static RwLock<Option<Arc<Segmenter>>> {
+    SEGMENTER.get_or_init(|| RwLock::new(None))
+}
+
+// 加载失败（如文件无法读取）时退回默认配置，不影响查询；文件再次修改后会重新尝试加载
+fn load_or_default(dir: &Path) -> Segmenter {
+    Segmenter::load_from(dir).unwrap_or_else(|e| {
+        println!("⚠️ 加载分词配置失败，使用默认配置: {}", e);
+        let dict_path = dir.join(USER_DICT_FILE);
+        let stopwords_path = dir.join(STOPWORDS_FILE);
+        let stopwords: HashSet<String> = DEFAULT_STOPWORDS
+            .iter()
+            .map(|word| word.to_string())
+            .collect();
+        Segmenter {
+            jieba: Jieba::new(),
+            status: SegmenterStatus {
+                user_dict_path: dict_path.to_string_lossy().to_string(),
+                user_dict_words: 0,
+                skipped_dict_lines: Vec::new(),
+                stopwords_path: stopwords_path.to_string_lossy().to_string(),
+                stopword_count: stopwords.len(),
+                using_default_stopwords: true,
+            },
+            stopwords,
+            dict_modified: modified_time(&dict_path),
+            stopwords_modified: modified_time(&stopwords_path),
+        }
+    })
+}
+
+/// 当前的分词器；首次使用时加载
+pub fn segmenter() -> Arc<Segmenter> {
+    if let Some(segmenter) = segmenter_slot().read().unwrap().as_ref() {
+        return segmenter.clone();
+    }
+    let mut slot = segmenter_slot().write().unwrap();
+    slot.get_or_insert_with(|| Arc::new(load_or_default(&get_data_dir())))
+        .clone()
+}
+
+/// 词典或停用词文件有变化时重新加载，返回是否重新加载
+pub fn refresh_segmenter_if_changed() -> bool {
+    if !segmenter().is_stale() {
+        return false;
+    }
+    reload_segmenter();
+    true
+}
+
+/// 立即重新加载用户词典与停用词
+pub fn reload_segmenter() -> SegmenterStatus {
+    let segmenter = Arc::new(load_or_default(&get_data_dir()));
+    let status = segmenter.status().clone();
+    *segmenter_slot().write().unwrap() = Some(segmenter);
+    println!(
+        "✅ 分词配置已加载：用户词典 {} 个词，停用词 {} 个",
+        status.user_dict_words, status.stopword_count
+    );
+    status
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn user_dictionary_and_stopwords_replace_defaults() {
+        let dir = std::env::temp_dir().join(format!("zerror-segmenter-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).expect("create temp dir");
+        let text = "阿莫西林克拉维酸钾适用于哪个城市的患者";
+
+        let default = Segmenter::load_from(&dir).expect("load default segmenter");
+        assert!(default.status().using_default_stopwords);
+        assert!(!default.cut_for_search(text).contains(&"阿莫西林克拉维酸钾"));
+        assert!(default.is_stopword("城市"));
+
+        std::fs::write(
+            dir.join(USER_DICT_FILE),
+            "# 药品名\n阿莫西林克拉维酸钾 2000 n\n坏行 不是数字\n",
+        )
+        .expect("write user dict");
+        std::fs::write(dir.join(STOPWORDS_FILE), "# 自定义停用词\n患者\n适用\n")
+            .expect("write stopwords");
+
+        let custom = Segmenter::load_from(&dir).expect("load custom segmenter");
+        assert_eq!(custom.status().user_dict_words, 1);
+        assert_eq!(
+            custom.status().skipped_dict_lines,
+            vec!["坏行 不是数字".to_string()]
+        );
+        assert!(!custom.status().using_default_stopwords);
+        assert!(custom.cut_for_search(text).contains(&"阿莫西林克拉维酸钾"));
+        // 用户停用词替换内置停用词：城市不再是停用词，患者是
+        assert!(!custom.is_stopword("城市"));
+        assert!(custom.is_stopword("患者"));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -1,7 +1,21 @@
-use crate::database::{insert_ai_response, query_database, set_question_pending_correction};
+use crate::answer_extraction::extract_answer_for_model;
+use crate::answer_format::{AnswerFormat, AnswerFormats};
+use crate::answer_validation::{validate_answer, AnswerValidationConfig};
+use crate::database::{
+    explain_query_match_in_scope, insert_ai_response, mark_question_pending_correction,
+    query_database, read_config_value, resolve_query_scope, scope_folder_chain, QueryMatch,
+    QueryMatchResult, QueryScope,
+};
+use crate::logger::{ModelReply, RequestLogger};
+use crate::prompt_template::{
+    build_query_prompts, PromptContext, PromptTemplateConfig, QueryPrompts, RenderedPrompt,
+};
+use crate::question_kind::{detect_question_kind, KindRegistry};
+use crate::text_normalize::{contains_html, html_to_text};
 use crate::types::{
-    ModelCallProgressRequest, ModelCallResponseRequest, QueryData, QueryRequest, QueryResponse,
-    ServerInfo, ServerState,
+    folder_refs_from_value, parse_folder_refs, ModelCallProgressRequest, ModelCallResponseRequest,
+    QueryData, QueryExplainRequest, QueryRequest, QueryResponse, QuestionDecoration, ServerInfo,
+    ServerState,
 };
 use futures_util::StreamExt;
 use regex::Regex;
@@ -15,18 +29,21 @@ use warp::Filter;
 
 const QUERY_TEST_PAGE_HTML: &str = include_str!("query_test_page.html");
 
+// 去掉 Authorization 请求头中的 Bearer 前缀
+fn bearer_token(value: &str) -> String {
+    let value = value.trim();
+    if value.to_lowercase().starts_with("bearer ") {
+        value[7..].trim().to_string()
+    } else {
+        value.to_string()
+    }
+}
+
 /// 验证管理员 token（从 Authorization: Bearer <token> 或直接值中提取）
 fn check_admin_token(auth: &Option<String>) -> bool {
     let token = match auth {
         None => return false,
-        Some(v) => {
-            let v = v.trim();
-            if v.to_lowercase().starts_with("bearer ") {
-                v[7..].trim().to_string()
-            } else {
-                v.to_string()
-            }
-        }
+        Some(v) => bearer_token(v),
     };
     if token.is_empty() {
         return false;
@@ -53,6 +70,139 @@ fn contains_url(text: &str) -> bool {
     url_regex.is_match(text)
 }
 
+// 去掉 OCS 转发的题目与选项中的 HTML 标记，返回清理前的原始题目 HTML（不含 HTML 时为 None）
+fn sanitize_query_request(request: &mut QueryRequest) -> Option<String> {
+    if let Some(options) = request
+        .options
+        .as_deref()
+        .filter(|options| contains_html(options))
+    {
+        request.options = Some(html_to_text(options));
+    }
+    if !contains_html(&request.title) {
+        return None;
+    }
+    let raw_title = std::mem::take(&mut request.title);
+    request.title = html_to_text(&raw_title);
+    Some(raw_title)
+}
+
+/// 题库查询携带的用户 token：Authorization 请求头优先，其次是 token 查询参数
+fn request_user_token(
+    headers: &HeaderMap,
+    params: Option<&HashMap<String, String>>,
+) -> Option<String> {
+    headers
+        .get("authorization")
+        .and_then(|value| value.to_str().ok())
+        .map(bearer_token)
+        .or_else(|| {
+            params
+                .and_then(|params| params.get("token"))
+                .map(|token| token.trim().to_string())
+        })
+        .filter(|token| !token.is_empty())
+}
+
+/// multiUser.users 中 token 绑定的文件夹（folders 字段，文件夹 ID 或路径），
+/// 未启用多用户（multiUser.enabled）或未绑定时为空
+fn user_bound_folders(token: Option<&str>) -> Vec<String> {
+    let Some(token) = token.map(str::trim).filter(|token| !token.is_empty()) else {
+        return Vec::new();
+    };
+    let Some(multi_user) = read_config_value("multiUser").filter(|v| {
+        v.get("enabled")
+            .and_then(|enabled| enabled.as_bool())
+            .unwrap_or(false)
+    }) else {
+        return Vec::new();
+    };
+    multi_user
+        .get("users")
+        .and_then(|u| u.as_array())
+        .and_then(|users| {
+            users
+                .iter()
+                .find(|user| user.get("token").and_then(|v| v.as_str()) == Some(token))
+        })
+        .and_then(|user| user.get("folders"))
+        .map(folder_refs_from_value)
+        .unwrap_or_default()
+}
+
+/// 本次查询的范围：请求指定的文件夹与 token 绑定的文件夹取交集，只有一方时取该方，都没有时为整个题库
+fn resolve_request_scope(
+    folders: &[String],
+    token: Option<&str>,
+) -> Result<Option<QueryScope>, String> {
+    let bound = user_bound_folders(token);
+    let requested = (!folders.is_empty())
+        .then(|| resolve_query_scope(folders))
+        .transpose()?;
+    let bound = (!bound.is_empty())
+        .then(|| resolve_query_scope(&bound))
+        .transpose()
+        .map_err(|e| format!("token 绑定的文件夹无效: {}", e))?;
+    match (requested, bound) {
+        (Some(requested), Some(bound)) => {
+            let scope = requested.intersect(&bound);
+            if scope.folder_ids.is_empty() {
+                return Err("请求的文件夹不在当前 token 可查询的范围内".to_string());
+            }
+            Ok(Some(scope))
+        }
+        (requested, bound) => Ok(requested.or(bound)),
+    }
+}
+
+/// 按本次请求的答案格式配置档转换返回给 OCS 的答案
+fn format_served_answer(format: &AnswerFormat, request: &QueryRequest, answer: &str) -> String {
+    format.format_answer(
+        answer,
+        request.options.as_deref(),
+        request.query_type.as_deref(),
+    )
+}
+
+// 查询字符串中的开关参数，1 / true / yes 为真
+fn query_param_flag(params: &HashMap<String, String>, key: &str) -> bool {
+    params
+        .get(key)
+        .is_some_and(|value| matches!(value.trim(), "1" | "true" | "yes"))
+}
+
+/// POST /query 的请求体；format、all 与 folders 也可以放在查询字符串中，请求体中的值优先
+fn query_post_request() -> impl Filter<Extract = (QueryRequest,), Error = warp::Rejection> + Clone {
+    warp::query::<HashMap<String, String>>()
+        .and(warp::body::json())
+        .map(merge_query_params)
+}
+
+fn merge_query_params(params: HashMap<String, String>, mut request: QueryRequest) -> QueryRequest {
+    if request.format.is_none() {
+        request.format = params.get("format").cloned();
+    }
+    if request.folders.is_empty() {
+        if let Some(folders) = params.get("folders") {
+            request.folders = parse_folder_refs(folders);
+        }
+    }
+    request.all = request.all || query_param_flag(&params, "all");
+    request
+}
+
+/// 请求全部匹配题目时使用的 Accept 类型
+const CANDIDATES_MEDIA_TYPE: &str = "application/vnd.zerror.candidates+json";
+
+/// 是否返回全部匹配题目：请求中 all 为真，或 Accept 请求头包含 CANDIDATES_MEDIA_TYPE
+fn wants_all_candidates(headers: &HeaderMap, all: bool) -> bool {
+    all || headers
+        .get_all("accept")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .any(|value| value.to_ascii_lowercase().contains(CANDIDATES_MEDIA_TYPE))
+}
+
 fn resolve_request_origin(headers: &HeaderMap) -> String {
     let host = headers
         .get("host")
@@ -70,16 +220,47 @@ fn escape_html(text: &str) -> String {
         .replace('\'', "&#39;")
 }
 
-fn build_pending_correction_button(origin: &str, question_id: i64, is_pending_correction: bool) -> String {
-    if question_id <= 0 {
-        return String::new();
+/// 题目字段的修饰方式：请求中的 format 参数优先，其次是 config.json 中的 questionDecoration，默认为按钮
+fn resolve_question_decoration(requested: Option<&str>) -> QuestionDecoration {
+    let requested = requested.map(str::to_string);
+    let configured = read_config_value("questionDecoration")
+        .and_then(|value| value.as_str().map(str::to_string));
+    for value in [requested, configured].into_iter().flatten() {
+        if value.trim().is_empty() {
+            continue;
+        }
+        match QuestionDecoration::parse(&value) {
+            Some(decoration) => return decoration,
+            None => println!("⚠️ 未知的题目修饰方式: {}，已忽略", value),
+        }
+    }
+    QuestionDecoration::default()
+}
+
+fn pending_correction_url(origin: &str, question_id: i64) -> Option<String> {
+    (question_id > 0).then(|| {
+        format!(
+            "{}/api/questions/{}/pending-correction",
+            origin, question_id
+        )
+    })
+}
+
+fn build_pending_correction_link(url: &str, is_pending_correction: bool) -> String {
+    if is_pending_correction {
+        return "<span style=\"color:#f59e0b;font-size:12px;\">已标记待修正</span>".to_string();
     }
+    format!(
+        "<a href=\"{}\" target=\"_blank\" rel=\"noopener noreferrer\" style=\"color:#ef4444;font-size:12px;\">标记为待修正</a>",
+        escape_html(url)
+    )
+}
 
+fn build_pending_correction_button(url: &str, is_pending_correction: bool) -> String {
     if is_pending_correction {
         return "<button type=\"button\" disabled style=\"padding:4px 10px;border:none;border-radius:999px;background:#f59e0b;color:#fff;font-size:12px;cursor:not-allowed;opacity:0.75;white-space:nowrap;\">已标记待修正</button>".to_string();
     }
 
-    let url = format!("{}/api/questions/{}/pending-correction", origin, question_id);
     format!(
         r#"<button type="button" style="padding:4px 10px;border:none;border-radius:999px;background:#ef4444;color:#fff;font-size:12px;cursor:pointer;white-space:nowrap;" onclick="(async()=>{{const btn=this;if(btn.dataset.loading==='1')return;const text=btn.textContent||'标记为待修正';btn.dataset.loading='1';btn.disabled=true;btn.textContent='标记中...';try{{const res=await fetch('{url}',{{method:'POST'}});const data=await res.json().catch(()=>({{success:false,message:'标记失败'}}));if(!res.ok||!data.success)throw new Error(data.message||'标记失败');btn.textContent='已标记待修正';btn.style.opacity='0.75';btn.style.cursor='not-allowed';}}catch(error){{btn.disabled=false;btn.textContent=text;alert(error&&error.message?error.message:'标记失败');}}finally{{delete btn.dataset.loading;}}}})()">标记为待修正</button>"#,
         url = url
@@ -93,16 +274,23 @@ fn build_query_data(
     answer: String,
     is_ai: bool,
     is_pending_correction: bool,
+    decoration: QuestionDecoration,
 ) -> QueryData {
-    let escaped_question = escape_html(question).replace('\n', "<br>");
-    let button_html = build_pending_correction_button(origin, question_id, is_pending_correction);
-    let question_html = if button_html.is_empty() {
-        escaped_question
-    } else {
-        format!(
+    let pending_correction_url = pending_correction_url(origin, question_id);
+    let escaped_question = || escape_html(question).replace('\n', "<br>");
+    let question_html = match (decoration, pending_correction_url.as_deref()) {
+        (QuestionDecoration::None, _) => question.to_string(),
+        (_, None) => escaped_question(),
+        (QuestionDecoration::Link, Some(url)) => format!(
+            "{}<br>{}",
+            escaped_question(),
+            build_pending_correction_link(url, is_pending_correction)
+        ),
+        (QuestionDecoration::Button, Some(url)) => format!(
             "<div style=\"display:flex;align-items:flex-start;gap:8px;flex-wrap:wrap;\"><span style=\"flex:1 1 auto;min-width:0;\">{}</span>{}</div>",
-            escaped_question, button_html
-        )
+            escaped_question(),
+            build_pending_correction_button(url, is_pending_correction)
+        ),
     };
 
     QueryData {
@@ -111,96 +299,197 @@ fn build_query_data(
         answer,
         is_ai,
         is_pending_correction,
+        pending_correction_url,
+        score: None,
+        is_verified: None,
+        folder_id: None,
+        folder: None,
     }
 }
 
-#[derive(Debug, Clone, Copy)]
-enum QuestionKind {
-    Single,
-    Multiple,
-    Judgement,
-    Completion,
+/// 返回全部匹配题目时附带得分与文件夹
+fn attach_match_details(mut data: QueryData, row: &QueryMatch) -> QueryData {
+    data.score = Some(row.score);
+    data.is_verified = Some(row.is_verified);
+    data.folder_id = Some(row.folder_id);
+    data.folder = row.folder.clone();
+    data
 }
 
-impl QuestionKind {
-    fn chinese_name(&self) -> &'static str {
-        match self {
-            QuestionKind::Single => "单选",
-            QuestionKind::Multiple => "多选",
-            QuestionKind::Judgement => "判断",
-            QuestionKind::Completion => "填空",
-        }
-    }
+/// 数据库中没有答案时发送给模型的提示词，按 promptTemplates 配置的模板生成
+fn build_model_query_prompt(
+    title: &str,
+    options: Option<&str>,
+    query_type: Option<&str>,
+    scope: Option<&QueryScope>,
+) -> QueryPrompts {
+    build_query_prompts(&PromptContext {
+        title: title.to_string(),
+        options: options.map(str::to_string),
+        query_type: query_type.map(str::to_string),
+        folders: scope_folder_chain(scope),
+    })
+}
 
-    fn prompt_hint(&self) -> &'static str {
-        match self {
-            QuestionKind::Single => "这是单选题,只有一个正确答案.请返回正确选项的完整文字内容,不要返回A/B/C/D等选项字母或序号.",
-            QuestionKind::Multiple => "这是多选题,可能有多个正确答案.请返回所有正确选项的完整文字内容,多个答案用\"###\"连接,不要返回A/B/C/D等选项字母.",
-            QuestionKind::Judgement => "这是判断题,请只回答正确或错误,不要添加任何其他文字或标点.",
-            QuestionKind::Completion => "这是填空题.如果有多空请用\"###\"连接每个空的答案,只有一个空则直接返回答案内容,不要加序号.",
-        }
-    }
+/// 预览题目在当前配置下发送给模型的提示词，model 为空时不考虑按模型配置的模板
+#[tauri::command]
+pub async fn preview_query_prompt(
+    mut request: QueryRequest,
+    model: Option<String>,
+) -> Result<RenderedPrompt, String> {
+    sanitize_query_request(&mut request);
+    let scope = resolve_request_scope(&request.folders, None)?;
+    let context = PromptContext {
+        title: request.title,
+        options: request.options,
+        query_type: request.query_type,
+        folders: scope_folder_chain(scope.as_ref()),
+    };
+    Ok(PromptTemplateConfig::load().render(&KindRegistry::load(), &context, model.as_deref()))
 }
 
-fn detect_question_kind(query_type: &str) -> Option<QuestionKind> {
-    let trimmed = query_type.trim();
-    if trimmed.is_empty() {
-        return None;
-    }
+/// 前端按实际调用的模型从模型调用请求的 query 与 model_queries 中选择提示词
+#[tauri::command]
+pub fn resolve_model_prompt(
+    prompt: String,
+    model_prompts: HashMap<String, String>,
+    model: String,
+) -> String {
+    crate::prompt_template::resolve_model_prompt(&prompt, &model_prompts, &model).to_string()
+}
 
-    let normalized = trimmed.to_lowercase();
+/// 模型给出的最终答案
+struct ModelAnswer {
+    answer: String,
+    /// 答案未通过校验，保存时标记为待纠错
+    needs_correction: bool,
+}
 
-    if normalized.contains("single") || trimmed.contains("单选") || trimmed.contains("单项选择")
-    {
-        Some(QuestionKind::Single)
-    } else if normalized.contains("multiple")
-        || trimmed.contains("多选")
-        || trimmed.contains("多项选择")
-    {
-        Some(QuestionKind::Multiple)
-    } else if normalized.contains("judgement")
-        || normalized.contains("judgment")
-        || trimmed.contains("判断")
-    {
-        Some(QuestionKind::Judgement)
-    } else if normalized.contains("completion") || trimmed.contains("填空") {
-        Some(QuestionKind::Completion)
-    } else {
-        None
-    }
+enum ModelAnswerError {
+    /// 模型返回了错误信息
+    Model(String),
+    /// 等待模型响应超时或通道关闭
+    Timeout(String),
 }
 
-fn build_model_query_prompt(
-    title: &str,
-    options: Option<&str>,
-    query_type: Option<&str>,
-) -> String {
-    let mut q = String::from(
-        "你是一个专业的答题助手。我会给你一道题目，请先分别做两件事：\n"
-    );
+/// 重新询问失败时保留上一次未通过校验的答案，标记为待纠错；不是重新询问时返回 None
+fn keep_previous_answer(previous: Option<String>, reason: &str) -> Option<ModelAnswer> {
+    let answer = previous?;
+    println!("⚠️ 重新询问模型失败，保留上一次的答案: {}", reason);
+    Some(ModelAnswer {
+        answer,
+        needs_correction: true,
+    })
+}
 
-    q.push_str("1. 仔细分析题目，理解题目在问什么，如果有选项则逐一分析每个选项是否正确。\n");
-    q.push_str("2. 输出最终答案，**只输出一个JSON对象**，不要加代码块标记，不要加任何其他文字。\n\n");
-    q.push_str("格式：{\"answer\": \"你的最终答案\"}\n\n");
+/// 请求模型作答，提取并校验答案。答案未通过校验时按 answerValidation 配置重新询问或标记为待纠错；
+/// 重新询问使用新的请求 ID 和 promptTemplates.retry 模板，重试超时、出错、认为题目不完整或没有给出答案时
+/// 保留上一次的答案并标记为待纠错。
+async fn request_model_answer(
+    logger: &RequestLogger,
+    request_id: &str,
+    prompt: String,
+    model_prompts: HashMap<String, String>,
+    request: &QueryRequest,
+    has_url: bool,
+) -> Result<ModelAnswer, ModelAnswerError> {
+    let rule = request
+        .query_type
+        .as_deref()
+        .and_then(detect_question_kind)
+        .map(|kind| kind.answer_rule);
+    let validation = AnswerValidationConfig::load();
+    // URL 题目由前端的视觉分析窗口处理，需要对应的请求日志，无法用新的请求 ID 重新询问
+    let retry_limit = if has_url { 0 } else { validation.retry_limit() };
+    // 等待模型调用完成（前端有keepalive心跳防止超时,此值为总时长上限）
+    let wait_secs = if has_url { 120 } else { 60 };
+
+    let mut call_id = request_id.to_string();
+    let mut call_prompt = prompt.clone();
+    let mut call_model_prompts = model_prompts.clone();
+    let mut previous: Option<String> = None;
+    let mut attempt = 0;
+    loop {
+        logger.send_model_call_request(call_id.clone(), call_prompt, call_model_prompts);
+
+        let reply = logger
+            .wait_for_model_response(call_id.clone(), wait_secs)
+            .await;
+        let ModelReply {
+            content: model_content,
+            model,
+        } = match reply {
+            Ok(reply) => reply,
+            Err(e) => {
+                return keep_previous_answer(previous, &e).ok_or(ModelAnswerError::Timeout(e))
+            }
+        };
+        println!("✅ Received model response: {}", model_content);
+        if let Some(err_msg) = is_model_error(&model_content) {
+            return keep_previous_answer(previous, &err_msg)
+                .ok_or(ModelAnswerError::Model(err_msg));
+        }
 
-    if let Some(raw_type) = query_type.map(str::trim).filter(|value| !value.is_empty()) {
-        if let Some(kind) = detect_question_kind(raw_type) {
-            q.push_str(&format!("【题目类型：{}题】\n", kind.chinese_name()));
-            q.push_str(&format!("提示：{}\n", kind.prompt_hint()));
-        } else {
-            q.push_str(&format!("【题目类型字段：{}】\n", raw_type));
+        let extraction = extract_answer_for_model(&model_content, model.as_deref());
+        match extraction.strategy {
+            Some(strategy) => {
+                println!("✅ 答案提取策略 {}: {}", strategy.name(), extraction.answer)
+            }
+            None => println!("⚠️ 未能提取到结构化答案,返回原始内容"),
         }
-    }
 
-    q.push_str(&format!("【题目】\n{}\n", title));
+        // Check for incomplete question response
+        if model_content.contains("题目不完整,无法确定具体问题.") {
+            if let Some(kept) = keep_previous_answer(previous, "模型认为题目不完整") {
+                return Ok(kept);
+            }
+            println!("⚠️ 检测到题目不完整,将答案留空");
+            return Ok(ModelAnswer {
+                answer: String::new(),
+                needs_correction: false,
+            });
+        }
 
-    if let Some(options) = options.map(str::trim).filter(|value| !value.is_empty()) {
-        q.push_str(&format!("【选项】\n{}\n", options));
-    }
+        let answer = extraction.answer.trim().to_string();
+        if answer.is_empty() {
+            if let Some(kept) = keep_previous_answer(previous, "没有给出答案") {
+                return Ok(kept);
+            }
+        }
+        if answer.is_empty() || !validation.enabled {
+            return Ok(ModelAnswer {
+                answer,
+                needs_correction: false,
+            });
+        }
 
-    q.push_str("\n请先分析，再输出答案JSON：");
+        let validated = validate_answer(&answer, request.options.as_deref(), rule);
+        let Some(problem) = validated.problem else {
+            return Ok(ModelAnswer {
+                answer: validated.answer,
+                needs_correction: false,
+            });
+        };
+        println!("⚠️ 答案未通过校验: {}", problem);
+        if attempt >= retry_limit {
+            return Ok(ModelAnswer {
+                answer: validated.answer,
+                needs_correction: true,
+            });
+        }
 
-    q
+        attempt += 1;
+        call_id = format!("{}-retry{}", request_id, attempt);
+        let templates = PromptTemplateConfig::load();
+        call_prompt = templates.render_retry(&prompt, &answer, &problem);
+        call_model_prompts = model_prompts
+            .iter()
+            .map(|(pattern, prompt)| {
+                (pattern.clone(), templates.render_retry(prompt, &answer, &problem))
+            })
+            .collect();
+        previous = Some(validated.answer);
+    }
 }
 
 /// 启动HTTP服务器
@@ -299,8 +588,8 @@ pub async fn start_server(
     let query_post_route = warp::path("query")
         .and(warp::post())
         .and(warp::header::headers_cloned())
-        .and(warp::body::json())
-        .and_then(move |headers: HeaderMap, request: QueryRequest| {
+        .and(query_post_request())
+        .and_then(move |headers: HeaderMap, mut request: QueryRequest| {
             let logger = logger_for_query.clone();
             async move {
                 let start_time = std::time::Instant::now();
@@ -338,6 +627,9 @@ pub async fn start_server(
                     user_agent,
                 );
 
+                // 清理题目中的 HTML，图片以地址形式保留在题目中
+                let raw_title_html = sanitize_query_request(&mut request);
+
                 // 检测title和options中是否包含URL
                 let mut has_url = contains_url(&request.title);
                 if let Some(options) = &request.options {
@@ -347,32 +639,77 @@ pub async fn start_server(
                 }
 
                 let request_origin = resolve_request_origin(&headers);
+                let user_token = request_user_token(&headers, None);
+                let (format_name, answer_format) = AnswerFormats::load().resolve_request(
+                    request.answer_format.as_deref(),
+                    user_token.as_deref(),
+                );
+                println!("🧾 使用答案格式: {}", format_name);
+                let decoration = resolve_question_decoration(request.format.as_deref());
+                let return_all = wants_all_candidates(&headers, request.all);
+
+                // 查询范围：请求指定的文件夹与 token 绑定的文件夹，无效时直接返回 400
+                let scope = match resolve_request_scope(&request.folders, user_token.as_deref()) {
+                    Ok(scope) => scope,
+                    Err(e) => {
+                        println!("❌ 查询范围无效: {}", e);
+                        let response = QueryResponse::error(e);
+                        logger.log_query_complete(
+                            request_id,
+                            "POST".to_string(),
+                            400,
+                            start_time.elapsed().as_millis() as u64,
+                            Some(serde_json::to_string(&response).unwrap_or_default()),
+                            None,
+                        );
+                        return Ok::<_, warp::Rejection>(warp::reply::json(&response));
+                    }
+                };
+                if let Some(scope) = &scope {
+                    println!("📁 查询范围: {} 个文件夹", scope.folder_ids.len());
+                }
 
                 // 先进行数据库查询（无论是否包含URL）
-                let result = match query_database(&request.title, request.options.as_deref()).await {
-                    Ok(results) => {
+                let mut matching_profile = None;
+                let result = match query_database(
+                    &request.title,
+                    request.options.as_deref(),
+                    request.profile.as_deref(),
+                    scope.as_ref(),
+                )
+                .await
+                {
+                    Ok(QueryMatchResult { rows: results, profile }) => {
+                        println!("📐 使用匹配配置: {}", profile);
+                        matching_profile = Some(profile);
                         if !results.is_empty() {
                             println!("✅ 在数据库中找到匹配结果: {} 条记录", results.len());
                             let data_list: Vec<QueryData> = results
                                 .into_iter()
-                                .map(|(id, question, answer, is_ai, is_pending_correction)| {
-                                    build_query_data(
+                                .map(|row| {
+                                    let data = build_query_data(
                                         &request_origin,
-                                        id,
-                                        &question,
-                                        answer,
-                                        is_ai,
-                                        is_pending_correction,
-                                    )
+                                        row.id,
+                                        &row.question,
+                                        format_served_answer(&answer_format, &request, &row.answer),
+                                        row.is_ai,
+                                        row.is_pending_correction,
+                                        decoration,
+                                    );
+                                    if return_all {
+                                        attach_match_details(data, &row)
+                                    } else {
+                                        data
+                                    }
                                 })
                                 .collect();
-                            let response = QueryResponse::success(data_list);
+                            let response = QueryResponse::success(data_list).with_all(return_all);
                             (200, response)
                         } else {
                             println!("🔍 数据库中未找到匹配结果: {}", request.title);
 
                             // 如果检测到URL,发送视觉分析请求（带 __URL_QUESTION__: 前缀）
-                            let formatted_query = if has_url {
+                            let (formatted_query, model_queries) = if has_url {
                                 println!("🔗 检测到URL,发送视觉分析请求: {}", request.title);
                                 let mut q = format!("__URL_QUESTION__:{}", request.title);
                                 if let Some(options) = &request.options {
@@ -380,74 +717,65 @@ pub async fn start_server(
                                         q.push_str(&format!("\n__OPTIONS__:{}", options));
                                     }
                                 }
-                                q
+                                (q, HashMap::new())
                             } else {
                                 // 普通题目：统一使用带分析的文本模型提示词
                                 println!("🤖 Database query returned no results, requesting model call for: {}", request.title);
-                                build_model_query_prompt(
+                                let prompts = build_model_query_prompt(
                                     &request.title,
                                     request.options.as_deref(),
                                     request.query_type.as_deref(),
-                                )
+                                    scope.as_ref(),
+                                );
+                                println!("📝 提示词模板: {}", prompts.prompt.source);
+                                (prompts.prompt.prompt, prompts.models)
                             };
 
-                            logger.send_model_call_request(request_id.clone(), formatted_query);
-
-                            // 等待模型调用完成（前端有keepalive心跳防止超时,此值为总时长上限）
-                            let wait_secs = if has_url { 120 } else { 60 };
-                            match logger.wait_for_model_response(request_id.clone(), wait_secs).await {
-                                Ok(model_content) => {
-                                    println!("✅ Received model response: {}", model_content);
-                                    if let Some(err_msg) = is_model_error(&model_content) {
-                                        let response = QueryResponse::error(err_msg);
-                                        (500, response)
+                            match request_model_answer(&logger, &request_id, formatted_query, model_queries, &request, has_url).await {
+                                Ok(ModelAnswer { answer: extracted_answer, needs_correction }) => {
+                                    // Store to database
+                                    let inserted_id = if extracted_answer.is_empty() {
+                                        println!("⚠️ AI最终处理结果答案为空,跳过保存题目");
+                                        0
                                     } else {
-                                        let mut extracted_answer = extract_answer_from_json(&model_content);
-
-                                        // Check for incomplete question response
-                                        if model_content.contains("题目不完整,无法确定具体问题.") {
-                                            extracted_answer = String::new();
-                                            println!("⚠️ 检测到题目不完整,将答案留空");
-                                        }
-
-                                        extracted_answer = extracted_answer.trim().to_string();
-
-                                        // Store to database
-                                        let inserted_id = if extracted_answer.is_empty() {
-                                            println!("⚠️ AI最终处理结果答案为空,跳过保存题目");
-                                            0
-                                        } else {
-                                            match insert_ai_response(
-                                                &request.title,
-                                                &extracted_answer,
-                                                request.options.clone(),
-                                                request.query_type.clone(),
-                                                true,
-                                            ) {
-                                                Ok(id) => {
-                                                    println!("✅ AI response stored to database");
-                                                    id
-                                                }
-                                                Err(e) => {
-                                                    println!("❌ Failed to store AI response: {}", e);
-                                                    0
-                                                }
-                                            }
-                                        };
-
-                                        let data = build_query_data(
-                                            &request_origin,
-                                            inserted_id,
+                                        match insert_ai_response(
                                             &request.title,
-                                            extracted_answer,
+                                            raw_title_html.as_deref(),
+                                            &extracted_answer,
+                                            request.options.clone(),
+                                            request.query_type.clone(),
                                             true,
-                                            false,
-                                        );
-                                        let response = QueryResponse::success(vec![data]);
-                                        (200, response)
-                                    }
+                                            needs_correction,
+                                            scope.as_ref(),
+                                        ) {
+                                            Ok(id) => {
+                                                println!("✅ AI response stored to database");
+                                                id
+                                            }
+                                            Err(e) => {
+                                                println!("❌ Failed to store AI response: {}", e);
+                                                0
+                                            }
+                                        }
+                                    };
+
+                                    let data = build_query_data(
+                                        &request_origin,
+                                        inserted_id,
+                                        &request.title,
+                                        format_served_answer(&answer_format, &request, &extracted_answer),
+                                        true,
+                                        needs_correction,
+                                        decoration,
+                                    );
+                                    let response = QueryResponse::success(vec![data]).with_all(return_all);
+                                    (200, response)
+                                }
+                                Err(ModelAnswerError::Model(err_msg)) => {
+                                    let response = QueryResponse::error(err_msg);
+                                    (500, response)
                                 }
-                                Err(e) => {
+                                Err(ModelAnswerError::Timeout(e)) => {
                                     println!("❌ Model call timeout or error: {}", e);
                                     let response = QueryResponse::error(format!("Model call failed: {}", e));
                                     (408, response)
@@ -466,13 +794,13 @@ pub async fn start_server(
                 let response_body = serde_json::to_string(&result.1).unwrap_or_default();
                 
                 // 记录请求完成
-                logger.log_request_complete(
+                logger.log_query_complete(
                     request_id,
                     "POST".to_string(),
-                    "/query".to_string(),
                     result.0,
                     response_time,
                     Some(response_body),
+                    matching_profile,
                 );
 
                 Ok::<_, warp::Rejection>(warp::reply::json(&result.1))
@@ -495,10 +823,18 @@ pub async fn start_server(
                 let options = params.get("options").cloned();
                 let query_type = params.get("type").cloned();
                 
-                let request = QueryRequest {
+                let mut request = QueryRequest {
                     title: title.clone(),
                     options,
                     query_type,
+                    profile: params.get("profile").cloned(),
+                    folders: params
+                        .get("folders")
+                        .map(|folders| parse_folder_refs(folders))
+                        .unwrap_or_default(),
+                    answer_format: params.get("answerFormat").cloned(),
+                    format: params.get("format").cloned(),
+                    all: query_param_flag(&params, "all"),
                 };
                 
                 let request_body = serde_json::to_string(&request).unwrap_or_default();
@@ -535,6 +871,9 @@ pub async fn start_server(
                     user_agent,
                 );
 
+                // 清理题目中的 HTML，图片以地址形式保留在题目中
+                let raw_title_html = sanitize_query_request(&mut request);
+
                 // 检测title和options中是否包含URL
                 let mut has_url = contains_url(&request.title);
                 if let Some(options) = &request.options {
@@ -544,32 +883,77 @@ pub async fn start_server(
                 }
 
                 let request_origin = resolve_request_origin(&headers);
+                let user_token = request_user_token(&headers, Some(&params));
+                let (format_name, answer_format) = AnswerFormats::load().resolve_request(
+                    request.answer_format.as_deref(),
+                    user_token.as_deref(),
+                );
+                println!("🧾 使用答案格式: {}", format_name);
+                let decoration = resolve_question_decoration(request.format.as_deref());
+                let return_all = wants_all_candidates(&headers, request.all);
+
+                // 查询范围：请求指定的文件夹与 token 绑定的文件夹，无效时直接返回 400
+                let scope = match resolve_request_scope(&request.folders, user_token.as_deref()) {
+                    Ok(scope) => scope,
+                    Err(e) => {
+                        println!("❌ 查询范围无效: {}", e);
+                        let response = QueryResponse::error(e);
+                        logger.log_query_complete(
+                            request_id,
+                            "GET".to_string(),
+                            400,
+                            start_time.elapsed().as_millis() as u64,
+                            Some(serde_json::to_string(&response).unwrap_or_default()),
+                            None,
+                        );
+                        return Ok::<_, warp::Rejection>(warp::reply::json(&response));
+                    }
+                };
+                if let Some(scope) = &scope {
+                    println!("📁 查询范围: {} 个文件夹", scope.folder_ids.len());
+                }
 
                 // 先进行数据库查询（无论是否包含URL）
-                let result = match query_database(&request.title, request.options.as_deref()).await {
-                    Ok(results) => {
+                let mut matching_profile = None;
+                let result = match query_database(
+                    &request.title,
+                    request.options.as_deref(),
+                    request.profile.as_deref(),
+                    scope.as_ref(),
+                )
+                .await
+                {
+                    Ok(QueryMatchResult { rows: results, profile }) => {
+                        println!("📐 使用匹配配置: {}", profile);
+                        matching_profile = Some(profile);
                         if !results.is_empty() {
                             println!("✅ 在数据库中找到匹配结果: {} 条记录", results.len());
                             let data_list: Vec<QueryData> = results
                                 .into_iter()
-                                .map(|(id, question, answer, is_ai, is_pending_correction)| {
-                                    build_query_data(
+                                .map(|row| {
+                                    let data = build_query_data(
                                         &request_origin,
-                                        id,
-                                        &question,
-                                        answer,
-                                        is_ai,
-                                        is_pending_correction,
-                                    )
+                                        row.id,
+                                        &row.question,
+                                        format_served_answer(&answer_format, &request, &row.answer),
+                                        row.is_ai,
+                                        row.is_pending_correction,
+                                        decoration,
+                                    );
+                                    if return_all {
+                                        attach_match_details(data, &row)
+                                    } else {
+                                        data
+                                    }
                                 })
                                 .collect();
-                            let response = QueryResponse::success(data_list);
+                            let response = QueryResponse::success(data_list).with_all(return_all);
                             (200, response)
                         } else {
                             println!("🔍 数据库中未找到匹配结果: {}", request.title);
 
                             // 如果检测到URL,发送视觉分析请求（带 __URL_QUESTION__: 前缀）
-                            let formatted_query = if has_url {
+                            let (formatted_query, model_queries) = if has_url {
                                 println!("🔗 检测到URL,发送视觉分析请求: {}", request.title);
                                 let mut q = format!("__URL_QUESTION__:{}", request.title);
                                 if let Some(options) = &request.options {
@@ -577,73 +961,64 @@ pub async fn start_server(
                                         q.push_str(&format!("\n__OPTIONS__:{}", options));
                                     }
                                 }
-                                q
+                                (q, HashMap::new())
                             } else {
                                 println!("🤖 Database query returned no results, requesting model call for: {}", request.title);
-                                build_model_query_prompt(
+                                let prompts = build_model_query_prompt(
                                     &request.title,
                                     request.options.as_deref(),
                                     request.query_type.as_deref(),
-                                )
+                                    scope.as_ref(),
+                                );
+                                println!("📝 提示词模板: {}", prompts.prompt.source);
+                                (prompts.prompt.prompt, prompts.models)
                             };
 
-                            logger.send_model_call_request(request_id.clone(), formatted_query);
-
-                            // 等待模型调用完成（前端有keepalive心跳防止超时,此值为总时长上限）
-                            let wait_secs = if has_url { 120 } else { 60 };
-                            match logger.wait_for_model_response(request_id.clone(), wait_secs).await {
-                                Ok(model_content) => {
-                                    println!("✅ Received model response: {}", model_content);
-                                    if let Some(err_msg) = is_model_error(&model_content) {
-                                        let response = QueryResponse::error(err_msg);
-                                        (500, response)
+                            match request_model_answer(&logger, &request_id, formatted_query, model_queries, &request, has_url).await {
+                                Ok(ModelAnswer { answer: extracted_answer, needs_correction }) => {
+                                    // Store to database
+                                    let inserted_id = if extracted_answer.is_empty() {
+                                        println!("⚠️ AI最终处理结果答案为空,跳过保存题目");
+                                        0
                                     } else {
-                                        let mut extracted_answer = extract_answer_from_json(&model_content);
-
-                                        // Check for incomplete question response
-                                        if model_content.contains("题目不完整,无法确定具体问题.") {
-                                            extracted_answer = String::new();
-                                            println!("⚠️ 检测到题目不完整,将答案留空");
-                                        }
-
-                                        extracted_answer = extracted_answer.trim().to_string();
-
-                                        // Store to database
-                                        let inserted_id = if extracted_answer.is_empty() {
-                                            println!("⚠️ AI最终处理结果答案为空,跳过保存题目");
-                                            0
-                                        } else {
-                                            match insert_ai_response(
-                                                &request.title,
-                                                &extracted_answer,
-                                                request.options.clone(),
-                                                request.query_type.clone(),
-                                                true,
-                                            ) {
-                                                Ok(id) => {
-                                                    println!("✅ AI response stored to database");
-                                                    id
-                                                }
-                                                Err(e) => {
-                                                    println!("❌ Failed to store AI response: {}", e);
-                                                    0
-                                                }
-                                            }
-                                        };
-
-                                        let data = build_query_data(
-                                            &request_origin,
-                                            inserted_id,
+                                        match insert_ai_response(
                                             &request.title,
-                                            extracted_answer,
+                                            raw_title_html.as_deref(),
+                                            &extracted_answer,
+                                            request.options.clone(),
+                                            request.query_type.clone(),
                                             true,
-                                            false,
-                                        );
-                                        let response = QueryResponse::success(vec![data]);
-                                        (200, response)
-                                    }
+                                            needs_correction,
+                                            scope.as_ref(),
+                                        ) {
+                                            Ok(id) => {
+                                                println!("✅ AI response stored to database");
+                                                id
+                                            }
+                                            Err(e) => {
+                                                println!("❌ Failed to store AI response: {}", e);
+                                                0
+                                            }
+                                        }
+                                    };
+
+                                    let data = build_query_data(
+                                        &request_origin,
+                                        inserted_id,
+                                        &request.title,
+                                        format_served_answer(&answer_format, &request, &extracted_answer),
+                                        true,
+                                        needs_correction,
+                                        decoration,
+                                    );
+                                    let response = QueryResponse::success(vec![data]).with_all(return_all);
+                                    (200, response)
+                                }
+                                Err(ModelAnswerError::Model(err_msg)) => {
+                                    let response = QueryResponse::error(err_msg);
+                                    (500, response)
                                 }
-                                Err(e) => {
+                                Err(ModelAnswerError::Timeout(e)) => {
                                     println!("❌ Model call timeout or error: {}", e);
                                     let response = QueryResponse::error(format!("Model call failed: {}", e));
                                     (408, response)
@@ -662,13 +1037,13 @@ pub async fn start_server(
                 let response_body = serde_json::to_string(&result.1).unwrap_or_default();
                 
                 // 记录请求完成
-                logger.log_request_complete(
+                logger.log_query_complete(
                     request_id,
                     "GET".to_string(),
-                    "/query".to_string(),
                     result.0,
                     response_time,
                     Some(response_body),
+                    matching_profile,
                 );
 
                 Ok::<_, warp::Rejection>(warp::reply::json(&result.1))
@@ -684,7 +1059,7 @@ pub async fn start_server(
         .and(warp::path("pending-correction"))
         .and(warp::post())
         .and_then(|question_id: i64| async move {
-            match set_question_pending_correction(question_id, true).await {
+            match mark_question_pending_correction(question_id) {
                 Ok(_) => Ok::<_, warp::Rejection>(warp::reply::with_status(
                     warp::reply::json(&serde_json::json!({
                         "success": true,
@@ -703,6 +1078,21 @@ pub async fn start_server(
             }
         });
 
+    // 题目中的“标记为待修正”链接打开的确认页面，确认后以 POST 提交
+    let pending_correction_page_route = warp::path("api")
+        .and(warp::path("questions"))
+        .and(warp::path::param::<i64>())
+        .and(warp::path("pending-correction"))
+        .and(warp::get())
+        .map(|question_id: i64| {
+            warp::reply::html(format!(
+                "<!DOCTYPE html><html><head><meta charset=\"utf-8\"><title>标记为待修正</title></head>\
+                 <body style=\"font-family:sans-serif;padding:24px;\"><p>确定将题目 #{} 标记为待修正吗？</p>\
+                 <form method=\"post\"><button type=\"submit\">标记为待修正</button></form></body></html>",
+                question_id
+            ))
+        });
+
     // 模型调用响应路由
     let logger_for_model_response = state.logger.clone();
     let model_response_route = warp::path("api")
@@ -727,6 +1117,7 @@ pub async fn start_server(
                     request.request_id.clone(),
                     request.content.clone(),
                     request.reasoning_content.clone(),
+                    request.model.clone(),
                     is_success,
                 );
 
@@ -957,18 +1348,71 @@ pub async fn start_server(
             }
         });
 
+    // POST /api/query/explain — 解释题库匹配过程，用于排查误匹配（需要管理员token验证）
+    let query_explain_route = warp::path("api")
+        .and(warp::path("query"))
+        .and(warp::path("explain"))
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(warp::header::optional::<String>("authorization"))
+        .and(warp::body::json())
+        .and_then(
+            |auth: Option<String>, request: QueryExplainRequest| async move {
+                if !check_admin_token(&auth) {
+                    return Ok::<_, warp::Rejection>(warp::reply::with_status(
+                        warp::reply::json(
+                            &serde_json::json!({"success": false, "message": "未授权"}),
+                        ),
+                        warp::http::StatusCode::UNAUTHORIZED,
+                    ));
+                }
+                let scope = match resolve_request_scope(&request.folders, request.token.as_deref())
+                {
+                    Ok(scope) => scope,
+                    Err(e) => {
+                        return Ok::<_, warp::Rejection>(warp::reply::with_status(
+                            warp::reply::json(&serde_json::json!({"success": false, "message": e})),
+                            warp::http::StatusCode::BAD_REQUEST,
+                        ));
+                    }
+                };
+                match explain_query_match_in_scope(
+                    request.title,
+                    request.options,
+                    request.top_k,
+                    request.profile,
+                    scope,
+                )
+                .await
+                {
+                    Ok(explanation) => Ok::<_, warp::Rejection>(warp::reply::with_status(
+                        warp::reply::json(
+                            &serde_json::json!({"success": true, "data": explanation}),
+                        ),
+                        warp::http::StatusCode::OK,
+                    )),
+                    Err(e) => Ok::<_, warp::Rejection>(warp::reply::with_status(
+                        warp::reply::json(&serde_json::json!({"success": false, "message": e})),
+                        warp::http::StatusCode::INTERNAL_SERVER_ERROR,
+                    )),
+                }
+            },
+        );
+
     // 组合所有路由（query路由和SSE路由不需要额外的日志中间件）
     let routes = root_route
         .or(root_head_route)
         .or(logged_routes)
         .or(query_route)
         .or(mark_pending_correction_route)
+        .or(pending_correction_page_route)
         .or(model_response_route)
         .or(model_progress_route)
         .or(sse_logs_route)
         .or(login_route)
         .or(models_get_route)
         .or(models_put_route)
+        .or(query_explain_route)
         .with(cors);
 
     // 解析绑定地址
@@ -1093,128 +1537,6 @@ async fn store_ai_response_to_database(request_id: &str, content: &str) -> Resul
     Ok(())
 }
 
-/// 从JSON响应中提取answer字段
-///
-/// # Arguments
-/// * `json_content` - AI返回的JSON字符串
-///
-/// # Returns
-/// * `String` - 提取的答案内容,如果解析失败则返回原始内容
-fn extract_answer_from_json(json_content: &str) -> String {
-    // 1) 去除可能的 markdown 代码块标记
-    let mut cleaned = json_content.trim().to_string();
-    if cleaned.starts_with("```json") {
-        cleaned = cleaned[7..].to_string();
-    } else if cleaned.starts_with("```") {
-        cleaned = cleaned[3..].to_string();
-    }
-    if cleaned.ends_with("```") {
-        cleaned = cleaned[..cleaned.len() - 3].to_string();
-    }
-    cleaned = cleaned.trim().to_string();
-
-    println!("🔍 清理后的内容: {}", cleaned);
-
-    // 提取答案的内部工具函数
-    fn extract_field_from_value(v: &Value) -> Option<String> {
-        if let Some(answer) = v.get("answer").and_then(|a| a.as_str()) {
-            return Some(answer.to_string());
-        }
-        if let Some(answer) = v.get("anwser").and_then(|a| a.as_str()) {
-            return Some(answer.to_string());
-        }
-        None
-    }
-
-    // 2) 首先尝试直接解析整个文本为 JSON
-    if let Ok(v) = serde_json::from_str::<Value>(&cleaned) {
-        if let Some(ans) = extract_field_from_value(&v) {
-            println!("✅ 直接解析文本为JSON并提取到答案: {}", ans);
-            return ans;
-        }
-    }
-
-    // 3) 失败则从末尾尝试提取最后一个平衡的 JSON 对象片段
-    fn extract_last_balanced_json(text: &str) -> Option<String> {
-        let bytes = text.as_bytes();
-        let mut end: Option<usize> = None;
-        let mut depth: i32 = 0;
-        let mut i = bytes.len();
-        while i > 0 {
-            i -= 1;
-            let b = bytes[i];
-            if end.is_none() {
-                if b == b'}' {
-                    end = Some(i);
-                    depth = 1;
-                    continue;
-                }
-            } else {
-                if b == b'}' {
-                    depth += 1;
-                } else if b == b'{' {
-                    depth -= 1;
-                    if depth == 0 {
-                        let start = i;
-                        let slice = &text[start..=end.unwrap()];
-                        return Some(slice.to_string());
-                    }
-                }
-            }
-        }
-        None
-    }
-
-    if let Some(json_str) = extract_last_balanced_json(&cleaned) {
-        println!("🔎 发现末尾的JSON片段: {}", json_str);
-        if let Ok(v) = serde_json::from_str::<Value>(&json_str) {
-            if let Some(ans) = extract_field_from_value(&v) {
-                println!("✅ 从末尾JSON片段中提取到答案: {}", ans);
-                return ans;
-            }
-        } else {
-            println!("⚠️ 末尾JSON片段解析失败");
-        }
-    }
-
-    // 4) 使用正则在混合文本中直接捕获 answer 字段
-    let re = Regex::new(r#"(?s)\{\s*\"(?:answer|anwser)\"\s*:\s*\"(.*?)\"[\s\S]*?\}"#).unwrap();
-    if let Some(caps) = re.captures(&cleaned) {
-        if let Some(m) = caps.get(1) {
-            let ans = m.as_str().to_string();
-            println!("✅ 通过正则从混合文本中捕获到答案: {}", ans);
-            return ans;
-        }
-    }
-
-    // 5) 尝试从中英文"答案:"或"answer:"后面提取文本
-    let text_re = Regex::new(r"(?i)(?:答案|answer)[：:]\s*(.+?)(?:\n|$)").unwrap();
-    if let Some(caps) = text_re.captures(&cleaned) {
-        if let Some(m) = caps.get(1) {
-            let ans = m.as_str().trim().to_string();
-            if !ans.is_empty() {
-                println!("✅ 从\"答案: \"标记后提取到文本: {}", &ans[..std::cmp::min(ans.len(), 100)]);
-                return ans;
-            }
-        }
-    }
-
-    // 6) 如果内容看起来是纯文本答案（不含JSON结构）,直接返回清理后的内容
-    let cleaned_trimmed = cleaned.trim();
-    if cleaned_trimmed.len() > 0 && cleaned_trimmed.len() < 2000 && !cleaned_trimmed.starts_with('{') && !cleaned_trimmed.starts_with('[') {
-        // 检查是否像是一个直接答案（简短文本,不含复杂结构）
-        let lines: Vec<&str> = cleaned_trimmed.lines().filter(|l| !l.trim().is_empty()).collect();
-        if lines.len() <= 3 {
-            println!("✅ 将纯文本内容作为答案使用: {}", &cleaned_trimmed[..std::cmp::min(cleaned_trimmed.len(), 100)]);
-            return cleaned_trimmed.to_string();
-        }
-    }
-
-    // 7) 回退：返回原始内容
-    println!("⚠️ 未能提取到结构化答案,返回原始内容");
-    json_content.to_string()
-}
-
 fn is_model_error(text: &str) -> Option<String> {
     let mut cleaned = text.trim().to_string();
     if cleaned.starts_with("```json") {
@@ -1289,3 +1611,88 @@ fn is_model_error(text: &str) -> Option<String> {
 
     None
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn query_data_question_decoration_modes() {
+        let origin = "http://127.0.0.1:3000";
+        let question = "1 < 2\n对吗";
+        let build = |id: i64, pending: bool, decoration: QuestionDecoration| {
+            build_query_data(
+                origin,
+                id,
+                question,
+                "正确".to_string(),
+                false,
+                pending,
+                decoration,
+            )
+        };
+
+        let plain = build(7, false, QuestionDecoration::None);
+        assert_eq!(plain.question, question);
+        assert_eq!(
+            plain.pending_correction_url.as_deref(),
+            Some("http://127.0.0.1:3000/api/questions/7/pending-correction")
+        );
+
+        let link = build(7, false, QuestionDecoration::Link);
+        assert!(link.question.starts_with("1 &lt; 2<br>对吗<br><a href="));
+        assert!(link
+            .question
+            .contains("/api/questions/7/pending-correction"));
+        assert!(!link.question.contains("onclick"));
+        assert!(build(7, true, QuestionDecoration::Link)
+            .question
+            .contains("已标记待修正"));
+
+        let button = build(7, false, QuestionDecoration::Button);
+        assert!(button.question.contains("<button"));
+        assert!(button.question.contains("1 &lt; 2<br>对吗"));
+
+        // 未入库的题目没有标记地址，也不附加链接或按钮
+        let unsaved = build(0, false, QuestionDecoration::Button);
+        assert_eq!(unsaved.question, "1 &lt; 2<br>对吗");
+        assert_eq!(unsaved.pending_correction_url, None);
+        let json = serde_json::to_value(&unsaved).expect("serialize query data");
+        assert!(json.get("pending_correction_url").is_none());
+    }
+
+    #[tokio::test]
+    async fn query_post_reads_format_all_and_folders_from_the_query_string() {
+        let filter = warp::path("query").and(query_post_request());
+        let request = warp::test::request()
+            .method("POST")
+            .path("/query?format=plain&all=1&folders=3,7")
+            .json(&serde_json::json!({ "title": "1 + 1 = ?" }))
+            .filter(&filter)
+            .await
+            .expect("query string parameters");
+        assert_eq!(request.format.as_deref(), Some("plain"));
+        assert_eq!(
+            resolve_question_decoration(request.format.as_deref()),
+            QuestionDecoration::None
+        );
+        assert!(request.all);
+        assert_eq!(request.folders, vec!["3", "7"]);
+
+        // 请求体中的值优先
+        let request = warp::test::request()
+            .method("POST")
+            .path("/query?format=plain&folders=3")
+            .json(&serde_json::json!({
+                "title": "1 + 1 = ?",
+                "format": "button",
+                "folders": [5]
+            }))
+            .filter(&filter)
+            .await
+            .expect("body parameters");
+        assert_eq!(request.format.as_deref(), Some("button"));
+        assert_eq!(request.folders, vec!["5"]);
+        assert!(!request.all);
+    }
+}
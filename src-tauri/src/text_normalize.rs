@@ -0,0 +1,305 @@
+//! 文本规范化：题库匹配（compute_query_match_score、extract_query_keywords、选项解析）
+//! 与题目入库前的清理共用，避免全角/半角、繁简体、引号括号样式、零宽字符和
+//! HTML 实体等差异导致本应相同的题目匹配不上。
+//!
+//! 繁体转简体默认开启，可在 config.json 中设置 `"traditionalToSimplified": false` 关闭
+//! （启动后首次匹配时读取）。
+
+use regex::Regex;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+use unicode_normalization::UnicodeNormalization;
+
+use crate::database::{read_config_value, ConfigCache};
+
+/// 解码 HTML 实体（`&nbsp;`、`&amp;`、`&#12290;` 等）
+pub fn decode_entities(text: &str) -> String {
+    html_escape::decode_html_entities(text).into_owned()
+}
+
+/// Unicode NFKC 规范化：合并兼容字符，全角字母数字与全角空格会变成半角
+pub fn nfkc(text: &str) -> String {
+    text.nfkc().collect()
+}
+
+fn is_zero_width(c: char) -> bool {
+    matches!(
+        c,
+        '\u{200B}' | '\u{200C}' | '\u{200D}' | '\u{2060}' | '\u{FEFF}' | '\u{00AD}'
+    )
+}
+
+/// 去掉零宽空格、零宽连接符、BOM 和软连字符
+pub fn remove_zero_width(text: &str) -> String {
+    text.chars().filter(|c| !is_zero_width(*c)).collect()
+}
+
+/// 宽度与样式折叠：NFKC 不处理的中文标点、引号和括号统一为半角形式
+pub fn fold_width(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            '“' | '”' | '„' | '‟' | '″' | '「' | '」' | '『' | '』' | '＂' => '"',
+            '‘' | '’' | '‚' | '‛' | '′' | '＇' => '\'',
+            '【' | '〔' | '〖' | '［' => '[',
+            '】' | '〕' | '〗' | '］' => ']',
+            '《' | '〈' | '＜' => '<',
+            '》' | '〉' | '＞' => '>',
+            '（' => '(',
+            '）' => ')',
+            '。' | '｡' => '.',
+            '、' | '､' => ',',
+            '～' | '〜' => '~',
+            '—' | '–' | '－' | '―' => '-',
+            '・' | '·' => '.',
+            _ => c,
+        })
+        .collect()
+}
+
+// 常见繁体字及其简体字，两个字符串按位置一一对应
+const TRADITIONAL_CHARS: &str = concat!(
+    "這個們來為說時國會學對過於與沒還進開關問題東兩無機長麼後點體現實發動經種應當樣將",
+    "產業義頭見話認讓從內場區電氣線數歲萬億統計記論設許請讀寫語課試確錯誤選項謂條號類",
+    "變報處車軍農辦聯係覺傳歷華漢陽陰廣鐵銀錢買賣貨費資質責貴員圖團圓園遠運達邊適節範",
+    "築藝藥蘇葉衛術製複雜難雙離風飛馬魚鳥麗齊龍黨門間聞閱隊際陸險隨麵頁順須領預頻顏願",
+    "顯飲館養餘驗驚髮鬥歐殺減溫測濟灣災熱營爭爾牆獨獲環畫療盡監盤眾礎禮禍稱積穩競筆簡",
+    "糧紀約紅級純紙細組結給絕絲綠維網編練緒總績織繼續羅習職聲聽腦臉興舉舊艦莊蓋薩蘭蟲",
+    "補裝裡規視親觀訊訓評詞詩該詳誰調談諸謝證識議護豐貝負財貧購賽趕趙跡軟較載輕輸轉辭",
+    "連週遊違遲郵鄉醫釋針鈴鋼錄鍵鏡閉陣陳隻雖雞雲靈靜韓響頂額飯駕騎鬧魯鳴麥黃齒龜價優",
+    "儲兒冊準劃劇勞勢務勝協單參啟喚嚴圍壓壞夠夢奪奮婦媽孫寧寬審尋導層屬島幣帶幫幹廠廢",
+    "廳張彈強歸徑復憂態懷戰戲戶擇擊擔據擴攝敗敵斷書構標樹橋權歡毀濃濕滿漁潔灑燈燒爺牽",
+    "犧狀獎獻瓊畢異皺碼磚礦禦稅穀窮竊簽籃籌糾紛紹終綜緊縣縮繩肅腳膽臨艱蘋虛蝦衝襲覽訪",
+    "診詢誇諾謀講譯讚貓貢販貫貼貿賀賓賞賠賴贈贏趨躍輔輪遞遷遺鄰醬鉛銅銷鋒鍋鎮鐘鑰閃閣",
+    "闊隱霧韋頓頒頗顧飄飽餓驅驟髒鮮鴨鵝鹽齡師帥壽孿彎愛憶擁極樂橫殘氫湯滅漸潛濾瀏烏煙",
+    "猶獅瑪甕癥矯禪穌窩筍箋簾籤紐絡綱緣縫繞纜罰聖脈膚藍虧螞蠶衆襯訂託訴詐誌誕誘諮謠譜",
+    "貸賬賭賺軌軸輛輩轄辯遙鄭鈣鉀鈉鋁鋅鍊鍛鏈鐳鑑閘闡陝隸雛靂頸顆颱颶餅饑駐騙鬆鬍鯨鱷",
+    "鳳鴿鶴鷹鹼黴鼴齋龐",
+);
+const SIMPLIFIED_CHARS: &str = concat!(
+    "这个们来为说时国会学对过于与没还进开关问题东两无机长么后点体现实发动经种应当样将",
+    "产业义头见话认让从内场区电气线数岁万亿统计记论设许请读写语课试确错误选项谓条号类",
+    "变报处车军农办联系觉传历华汉阳阴广铁银钱买卖货费资质责贵员图团圆园远运达边适节范",
+    "筑艺药苏叶卫术制复杂难双离风飞马鱼鸟丽齐龙党门间闻阅队际陆险随面页顺须领预频颜愿",
+    "显饮馆养余验惊发斗欧杀减温测济湾灾热营争尔墙独获环画疗尽监盘众础礼祸称积稳竞笔简",
+    "粮纪约红级纯纸细组结给绝丝绿维网编练绪总绩织继续罗习职声听脑脸兴举旧舰庄盖萨兰虫",
+    "补装里规视亲观讯训评词诗该详谁调谈诸谢证识议护丰贝负财贫购赛赶赵迹软较载轻输转辞",
+    "连周游违迟邮乡医释针铃钢录键镜闭阵陈只虽鸡云灵静韩响顶额饭驾骑闹鲁鸣麦黄齿龟价优",
+    "储儿册准划剧劳势务胜协单参启唤严围压坏够梦夺奋妇妈孙宁宽审寻导层属岛币带帮干厂废",
+    "厅张弹强归径复忧态怀战戏户择击担据扩摄败敌断书构标树桥权欢毁浓湿满渔洁洒灯烧爷牵",
+    "牺状奖献琼毕异皱码砖矿御税谷穷窃签篮筹纠纷绍终综紧县缩绳肃脚胆临艰苹虚虾冲袭览访",
+    "诊询夸诺谋讲译赞猫贡贩贯贴贸贺宾赏赔赖赠赢趋跃辅轮递迁遗邻酱铅铜销锋锅镇钟钥闪阁",
+    "阔隐雾韦顿颁颇顾飘饱饿驱骤脏鲜鸭鹅盐龄师帅寿孪弯爱忆拥极乐横残氢汤灭渐潜滤浏乌烟",
+    "犹狮玛瓮症矫禅稣窝笋笺帘签纽络纲缘缝绕缆罚圣脉肤蓝亏蚂蚕众衬订托诉诈志诞诱咨谣谱",
+    "贷账赌赚轨轴辆辈辖辩遥郑钙钾钠铝锌炼锻链镭鉴闸阐陕隶雏雳颈颗台飓饼饥驻骗松胡鲸鳄",
+    "凤鸽鹤鹰碱霉鼹斋庞",
+);
+
+static TRADITIONAL_TO_SIMPLIFIED: OnceLock<HashMap<char, char>> = OnceLock::new();
+
+/// 繁体转简体（逐字替换，覆盖题目中常见的繁体字）
+pub fn to_simplified(text: &str) -> String {
+    let table = TRADITIONAL_TO_SIMPLIFIED.get_or_init(|| {
+        TRADITIONAL_CHARS
+            .chars()
+            .zip(SIMPLIFIED_CHARS.chars())
+            .collect()
+    });
+    text.chars()
+        .map(|c| table.get(&c).copied().unwrap_or(c))
+        .collect()
+}
+
+/// 连续空白（含换行）合并为一个空格并去掉首尾空白
+pub fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+// 每段文本规范化时都会用到，缓存配置值，config.json 修改后重新读取
+fn traditional_conversion_enabled() -> bool {
+    static ENABLED: ConfigCache<bool> = ConfigCache::new();
+    *ENABLED.get(|| {
+        read_config_value("traditionalToSimplified")
+            .and_then(|value| value.as_bool())
+            .unwrap_or(true)
+    })
+}
+
+// 不合并空白的规范化，供需要保留换行的场景（如按行拆分选项）使用
+pub fn normalize_text_keep_lines(text: &str) -> String {
+    let text = remove_zero_width(&nfkc(&decode_entities(text)));
+    let text = fold_width(&text);
+    if traditional_conversion_enabled() {
+        to_simplified(&text)
+    } else {
+        text
+    }
+}
+
+/// 匹配用规范化：实体解码、NFKC、零宽字符、宽度折叠、繁转简（可配置）、空白合并
+pub fn normalize_text(text: &str) -> String {
+    collapse_whitespace(&normalize_text_keep_lines(text))
+}
+
+/// 题目入库前的清理：解码实体、去掉零宽字符，每行内合并空白并去掉空行。
+/// 不做 NFKC 与繁简转换，保留题目原本的显示形式。
+pub fn clean_stored_text(text: &str) -> String {
+    remove_zero_width(&decode_entities(text))
+        .lines()
+        .map(|line| {
+            // &nbsp; 解码后是不换行空格，split_whitespace 会把它当作空白处理
+            line.split_whitespace().collect::<Vec<_>>().join(" ")
+        })
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// 只识别常见的 HTML 标签，避免把 "a<b" 之类的题目内容当成标记
+const HTML_TAG_NAMES: &str =
+    "a|b|br|code|div|em|font|h[1-6]|hr|i|img|label|li|ol|p|pre|section|span|strong|sub|sup|table|tbody|td|th|thead|tr|u|ul";
+
+static HTML_MARKUP: OnceLock<Regex> = OnceLock::new();
+static HTML_IMG_SRC: OnceLock<Regex> = OnceLock::new();
+
+fn html_markup_regex() -> &'static Regex {
+    HTML_MARKUP.get_or_init(|| {
+        Regex::new(&format!(
+            r"(?is)<!--.*?-->|<(script|style)\b[^>]*>.*?</(?:script|style)\s*>|<(/?)({})\b((?:\s+[a-zA-Z_:][^<>]*)?\s*/?)>",
+            HTML_TAG_NAMES
+        ))
+        .unwrap()
+    })
+}
+
+fn html_img_src_regex() -> &'static Regex {
+    HTML_IMG_SRC
+        .get_or_init(|| Regex::new(r#"(?i)\bsrc\s*=\s*(?:"([^"]*)"|'([^']*)'|([^\s>]+))"#).unwrap())
+}
+
+/// 文本中是否包含 HTML 标记
+pub fn contains_html(text: &str) -> bool {
+    html_markup_regex().is_match(text)
+}
+
+/// HTML 转纯文本：去掉标签、样式与脚本，块级标签和 `<br>` 转为换行，
+/// `<img>` 保留为图片地址，保证 extract_urls 仍能取到题目中的图片
+pub fn html_to_text(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut last_end = 0;
+
+    for captures in html_markup_regex().captures_iter(html) {
+        let Some(whole) = captures.get(0) else {
+            continue;
+        };
+        // HTML 源码中的换行不代表分行，分行只来自 <br> 和块级标签
+        text.push_str(&html[last_end..whole.start()].replace(['\r', '\n'], " "));
+        last_end = whole.end();
+
+        let Some(tag) = captures.get(3).map(|m| m.as_str().to_lowercase()) else {
+            // 注释、<script>、<style> 整段丢弃
+            continue;
+        };
+        let is_closing = captures.get(2).is_some_and(|m| !m.as_str().is_empty());
+        let attributes = captures.get(4).map(|m| m.as_str()).unwrap_or_default();
+
+        match tag.as_str() {
+            "img" => {
+                let src = html_img_src_regex().captures(attributes).and_then(|src| {
+                    src.get(1)
+                        .or_else(|| src.get(2))
+                        .or_else(|| src.get(3))
+                        .map(|m| decode_entities(m.as_str().trim()))
+                });
+                if let Some(src) = src.filter(|src| !src.is_empty()) {
+                    text.push(' ');
+                    text.push_str(&src);
+                    text.push(' ');
+                }
+            }
+            "br" | "hr" => text.push('\n'),
+            "p" | "div" | "li" | "tr" | "section" | "pre" | "table" | "ul" | "ol" => {
+                text.push('\n')
+            }
+            tag if tag.len() == 2 && tag.starts_with('h') => text.push('\n'),
+            "td" | "th" if !is_closing => text.push(' '),
+            _ => {}
+        }
+    }
+    text.push_str(&html[last_end..].replace(['\r', '\n'], " "));
+
+    clean_stored_text(&text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_html_entities() {
+        assert_eq!(
+            decode_entities("A&amp;B&nbsp;C &lt;D&gt; &#12290;"),
+            "A&B\u{a0}C <D> 。"
+        );
+    }
+
+    #[test]
+    fn applies_nfkc_and_width_folding() {
+        assert_eq!(nfkc("ＡＢＣ１２３，（甲）　乙"), "ABC123,(甲) 乙");
+        assert_eq!(
+            fold_width("“引号”‘单引号’【括号】《书名》。、"),
+            "\"引号\"'单引号'[括号]<书名>.,"
+        );
+    }
+
+    #[test]
+    fn removes_zero_width_characters() {
+        assert_eq!(
+            remove_zero_width("中\u{200B}国\u{FEFF}的\u{200D}首都"),
+            "中国的首都"
+        );
+    }
+
+    #[test]
+    fn converts_traditional_to_simplified() {
+        assert_eq!(
+            to_simplified("中華人民共和國成立於哪一年"),
+            "中华人民共和国成立于哪一年"
+        );
+        // 简体与不在表中的字保持不变
+        assert_eq!(to_simplified("光合作用"), "光合作用");
+    }
+
+    #[test]
+    fn collapses_whitespace() {
+        assert_eq!(
+            collapse_whitespace("  下列\t说法\n\n正确的是  "),
+            "下列 说法 正确的是"
+        );
+        assert_eq!(
+            clean_stored_text("  A.&nbsp;北京  \n\n B.\u{200B}上海 "),
+            "A. 北京\nB.上海"
+        );
+    }
+
+    #[test]
+    fn normalize_text_applies_every_step() {
+        assert_eq!(
+            normalize_text("中華人民共和國&nbsp;成立於　哪一年？"),
+            "中华人民共和国 成立于 哪一年?"
+        );
+    }
+
+    #[test]
+    fn strips_html_and_keeps_image_urls() {
+        let html = "<p style=\"color:red\"><span>看图回答</span><br/>\n<img class=\"q\" src=\"https://a.example/1.png?x=1&amp;y=2\">图中是什么动物？</p><!-- 注释 --><style>p{}</style>";
+        assert!(contains_html(html));
+        assert_eq!(
+            html_to_text(html),
+            "看图回答\nhttps://a.example/1.png?x=1&y=2 图中是什么动物？"
+        );
+
+        // 普通的比较符号不是 HTML
+        assert!(!contains_html("若 a<b 且 b>c，则下列正确的是"));
+        assert_eq!(html_to_text("<b>粗体</b>与<I>斜体</I>"), "粗体与斜体");
+    }
+}
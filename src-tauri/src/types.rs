@@ -20,6 +20,100 @@ pub struct QueryRequest {
     pub options: Option<String>,
     #[serde(rename = "type")]
     pub query_type: Option<String>,
+    /// 指定匹配配置档，为空时按文件夹配置或默认配置档
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+    /// 指定答案格式配置档，为空时按 token 对应用户的配置或默认配置
+    #[serde(
+        default,
+        rename = "answerFormat",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub answer_format: Option<String>,
+    /// 题目字段的修饰方式（plain / none / link / button），为空时按 questionDecoration 配置
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub format: Option<String>,
+    /// 返回全部匹配题目（含得分与文件夹），默认只返回得分最高的一条
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub all: bool,
+    /// 只在这些文件夹（文件夹 ID 或 "父文件夹/子文件夹" 形式的路径）及其子文件夹中查询，
+    /// 可以是数组或逗号分隔的字符串；为空时按 token 绑定的文件夹或整个题库
+    #[serde(
+        default,
+        deserialize_with = "deserialize_folder_refs",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub folders: Vec<String>,
+}
+
+/// 解析逗号分隔的文件夹 ID 或路径
+pub fn parse_folder_refs(value: &str) -> Vec<String> {
+    value
+        .split([',', '，'])
+        .map(str::trim)
+        .filter(|folder| !folder.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// 从 JSON 中读取文件夹 ID 或路径：数字、逗号分隔的字符串或由它们组成的数组
+pub fn folder_refs_from_value(value: &serde_json::Value) -> Vec<String> {
+    match value {
+        serde_json::Value::String(folders) => parse_folder_refs(folders),
+        serde_json::Value::Number(id) => vec![id.to_string()],
+        serde_json::Value::Array(items) => items.iter().flat_map(folder_refs_from_value).collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn deserialize_folder_refs<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let value = serde_json::Value::deserialize(deserializer)?;
+    Ok(folder_refs_from_value(&value))
+}
+
+/// 返回给 OCS 的题目字段的修饰方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum QuestionDecoration {
+    /// 原始题目文本，不做 HTML 转义也不附加内容
+    None,
+    /// 转义后的题目加一个“标记为待修正”链接
+    Link,
+    /// 转义后的题目加一个带脚本的“标记为待修正”按钮
+    #[default]
+    Button,
+}
+
+impl QuestionDecoration {
+    /// 解析配置或请求中的修饰方式，plain 与 none 相同，无法识别时返回 None
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "none" | "plain" => Some(QuestionDecoration::None),
+            "link" => Some(QuestionDecoration::Link),
+            "button" | "html" => Some(QuestionDecoration::Button),
+            _ => None,
+        }
+    }
+}
+
+/// 匹配解释请求结构体
+#[derive(Debug, Serialize, Deserialize)]
+pub struct QueryExplainRequest {
+    pub title: String,
+    pub options: Option<String>,
+    #[serde(default)]
+    pub top_k: Option<usize>,
+    #[serde(default)]
+    pub profile: Option<String>,
+    /// 与 /query 的 folders 相同，只解释这些文件夹及其子文件夹中的候选
+    #[serde(default, deserialize_with = "deserialize_folder_refs")]
+    pub folders: Vec<String>,
+    /// 按该 token 绑定的文件夹解释，用于复现用户的查询
+    #[serde(default)]
+    pub token: Option<String>,
 }
 
 /// 模型调用响应请求结构体
@@ -29,6 +123,9 @@ pub struct ModelCallResponseRequest {
     pub content: String,
     #[serde(default)]
     pub reasoning_content: Option<String>,
+    /// 给出答案的模型，用于选择答案提取策略
+    #[serde(default)]
+    pub model: Option<String>,
     #[serde(default)]
     pub is_success: Option<bool>,
 }
@@ -41,12 +138,14 @@ pub struct ModelCallProgressRequest {
 }
 
 /// 查询响应结构体
-/// data 序列化为单个对象（取第一条），兼容题库配置 res.data.question/res.data.answer
+/// data 序列化为单个对象（取第一条），兼容题库配置 res.data.question/res.data.answer；
+/// all 为 true 时 data 序列化为按得分排列的数组
 #[derive(Debug)]
 pub struct QueryResponse {
     pub code: i32,
     pub data: Option<Vec<QueryData>>,
     pub message: Option<String>,
+    pub all: bool,
 }
 
 impl Serialize for QueryResponse {
@@ -55,6 +154,9 @@ impl Serialize for QueryResponse {
         let mut map = serializer.serialize_map(None)?;
         map.serialize_entry("code", &self.code)?;
         match &self.data {
+            Some(list) if self.all => {
+                map.serialize_entry("data", list)?;
+            }
             Some(list) if !list.is_empty() => {
                 // 取第一条，序列化为对象而非数组
                 map.serialize_entry("data", &list[0])?;
@@ -83,12 +185,14 @@ impl<'de> Deserialize<'de> for QueryResponse {
                 let mut code = 0i32;
                 let mut data: Option<Vec<QueryData>> = None;
                 let mut message = None;
+                let mut all = false;
                 while let Some(key) = map.next_key::<String>()? {
                     match key.as_str() {
                         "code" => code = map.next_value()?,
                         "data" => {
                             let v: serde_json::Value = map.next_value()?;
                             if v.is_array() {
+                                all = true;
                                 data = serde_json::from_value(v).ok();
                             } else if v.is_object() {
                                 let item: QueryData =
@@ -106,6 +210,7 @@ impl<'de> Deserialize<'de> for QueryResponse {
                     code,
                     data,
                     message,
+                    all,
                 })
             }
         }
@@ -121,6 +226,20 @@ pub struct QueryData {
     pub answer: String,
     pub is_ai: bool,
     pub is_pending_correction: bool,
+    /// 把题目标记为待修正的地址（POST），题目未入库时为空
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pending_correction_url: Option<String>,
+    /// 以下字段只在返回全部匹配题目时填写：综合匹配得分（AI 新生成的答案没有得分）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub score: Option<f64>,
+    /// 题目是否已人工核实
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub is_verified: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub folder_id: Option<i64>,
+    /// "父文件夹/子文件夹" 形式的文件夹路径
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub folder: Option<String>,
 }
 
 /// 请求日志结构体
@@ -143,6 +262,7 @@ impl QueryResponse {
             code: 1,
             data: Some(data),
             message: None,
+            all: false,
         }
     }
 
@@ -152,6 +272,7 @@ impl QueryResponse {
             code: 0,
             data: None,
             message: Some("No matching records found".to_string()),
+            all: false,
         }
     }
 
@@ -161,8 +282,15 @@ impl QueryResponse {
             code: 0,
             data: None,
             message: Some(message),
+            all: false,
         }
     }
+
+    /// 返回全部匹配题目时 data 序列化为数组
+    pub fn with_all(mut self, all: bool) -> Self {
+        self.all = all;
+        self
+    }
 }
 
 /// 服务器状态管理结构体
@@ -198,3 +326,70 @@ impl Default for ServerState {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn query_response_returns_one_match_unless_all_are_requested() {
+        let data = |id: i64| QueryData {
+            id,
+            question: "题目".to_string(),
+            answer: "答案".to_string(),
+            is_ai: false,
+            is_pending_correction: false,
+            pending_correction_url: None,
+            score: None,
+            is_verified: None,
+            folder_id: None,
+            folder: None,
+        };
+        let single = serde_json::to_value(QueryResponse::success(vec![data(1), data(2)]))
+            .expect("serialize response");
+        assert_eq!(single["data"]["id"], 1);
+        assert!(single["data"].get("score").is_none());
+
+        let ranked = QueryData {
+            score: Some(0.9),
+            folder_id: Some(2),
+            folder: Some("生物/细胞".to_string()),
+            ..data(1)
+        };
+        let all =
+            serde_json::to_value(QueryResponse::success(vec![ranked, data(2)]).with_all(true))
+                .expect("serialize response");
+        assert_eq!(all["data"].as_array().map(Vec::len), Some(2));
+        assert_eq!(all["data"][0]["score"], 0.9);
+        assert_eq!(all["data"][0]["folder"], "生物/细胞");
+    }
+
+    #[test]
+    fn folders_accept_an_array_or_comma_separated_string() {
+        let request: QueryRequest =
+            serde_json::from_str(r#"{"title":"题目","folders":[12,"英语/四级"]}"#)
+                .expect("parse request");
+        assert_eq!(request.folders, vec!["12", "英语/四级"]);
+        let request: QueryRequest =
+            serde_json::from_str(r#"{"title":"题目","folders":"1， 英语,"}"#)
+                .expect("parse request");
+        assert_eq!(request.folders, vec!["1", "英语"]);
+        let request: QueryExplainRequest =
+            serde_json::from_str(r#"{"title":"题目","options":null,"folders":"英语"}"#)
+                .expect("parse explain request");
+        assert_eq!(request.folders, vec!["英语"]);
+    }
+
+    #[test]
+    fn question_decoration_names_are_case_insensitive() {
+        assert_eq!(
+            QuestionDecoration::parse(" Plain "),
+            Some(QuestionDecoration::None)
+        );
+        assert_eq!(
+            QuestionDecoration::parse("link"),
+            Some(QuestionDecoration::Link)
+        );
+        assert_eq!(QuestionDecoration::parse("fancy"), None);
+    }
+}
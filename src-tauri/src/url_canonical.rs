@@ -0,0 +1,300 @@
+//! 图片题 URL 规范化：同一张图片可能来自不同的 CDN 域名、带有签名参数或 `?x-oss-process` 缩放参数，
+//! 比较题目中的 URL 前先转换为规范形式。入库时题目的规范 URL 保存在 AIResponses.CanonicalUrls 中，
+//! 同时在 CanonicalUrlsRules 中记录所用规则的指纹；规则修改后指纹不同的记录改为现场计算。
+//!
+//! config.json 中可以覆盖默认规则：
+//! ```json
+//! {
+//!   "urlCanonicalization": {
+//!     "stripQueryParams": ["x-oss-*", "expires", "signature", "token", "sign"],
+//!     "stripAllQueryParams": false,
+//!     "hostAliases": { "img2.example.com": "img.example.com", "*.cdn.example.com": "img.example.com" },
+//!     "pathKeys": [{ "hosts": ["*.example.com"], "pattern": "/([0-9a-f]{32})\\.(png|jpg)$", "key": "example-img:$1" }]
+//!   }
+//! }
+//! ```
+//! pathKeys 中第一个匹配的规则直接以展开后的 key 作为规范形式，不同域名、路径前缀的同一文件可以得到相同结果。
+//! 默认只去掉常见对象存储的签名与图片处理参数。`token`、`sign` 等通用参数名在很多站点上用于区分不同的图片，
+//! 需要时在 stripQueryParams 中自行添加（会替换默认列表）。
+//! 修改规则后调用 rebuild_canonical_urls 重新计算已入库题目的规范 URL（启动时也会自动重新计算）。
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use url::Url;
+
+use crate::database::{read_config_value, ConfigCache};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PathKeyRule {
+    /// 适用的域名（规范化后），支持 `*.example.com`；为空时适用于所有域名
+    #[serde(default)]
+    pub hosts: Vec<String>,
+    /// 匹配 URL 路径的正则表达式
+    pub pattern: String,
+    /// 规范形式模板，可以使用 `$1`、`$name` 引用捕获组
+    #[serde(default = "default_path_key")]
+    pub key: String,
+}
+
+fn default_path_key() -> String {
+    "$0".to_string()
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct UrlCanonicalRules {
+    /// 需要去掉的查询参数名（不区分大小写），以 `*` 结尾时按前缀匹配
+    pub strip_query_params: Vec<String>,
+    /// 去掉全部查询参数
+    pub strip_all_query_params: bool,
+    /// 域名别名：键为别名（支持 `*.example.com`），值为规范域名
+    pub host_aliases: HashMap<String, String>,
+    pub path_keys: Vec<PathKeyRule>,
+    /// http 与 https 视为相同
+    pub ignore_scheme: bool,
+}
+
+impl Default for UrlCanonicalRules {
+    fn default() -> Self {
+        // 常见对象存储 / CDN 的签名、过期时间与图片处理参数
+        let strip_query_params = [
+            "x-oss-*",
+            "x-amz-*",
+            "x-cos-*",
+            "ossaccesskeyid",
+            "accesskeyid",
+            "expires",
+            "signature",
+            "auth_key",
+            "imageview2*",
+            "imagemogr2*",
+        ];
+        UrlCanonicalRules {
+            strip_query_params: strip_query_params.iter().map(|p| p.to_string()).collect(),
+            strip_all_query_params: false,
+            host_aliases: HashMap::new(),
+            path_keys: Vec::new(),
+            ignore_scheme: true,
+        }
+    }
+}
+
+fn host_matches(pattern: &str, host: &str) -> bool {
+    let pattern = pattern.trim().to_lowercase();
+    match pattern.strip_prefix("*.") {
+        Some(suffix) => host
+            .strip_suffix(suffix)
+            .is_some_and(|prefix| prefix.ends_with('.')),
+        None => pattern == host,
+    }
+}
+
+pub struct UrlCanonicalizer {
+    rules: UrlCanonicalRules,
+    path_keys: Vec<(PathKeyRule, Regex)>,
+    fingerprint: String,
+}
+
+// 规则的指纹：规则序列化后的 FNV-1a 哈希。serde_json 的对象按键排序，结果与 HashMap 的遍历顺序无关
+fn rules_fingerprint(rules: &UrlCanonicalRules) -> String {
+    let serialized = serde_json::to_value(rules)
+        .map(|value| value.to_string())
+        .unwrap_or_default();
+    let hash = serialized
+        .bytes()
+        .fold(0xcbf29ce484222325u64, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+        });
+    format!("{:016x}", hash)
+}
+
+impl Default for UrlCanonicalizer {
+    fn default() -> Self {
+        UrlCanonicalizer::new(UrlCanonicalRules::default())
+    }
+}
+
+impl UrlCanonicalizer {
+    /// 无法编译的路径规则会被忽略并打印警告
+    pub fn new(rules: UrlCanonicalRules) -> Self {
+        let path_keys = rules
+            .path_keys
+            .iter()
+            .filter_map(|rule| match Regex::new(&rule.pattern) {
+                Ok(regex) => Some((rule.clone(), regex)),
+                Err(e) => {
+                    println!("⚠️ URL 路径规则 {} 无效，已忽略: {}", rule.pattern, e);
+                    None
+                }
+            })
+            .collect();
+        let fingerprint = rules_fingerprint(&rules);
+        UrlCanonicalizer {
+            rules,
+            path_keys,
+            fingerprint,
+        }
+    }
+
+    /// 读取 config.json 中的 urlCanonicalization，未配置或格式错误时使用默认规则
+    pub fn load() -> Self {
+        let rules = match read_config_value("urlCanonicalization") {
+            Some(value) => serde_json::from_value(value).unwrap_or_else(|e| {
+                println!("⚠️ URL 规范化配置格式错误，使用默认规则: {}", e);
+                UrlCanonicalRules::default()
+            }),
+            None => UrlCanonicalRules::default(),
+        };
+        UrlCanonicalizer::new(rules)
+    }
+
+    /// 与 load 相同，但在 config.json 未修改时复用上次的结果（含已编译的路径规则）
+    pub fn cached() -> Arc<Self> {
+        static CACHE: ConfigCache<UrlCanonicalizer> = ConfigCache::new();
+        CACHE.get(UrlCanonicalizer::load)
+    }
+
+    /// 当前规则的指纹，规则不变时保持不变
+    pub fn fingerprint(&self) -> &str {
+        &self.fingerprint
+    }
+
+    fn resolve_host(&self, host: &str) -> String {
+        self.rules
+            .host_aliases
+            .iter()
+            .find(|(alias, _)| host_matches(alias, host))
+            .map(|(_, canonical)| canonical.trim().to_lowercase())
+            .unwrap_or_else(|| host.to_string())
+    }
+
+    fn should_strip(&self, param: &str) -> bool {
+        if self.rules.strip_all_query_params {
+            return true;
+        }
+        let name = param.split('=').next().unwrap_or_default().to_lowercase();
+        self.rules.strip_query_params.iter().any(|pattern| {
+            let pattern = pattern.trim().to_lowercase();
+            match pattern.strip_suffix('*') {
+                Some(prefix) => name.starts_with(prefix),
+                None => name == pattern,
+            }
+        })
+    }
+
+    /// URL 的规范形式：统一域名大小写与别名、去掉默认端口、片段和指定的查询参数，
+    /// 其余查询参数按字典序排列。无法解析的 URL 原样返回。
+    pub fn canonicalize(&self, url: &str) -> String {
+        let Ok(parsed) = Url::parse(url) else {
+            return url.to_string();
+        };
+        let Some(host) = parsed.host_str() else {
+            return url.to_string();
+        };
+        let host = self.resolve_host(host);
+
+        for (rule, regex) in &self.path_keys {
+            if !rule.hosts.is_empty() && !rule.hosts.iter().any(|p| host_matches(p, &host)) {
+                continue;
+            }
+            if let Some(captures) = regex.captures(parsed.path()) {
+                let mut key = String::new();
+                captures.expand(&rule.key, &mut key);
+                return key;
+            }
+        }
+
+        let scheme = if self.rules.ignore_scheme {
+            "https"
+        } else {
+            parsed.scheme()
+        };
+        let mut canonical = format!("{}://{}", scheme, host);
+        if let Some(port) = parsed.port() {
+            canonical.push_str(&format!(":{}", port));
+        }
+        canonical.push_str(parsed.path());
+
+        if let Some(query) = parsed.query() {
+            let mut params: Vec<&str> = query
+                .split('&')
+                .filter(|param| !param.is_empty() && !self.should_strip(param))
+                .collect();
+            params.sort_unstable();
+            if !params.is_empty() {
+                canonical.push('?');
+                canonical.push_str(&params.join("&"));
+            }
+        }
+        canonical
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn canonicalizes_cdn_urls() {
+        let canonicalizer = UrlCanonicalizer::default();
+        assert_eq!(
+            canonicalizer.canonicalize(
+                "http://IMG.example.com/a/1.png?x-oss-process=image/resize,w_200&Expires=1&OSSAccessKeyId=k&Signature=s#top"
+            ),
+            "https://img.example.com/a/1.png"
+        );
+        // 其余参数保留并排序
+        assert_eq!(
+            canonicalizer.canonicalize("https://img.example.com/q?id=2&v=1&imageView2/2/w/200"),
+            "https://img.example.com/q?id=2&v=1"
+        );
+        assert_eq!(canonicalizer.canonicalize("https://"), "https://");
+        // token、sign 等通用参数默认保留
+        assert_eq!(
+            canonicalizer.canonicalize("https://img.example.com/q.png?token=1&sign=2"),
+            "https://img.example.com/q.png?sign=2&token=1"
+        );
+        assert_eq!(
+            canonicalizer.fingerprint(),
+            UrlCanonicalizer::default().fingerprint()
+        );
+
+        let canonicalizer = UrlCanonicalizer::new(UrlCanonicalRules {
+            host_aliases: [(
+                "*.cdn.example.com".to_string(),
+                "img.example.com".to_string(),
+            )]
+            .into_iter()
+            .collect(),
+            path_keys: vec![PathKeyRule {
+                hosts: vec!["files.example.org".to_string()],
+                pattern: r"/([0-9a-f]{8})\.png$".to_string(),
+                key: "file:$1".to_string(),
+            }],
+            ..Default::default()
+        });
+        assert_eq!(
+            canonicalizer.canonicalize("https://cn1.cdn.example.com/a/1.png"),
+            "https://img.example.com/a/1.png"
+        );
+        assert_eq!(
+            canonicalizer.canonicalize("https://cdn.example.com/a/1.png"),
+            "https://cdn.example.com/a/1.png"
+        );
+        assert_eq!(
+            canonicalizer.canonicalize("https://files.example.org/v2/thumb/0badf00d.png?t=1"),
+            "file:0badf00d"
+        );
+        assert_eq!(
+            canonicalizer.canonicalize("https://files.example.org/static/0badf00d.png"),
+            "file:0badf00d"
+        );
+        assert_ne!(
+            canonicalizer.fingerprint(),
+            UrlCanonicalizer::default().fingerprint()
+        );
+    }
+}
@@ -19,6 +19,9 @@ export interface AIResponse {
   create_time: string;
   is_ai?: boolean;
   is_pending_correction?: boolean;
+  is_verified?: boolean;
+  // 由 question_type 识别出的题型 id，无法识别时为空
+  question_kind?: string | null;
 }
 
 export interface PaginatedAIResponses {
@@ -271,6 +274,25 @@ class DatabaseService {
     }
   }
 
+  // 核实即视为答案正确，同时清除待修正标记
+  async setQuestionVerified(questionId: number, verified: boolean): Promise<void> {
+    if (!this.isTauri) {
+      const question = mockAIResponses.find(q => q.id === questionId);
+      if (question) {
+        question.is_verified = verified;
+        if (verified) question.is_pending_correction = false;
+      }
+      return;
+    }
+
+    try {
+      await invoke('set_question_verified', { id: questionId, verified });
+    } catch (error) {
+      console.error('更新核实状态失败:', error);
+      throw error;
+    }
+  }
+
   async getFolderQuestionCount(folderId: number): Promise<number> {
     if (!this.isTauri) {
       return mockAIResponses.filter(response => response.folder_id === folderId).length;
@@ -456,6 +478,37 @@ class DatabaseService {
     }
   }
 
+  // 批量导入题目，返回实际导入的数量
+  async importQuestions(questions: { content: string; options?: string; answer: string; question_type?: string }[], folderId: string | number): Promise<number> {
+    const targetFolderId = typeof folderId === 'string' ? parseInt(folderId) : folderId;
+
+    if (!this.isTauri) {
+      let count = 0;
+      for (const question of questions) {
+        if (!question.content.trim()) continue;
+        await this.addQuestion({ ...question, folderId: targetFolderId });
+        count++;
+      }
+      return count;
+    }
+
+    try {
+      // Rust Command: import_questions(questions, folder_id)
+      return await invoke<number>('import_questions', {
+        questions: questions.map(q => ({
+          content: q.content,
+          options: q.options || null,
+          answer: q.answer,
+          question_type: q.question_type || null
+        })),
+        folderId: targetFolderId
+      });
+    } catch (error) {
+      console.error('导入题目失败:', error);
+      throw error;
+    }
+  }
+
   // 更新题目
   async updateQuestion(questionId: number, updateData: { question?: string; options?: string | null; answer?: string; question_type?: string }): Promise<void> {
     if (!this.isTauri) {
@@ -53,6 +53,10 @@ export interface UserConfig {
   name: string
   token: string
   createdAt: string
+  // 该用户的 /query 返回答案使用的答案格式配置档（answerFormats）
+  answerFormat?: string
+  // 该用户的 /query 只在这些文件夹（文件夹 ID 或 "父文件夹/子文件夹" 路径）及其子文件夹中查询，新题目也保存到其中
+  folders?: (number | string)[]
 }
 
 export interface AlgorithmConfig {